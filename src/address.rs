@@ -0,0 +1,292 @@
+//! Normalize addresses before they are geocoded.
+//!
+//! Address columns are often filled by hand, e.g. with the whole address
+//! in the `street` column (`Hauptstr.1\n44787 Bochum`) or with an additional
+//! `c/o` line, so the geocoder doesn't find them.
+//!
+//! The exports show the address of an entry in one line (see [address_line]).
+
+use std::{fmt, str::FromStr};
+
+use anyhow::bail;
+use ofdb_boundary::{Address, Entry};
+
+/// The conventions of the addresses of a region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressLocale {
+    /// German addresses (`Hauptstr. 1`, `44787 Bochum`).
+    De,
+    /// English addresses (`1 Main St.`).
+    En,
+}
+
+impl AddressLocale {
+    /// Abbreviations of the street types and their full form.
+    ///
+    /// The abbreviations may also be the suffix of a word (`Hauptstr.`).
+    fn suffixes(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::De => &[("str.", "straße")],
+            Self::En => &[],
+        }
+    }
+
+    /// Abbreviations of the street types that are complete words.
+    fn words(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::De => &[("Str.", "Straße"), ("Pl.", "Platz")],
+            Self::En => &[
+                ("Rd.", "Road"),
+                ("Ave.", "Avenue"),
+                ("Blvd.", "Boulevard"),
+                ("Ln.", "Lane"),
+            ],
+        }
+    }
+
+    /// Abbreviations that are only expanded at the end of the street
+    /// (e.g. `St.` could also be `Saint`).
+    fn last_words(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::De => &[],
+            Self::En => &[("St.", "Street")],
+        }
+    }
+}
+
+impl FromStr for AddressLocale {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "de" => Self::De,
+            "en" => Self::En,
+            _ => bail!("Unknown address locale '{s}' (expected 'de' or 'en')"),
+        })
+    }
+}
+
+impl fmt::Display for AddressLocale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::De => "de",
+            Self::En => "en",
+        })
+    }
+}
+
+/// Clean up the fields of the address:
+///
+/// - lines with `c/o` are removed
+/// - a `zip city` line of the street is moved into the empty `zip` and `city`
+/// - the house number is separated from the street (`Hauptstr.1` → `Hauptstr. 1`)
+/// - abbreviations of the locale are expanded (`Hauptstr.` → `Hauptstraße`)
+pub fn normalize(addr: &mut Address, locale: AddressLocale) {
+    if let Some(street) = addr.street.take() {
+        let mut lines = street
+            .split(['\n', '\r', ','])
+            .map(|line| strip_care_of(line).trim())
+            .filter(|line| !line.is_empty());
+        addr.street = lines.next().map(|line| normalize_street(line, locale));
+        for line in lines {
+            match split_zip_city(line) {
+                Some((zip, city)) if addr.zip.is_none() && addr.city.is_none() => {
+                    addr.zip = Some(zip.to_string());
+                    addr.city = Some(city.to_string());
+                }
+                _ => log::debug!("Ignore the address line '{line}'"),
+            }
+        }
+    }
+    if addr.zip.is_none() {
+        if let Some((zip, city)) = addr.city.as_deref().and_then(split_zip_city) {
+            addr.zip = Some(zip.to_string());
+            addr.city = Some(city.to_string());
+        }
+    }
+    for field in [
+        &mut addr.zip,
+        &mut addr.city,
+        &mut addr.country,
+        &mut addr.state,
+    ] {
+        *field = field
+            .take()
+            .map(|value| collapse_whitespace(&value))
+            .filter(|value| !value.is_empty());
+    }
+}
+
+/// Everything before `c/o`.
+fn strip_care_of(line: &str) -> &str {
+    match line
+        .as_bytes()
+        .windows(3)
+        .position(|w| w.eq_ignore_ascii_case(b"c/o"))
+    {
+        Some(idx) => &line[..idx],
+        None => line,
+    }
+}
+
+fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Split a line like `44787 Bochum`.
+pub(crate) fn split_zip_city(line: &str) -> Option<(&str, &str)> {
+    let (zip, city) = line.trim().split_once(char::is_whitespace)?;
+    let is_zip = (4..=5).contains(&zip.len()) && zip.chars().all(|c| c.is_ascii_digit());
+    let city = city.trim();
+    (is_zip && !city.is_empty()).then_some((zip, city))
+}
+
+fn normalize_street(street: &str, locale: AddressLocale) -> String {
+    let street = separate_house_number(&collapse_whitespace(street));
+    let words: Vec<_> = street.split(' ').collect();
+    let last = words.len() - 1;
+    words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| expand_abbreviation(word, locale, i == last))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Insert a space before a house number that follows the street name directly.
+fn separate_house_number(street: &str) -> String {
+    let chars: Vec<_> = street.chars().collect();
+    let Some(start) = chars.iter().position(char::is_ascii_digit) else {
+        return street.to_string();
+    };
+    let glued = start > 0 && (chars[start - 1].is_alphabetic() || chars[start - 1] == '.');
+    let number = &chars[start..];
+    let is_house_number = number.len() <= 8
+        && number
+            .iter()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '/'));
+    if glued && is_house_number {
+        let (name, number): (String, String) =
+            (chars[..start].iter().collect(), number.iter().collect());
+        format!("{name} {number}")
+    } else {
+        street.to_string()
+    }
+}
+
+fn expand_abbreviation(word: &str, locale: AddressLocale, is_last: bool) -> String {
+    let last_words = if is_last { locale.last_words() } else { &[] };
+    if let Some((_, full)) = locale
+        .words()
+        .iter()
+        .chain(last_words)
+        .find(|(abbr, _)| *abbr == word)
+    {
+        return full.to_string();
+    }
+    for (abbr, full) in locale.suffixes() {
+        let Some(idx) = word.len().checked_sub(abbr.len()).filter(|idx| *idx > 0) else {
+            continue;
+        };
+        match (word.get(..idx), word.get(idx..)) {
+            (Some(stem), Some(suffix)) if suffix.eq_ignore_ascii_case(abbr) => {
+                return format!("{stem}{full}");
+            }
+            _ => {}
+        }
+    }
+    word.to_string()
+}
+
+/// The address of an entry in one line, e.g. `Hauptstr. 1, 12345 Berlin, Deutschland`.
+///
+/// The country is left out if it is clear from the context (e.g. a list of a region).
+pub fn address_line(e: &Entry, with_country: bool) -> String {
+    let field = |f: &Option<String>| f.as_deref().unwrap_or_default().trim().to_string();
+    let place = [field(&e.zip), field(&e.city)]
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let country = if with_country {
+        field(&e.country)
+    } else {
+        String::new()
+    };
+    [field(&e.street), place, country]
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_in_one_line() {
+        let mut entry = crate::fixtures::generate(1, 0).entries.remove(0);
+        entry.street = Some(" Hauptstr. 1 ".to_string());
+        entry.zip = None;
+        entry.city = Some("Berlin".to_string());
+        entry.country = Some("Deutschland".to_string());
+        assert_eq!(
+            address_line(&entry, true),
+            "Hauptstr. 1, Berlin, Deutschland"
+        );
+        assert_eq!(address_line(&entry, false), "Hauptstr. 1, Berlin");
+        entry.zip = Some("12345".to_string());
+        entry.street = None;
+        assert_eq!(address_line(&entry, false), "12345 Berlin");
+    }
+
+    fn address(street: &str, zip: Option<&str>, city: Option<&str>) -> Address {
+        Address {
+            street: Some(street.to_string()),
+            zip: zip.map(ToString::to_string),
+            city: city.map(ToString::to_string),
+            country: None,
+            state: None,
+        }
+    }
+
+    #[test]
+    fn normalize_german_addresses() {
+        let mut addr = address("Hauptstr.1\n44787  Bochum", None, None);
+        normalize(&mut addr, AddressLocale::De);
+        assert_eq!(addr.street.as_deref(), Some("Hauptstraße 1"));
+        assert_eq!(addr.zip.as_deref(), Some("44787"));
+        assert_eq!(addr.city.as_deref(), Some("Bochum"));
+
+        let mut addr = address("Berliner Str. 12a c/o Müller", None, Some("44787 Bochum"));
+        normalize(&mut addr, AddressLocale::De);
+        assert_eq!(addr.street.as_deref(), Some("Berliner Straße 12a"));
+        assert_eq!(addr.zip.as_deref(), Some("44787"));
+        assert_eq!(addr.city.as_deref(), Some("Bochum"));
+
+        let mut addr = address("c/o Verein\r\nAm Markt 3", Some("12345"), Some("Stadt"));
+        normalize(&mut addr, AddressLocale::De);
+        assert_eq!(addr.street.as_deref(), Some("Am Markt 3"));
+
+        let mut addr = address("Hauptstraße 1", Some("44787"), Some("Bochum\n"));
+        normalize(&mut addr, AddressLocale::De);
+        assert_eq!(addr.street.as_deref(), Some("Hauptstraße 1"));
+        assert_eq!(addr.city.as_deref(), Some("Bochum"));
+    }
+
+    #[test]
+    fn normalize_english_addresses() {
+        let mut addr = address("12 St. James Rd.", None, Some("London"));
+        normalize(&mut addr, AddressLocale::En);
+        assert_eq!(addr.street.as_deref(), Some("12 St. James Road"));
+        let mut addr = address("1 Main St.", None, None);
+        normalize(&mut addr, AddressLocale::En);
+        assert_eq!(addr.street.as_deref(), Some("1 Main Street"));
+    }
+
+    #[test]
+    fn parse_locale() {
+        assert_eq!("DE".parse::<AddressLocale>().unwrap(), AddressLocale::De);
+        assert!("fr".parse::<AddressLocale>().is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use ofdb_boundary::{
+    Credentials, Entry, MapBbox, NewPlace, NewPlaceRating, PlaceHistory, PlaceSearchResult, Rating,
+    Review, SearchResponse, UpdatePlace,
+};
+use reqwest::StatusCode;
+use uuid::Uuid;
+
+use crate::{
+    search::{emulate_duplicate_search, DuplicateSearch},
+    Error, OfdbClient, Result,
+};
+
+/// Operations of the OpenFairDB API.
+///
+/// This is implemented by [OfdbClient]
+/// and can be implemented by in-memory fakes
+/// to test code without a running server.
+/// Optional operations that an implementation doesn't provide fail with [not_supported].
+pub trait OfdbApi {
+    fn create_place(&self, new_place: &NewPlace) -> Result<String>;
+    fn update_place(&self, id: &str, place: &UpdatePlace) -> Result<String>;
+    fn read_entries(&self, uuids: Vec<Uuid>) -> Result<Vec<Entry>>;
+    fn search(&self, txt: &str, bbox: &MapBbox) -> Result<SearchResponse>;
+    fn review(&self, uuids: Vec<Uuid>, review: Review) -> Result<()>;
+    fn login(&self, _req: &Credentials) -> Result<()> {
+        Err(not_supported("login"))
+    }
+    fn place_history(&self, _uuid: Uuid) -> Result<PlaceHistory> {
+        Err(not_supported("place_history"))
+    }
+    fn create_rating(&self, _rating: &NewPlaceRating) -> Result<()> {
+        Err(not_supported("create_rating"))
+    }
+    fn read_ratings(&self, _ids: Vec<String>) -> Result<Vec<Rating>> {
+        Err(not_supported("read_ratings"))
+    }
+    fn search_duplicates(&self, _new_place: &NewPlace) -> Result<Option<Vec<PlaceSearchResult>>> {
+        Err(not_supported("search_duplicates"))
+    }
+    /// Search duplicates within a distance and with a min. similarity of the titles.
+    ///
+    /// By default this is emulated with [OfdbApi::search].
+    fn search_duplicates_with(
+        &self,
+        new_place: &NewPlace,
+        options: &DuplicateSearch,
+    ) -> Result<Option<Vec<PlaceSearchResult>>>
+    where
+        Self: Sized,
+    {
+        emulate_duplicate_search(self, new_place, options)
+    }
+}
+
+/// The error of an operation that an implementation doesn't support
+/// (`501 Not Implemented`).
+pub fn not_supported(operation: &str) -> Error {
+    Error::Api {
+        status: StatusCode::NOT_IMPLEMENTED.as_u16(),
+        message: format!("The operation '{operation}' is not supported"),
+    }
+}
+
+impl OfdbApi for OfdbClient {
+    fn create_place(&self, new_place: &NewPlace) -> Result<String> {
+        OfdbClient::create_place(self, new_place)
+    }
+    fn update_place(&self, id: &str, place: &UpdatePlace) -> Result<String> {
+        OfdbClient::update_place(self, id, place)
+    }
+    fn read_entries(&self, uuids: Vec<Uuid>) -> Result<Vec<Entry>> {
+        OfdbClient::read_entries(self, uuids)
+    }
+    fn login(&self, req: &Credentials) -> Result<()> {
+        OfdbClient::login(self, req)
+    }
+    fn review(&self, uuids: Vec<Uuid>, review: Review) -> Result<()> {
+        OfdbClient::review(self, uuids, review)
+    }
+    fn place_history(&self, uuid: Uuid) -> Result<PlaceHistory> {
+        OfdbClient::place_history(self, uuid)
+    }
+    fn create_rating(&self, rating: &NewPlaceRating) -> Result<()> {
+        OfdbClient::create_rating(self, rating)
+    }
+    fn read_ratings(&self, ids: Vec<String>) -> Result<Vec<Rating>> {
+        OfdbClient::read_ratings(self, ids)
+    }
+    fn search(&self, txt: &str, bbox: &MapBbox) -> Result<SearchResponse> {
+        OfdbClient::search(self, txt, bbox)
+    }
+    fn search_duplicates(&self, new_place: &NewPlace) -> Result<Option<Vec<PlaceSearchResult>>> {
+        OfdbClient::search_duplicates(self, new_place)
+    }
+    fn search_duplicates_with(
+        &self,
+        new_place: &NewPlace,
+        options: &DuplicateSearch,
+    ) -> Result<Option<Vec<PlaceSearchResult>>> {
+        OfdbClient::search_duplicates_with(self, new_place, options)
+    }
+}
@@ -0,0 +1,248 @@
+//! Heuristics to find spam and other suspicious entries.
+//!
+//! The result can be used as input of the `review` command.
+
+use std::{collections::HashMap, fmt, io::Write};
+
+use anyhow::Result;
+use ofdb_boundary::Entry;
+use serde::Serialize;
+
+use crate::matching::normalize_title;
+
+/// Domains of well-known disposable e-mail services.
+const DISPOSABLE_EMAIL_DOMAINS: &[&str] = &[
+    "10minutemail.com",
+    "byom.de",
+    "discard.email",
+    "dispostable.com",
+    "einrot.com",
+    "fakeinbox.com",
+    "getnada.com",
+    "guerrillamail.com",
+    "maildrop.cc",
+    "mailinator.com",
+    "mintemail.com",
+    "mytemp.email",
+    "sharklasers.com",
+    "spambog.com",
+    "temp-mail.org",
+    "tempmail.com",
+    "throwawaymail.com",
+    "trashmail.com",
+    "trashmail.de",
+    "wegwerfmail.de",
+    "yopmail.com",
+];
+
+/// Min. number of characters a description should have besides links.
+const MIN_TEXT_LEN: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// Number of entries with the same (normalized) title.
+    DuplicateTitle(usize),
+    EmptyDescription,
+    LinkOnlyDescription,
+    NullIsland,
+    DisposableEmail(String),
+}
+
+impl Finding {
+    /// The higher the score, the more likely an entry is junk.
+    pub fn score(&self) -> u32 {
+        match self {
+            Self::DuplicateTitle(_) => 2,
+            Self::EmptyDescription => 3,
+            Self::LinkOnlyDescription | Self::DisposableEmail(_) => 4,
+            Self::NullIsland => 5,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateTitle(count) => write!(f, "duplicate title ({count} entries)"),
+            Self::EmptyDescription => f.write_str("empty description"),
+            Self::LinkOnlyDescription => f.write_str("description only contains links"),
+            Self::NullIsland => f.write_str("coordinates at 0/0"),
+            Self::DisposableEmail(domain) => write!(f, "disposable e-mail domain '{domain}'"),
+        }
+    }
+}
+
+/// A row of the audit report in the format of the `review` command.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord {
+    pub id: String,
+    /// The review status, empty until the entry was checked by a human
+    /// (a duplicate title is not suspicious for all entries with that title).
+    pub status: &'static str,
+    pub comment: String,
+    pub score: u32,
+    pub title: String,
+}
+
+/// Check the entries and rank the suspicious ones (highest score first).
+pub fn audit(entries: &[Entry]) -> Vec<AuditRecord> {
+    let mut titles: HashMap<String, usize> = HashMap::new();
+    for e in entries {
+        *titles.entry(normalize_title(&e.title)).or_default() += 1;
+    }
+    let mut records: Vec<_> = entries
+        .iter()
+        .filter_map(|e| {
+            let findings = findings(e, titles[&normalize_title(&e.title)]);
+            if findings.is_empty() {
+                return None;
+            }
+            let comment = findings
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            Some(AuditRecord {
+                id: e.id.clone(),
+                status: "",
+                comment: format!("Suspicious: {comment}"),
+                score: findings.iter().map(Finding::score).sum(),
+                title: e.title.clone(),
+            })
+        })
+        .collect();
+    records.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.title.cmp(&b.title)));
+    records
+}
+
+fn findings(e: &Entry, same_titles: usize) -> Vec<Finding> {
+    let mut findings = vec![];
+    if same_titles > 1 {
+        findings.push(Finding::DuplicateTitle(same_titles));
+    }
+    if e.description.trim().is_empty() {
+        findings.push(Finding::EmptyDescription);
+    } else if is_link_only(&e.description) {
+        findings.push(Finding::LinkOnlyDescription);
+    }
+    if e.lat.abs() < 0.001 && e.lng.abs() < 0.001 {
+        findings.push(Finding::NullIsland);
+    }
+    if let Some(domain) = e
+        .email
+        .as_deref()
+        .and_then(|email| email.rsplit_once('@'))
+        .map(|(_, domain)| domain.trim().to_lowercase())
+        .filter(|domain| DISPOSABLE_EMAIL_DOMAINS.contains(&domain.as_str()))
+    {
+        findings.push(Finding::DisposableEmail(domain));
+    }
+    findings
+}
+
+fn is_link_only(description: &str) -> bool {
+    let mut has_link = false;
+    let text_len: usize = description
+        .split_whitespace()
+        .filter(|word| {
+            let word = word.to_lowercase();
+            let is_link = word.starts_with("http://")
+                || word.starts_with("https://")
+                || word.starts_with("www.");
+            has_link |= is_link;
+            !is_link
+        })
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).count())
+        .sum();
+    has_link && text_len < MIN_TEXT_LEN
+}
+
+pub fn write_audit<W: Write>(w: W, records: &[AuditRecord]) -> Result<()> {
+    let mut wtr = csv::WriterBuilder::new().from_writer(w);
+    for r in records {
+        wtr.serialize(r)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rank_suspicious_entries() {
+        let mut entries = crate::fixtures::generate(6, 3).entries;
+        for (i, e) in entries.iter_mut().enumerate() {
+            e.title = format!("Entry {i}");
+        }
+        entries[0].description = "  ".to_string();
+        entries[1].description = "Mehr: https://example.org www.example.org".to_string();
+        entries[2].lat = 0.0;
+        entries[2].lng = 0.0;
+        entries[3].email = Some("foo@Mailinator.com".to_string());
+        entries[4].title = "Entry 5 e.V.".to_string();
+
+        let records = audit(&entries);
+        let ranking: Vec<_> = records
+            .iter()
+            .map(|r| (r.title.as_str(), r.score))
+            .collect();
+        assert_eq!(
+            ranking,
+            vec![
+                ("Entry 2", 5),
+                ("Entry 1", 4),
+                ("Entry 3", 4),
+                ("Entry 0", 3),
+                ("Entry 5", 2),
+                ("Entry 5 e.V.", 2),
+            ]
+        );
+        assert_eq!(
+            records[2].comment,
+            "Suspicious: disposable e-mail domain 'mailinator.com'"
+        );
+        assert_eq!(
+            records[5].comment,
+            "Suspicious: duplicate title (2 entries)"
+        );
+        assert!(records.iter().all(|r| r.status.is_empty()));
+    }
+
+    #[test]
+    fn detect_link_only_descriptions() {
+        assert!(is_link_only("https://example.org"));
+        assert!(is_link_only("Infos: www.example.org"));
+        assert!(!is_link_only(
+            "Ein Repair Café in Bochum: https://example.org"
+        ));
+        assert!(!is_link_only("Kurz"));
+    }
+
+    #[test]
+    fn write_review_csv() {
+        let mut records = vec![AuditRecord {
+            id: "0c0b0ed1d2d54e2a8a4bb1e6c4d0e4b5".to_string(),
+            status: "rejected",
+            comment: "Suspicious: coordinates at 0/0".to_string(),
+            score: 5,
+            title: "Foo".to_string(),
+        }];
+        records.push(AuditRecord {
+            id: "1c0b0ed1d2d54e2a8a4bb1e6c4d0e4b5".to_string(),
+            status: "",
+            comment: "Suspicious: duplicate title (2 entries)".to_string(),
+            score: 2,
+            title: "Bar".to_string(),
+        });
+        let mut csv = vec![];
+        write_audit(&mut csv, &records).unwrap();
+        let reviews = crate::csv::reviews_from_reader(&csv[..]).unwrap();
+        assert_eq!(reviews.len(), 1);
+        assert_eq!(
+            reviews[0].1.comment.as_deref(),
+            Some("Suspicious: coordinates at 0/0")
+        );
+    }
+}
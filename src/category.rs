@@ -0,0 +1,16 @@
+//! The categories of the entries of the OpenFairDB.
+
+/// ID of the category "initiative".
+pub const CATEGORY_INITIATIVE: &str = "2cd00bebec0c48ba9db761da48678134";
+
+/// ID of the category "company".
+pub const CATEGORY_COMPANY: &str = "77b3c33a92554bcf8e8c2c86cedd6f6f";
+
+/// The name of a known category or the ID itself.
+pub fn category_name(id: &str) -> &str {
+    match id {
+        CATEGORY_INITIATIVE => "initiative",
+        CATEGORY_COMPANY => "company",
+        id => id,
+    }
+}
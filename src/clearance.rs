@@ -0,0 +1,131 @@
+//! Clearance of changes to entries with tags that are moderated by an organization.
+//!
+//! If an organization requires a clearance for its tags,
+//! new revisions of the tagged entries are pending
+//! until the organization clears them.
+//! All requests require the API token of the organization (`--org-token`).
+
+use anyhow::{bail, Result};
+use ofdb_boundary::Entry;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::report::ENTRY_URL;
+
+/// Response of counting requests.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ResultCount {
+    pub count: u64,
+}
+
+/// An entry with changes that are waiting for a clearance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingClearance {
+    pub place_id: String,
+    /// Timestamp (milliseconds) of the first pending change.
+    pub created_at: i64,
+    /// `None` if no revision was cleared yet.
+    pub last_cleared_revision: Option<u64>,
+}
+
+/// Accept the changes of an entry up to a revision.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Clearance {
+    pub place_id: String,
+    pub cleared_revision: Option<u64>,
+}
+
+/// A pending clearance together with the current state of the entry.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingEntry {
+    pub id: String,
+    pub title: String,
+    pub current_revision: Option<u64>,
+    pub last_cleared_revision: Option<u64>,
+    pub created_at: i64,
+    pub url: String,
+}
+
+pub fn pending_entries(pending: Vec<PendingClearance>, entries: &[Entry]) -> Vec<PendingEntry> {
+    pending
+        .into_iter()
+        .map(|p| {
+            let entry = entries.iter().find(|e| same_id(&e.id, &p.place_id));
+            PendingEntry {
+                title: entry.map(|e| e.title.clone()).unwrap_or_default(),
+                current_revision: entry.map(|e| e.version),
+                last_cleared_revision: p.last_cleared_revision,
+                created_at: p.created_at,
+                url: format!("{ENTRY_URL}{}", p.place_id),
+                id: p.place_id,
+            }
+        })
+        .collect()
+}
+
+/// Clear the entries up to the revision `until`
+/// or (if it is missing) up to their current revision.
+pub fn clearances(uuids: &[Uuid], until: Option<u64>, entries: &[Entry]) -> Result<Vec<Clearance>> {
+    uuids
+        .iter()
+        .map(|uuid| {
+            let place_id = uuid.simple().to_string();
+            let cleared_revision = match until {
+                Some(revision) => revision,
+                None => match entries.iter().find(|e| same_id(&e.id, &place_id)) {
+                    Some(entry) => entry.version,
+                    None => bail!("Entry {place_id} does not exist"),
+                },
+            };
+            Ok(Clearance {
+                place_id,
+                cleared_revision: Some(cleared_revision),
+            })
+        })
+        .collect()
+}
+
+fn same_id(a: &str, b: &str) -> bool {
+    match (a.parse::<Uuid>(), b.parse::<Uuid>()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_pending_entries() {
+        let mut entries = crate::fixtures::generate(2, 4).entries;
+        entries[0].version = 3;
+        let pending = vec![PendingClearance {
+            place_id: entries[0].id.clone(),
+            created_at: 1_600_000_000_000,
+            last_cleared_revision: Some(1),
+        }];
+        let pending = pending_entries(pending, &entries);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].title, entries[0].title);
+        assert_eq!(pending[0].current_revision, Some(3));
+        assert_eq!(pending[0].last_cleared_revision, Some(1));
+    }
+
+    #[test]
+    fn clear_up_to_a_revision() {
+        let mut entries = crate::fixtures::generate(2, 4).entries;
+        entries[0].version = 3;
+        let uuid: Uuid = entries[0].id.parse().unwrap();
+        let unknown = Uuid::from_u128(42);
+
+        let clearances = clearances(&[uuid], None, &entries).unwrap();
+        assert_eq!(clearances[0].place_id, uuid.simple().to_string());
+        assert_eq!(clearances[0].cleared_revision, Some(3));
+        assert_eq!(
+            super::clearances(&[uuid, unknown], Some(2), &entries).unwrap()[1].cleared_revision,
+            Some(2)
+        );
+        assert!(super::clearances(&[unknown], None, &entries).is_err());
+    }
+}
@@ -0,0 +1,8 @@
+//! Parts of the command line interface that are not part of the library.
+
+pub mod bench;
+pub mod doctor;
+pub mod import;
+pub mod progress;
+pub mod review;
+pub mod update;
@@ -0,0 +1,256 @@
+//! Measure the latency of the API,
+//! e.g. to choose the rate limit and the size of an import for an instance.
+
+use std::{
+    fmt,
+    sync::{mpsc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use ofdb_boundary::{NewPlace, Review, ReviewStatus};
+use serde::Serialize;
+use uuid::Uuid;
+
+use ofdb_cli::{fixtures, search::bbox_around, OfdbClient, Result, DEFAULT_READ_CHUNK_SIZE};
+
+/// Tag of the entries that are created by the benchmark.
+pub const BENCH_TAG: &str = "ofdb-bench";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Create,
+    Read,
+    Search,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Create => "create",
+            Self::Read => "read",
+            Self::Search => "search",
+        };
+        f.pad(name)
+    }
+}
+
+/// Latencies of the requests of an operation in milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Latencies {
+    pub operation: Operation,
+    pub requests: usize,
+    pub errors: usize,
+    pub requests_per_second: f64,
+    pub min: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Latencies {
+    /// Calculate the percentiles of the successful requests
+    /// (nearest-rank method).
+    pub fn new(
+        operation: Operation,
+        mut durations: Vec<Duration>,
+        errors: usize,
+        elapsed: Duration,
+    ) -> Self {
+        durations.sort();
+        let ms = |d: &Duration| d.as_secs_f64() * 1000.0;
+        let percentile = |p: f64| {
+            if durations.is_empty() {
+                return 0.0;
+            }
+            let rank = (p / 100.0 * durations.len() as f64).ceil() as usize;
+            ms(&durations[rank.clamp(1, durations.len()) - 1])
+        };
+        let requests = durations.len() + errors;
+        let mean = if durations.is_empty() {
+            0.0
+        } else {
+            durations.iter().map(ms).sum::<f64>() / durations.len() as f64
+        };
+        Self {
+            operation,
+            requests,
+            errors,
+            requests_per_second: requests as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            min: durations.first().map(ms).unwrap_or_default(),
+            mean,
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+            max: durations.last().map(ms).unwrap_or_default(),
+        }
+    }
+}
+
+impl fmt::Display for Latencies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<7} {:>8} {:>6} {:>8.1} {:>8.1} {:>8.1} {:>8.1} {:>8.1} {:>8.1} {:>8.1}",
+            self.operation,
+            self.requests,
+            self.errors,
+            self.requests_per_second,
+            self.min,
+            self.mean,
+            self.p50,
+            self.p90,
+            self.p99,
+            self.max
+        )
+    }
+}
+
+/// Header of the table of [Latencies].
+pub const TABLE_HEADER: &str =
+    "op      requests errors    req/s   min ms  mean ms   p50 ms   p90 ms   p99 ms   max ms";
+
+/// Create `requests` fake entries, read them and search them
+/// with up to `concurrency` parallel requests.
+///
+/// The places are generated with the `seed`, so that repeated runs
+/// don't create the same places. The created entries are tagged with [BENCH_TAG]
+/// and archived afterwards.
+pub fn bench(
+    client: &OfdbClient,
+    requests: usize,
+    concurrency: usize,
+    seed: u64,
+) -> Vec<Latencies> {
+    log::info!("Generate the places with the seed {seed}");
+    let new_places: Vec<_> = fixtures::generate(requests, seed)
+        .new_places
+        .into_iter()
+        .map(|mut place| {
+            place.tags.push(BENCH_TAG.to_string());
+            place
+        })
+        .collect();
+
+    let (create, ids) = measure(Operation::Create, &new_places, concurrency, |place| {
+        client.create_place(place)
+    });
+    log::info!("Created {} entries", ids.len());
+    let ids: Vec<Uuid> = ids.iter().filter_map(|id| id.parse().ok()).collect();
+
+    let (read, _) = measure(Operation::Read, &ids, concurrency, |id| {
+        client.read_entries(vec![*id])
+    });
+    let (search, _) = measure(Operation::Search, &new_places, concurrency, |place| {
+        search_place(client, place)
+    });
+    archive(client, &ids);
+    vec![create, read, search]
+}
+
+/// A seed that differs between the runs.
+pub fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// Archive the entries that were created by the benchmark.
+fn archive(client: &OfdbClient, ids: &[Uuid]) {
+    log::info!("Archive {} entries", ids.len());
+    for chunk in ids.chunks(DEFAULT_READ_CHUNK_SIZE) {
+        let review = Review {
+            status: ReviewStatus::Archived,
+            comment: Some(format!("Created by the benchmark ({BENCH_TAG})")),
+        };
+        if let Err(err) = client.review(chunk.to_vec(), review) {
+            log::warn!(
+                "Unable to archive the entries of the benchmark, \
+                 archive the entries tagged '{BENCH_TAG}' manually: {err}"
+            );
+            return;
+        }
+    }
+}
+
+fn search_place(client: &OfdbClient, place: &NewPlace) -> Result<()> {
+    client.search(&place.title, &bbox_around((place.lat, place.lng), 1_000.0))?;
+    Ok(())
+}
+
+/// Call `request` for each item and return the latencies
+/// and the results of the successful requests.
+fn measure<T: Sync, R: Send>(
+    operation: Operation,
+    items: &[T],
+    concurrency: usize,
+    request: impl Fn(&T) -> Result<R> + Sync,
+) -> (Latencies, Vec<R>) {
+    log::info!("Measure {} {operation} requests", items.len());
+    let queue = Mutex::new(items.iter());
+    let (tx, rx) = mpsc::channel();
+    let started = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..concurrency.max(1) {
+            let tx = tx.clone();
+            let (queue, request) = (&queue, &request);
+            s.spawn(move || loop {
+                let Some(item) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let start = Instant::now();
+                let result = request(item);
+                if tx.send((start.elapsed(), result)).is_err() {
+                    break;
+                }
+            });
+        }
+    });
+    drop(tx);
+    let elapsed = started.elapsed();
+    let mut durations = vec![];
+    let mut results = vec![];
+    let mut errors = 0;
+    for (duration, result) in rx {
+        match result {
+            Ok(result) => {
+                durations.push(duration);
+                results.push(result);
+            }
+            Err(err) => {
+                log::warn!("{operation} request failed: {err}");
+                errors += 1;
+            }
+        }
+    }
+    (
+        Latencies::new(operation, durations, errors, elapsed),
+        results,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        let durations = (1..=100).rev().map(Duration::from_millis).collect();
+        let latencies = Latencies::new(Operation::Read, durations, 2, Duration::from_secs(2));
+        assert_eq!(latencies.requests, 102);
+        assert_eq!(latencies.requests_per_second, 51.0);
+        assert_eq!(latencies.min, 1.0);
+        assert_eq!(latencies.mean, 50.5);
+        assert_eq!(latencies.p50, 50.0);
+        assert_eq!(latencies.p90, 90.0);
+        assert_eq!(latencies.p99, 99.0);
+        assert_eq!(latencies.max, 100.0);
+
+        let latencies = Latencies::new(Operation::Search, vec![], 3, Duration::from_secs(1));
+        assert_eq!(latencies.p99, 0.0);
+        assert_eq!(latencies.errors, 3);
+    }
+}
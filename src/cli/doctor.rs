@@ -0,0 +1,196 @@
+//! Checks of the connectivity and configuration.
+
+use std::fmt;
+
+use ofdb_boundary::Credentials;
+use ofdb_core::gateways::geocode::GeoCodingGateway;
+use ofdb_entities::address::Address;
+use ofdb_gateways::opencage::OpenCage;
+
+use ofdb_cli::{compat, OfdbClient};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: CheckStatus, message: impl Into<String>) -> Self {
+        let message = message.into();
+        Self {
+            name,
+            status,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = match self.status {
+            CheckStatus::Ok => "✓",
+            CheckStatus::Warning => "!",
+            CheckStatus::Failed => "✗",
+            CheckStatus::Skipped => "-",
+        };
+        write!(f, "[{mark}] {}: {}", self.name, self.message)
+    }
+}
+
+/// Remove trailing slashes and append the API version if it's missing.
+pub fn normalize_api_url(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/');
+    let has_version = url
+        .rsplit_once('/')
+        .map(|(_, last)| {
+            last.strip_prefix('v')
+                .is_some_and(|v| !v.is_empty() && v.chars().all(|c| c.is_ascii_digit()))
+        })
+        .unwrap_or(false);
+    if has_version {
+        url.to_string()
+    } else {
+        format!("{url}/v0")
+    }
+}
+
+pub fn check_api_url(url: Option<&str>) -> (Check, Option<String>) {
+    const NAME: &str = "API URL";
+    let Some(url) = url else {
+        return (
+            Check::new(NAME, CheckStatus::Failed, "missing (use --api-url)"),
+            None,
+        );
+    };
+    let normalized = normalize_api_url(url);
+    let check = if normalized == url {
+        Check::new(NAME, CheckStatus::Ok, url)
+    } else {
+        Check::new(
+            NAME,
+            CheckStatus::Warning,
+            format!("'{url}' should be '{normalized}'"),
+        )
+    };
+    (check, Some(normalized))
+}
+
+pub fn check_connection(client: &OfdbClient) -> Check {
+    const NAME: &str = "Connection";
+    match client.server_version() {
+        Ok(version) => Check::new(
+            NAME,
+            CheckStatus::Ok,
+            format!("{} (server version {version})", client.api_url()),
+        ),
+        Err(err) => Check::new(NAME, CheckStatus::Failed, err.to_string()),
+    }
+}
+
+pub fn check_server_version(client: &OfdbClient) -> Check {
+    const NAME: &str = "Server version";
+    match compat::server_info(client) {
+        Ok(info) => match info.warning() {
+            Some(warning) => Check::new(NAME, CheckStatus::Warning, warning),
+            None => Check::new(
+                NAME,
+                CheckStatus::Ok,
+                format!(
+                    "{} is compatible with the API types {}",
+                    info.version_text,
+                    compat::API_TYPES_VERSION
+                ),
+            ),
+        },
+        Err(err) => Check::new(NAME, CheckStatus::Failed, err.to_string()),
+    }
+}
+
+pub fn check_opencage_api_key(key: Option<String>) -> Check {
+    const NAME: &str = "OpenCage API key";
+    if key.is_none() {
+        return Check::new(
+            NAME,
+            CheckStatus::Skipped,
+            "no key given (--opencage-api-key), addresses can't be geocoded",
+        );
+    }
+    let addr = Address {
+        city: Some("Berlin".to_string()),
+        country: Some("Deutschland".to_string()),
+        ..Default::default()
+    };
+    match OpenCage::new(key).resolve_address_lat_lng(&addr) {
+        Some(_) => Check::new(NAME, CheckStatus::Ok, "a test address was resolved"),
+        None => Check::new(
+            NAME,
+            CheckStatus::Failed,
+            "unable to resolve a test address (invalid key or no connection)",
+        ),
+    }
+}
+
+pub fn check_login(client: &OfdbClient, credentials: Option<Credentials>) -> Check {
+    const NAME: &str = "Login";
+    let Some(credentials) = credentials else {
+        return Check::new(
+            NAME,
+            CheckStatus::Skipped,
+            "no credentials given (--email, --password)",
+        );
+    };
+    let email = credentials.email.clone();
+    match client.login(&credentials) {
+        Ok(()) => Check::new(NAME, CheckStatus::Ok, format!("logged in as {email}")),
+        Err(err) => Check::new(NAME, CheckStatus::Failed, err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_urls() {
+        assert_eq!(
+            normalize_api_url("https://api.ofdb.io/v0"),
+            "https://api.ofdb.io/v0"
+        );
+        assert_eq!(
+            normalize_api_url("https://api.ofdb.io/v0/"),
+            "https://api.ofdb.io/v0"
+        );
+        assert_eq!(
+            normalize_api_url("https://api.ofdb.io/"),
+            "https://api.ofdb.io/v0"
+        );
+        assert_eq!(
+            normalize_api_url("http://localhost:6767"),
+            "http://localhost:6767/v0"
+        );
+        assert_eq!(
+            normalize_api_url("https://example.org/ofdb/v1"),
+            "https://example.org/ofdb/v1"
+        );
+    }
+
+    #[test]
+    fn check_missing_api_url() {
+        let (check, url) = check_api_url(None);
+        assert_eq!(check.status, CheckStatus::Failed);
+        assert!(url.is_none());
+        let (check, url) = check_api_url(Some("https://api.ofdb.io"));
+        assert_eq!(check.status, CheckStatus::Warning);
+        assert_eq!(url.as_deref(), Some("https://api.ofdb.io/v0"));
+    }
+}
@@ -0,0 +1,649 @@
+//! The import of new entries.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use clap::Args;
+use ofdb_boundary::NewPlace;
+use ofdb_core::gateways::geocode::GeoCodingGateway;
+
+use ofdb_cli::{
+    csv,
+    deadline::{Deadline, DeadlineExceeded},
+    import::{self, import_places_with, ImportResult, PlacesImport, Report},
+    kml,
+    metrics::{self, Phase, PhaseTimer},
+    pipeline,
+    report::{self, ReportFormat},
+    spreadsheet, state, tr, webhook, OfdbClient,
+};
+
+use super::progress;
+use crate::{
+    failure_reports, input_files, json_results, open_input, open_state, process_files,
+    skip_processed, with_server_check, Context, CsvArgs, FileOutcome, FileType, FilterArgs,
+    SpreadsheetArgs,
+};
+
+#[derive(Args)]
+pub struct ImportArgs {
+    #[clap(
+        required_unless_present_any = ["from_airtable", "from_google_sheet"],
+        num_args = 1..,
+        help = "JSON, CSV or KML files with entries (glob patterns like 'data/*.csv' are supported)"
+    )]
+    files: Vec<PathBuf>,
+    #[clap(flatten)]
+    spreadsheets: Box<SpreadsheetArgs>,
+    #[clap(
+        long = "report-file",
+        help = "File with the import report",
+        default_value = "import-report.json"
+    )]
+    report_file: PathBuf,
+    #[clap(
+        long = "report-format",
+        help = "Format of the report (json, html or csv)",
+        default_value = "json"
+    )]
+    report_format: ReportFormat,
+    #[clap(long = "opencage-api-key", help = "OpenCage API key")]
+    opencage_api_key: Option<String>,
+
+    #[clap(
+        long = "ignore-duplicates",
+        help = "create a new entry, even if it becomes a duplicate"
+    )]
+    ignore_duplicates: bool,
+    #[clap(
+        long = "validate-webhook",
+        help = "URL of an external service to validate each record"
+    )]
+    validate_webhook: Option<String>,
+    #[clap(
+        long = "org-tag",
+        help = "Tag of the organization (see --org-token) that is added to all entries"
+    )]
+    org_tag: Option<String>,
+    #[clap(
+        long = "only-failures",
+        help = "Only import the records of a previous report that were not imported (failed, invalid or never sent)"
+    )]
+    only_failures: Option<PathBuf>,
+    #[clap(
+        long = "stream",
+        help = "Import each CSV record right after reading it (for very large files); invalid records don't stop the import"
+    )]
+    stream: bool,
+    #[clap(
+        long = "chunk-size",
+        conflicts_with = "stream",
+        help = "Import the records in chunks of this size with a checkpoint after each chunk (for very large files)",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    chunk_size: Option<u64>,
+    #[clap(
+        long = "pause-between",
+        requires = "chunk_size",
+        help = "Pause between the chunks (e.g. '60s' or '5min')"
+    )]
+    pause_between: Option<humantime::Duration>,
+    #[clap(
+        long = "state-file",
+        help = "File with the processed records to resume an interrupted run (e.g. 'run.state.json')"
+    )]
+    state_file: Option<PathBuf>,
+    #[clap(
+        long = "kml-tags",
+        help = "Field of the extended data of KML placemarks with tags ('folder' is the name of the folder)"
+    )]
+    kml_tags: Vec<String>,
+    #[clap(
+        long = "kml-tag-map",
+        help = "Replace a value of the KML tag fields with other tags (e.g. 'Unverpackt=zero-waste,unverpackt')"
+    )]
+    kml_tag_map: Vec<kml::TagMapping>,
+    #[clap(flatten)]
+    pub csv: CsvArgs,
+    #[clap(flatten)]
+    filter: FilterArgs,
+}
+
+/// Import the entries of all input files and write a single report.
+pub fn run(ctx: &Context, args: ImportArgs) -> Result<()> {
+    let ImportArgs {
+        files,
+        spreadsheets,
+        report_file,
+        report_format,
+        opencage_api_key,
+        ignore_duplicates,
+        validate_webhook,
+        org_tag,
+        only_failures,
+        stream,
+        chunk_size,
+        pause_between,
+        state_file,
+        kml_tags,
+        kml_tag_map,
+        csv,
+        filter,
+    } = args;
+    if org_tag.is_some() && ctx.opt.org_token.is_none() {
+        bail!("An organization tag requires the token of the organization (--org-token)");
+    }
+    let client = with_server_check(ctx.opt.client()?);
+    let mut files = input_files(files)?;
+    let spreadsheets = read_spreadsheets(&ctx.opt.http_client()?, *spreadsheets, &mut files)?;
+    let mut state = open_state(state_file, "import")?;
+    let report_file = ctx.report_file(report_file, report_format);
+    let result = ctx.with_geo_coding(opencage_api_key, |geo_coding, usage, matches| {
+        let options = ImportOptions {
+            ignore_duplicates,
+            validate_webhook,
+            org_tag,
+            only_failures: only_failures
+                .map(|path| failure_reports(&path, &files))
+                .transpose()?,
+            stream,
+            chunk_size: chunk_size.map(|size| size as usize),
+            pause_between: pause_between.map(Into::into).unwrap_or_default(),
+            spreadsheets,
+            kml: kml::KmlOptions {
+                tag_fields: kml_tags,
+                tag_mappings: kml_tag_map,
+                tag_separator: csv.tag_separator,
+            },
+            csv: csv::CsvOptions {
+                geocoder_usage: Some(Arc::clone(usage)),
+                geocode_matches: Some(matches),
+                filter: filter.into(),
+                ..csv.into()
+            },
+        };
+        process_files(files, report_file, |path| {
+            import(
+                &client,
+                path,
+                geo_coding,
+                &options,
+                state.as_mut(),
+                ctx.deadline,
+            )
+        })
+    });
+    if ctx.opt.org_token.is_some() {
+        match client.count_pending_clearances() {
+            Ok(0) => {}
+            Ok(count) => log::info!("{count} entries are waiting for a clearance"),
+            Err(err) => log::warn!("Unable to count pending clearances: {err}"),
+        }
+    }
+    result
+}
+
+/// Read the online tables as CSV and add them to the input files.
+fn read_spreadsheets(
+    http: &reqwest::blocking::Client,
+    args: SpreadsheetArgs,
+    files: &mut Vec<PathBuf>,
+) -> Result<HashMap<PathBuf, Vec<u8>>> {
+    let SpreadsheetArgs {
+        from_airtable,
+        airtable_token,
+        from_google_sheet,
+        google_api_key,
+    } = args;
+    let mut spreadsheets = HashMap::new();
+    for table in from_airtable {
+        let token = airtable_token.as_deref().unwrap_or_default();
+        let csv = spreadsheet::airtable_csv(http, &table, token)?;
+        let name = PathBuf::from(format!("airtable:{table}"));
+        spreadsheets.insert(name.clone(), csv);
+        files.push(name);
+    }
+    for table in from_google_sheet {
+        let api_key = google_api_key.as_deref().unwrap_or_default();
+        let csv = spreadsheet::google_sheet_csv(http, &table, api_key)?;
+        let name = PathBuf::from(format!("google-sheet:{table}"));
+        spreadsheets.insert(name.clone(), csv);
+        files.push(name);
+    }
+    Ok(spreadsheets)
+}
+
+struct ImportOptions {
+    ignore_duplicates: bool,
+    validate_webhook: Option<String>,
+    org_tag: Option<String>,
+    /// Reports of a previous run.
+    only_failures: Option<Vec<report::FileReport<serde_json::Value>>>,
+    stream: bool,
+    /// Import the records in chunks of this size.
+    chunk_size: Option<usize>,
+    pause_between: Duration,
+    /// Records of online tables (as CSV) by their input name, e.g. `airtable:BASE/TABLE`.
+    spreadsheets: HashMap<PathBuf, Vec<u8>>,
+    /// Conversion of the placemarks of KML files.
+    kml: kml::KmlOptions,
+    csv: csv::CsvOptions,
+}
+
+fn import(
+    client: &OfdbClient,
+    path: PathBuf,
+    geo_coding: &dyn GeoCodingGateway,
+    options: &ImportOptions,
+    state: Option<&mut state::RunState>,
+    deadline: Deadline,
+) -> Result<FileOutcome<NewPlace>> {
+    let timer = PhaseTimer::start();
+    let requests = client.request_stats().snapshot();
+    let geo_coding = metrics::TimedGeoCoding::new(geo_coding, &timer);
+    let mut outcome = import_file(client, path, &geo_coding, options, state, &timer, deadline)?;
+    let metrics = timer.metrics(
+        outcome.report.record_count(),
+        &client.request_stats().since(&requests),
+    );
+    log::info!(
+        "Processed {} records in {} ({:.1} records/s, {} API requests)",
+        outcome.report.record_count(),
+        humantime::format_duration(Duration::from_millis(metrics.duration_ms)),
+        metrics.records_per_second,
+        metrics.requests
+    );
+    outcome.report.metrics = Some(metrics);
+    Ok(outcome)
+}
+
+fn import_file(
+    client: &OfdbClient,
+    path: PathBuf,
+    geo_coding: &dyn GeoCodingGateway,
+    options: &ImportOptions,
+    mut state: Option<&mut state::RunState>,
+    timer: &PhaseTimer,
+    deadline: Deadline,
+) -> Result<FileOutcome<NewPlace>> {
+    let file = path.display().to_string();
+    let mut csv_options = options.csv.clone();
+    if let Some(reports) = &options.only_failures {
+        let Some(section) = reports.iter().find(|r| r.file == file) else {
+            bail!("The report of --only-failures has no section '{file}'");
+        };
+        // The records that were never sent (e.g. because of invalid records) are imported too
+        let imported = report::imported_records(&section.report);
+        log::info!(
+            "Skip the {} records that were imported by the run of the report",
+            imported.len()
+        );
+        csv_options.filter.skip = imported;
+    }
+    skip_processed(&mut csv_options.filter, state.as_deref(), &file);
+    let input = open_import_input(client, &path, options)?;
+    let Some(chunk_size) = options.chunk_size else {
+        return import_records(
+            client,
+            &path,
+            input,
+            geo_coding,
+            &csv_options,
+            options,
+            state,
+            timer,
+            deadline,
+        );
+    };
+    // The input is read once, each chunk reads its records from memory
+    let (file_type, mut reader) = input;
+    let mut content = vec![];
+    reader.read_to_end(&mut content)?;
+    let mut report = Report::default();
+    let (offset, limit) = (csv_options.filter.offset, csv_options.filter.limit);
+    for chunk in 0.. {
+        let start = chunk * chunk_size;
+        let size = limit.map_or(chunk_size, |limit| {
+            limit.saturating_sub(start).min(chunk_size)
+        });
+        if size == 0 {
+            break;
+        }
+        if chunk > 0 && !options.pause_between.is_zero() {
+            log::info!(
+                "Pause for {} before the next chunk",
+                humantime::format_duration(options.pause_between)
+            );
+            std::thread::sleep(options.pause_between);
+        }
+        log::info!("Import chunk {} ({size} records)", chunk + 1);
+        let mut chunk_options = csv_options.clone();
+        chunk_options.filter.offset = offset + start;
+        chunk_options.filter.limit = Some(size);
+        let reader: Box<dyn BufRead + '_> = Box::new(content.as_slice());
+        let outcome = import_records(
+            client,
+            &path,
+            (file_type, reader),
+            geo_coding,
+            &chunk_options,
+            options,
+            state.as_deref_mut(),
+            timer,
+            deadline,
+        )?;
+        let invalid = outcome
+            .report
+            .csv_import_failures
+            .iter()
+            .any(|f| !f.deferred);
+        let complete = outcome.report.record_count() == size;
+        report.append(outcome.report);
+        if outcome.error.is_some() {
+            return Ok(FileOutcome {
+                report,
+                error: outcome.error,
+            });
+        }
+        if invalid {
+            log::warn!(
+                "Stop after chunk {} because of invalid records, fix them and run the import again",
+                chunk + 1
+            );
+            break;
+        }
+        if !complete {
+            break;
+        }
+    }
+    Ok(FileOutcome {
+        report,
+        error: None,
+    })
+}
+
+/// Open the input of an import, the placemarks of KML files are converted to CSV.
+fn open_import_input<'a>(
+    client: &OfdbClient,
+    path: &Path,
+    options: &'a ImportOptions,
+) -> Result<(FileType, Box<dyn BufRead + 'a>)> {
+    let (file_type, mut reader): (_, Box<dyn BufRead + 'a>) = match options.spreadsheets.get(path) {
+        Some(csv) => (FileType::Csv, Box::new(csv.as_slice())),
+        None => open_input(path, client.http_client())?,
+    };
+    log::info!(
+        "Import entries from file ({}): {}",
+        format!("{:?}", file_type).to_uppercase(),
+        path.display()
+    );
+    // The placemarks are imported like the records of a CSV file
+    Ok(match file_type {
+        FileType::Kml => {
+            let mut kml = String::new();
+            reader.read_to_string(&mut kml)?;
+            let csv = kml::placemarks_to_csv(&kml, &options.kml)?;
+            (FileType::Csv, Box::new(io::Cursor::new(csv)))
+        }
+        _ => (file_type, reader),
+    })
+}
+
+/// Import the records of an input that match the filter of the options.
+#[allow(clippy::too_many_arguments)]
+fn import_records(
+    client: &OfdbClient,
+    path: &Path,
+    (file_type, reader): (FileType, Box<dyn BufRead + '_>),
+    geo_coding: &dyn GeoCodingGateway,
+    csv_options: &csv::CsvOptions,
+    options: &ImportOptions,
+    mut state: Option<&mut state::RunState>,
+    timer: &PhaseTimer,
+    deadline: Deadline,
+) -> Result<FileOutcome<NewPlace>> {
+    let file = path.display().to_string();
+    let ImportOptions {
+        ignore_duplicates,
+        validate_webhook,
+        org_tag,
+        stream,
+        ..
+    } = options;
+    let ignore_duplicates = *ignore_duplicates;
+    if ignore_duplicates {
+        log::warn!(
+            "{}",
+            tr!("Ignore duplicates: create a new entry, even if it becomes a duplicate")
+        );
+    }
+    if *stream && file_type == FileType::Csv {
+        return import_stream(
+            client,
+            reader,
+            geo_coding,
+            csv_options,
+            options,
+            |result| checkpoint(state.as_deref_mut(), &file, result),
+            timer,
+            deadline,
+        );
+    }
+    let mut results = match file_type {
+        FileType::Json => {
+            if !csv_options.filter.conditions.is_empty() {
+                bail!("Filters are currently not supported for JSON files");
+            }
+            let places: Vec<NewPlace> = serde_json::from_reader(reader)?;
+            log::debug!("Read {} places from JSON file", places.len());
+            json_results(places, &csv_options.filter)
+        }
+        FileType::Csv => timer.time(Phase::Read, || {
+            csv::new_places_with_options(reader, geo_coding, csv_options)
+        })?,
+        FileType::Kml => unreachable!("KML files are converted to CSV"),
+    };
+    if let Some(tag) = org_tag {
+        import::add_tag(&mut results, tag);
+    }
+    let mut warnings = import::csv_import_warnings(&results);
+    let results = match validate_webhook {
+        Some(url) => {
+            let (results, mut webhook_warnings) =
+                webhook::validate_results(client.http_client(), url, results)?;
+            warnings.append(&mut webhook_warnings);
+            results
+        }
+        None => results,
+    };
+    // Deferred records are valid, so the geocoded records are imported anyway
+    if results
+        .iter()
+        .any(|r| r.result.is_err() && !r.is_deferred())
+    {
+        let mut report = Report::from(results);
+        report.csv_import_warnings = warnings;
+        log::warn!(
+            "{}",
+            tr!(
+                "{count} csv records contain errors",
+                count = report.csv_import_failures.len()
+            )
+        );
+        return Ok(FileOutcome {
+            report,
+            error: None,
+        });
+    }
+    let (results, deferred): (Vec<_>, Vec<_>) = results.into_iter().partition(|r| r.result.is_ok());
+    if !deferred.is_empty() {
+        log::warn!(
+            "{} records are deferred because the geocoding budget is used up",
+            deferred.len()
+        );
+    }
+    let deferred = Report::from(deferred).csv_import_failures;
+    let places: Vec<_> = results
+        .into_iter()
+        .map(|r| (r.record_nr, r.result.unwrap()))
+        .collect();
+    let check_duplicates = |new_place: &NewPlace| {
+        if ignore_duplicates {
+            Ok(None)
+        } else {
+            timer.time(Phase::DuplicateCheck, || {
+                client.search_duplicates(new_place)
+            })
+        }
+    };
+    let places_with_ids = places
+        .iter()
+        .map(|(record_nr, p)| (Some(record_nr.to_string()), p))
+        .collect();
+    let mut progress = progress::Progress::new("Import", places.len());
+    let PlacesImport {
+        results,
+        deadline_exceeded,
+    } = timer.time(Phase::Import, || {
+        import_places_with(
+            client,
+            places_with_ids,
+            check_duplicates,
+            deadline,
+            |result| {
+                checkpoint(state.as_deref_mut(), &file, result);
+                progress.inc(match &result.result {
+                    Ok(_) => progress::Outcome::Success,
+                    Err(import::Error::Duplicates(_)) => progress::Outcome::Duplicate,
+                    Err(_) => progress::Outcome::Failure,
+                })
+            },
+        )
+    })?;
+    progress.finish();
+    let mut report = Report::from(results);
+    report.csv_import_warnings = warnings;
+    report.csv_import_failures = deferred;
+    if !report.successes.is_empty() {
+        log::info!(
+            "{}",
+            tr!(
+                "Successfully imported {count} places",
+                count = report.successes.len()
+            )
+        );
+    }
+    if !report.duplicates.is_empty() {
+        log::warn!(
+            "{}",
+            tr!(
+                "Found {count} places with possible duplicates",
+                count = report.duplicates.len()
+            )
+        );
+    }
+    if !report.failures.is_empty() {
+        log::warn!(
+            "{}",
+            tr!(
+                "{count} places contain errors",
+                count = report.failures.len()
+            )
+        );
+    }
+    Ok(FileOutcome {
+        report,
+        error: deadline_exceeded.then(|| DeadlineExceeded.into()),
+    })
+}
+
+/// Save the record of a result as processed (the CLI uses the record number as import ID).
+fn checkpoint(state: Option<&mut state::RunState>, file: &str, result: &ImportResult<'_>) {
+    let record_nr = result.import_id.as_deref().and_then(|id| id.parse().ok());
+    if let (Some(state), Some(record_nr)) = (state, record_nr) {
+        state.checkpoint_result(file, record_nr, &result.result);
+    }
+}
+
+/// Read, geocode and import the records of a CSV file one after another.
+#[allow(clippy::too_many_arguments)]
+fn import_stream(
+    client: &OfdbClient,
+    reader: impl Read,
+    geo_coding: &dyn GeoCodingGateway,
+    csv_options: &csv::CsvOptions,
+    options: &ImportOptions,
+    mut on_processed: impl FnMut(&ImportResult<'_>),
+    timer: &PhaseTimer,
+    deadline: Deadline,
+) -> Result<FileOutcome<NewPlace>> {
+    let started = Instant::now();
+    let records = csv::stream_new_places(reader, geo_coding, csv_options.clone())?;
+    let records = timer.time_iter(Phase::Read, records);
+    let mut warnings = vec![];
+    let mut webhook_error = None;
+    let records = records.map_while(|mut record| {
+        warnings.extend(import::csv_import_warnings(std::slice::from_ref(&record)));
+        if let Some(tag) = &options.org_tag {
+            import::add_tag(std::slice::from_mut(&mut record), tag);
+        }
+        let Some(url) = &options.validate_webhook else {
+            return Some(record);
+        };
+        match webhook::validate_results(client.http_client(), url, vec![record]) {
+            Ok((mut validated, mut w)) => {
+                warnings.append(&mut w);
+                validated.pop()
+            }
+            Err(err) => {
+                webhook_error = Some(err);
+                None
+            }
+        }
+    });
+    let check_duplicates = |new_place: &NewPlace| {
+        if options.ignore_duplicates {
+            Ok(None)
+        } else {
+            timer.time(Phase::DuplicateCheck, || {
+                client.search_duplicates(new_place)
+            })
+        }
+    };
+    let mut progress = progress::Progress::unbounded("Import");
+    let outcome = pipeline::stream_places(client, records, check_duplicates, deadline, |result| {
+        on_processed(result);
+        progress.inc(match &result.result {
+            Ok(_) => progress::Outcome::Success,
+            Err(import::Error::Duplicates(_)) => progress::Outcome::Duplicate,
+            Err(_) => progress::Outcome::Failure,
+        })
+    })?;
+    progress.finish();
+    timer.add(
+        Phase::Import,
+        started.elapsed().saturating_sub(timer.get(Phase::Read)),
+    );
+    if let Some(err) = webhook_error {
+        return Err(err);
+    }
+    let mut report = outcome.report;
+    report.csv_import_warnings = warnings;
+    log::info!(
+        "Imported {} places, {} possible duplicates, {} failures and {} invalid records",
+        report.successes.len(),
+        report.duplicates.len(),
+        report.failures.len(),
+        report.csv_import_failures.len()
+    );
+    Ok(FileOutcome {
+        report,
+        error: outcome.deadline_exceeded.then(|| DeadlineExceeded.into()),
+    })
+}
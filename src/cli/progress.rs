@@ -0,0 +1,200 @@
+//! Progress of long-running commands.
+//!
+//! If stdout is a terminal, a progress bar is shown,
+//! otherwise the progress is logged periodically.
+
+use std::{
+    io::{self, IsTerminal},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Interval of the progress log messages without a progress bar.
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+static MULTI_PROGRESS: OnceLock<MultiProgress> = OnceLock::new();
+
+fn multi_progress() -> &'static MultiProgress {
+    MULTI_PROGRESS.get_or_init(MultiProgress::new)
+}
+
+/// Hides the progress bars while a log message is written.
+struct SuspendingLogger<L>(L);
+
+impl<L: Log> Log for SuspendingLogger<L> {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.0.enabled(record.metadata()) {
+            multi_progress().suspend(|| self.0.log(record));
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+/// Install a logger that doesn't mess up the progress bars.
+pub fn init_logger<L: Log + 'static>(logger: L, level: LevelFilter) -> anyhow::Result<()> {
+    log::set_boxed_logger(Box::new(SuspendingLogger(logger)))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Duplicate,
+    Failure,
+}
+
+pub struct Progress {
+    label: &'static str,
+    bar: Option<ProgressBar>,
+    /// Unknown if the input is streamed.
+    total: Option<u64>,
+    processed: u64,
+    successes: u64,
+    duplicates: u64,
+    failures: u64,
+    started: Instant,
+    last_log: Instant,
+}
+
+impl Progress {
+    pub fn new(label: &'static str, total: usize) -> Self {
+        let total = total as u64;
+        let bar = io::stdout().is_terminal().then(|| {
+            let bar = multi_progress().add(ProgressBar::new(total));
+            bar.set_style(
+                ProgressStyle::with_template(
+                    "{prefix} [{bar:40}] {pos}/{len} ({eta} remaining) {msg}",
+                )
+                .expect("valid template")
+                .progress_chars("=> "),
+            );
+            bar.set_prefix(label);
+            bar
+        });
+        Self::with_bar(label, bar, Some(total))
+    }
+
+    /// Progress of an input with an unknown number of records.
+    pub fn unbounded(label: &'static str) -> Self {
+        let bar = io::stdout().is_terminal().then(|| {
+            let bar = multi_progress().add(ProgressBar::new_spinner());
+            bar.set_style(
+                ProgressStyle::with_template("{prefix} {spinner} {pos} ({elapsed}) {msg}")
+                    .expect("valid template"),
+            );
+            bar.set_prefix(label);
+            bar
+        });
+        Self::with_bar(label, bar, None)
+    }
+
+    fn with_bar(label: &'static str, bar: Option<ProgressBar>, total: Option<u64>) -> Self {
+        let now = Instant::now();
+        Self {
+            label,
+            bar,
+            total,
+            processed: 0,
+            successes: 0,
+            duplicates: 0,
+            failures: 0,
+            started: now,
+            last_log: now,
+        }
+    }
+
+    pub fn inc(&mut self, outcome: Outcome) {
+        self.inc_by(outcome, 1);
+    }
+
+    pub fn inc_by(&mut self, outcome: Outcome, n: usize) {
+        let n = n as u64;
+        match outcome {
+            Outcome::Success => self.successes += n,
+            Outcome::Duplicate => self.duplicates += n,
+            Outcome::Failure => self.failures += n,
+        }
+        self.processed += n;
+        match &self.bar {
+            Some(bar) => {
+                bar.set_message(self.counters());
+                bar.inc(n);
+            }
+            None => {
+                let done = self.total.is_some_and(|total| self.processed >= total);
+                if self.last_log.elapsed() < LOG_INTERVAL && !done {
+                    return;
+                }
+                self.last_log = Instant::now();
+                match self.total {
+                    Some(total) => log::info!(
+                        "{}: {}/{total} ({}), {} remaining",
+                        self.label,
+                        self.processed,
+                        self.counters(),
+                        humantime::format_duration(self.eta())
+                    ),
+                    None => log::info!("{}: {} ({})", self.label, self.processed, self.counters()),
+                }
+            }
+        }
+    }
+
+    fn counters(&self) -> String {
+        let mut counters = format!("ok: {}", self.successes);
+        if self.duplicates > 0 {
+            counters.push_str(&format!(", duplicates: {}", self.duplicates));
+        }
+        counters.push_str(&format!(", failed: {}", self.failures));
+        counters
+    }
+
+    /// Estimated time remaining (rounded to seconds).
+    fn eta(&self) -> Duration {
+        if self.processed == 0 {
+            return Duration::ZERO;
+        }
+        let Some(total) = self.total else {
+            return Duration::ZERO;
+        };
+        let remaining = total.saturating_sub(self.processed);
+        let secs = self.started.elapsed().as_secs_f64() / self.processed as f64 * remaining as f64;
+        Duration::from_secs(secs.round() as u64)
+    }
+
+    pub fn finish(self) {
+        if let Some(bar) = self.bar {
+            bar.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_outcomes() {
+        let mut progress = Progress {
+            bar: None,
+            ..Progress::new("Test", 10)
+        };
+        progress.inc(Outcome::Success);
+        progress.inc_by(Outcome::Failure, 2);
+        assert_eq!(progress.processed, 3);
+        assert_eq!(progress.counters(), "ok: 1, failed: 2");
+        progress.inc(Outcome::Duplicate);
+        assert_eq!(progress.counters(), "ok: 1, duplicates: 1, failed: 2");
+    }
+}
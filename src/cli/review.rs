@@ -0,0 +1,69 @@
+//! The review of entries.
+
+use std::{fs::File, io, path::PathBuf};
+
+use anyhow::Result;
+
+use ofdb_cli::{csv, deadline::DeadlineExceeded, filter, report, review, tr, OfdbClient};
+
+use super::progress;
+use crate::{confirm, Context};
+
+/// Review the entries of a CSV file, grouped by the new status.
+pub fn review(
+    ctx: &Context,
+    client: &OfdbClient,
+    path: PathBuf,
+    filter: &filter::RecordFilter,
+) -> Result<()> {
+    log::info!("Read reviews from file: {}", path.display());
+    let file = File::open(path)?;
+    let reader = io::BufReader::new(file);
+    let reviews = csv::reviews_with_filter(reader, filter)?;
+    log::info!(
+        "{}",
+        tr!(
+            "{count} reviews where found in CSV file",
+            count = reviews.len()
+        )
+    );
+    let count = reviews.len();
+    let review_groups = review::group_reviews(reviews);
+    let hidden = review::count_hidden(&review_groups);
+    if hidden > 0 {
+        confirm(
+            &tr!(
+                "{count} entries will be archived or rejected and hidden on the map. Continue?",
+                count = hidden
+            ),
+            ctx.opt.yes,
+        )?;
+    }
+    let mut progress = progress::Progress::new("Review", count);
+    let mut summary = report::Summary::default();
+    let mut deadline_exceeded = false;
+    for (rev, uuids) in review_groups {
+        if ctx.deadline.is_expired() {
+            deadline_exceeded = true;
+            break;
+        }
+        log::debug!("Review the following place IDs: {uuids:#?}");
+        let count = uuids.len();
+        if let Err(err) = client.review(uuids.into_iter().collect(), rev) {
+            log::warn!("{}", tr!("Unable to review: {error}", error = err));
+            progress.inc_by(progress::Outcome::Failure, count);
+            summary.failures += count;
+        } else {
+            progress.inc_by(progress::Outcome::Success, count);
+            summary.successes += count;
+        }
+    }
+    progress.finish();
+    if ctx.opt.summary_json {
+        println!("{}", summary.json_line(None)?);
+    }
+    if deadline_exceeded {
+        return Err(DeadlineExceeded.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,429 @@
+//! The update of existing entries.
+
+use std::{
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+use ofdb_boundary::{Entry, UpdatePlace};
+use uuid::Uuid;
+
+use ofdb_cli::{
+    csv,
+    deadline::{Deadline, DeadlineExceeded},
+    diff, filter,
+    import::{
+        self, ConflictReport, CsvImportResult, CsvImportWarningReport, FailureKind, Report,
+        UpdateResult,
+    },
+    json_patch, lookup, remerge, state, tr, webhook, Error, OfdbClient,
+};
+
+use super::progress;
+use crate::{json_results, open_input, skip_processed, FileOutcome, FileType};
+
+pub struct UpdateOptions {
+    pub patch: bool,
+    pub validate_webhook: Option<String>,
+    pub links_file: Option<PathBuf>,
+    pub ignore_unknown_ids: bool,
+    /// Find the entries of a file without IDs.
+    pub match_by: Option<lookup::MatchBy>,
+    /// Re-apply the changes after a version conflict.
+    pub remerge: bool,
+    pub filter: filter::RecordFilter,
+    pub protect: diff::ProtectedFields,
+    pub patch_options: csv::PatchOptions,
+}
+
+pub fn update(
+    client: &OfdbClient,
+    path: PathBuf,
+    options: &UpdateOptions,
+    state: Option<&mut state::RunState>,
+    deadline: Deadline,
+) -> Result<FileOutcome<Entry>> {
+    let patch = options.patch;
+    let file = path.display().to_string();
+    let mut filter = options.filter.clone();
+    skip_processed(&mut filter, state.as_deref(), &file);
+    let filter = &filter;
+    let (file_type, reader) = open_input(&path, client.http_client())?;
+    log::info!(
+        "Update entries from file ({}): {}",
+        format!("{:?}", file_type).to_uppercase(),
+        path.display()
+    );
+    let results = match file_type {
+        FileType::Kml => bail!("KML files can only be imported"),
+        FileType::Json => {
+            if !filter.conditions.is_empty() {
+                bail!("Filters are currently not supported for JSON files");
+            }
+            if options.match_by.is_some() {
+                bail!("Only the entries of CSV files can be matched");
+            }
+            if patch {
+                json_patch::patch_places_from_json(reader, client, filter)?
+            } else {
+                let places: Vec<Entry> = serde_json::from_reader(reader)?;
+                log::debug!("Read {} places from JSON file", places.len());
+                json_results(places, filter)
+            }
+        }
+        FileType::Csv => {
+            let patch_options = &options.patch_options;
+            if patch {
+                csv::patch_places_with_filter(reader, client, filter, patch_options)?
+            } else if let Some(match_by) = &options.match_by {
+                lookup::match_entries(
+                    reader,
+                    client,
+                    match_by,
+                    filter,
+                    patch_options.tag_separator,
+                    patch_options.strict_columns,
+                )?
+            } else {
+                csv::places_with_filter(
+                    reader,
+                    filter,
+                    patch_options.tag_separator,
+                    patch_options.strict_columns,
+                )?
+            }
+        }
+    };
+    update_entries(client, results, options, state, &file, deadline)
+}
+
+/// Apply the patch of a template to all entries of the IDs file.
+pub fn patch_template(
+    client: &OfdbClient,
+    template: PathBuf,
+    ids_file: &Path,
+    options: &UpdateOptions,
+    state: Option<&mut state::RunState>,
+    deadline: Deadline,
+) -> Result<FileOutcome<Entry>> {
+    if !options.filter.is_empty() {
+        bail!("Filters are not supported for templates");
+    }
+    let (file_type, reader) = open_input(&template, client.http_client())?;
+    if file_type != FileType::Csv {
+        bail!("The template has to be a CSV file");
+    }
+    log::info!("Read IDs from file: {}", ids_file.display());
+    let uuids = csv::uuids_from_reader(io::BufReader::new(File::open(ids_file)?))?;
+    log::info!(
+        "Patch {} entries with template: {}",
+        uuids.len(),
+        template.display()
+    );
+    let mut results =
+        csv::patch_places_with_template(reader, uuids, client, &options.patch_options)?;
+    // The record numbers are the positions in the IDs file
+    let file = ids_file.display().to_string();
+    if let Some(processed) = state.as_deref().map(|s| s.processed(&file)) {
+        results.retain(|r| !processed.contains(&r.record_nr));
+    }
+    update_entries(client, results, options, state, &file, deadline)
+}
+
+fn update_entries(
+    client: &OfdbClient,
+    results: Vec<CsvImportResult<Entry>>,
+    options: &UpdateOptions,
+    mut state: Option<&mut state::RunState>,
+    file: &str,
+    deadline: Deadline,
+) -> Result<FileOutcome<Entry>> {
+    let UpdateOptions {
+        patch,
+        validate_webhook,
+        links_file,
+        ignore_unknown_ids,
+        protect,
+        remerge,
+        ..
+    } = options;
+    let patch = *patch;
+    if links_file.is_some() && protect.contains("links") {
+        bail!("The links are protected and can't be replaced by a links file");
+    }
+    // Unknown IDs of patches are already detected while reading the current entries
+    let current_entries = if patch && protect.is_empty() {
+        vec![]
+    } else {
+        let uuids = results
+            .iter()
+            .filter_map(|r| r.result.as_ref().ok())
+            .filter_map(|e| e.id.parse::<Uuid>().ok())
+            .collect::<Vec<_>>();
+        log::info!("Read the current state of {} entries", uuids.len());
+        client.read_entries(uuids)?
+    };
+    let results = if patch {
+        results
+    } else {
+        let known_ids = current_entries
+            .iter()
+            .filter_map(|e| e.id.parse().ok())
+            .collect();
+        csv::mark_unknown_ids(results, &known_ids)
+    };
+    let results = if protect.is_empty() {
+        results
+    } else {
+        csv::reject_protected_changes(results, &current_entries, protect)
+    };
+    let unknown_ids = csv::unknown_ids(&results);
+    if !unknown_ids.is_empty() {
+        log::warn!(
+            "{} entries do not exist on {}: {}",
+            unknown_ids.len(),
+            client.api_url(),
+            unknown_ids.join(", ")
+        );
+        if !ignore_unknown_ids {
+            let mut report = Report::from(results);
+            report.unknown_ids = unknown_ids;
+            let count = report.unknown_ids.len();
+            return Ok(FileOutcome {
+                report,
+                error: Some(anyhow!(
+                    "{count} IDs are unknown (maybe the file belongs to another instance), \
+                     use --ignore-unknown-ids to update the other entries"
+                )),
+            });
+        }
+    }
+    let mut warnings = import::csv_import_warnings(&results);
+    let results = match validate_webhook {
+        Some(url) => {
+            let (results, mut webhook_warnings) =
+                webhook::validate_results(client.http_client(), url, results)?;
+            warnings.append(&mut webhook_warnings);
+            results
+        }
+        None => results,
+    };
+    let csv_report = Report::from(results.clone());
+    if !csv_report.csv_import_failures.is_empty() {
+        log::warn!(
+            "{} csv records contain errors ",
+            csv_report.csv_import_failures.len()
+        );
+    }
+    let mut places: Vec<_> = results
+        .into_iter()
+        .filter_map(|r| r.result.ok().map(|p| (r.record_nr, p)))
+        .collect();
+    log::debug!("Update {} places", places.len());
+
+    if let Some(links_file) = links_file {
+        log::info!("Read custom links from file: {}", links_file.display());
+        let reader = io::BufReader::new(File::open(links_file)?);
+        let mut links = csv::custom_links_by_entry(csv::custom_link_records_from_reader(reader)?)?;
+        csv::replace_custom_links(places.iter_mut().map(|(_, place)| place), &mut links)?;
+        if !links.is_empty() {
+            log::warn!(
+                "The links file contains links of {} entries that are not updated",
+                links.len()
+            );
+        }
+    }
+
+    let total = places.len();
+    let mut progress = progress::Progress::new("Update", total);
+    let mut results = vec![];
+    let mut conflicts = vec![];
+    let mut deadline_exceeded = false;
+    for (i, (record_nr, entry)) in places.iter().enumerate() {
+        if deadline.is_expired() {
+            log::warn!(
+                "{}",
+                tr!(
+                    "Stop after updating {done} of {total} places",
+                    done = i,
+                    total = total
+                )
+            );
+            deadline_exceeded = true;
+            break;
+        }
+        let update = UpdatePlace::from(entry.clone());
+        let mut result = client.update_place(&entry.id, &update);
+        if *remerge && matches!(result, Err(Error::VersionConflict(_))) {
+            // The patches are based on the previous version
+            let base_version = entry.version - u64::from(patch);
+            let base = current_entries.iter().find(|e| e.id == entry.id);
+            let import_id = record_nr.to_string();
+            result = match update_remerged(client, entry, base, base_version, &import_id) {
+                Ok(Remerged::Updated { id, version }) => {
+                    warnings.push(CsvImportWarningReport {
+                        record_nr: *record_nr,
+                        warning: format!(
+                            "The entry was edited in the meantime (version {version}), \
+                             the changes were applied to the current version"
+                        ),
+                    });
+                    Ok(id)
+                }
+                Ok(Remerged::Conflict(conflict)) => {
+                    let fields = conflict
+                        .fields
+                        .iter()
+                        .map(|f| f.field.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let msg = format!(
+                        "The entry was edited in the meantime (version {}), \
+                         conflicting changes of: {fields}",
+                        conflict.current_version
+                    );
+                    conflicts.push(conflict);
+                    Err(Error::VersionConflict(msg))
+                }
+                Ok(Remerged::Failed(err)) => Err(err),
+                Err(err) => {
+                    log::warn!(
+                        "Could not re-apply the changes of '{}': {err}",
+                        update.title
+                    );
+                    result
+                }
+            };
+        }
+        let result = match result {
+            Ok(updated_id) => {
+                debug_assert!(updated_id == entry.id);
+                log::debug!(
+                    "Successfully updated '{}' with ID={}",
+                    update.title,
+                    entry.id
+                );
+                progress.inc(progress::Outcome::Success);
+                Ok(updated_id)
+            }
+            Err(err) => {
+                log::warn!(
+                    "{}",
+                    tr!(
+                        "Could not update '{title}': {error}",
+                        title = update.title,
+                        error = err
+                    )
+                );
+                progress.inc(progress::Outcome::Failure);
+                Err(import::Error::from(err))
+            }
+        };
+        results.push(UpdateResult {
+            place: entry,
+            import_id: Some(record_nr.to_string()),
+            result,
+        });
+        if let Some(state) = state.as_deref_mut() {
+            state.checkpoint_result(file, *record_nr, &results.last().unwrap().result);
+        }
+    }
+    progress.finish();
+    let mut report = Report::from(results);
+    report.csv_import_failures = csv_report.csv_import_failures;
+    report.csv_import_warnings = warnings;
+    report.unknown_ids = unknown_ids;
+    report.conflicts = conflicts;
+    let permission_denied = report
+        .failures
+        .iter()
+        .filter(|f| f.kind == FailureKind::PermissionDenied)
+        .count();
+    if permission_denied > 0 {
+        log::warn!(
+            "{}",
+            tr!(
+                "No permission to update {count} places",
+                count = permission_denied
+            )
+        );
+    }
+    if !report.failures.is_empty() {
+        log::warn!(
+            "{}",
+            tr!(
+                "{count} places could not be updated",
+                count = report.failures.len()
+            )
+        );
+    }
+    Ok(FileOutcome {
+        report,
+        error: deadline_exceeded.then(|| DeadlineExceeded.into()),
+    })
+}
+
+/// Number of attempts to update an entry that is edited in the meantime.
+const MAX_REMERGE_ATTEMPTS: usize = 3;
+
+/// Outcome of [update_remerged].
+enum Remerged {
+    /// The changes were applied to the current version.
+    Updated { id: String, version: u64 },
+    /// Both sides changed the same fields.
+    Conflict(ConflictReport),
+    /// The update failed, e.g. because the entry was edited again.
+    Failed(ofdb_cli::Error),
+}
+
+/// Re-apply the changes of an entry to its current version after a version conflict.
+fn update_remerged(
+    client: &OfdbClient,
+    entry: &Entry,
+    base: Option<&Entry>,
+    base_version: u64,
+    import_id: &str,
+) -> Result<Remerged> {
+    let mut outcome = Remerged::Failed(Error::VersionConflict(String::new()));
+    for _ in 0..MAX_REMERGE_ATTEMPTS {
+        match remerge::remerge(client, entry, base, base_version)? {
+            remerge::Remerge::Merged {
+                entry: merged,
+                theirs,
+            } => {
+                log::info!(
+                    "Re-apply the changes of '{}' to version {}",
+                    entry.title,
+                    theirs.version
+                );
+                outcome = match client.update_place(&entry.id, &UpdatePlace::from(merged)) {
+                    Ok(id) => {
+                        return Ok(Remerged::Updated {
+                            id,
+                            version: theirs.version,
+                        })
+                    }
+                    Err(err @ Error::VersionConflict(_)) => Remerged::Failed(err),
+                    Err(err) => return Ok(Remerged::Failed(err)),
+                };
+            }
+            remerge::Remerge::Conflicts {
+                base,
+                theirs,
+                conflicts,
+            } => {
+                return Ok(Remerged::Conflict(ConflictReport {
+                    import_id: Some(import_id.to_string()),
+                    entry_id: entry.id.clone(),
+                    title: theirs.title,
+                    base_version: base.version,
+                    current_version: theirs.version,
+                    fields: conflicts,
+                }));
+            }
+        }
+    }
+    Ok(outcome)
+}
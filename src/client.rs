@@ -0,0 +1,808 @@
+use ofdb_boundary::{
+    Credentials, Entry, MapBbox, NewPlace, NewPlaceRating, PlaceHistory, PlaceSearchResult, Rating,
+    Review, SearchResponse, TagFrequency, UpdatePlace,
+};
+use reqwest::{
+    blocking::{Client, ClientBuilder, RequestBuilder, Response},
+    header::CONTENT_TYPE,
+    StatusCode, Url,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    io::Write,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+use crate::{
+    clearance::{Clearance, PendingClearance, ResultCount},
+    compat::Capabilities,
+    export::ExportedPlaces,
+    http::{HttpOptions, DEFAULT_CONNECT_TIMEOUT, DEFAULT_TIMEOUT},
+    metrics::RequestStats,
+    rate_limit::{RateLimit, TokenBucket},
+    replay::{RecordedRequest, Recording, Replay},
+    search::{DuplicateSearch, SearchPages, SearchQuery},
+    tags::TagQuery,
+    trace::HttpTrace,
+    users::NewUser,
+    Error, Result,
+};
+
+/// Builder of the HTTP clients for the API.
+///
+/// The requests are limited by the timeouts of the [OfdbClient] instead of the HTTP client,
+/// so that streamed downloads are not aborted.
+pub(crate) fn http_client_builder() -> ClientBuilder {
+    Client::builder()
+        // Disable idle pool:
+        // see https://github.com/hyperium/hyper/issues/2136#issuecomment-861826148
+        .pool_max_idle_per_host(0)
+        .connect_timeout(DEFAULT_CONNECT_TIMEOUT)
+        .timeout(None)
+}
+
+/// Default number of entries that are read with one request.
+pub const DEFAULT_READ_CHUNK_SIZE: usize = 50;
+
+/// Client of the OpenFairDB JSON API.
+///
+/// ```no_run
+/// # fn main() -> ofdb_cli::Result<()> {
+/// let client = ofdb_cli::OfdbClient::new("https://dev.ofdb.io/v0")?;
+/// let uuid = "74030edff6034414a47a337c386913e1".parse().unwrap();
+/// let entries = client.read_entries(vec![uuid])?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OfdbClient {
+    api: String,
+    client: Client,
+    reauthentication: Option<Login>,
+    rate_limit: Option<Arc<TokenBucket>>,
+    org_token: Option<OrgToken>,
+    read_chunk_size: usize,
+    stats: Arc<RequestStats>,
+    trace: Option<HttpTrace>,
+    recording: Option<Arc<Recording>>,
+    replay: Option<Arc<Replay>>,
+    capabilities: Capabilities,
+    request_timeout: Option<Duration>,
+    stream_timeout: Option<Duration>,
+}
+
+/// API token of an organization.
+#[derive(Clone)]
+struct OrgToken(String);
+
+impl fmt::Debug for OrgToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OrgToken(..)")
+    }
+}
+
+#[derive(Clone)]
+struct Login {
+    email: String,
+    /// The password is only requested when the session expired, e.g. by a prompt.
+    password: Arc<dyn Fn() -> Result<String> + Send + Sync>,
+}
+
+impl fmt::Debug for Login {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Login")
+            .field("email", &self.email)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OfdbClient {
+    /// Create a new client with an enabled cookie store.
+    pub fn new(api_url: impl Into<String>) -> Result<Self> {
+        Self::with_http_options(api_url, &HttpOptions::default())
+    }
+
+    /// Create a new client with an enabled cookie store
+    /// and a timeout, proxy or additional certificates.
+    pub fn with_http_options(api_url: impl Into<String>, options: &HttpOptions) -> Result<Self> {
+        let client = options
+            .apply_connection(http_client_builder())?
+            .cookie_store(true)
+            .build()?;
+        Ok(Self::with_client(api_url, client).with_timeouts(options))
+    }
+
+    /// Create a new client that uses an existing HTTP client.
+    pub fn with_client(api_url: impl Into<String>, client: Client) -> Self {
+        let api = api_url.into();
+        Self {
+            api,
+            client,
+            reauthentication: None,
+            rate_limit: None,
+            org_token: None,
+            read_chunk_size: DEFAULT_READ_CHUNK_SIZE,
+            stats: Arc::default(),
+            trace: None,
+            recording: None,
+            replay: None,
+            capabilities: Capabilities::default(),
+            request_timeout: None,
+            stream_timeout: None,
+        }
+    }
+
+    /// Limit the duration of the requests (see [HttpOptions::timeout]
+    /// and [HttpOptions::stream_timeout]).
+    pub fn with_timeouts(mut self, options: &HttpOptions) -> Self {
+        self.request_timeout = Some(options.timeout.unwrap_or(DEFAULT_TIMEOUT));
+        self.stream_timeout = options.stream_timeout;
+        self
+    }
+
+    /// Send the API token of an organization with each request.
+    ///
+    /// The token is required to create or update entries
+    /// with tags that are owned by the organization
+    /// and to clear pending changes.
+    pub fn with_org_token(mut self, token: String) -> Self {
+        self.org_token = Some(OrgToken(token));
+        self
+    }
+
+    /// Login again (once) if a request fails because the session expired.
+    pub fn with_reauthentication(self, email: String, password: String) -> Self {
+        self.with_lazy_reauthentication(email, move || Ok(password.clone()))
+    }
+
+    /// Login again (once) if a request fails because the session expired,
+    /// with a password that is only requested then (e.g. by a prompt).
+    pub fn with_lazy_reauthentication(
+        mut self,
+        email: String,
+        password: impl Fn() -> Result<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.reauthentication = Some(Login {
+            email,
+            password: Arc::new(password),
+        });
+        self
+    }
+
+    /// Max. number of entries that are read with one request.
+    ///
+    /// All IDs are part of the URL, so large chunks might exceed
+    /// the URL length limit of the server or a proxy.
+    pub fn with_read_chunk_size(mut self, size: usize) -> Self {
+        self.read_chunk_size = size.max(1);
+        self
+    }
+
+    /// Log the requests and responses.
+    pub fn with_http_trace(mut self, trace: HttpTrace) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Record the requests and responses.
+    pub fn with_recording(mut self, recording: Arc<Recording>) -> Self {
+        self.recording = Some(recording);
+        self
+    }
+
+    /// Respond with recorded responses instead of sending the requests.
+    pub fn with_replay(mut self, replay: Arc<Replay>) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+
+    /// Use optional features of the server (see [crate::compat::server_info]).
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api
+    }
+
+    pub fn http_client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Number and latency of the requests of this client and its clones.
+    pub fn request_stats(&self) -> &RequestStats {
+        &self.stats
+    }
+
+    /// Limit the number of requests.
+    ///
+    /// Clones of the client share the same limit.
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(Arc::new(TokenBucket::new(limit)));
+        self
+    }
+
+    fn send(&self, request: RequestBuilder) -> Result<Response> {
+        self.send_with_timeout(request, self.request_timeout)
+    }
+
+    fn send_with_timeout(
+        &self,
+        request: RequestBuilder,
+        timeout: Option<Duration>,
+    ) -> Result<Response> {
+        let request = match timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        };
+        let request = match &self.org_token {
+            Some(OrgToken(token)) => request.bearer_auth(token),
+            None => request,
+        };
+        let request = request.build()?;
+        if let Some(replay) = &self.replay {
+            let request = RecordedRequest::new(&self.api, &request);
+            let description = request.to_string();
+            return replay
+                .respond(request)
+                .ok_or(Error::NotRecorded(description));
+        }
+        if let Some(bucket) = &self.rate_limit {
+            bucket.acquire();
+        }
+        let recorded = self
+            .recording
+            .as_ref()
+            .map(|_| RecordedRequest::new(&self.api, &request));
+        let started = Instant::now();
+        let response = match &self.trace {
+            Some(trace) => trace.execute(&self.client, request),
+            None => self.client.execute(request),
+        };
+        self.stats.record(started.elapsed());
+        match (&self.recording, recorded) {
+            (Some(recording), Some(request)) => Ok(recording.record(request, response?)?),
+            _ => Ok(response?),
+        }
+    }
+
+    fn reauthenticated<T>(&self, request: impl Fn() -> Result<T>) -> Result<T> {
+        match (request(), &self.reauthentication) {
+            (Err(Error::Unauthorized(msg)), Some(Login { email, password })) => {
+                log::warn!("The session is not valid anymore ({msg}), try to login again");
+                self.login(&Credentials {
+                    email: email.clone(),
+                    password: password()?,
+                })?;
+                request()
+            }
+            (result, _) => result,
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.api, path)
+    }
+
+    /// The URL of `path` with an additional percent-encoded path segment,
+    /// e.g. an e-mail address.
+    fn url_with_segment(&self, path: &str, segment: &str) -> Result<Url> {
+        let invalid = || Error::InvalidUrl(self.api.clone());
+        let mut url = Url::parse(&self.url(path)).map_err(|_| invalid())?;
+        url.path_segments_mut()
+            .map_err(|()| invalid())?
+            .pop_if_empty()
+            .push(segment);
+        Ok(url)
+    }
+
+    pub fn create_place(&self, new_place: &NewPlace) -> Result<String> {
+        self.reauthenticated(|| {
+            let res = self.send(self.client.post(self.url("entries")).json(&new_place))?;
+            self.handle_response(res)
+        })
+    }
+
+    pub fn update_place(&self, id: &str, place: &UpdatePlace) -> Result<String> {
+        self.reauthenticated(|| {
+            let mut place = place.clone();
+            place.version += 1;
+            let url = self.url(&format!("entries/{id}"));
+            let res = self.send(self.client.put(url).json(&place))?;
+            self.handle_response(res)
+        })
+    }
+
+    /// Read the entries in chunks and return them in the order of the IDs.
+    ///
+    /// Unknown IDs are skipped and each entry is only returned once.
+    pub fn read_entries(&self, uuids: Vec<Uuid>) -> Result<Vec<Entry>> {
+        log::debug!("Read {} places", uuids.len());
+        let mut all_entries = vec![];
+        for chunk in uuids.chunks(self.read_chunk_size) {
+            let mut entries = self.reauthenticated(|| {
+                let ids = join_uuids(chunk.iter().copied());
+                let url = self.url(&format!("entries/{ids}"));
+                let res = self.send(self.client.get(url))?;
+                self.handle_response(res)
+            })?;
+            all_entries.append(&mut entries);
+        }
+        Ok(in_order_of(&uuids, all_entries))
+    }
+
+    /// Login
+    ///
+    /// Important:
+    /// The
+    /// [cookie store](https://docs.rs/reqwest/0.11.1/reqwest/struct.ClientBuilder.html#method.cookie_store)
+    /// of the HTTP client should be enabled.
+    pub fn login(&self, req: &Credentials) -> Result<()> {
+        log::info!("Try to login with '{}' ", req.email);
+        let res = self.send(
+            self.client
+                .post(self.url("login"))
+                .header("Access-Control-Allow-Credentials", "true")
+                .json(&req),
+        )?;
+        self.handle_response(res)
+    }
+
+    /// Register a new user.
+    ///
+    /// The server sends an e-mail to confirm the address.
+    pub fn register_user(&self, user: &NewUser) -> Result<()> {
+        log::debug!("Register user '{}'", user.email);
+        let res = self.send(self.client.post(self.url("users")).json(user))?;
+        self.handle_response(res)
+    }
+
+    /// Delete a user account.
+    ///
+    /// Important:
+    /// This requires a login as this user or as admin.
+    pub fn delete_user(&self, email: &str) -> Result<()> {
+        self.reauthenticated(|| {
+            let url = self.url_with_segment("users", email)?;
+            let res = self.send(self.client.delete(url))?;
+            self.handle_response(res)
+        })
+    }
+
+    /// Request an e-mail to reset the password of a user.
+    ///
+    /// Resetting the password with the link of the e-mail
+    /// also confirms the e-mail address.
+    pub fn request_password_reset(&self, email: &str) -> Result<()> {
+        let res = self.send(
+            self.client
+                .post(self.url("users/reset-password-request"))
+                .json(&serde_json::json!({ "email": email })),
+        )?;
+        self.handle_response(res)
+    }
+
+    /// Logout, i.e. invalidate the session.
+    pub fn logout(&self) -> Result<()> {
+        let res = self.send(self.client.post(self.url("logout")))?;
+        self.handle_response(res)
+    }
+
+    pub fn review(&self, uuids: Vec<Uuid>, review: Review) -> Result<()> {
+        self.reauthenticated(|| {
+            let url = self.url(&format!(
+                "places/{}/review",
+                join_uuids(uuids.iter().copied())
+            ));
+            log::debug!("Send review of {} places to {url}", uuids.len());
+            let res = self.send(self.client.post(&url).json(&review))?;
+            self.handle_response(res)
+        })
+    }
+
+    /// Read all revisions of a place.
+    ///
+    /// Important:
+    /// This requires a login with scout or admin rights.
+    pub fn place_history(&self, uuid: Uuid) -> Result<PlaceHistory> {
+        self.reauthenticated(|| {
+            let url = self.url(&format!("places/{}/history", uuid.simple()));
+            let res = self.send(self.client.get(url))?;
+            self.handle_response(res)
+        })
+    }
+
+    /// Read the version of the server (a plain text response).
+    pub fn server_version(&self) -> Result<String> {
+        let res = self.send(self.client.get(self.url("server/version")))?;
+        if res.status().is_success() {
+            Ok(res.text()?.trim().to_string())
+        } else {
+            self.handle_response(res)
+        }
+    }
+
+    /// Count the entries with changes that are waiting for a clearance.
+    ///
+    /// Important:
+    /// This requires the token of an organization.
+    pub fn count_pending_clearances(&self) -> Result<u64> {
+        self.reauthenticated(|| {
+            let res = self.send(self.client.get(self.url("clearance/places/count")))?;
+            let ResultCount { count } = self.handle_response(res)?;
+            Ok(count)
+        })
+    }
+
+    /// Read the entries with changes that are waiting for a clearance.
+    ///
+    /// Important:
+    /// This requires the token of an organization.
+    pub fn pending_clearances(
+        &self,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Vec<PendingClearance>> {
+        let mut query = vec![];
+        if let Some(offset) = offset {
+            query.push(("offset", offset));
+        }
+        if let Some(limit) = limit {
+            query.push(("limit", limit));
+        }
+        self.reauthenticated(|| {
+            let url = self.url("clearance/places");
+            let res = self.send(self.client.get(url).query(&query))?;
+            self.handle_response(res)
+        })
+    }
+
+    /// Accept the changes of the entries up to the given revisions.
+    ///
+    /// Returns the number of cleared entries.
+    ///
+    /// Important:
+    /// This requires the token of an organization.
+    pub fn clear_places(&self, clearances: &[Clearance]) -> Result<u64> {
+        log::debug!("Clear {} places", clearances.len());
+        self.reauthenticated(|| {
+            let url = self.url("clearance/places");
+            let res = self.send(self.client.post(url).json(&clearances))?;
+            let ResultCount { count } = self.handle_response(res)?;
+            Ok(count)
+        })
+    }
+
+    pub fn create_rating(&self, rating: &NewPlaceRating) -> Result<()> {
+        self.reauthenticated(|| {
+            let res = self.send(self.client.post(self.url("ratings")).json(&rating))?;
+            self.handle_response(res)
+        })
+    }
+
+    pub fn read_ratings(&self, ids: Vec<String>) -> Result<Vec<Rating>> {
+        self.reauthenticated(|| {
+            let url = self.url(&format!("ratings/{}", ids.join(",")));
+            let res = self.send(self.client.get(url))?;
+            self.handle_response(res)
+        })
+    }
+
+    pub fn search(&self, txt: &str, bbox: &MapBbox) -> Result<SearchResponse> {
+        self.search_with(&SearchQuery {
+            text: txt.to_string(),
+            bbox: *bbox,
+            ..Default::default()
+        })
+    }
+
+    /// Search with all parameters.
+    ///
+    /// The number of results is limited by the server.
+    pub fn search_with(&self, query: &SearchQuery) -> Result<SearchResponse> {
+        self.reauthenticated(|| {
+            let res = self.send(self.client.get(self.url("search")).query(&query.params()))?;
+            self.handle_response(res)
+        })
+    }
+
+    /// Page through all results of a search.
+    ///
+    /// The `limit` of the query is used as the page size.
+    pub fn search_all(
+        &self,
+        query: SearchQuery,
+    ) -> SearchPages<impl FnMut(&SearchQuery) -> Result<SearchResponse> + '_> {
+        SearchPages::new(query, move |query: &SearchQuery| self.search_with(query))
+    }
+
+    /// Read all entries within the bounding box from the export endpoint.
+    ///
+    /// The entries are deserialized while they are received,
+    /// so large datasets don't have to be paged.
+    ///
+    /// Important:
+    /// This requires a login as scout or admin.
+    pub fn export_places(&self, bbox: &MapBbox, text: &str) -> Result<ExportedPlaces<Response>> {
+        let res = self.reauthenticated(|| self.request_export(bbox, text))?;
+        Ok(ExportedPlaces::from_reader(res))
+    }
+
+    /// Copy the CSV export of all entries within the bounding box to `w`.
+    ///
+    /// Returns the number of bytes.
+    ///
+    /// Important:
+    /// This requires a login as scout or admin.
+    pub fn export_places_csv(&self, bbox: &MapBbox, text: &str, w: &mut impl Write) -> Result<u64> {
+        let mut res = self.reauthenticated(|| self.request_export(bbox, text))?;
+        res.copy_to(w).map_err(Error::from)
+    }
+
+    fn request_export(&self, bbox: &MapBbox, text: &str) -> Result<Response> {
+        let MapBbox { sw, ne } = bbox;
+        let bbox_string = format!("{},{},{},{}", sw.lat, sw.lng, ne.lat, ne.lng);
+        let mut query = vec![("bbox", bbox_string)];
+        if !text.trim().is_empty() {
+            query.push(("text", text.trim().to_string()));
+        }
+        let res = self.send_with_timeout(
+            self.client
+                .get(self.url("export/entries.csv"))
+                .query(&query),
+            self.stream_timeout,
+        )?;
+        if res.status().is_success() {
+            Ok(res)
+        } else {
+            Err(self.error_response(res))
+        }
+    }
+
+    /// Count all entries of the instance.
+    pub fn count_entries(&self) -> Result<u64> {
+        self.reauthenticated(|| {
+            let res = self.send(self.client.get(self.url("count/entries")))?;
+            self.handle_response(res)
+        })
+    }
+
+    /// Count the different tags of the instance.
+    pub fn count_tags(&self) -> Result<u64> {
+        self.reauthenticated(|| {
+            let res = self.send(self.client.get(self.url("count/tags")))?;
+            self.handle_response(res)
+        })
+    }
+
+    /// Read the tags sorted by the number of entries.
+    pub fn most_popular_tags(&self, query: &TagQuery) -> Result<Vec<TagFrequency>> {
+        self.reauthenticated(|| {
+            let res = self.send(
+                self.client
+                    .get(self.url("entries/most-popular-tags"))
+                    .query(&query.params()),
+            )?;
+            self.handle_response(res)
+        })
+    }
+
+    pub fn search_duplicates(
+        &self,
+        new_place: &NewPlace,
+    ) -> Result<Option<Vec<PlaceSearchResult>>> {
+        self.reauthenticated(|| {
+            let res = self.send(
+                self.client
+                    .post(self.url("search/duplicates"))
+                    .json(&new_place),
+            )?;
+            let res: Vec<PlaceSearchResult> = self.handle_response(res)?;
+            Ok(if res.is_empty() { None } else { Some(res) })
+        })
+    }
+
+    /// Search duplicates within a distance and with a min. similarity of the titles.
+    ///
+    /// The parameters are forwarded to the server,
+    /// but servers that don't support them yet use their own criteria.
+    /// Therefore a usual search around the place is added
+    /// (unless the [Capabilities] of the server include the parameters)
+    /// and all results are filtered by the parameters.
+    pub fn search_duplicates_with(
+        &self,
+        new_place: &NewPlace,
+        options: &DuplicateSearch,
+    ) -> Result<Option<Vec<PlaceSearchResult>>> {
+        let mut results: Vec<PlaceSearchResult> = self.reauthenticated(|| {
+            let res = self.send(
+                self.client
+                    .post(self.url("search/duplicates"))
+                    .query(&options.params())
+                    .json(&new_place),
+            )?;
+            self.handle_response(res)
+        })?;
+        if !self.capabilities.duplicate_search_params {
+            results.extend(self.search_with(&options.query(new_place))?.visible);
+        }
+        let duplicates = options.filter(new_place, results);
+        Ok((!duplicates.is_empty()).then_some(duplicates))
+    }
+
+    fn handle_response<T>(&self, res: Response) -> Result<T>
+    where
+        T: for<'de> serde::Deserialize<'de>,
+    {
+        let status = res.status();
+        let content_type = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let body = res.text()?;
+        if status.is_success() {
+            serde_json::from_str(&body).map_err(|err| {
+                if is_json(&content_type, &body) {
+                    Error::Decode(err.to_string())
+                } else {
+                    not_json(&self.api, status, content_type, &body)
+                }
+            })
+        } else {
+            Err(self.error(status, content_type, &body))
+        }
+    }
+
+    /// The error of an unsuccessful response.
+    fn error_response(&self, res: Response) -> Error {
+        let status = res.status();
+        let content_type = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        match res.text() {
+            Ok(body) => self.error(status, content_type, &body),
+            Err(err) => err.into(),
+        }
+    }
+
+    fn error(&self, status: StatusCode, content_type: String, body: &str) -> Error {
+        if is_html(&content_type, body) {
+            return not_json(&self.api, status, content_type, body);
+        }
+        let message = serde_json::from_str::<ofdb_boundary::Error>(body)
+            .map(|err| err.message)
+            .unwrap_or_else(|_| status.to_string());
+        Error::from_status(status, message)
+    }
+}
+
+/// Sort the entries by the position of their ID.
+fn in_order_of(uuids: &[Uuid], entries: Vec<Entry>) -> Vec<Entry> {
+    let mut entries: HashMap<_, _> = entries
+        .into_iter()
+        .filter_map(|e| Some((e.id.parse::<Uuid>().ok()?, e)))
+        .collect();
+    uuids.iter().filter_map(|id| entries.remove(id)).collect()
+}
+
+fn join_uuids(uuids: impl IntoIterator<Item = Uuid>) -> String {
+    uuids
+        .into_iter()
+        .map(Uuid::simple)
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Max. number of characters of a response body that are shown in errors.
+const MAX_SNIPPET_LEN: usize = 120;
+
+fn is_json(content_type: &str, body: &str) -> bool {
+    content_type.contains("json") || body.trim_start().starts_with(['{', '['])
+}
+
+fn is_html(content_type: &str, body: &str) -> bool {
+    content_type.contains("html") || body.trim_start().starts_with('<')
+}
+
+/// Describe a response that is not JSON,
+/// e.g. the HTML page of a web server if the path of the API URL is wrong.
+fn not_json(api: &str, status: StatusCode, content_type: String, body: &str) -> Error {
+    let body = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut snippet: String = body.chars().take(MAX_SNIPPET_LEN).collect();
+    if snippet.len() < body.len() {
+        snippet.push('…');
+    }
+    let api = api.trim_end_matches('/');
+    let hint = (!api.ends_with("/v0")).then(|| format!("did you mean {api}/v0?"));
+    Error::NotJson {
+        status: status.as_u16(),
+        content_type,
+        snippet,
+        hint,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_encode_the_email_of_a_user_url() {
+        let client = OfdbClient::new("https://dev.ofdb.io/v0").unwrap();
+        let url = client
+            .url_with_segment("users", "a/b?c#d@example.org")
+            .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://dev.ofdb.io/v0/users/a%2Fb%3Fc%23d@example.org"
+        );
+    }
+
+    #[test]
+    fn keep_the_order_of_the_ids() {
+        let entries = crate::fixtures::generate(3, 7).entries;
+        let uuids: Vec<Uuid> = entries.iter().map(|e| e.id.parse().unwrap()).collect();
+        let unknown = Uuid::from_u128(1);
+        let ids = vec![uuids[2], unknown, uuids[0], uuids[2], uuids[1]];
+        let sorted = in_order_of(&ids, entries.clone());
+        let sorted_ids: Vec<_> = sorted.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(
+            sorted_ids,
+            vec![
+                entries[2].id.as_str(),
+                entries[0].id.as_str(),
+                entries[1].id.as_str()
+            ]
+        );
+    }
+
+    #[test]
+    fn suggest_api_version_if_the_server_returns_html() {
+        let body = "<!DOCTYPE html>\n<html>\n  <head><title>Karte von morgen</title></head></html>";
+        let err = not_json(
+            "https://api.ofdb.io/",
+            StatusCode::NOT_FOUND,
+            "text/html".to_string(),
+            body,
+        );
+        let msg = err.to_string();
+        assert!(
+            msg.contains("did you mean https://api.ofdb.io/v0?"),
+            "{msg}"
+        );
+        assert!(msg.contains("<!DOCTYPE html> <html> <head>"), "{msg}");
+        assert_eq!(err.status(), Some(404));
+
+        let err = not_json(
+            "https://api.ofdb.io/v0",
+            StatusCode::OK,
+            String::new(),
+            &"x".repeat(500),
+        );
+        let Error::NotJson { snippet, hint, .. } = err else {
+            panic!("unexpected error");
+        };
+        assert_eq!(hint, None);
+        assert_eq!(snippet.chars().count(), MAX_SNIPPET_LEN + 1);
+    }
+
+    #[test]
+    fn detect_content() {
+        assert!(is_json("application/json", ""));
+        assert!(is_json("", " [1,2]"));
+        assert!(!is_json("text/html", "<html>"));
+        assert!(is_html("text/html; charset=utf-8", ""));
+        assert!(is_html("", "<html>"));
+        assert!(!is_html("application/json", "{}"));
+    }
+}
@@ -0,0 +1,194 @@
+//! Compatibility of the API types with the version of the server.
+//!
+//! Instances run different server versions with slightly different fields,
+//! so the CLI warns if the server is older or newer than its API types
+//! and only uses optional features that the server supports.
+
+use std::{cmp::Ordering, fmt};
+
+use crate::OfdbClient;
+
+/// Version of the OpenFairDB API types the CLI is built with
+/// (the `rev` of `ofdb-boundary` in `Cargo.toml`).
+pub const API_TYPES_VERSION: Version = Version::new(0, 12, 7);
+
+/// First server version that supports `max_distance` and `min_similarity`
+/// in the search for duplicates.
+const DUPLICATE_SEARCH_PARAMS_SINCE: Version = Version::new(0, 13, 0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Find a version like `0.12.7`, `v0.12` or `0.12.7-dev` in the text.
+    pub fn parse(text: &str) -> Option<Self> {
+        text.split_whitespace().find_map(|word| {
+            let word = word.trim_start_matches('v');
+            let core = word.split(['-', '+']).next()?;
+            let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+            let major = parts.next()??;
+            let minor = parts.next()??;
+            let patch = parts.next().unwrap_or(Some(0))?;
+            parts
+                .next()
+                .is_none()
+                .then_some(Self::new(major, minor, patch))
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    /// Same major and minor version.
+    Compatible,
+    /// The server might not know all fields of the API types.
+    ServerOlder,
+    /// The server might have fields the CLI doesn't know.
+    ServerNewer,
+    /// The version of the server is unknown.
+    Unknown,
+}
+
+impl Compatibility {
+    pub fn of(server: Option<Version>) -> Self {
+        let Some(server) = server else {
+            return Self::Unknown;
+        };
+        let key = |v: Version| (v.major, v.minor);
+        match key(server).cmp(&key(API_TYPES_VERSION)) {
+            Ordering::Less => Self::ServerOlder,
+            Ordering::Equal => Self::Compatible,
+            Ordering::Greater => Self::ServerNewer,
+        }
+    }
+}
+
+/// Optional features of the server.
+///
+/// The default is the set of features that every supported server has.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The search for duplicates respects `max_distance` and `min_similarity`.
+    pub duplicate_search_params: bool,
+}
+
+impl Capabilities {
+    pub fn of(server: Option<Version>) -> Self {
+        let since = |version| server.is_some_and(|server| server >= version);
+        Self {
+            duplicate_search_params: since(DUPLICATE_SEARCH_PARAMS_SINCE),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerInfo {
+    /// The version as reported by the server.
+    pub version_text: String,
+    pub version: Option<Version>,
+    pub compatibility: Compatibility,
+    pub capabilities: Capabilities,
+}
+
+impl ServerInfo {
+    pub fn new(version_text: String) -> Self {
+        let version = Version::parse(&version_text);
+        Self {
+            version_text,
+            version,
+            compatibility: Compatibility::of(version),
+            capabilities: Capabilities::of(version),
+        }
+    }
+
+    /// A warning if the server and the API types might not fit together.
+    pub fn warning(&self) -> Option<String> {
+        let text = &self.version_text;
+        match self.compatibility {
+            Compatibility::Compatible => None,
+            Compatibility::ServerOlder => Some(format!(
+                "The server version {text} is older than the API types ({API_TYPES_VERSION}), \
+                 some fields might be ignored by the server"
+            )),
+            Compatibility::ServerNewer => Some(format!(
+                "The server version {text} is newer than the API types ({API_TYPES_VERSION}), \
+                 new fields of the server are not supported"
+            )),
+            Compatibility::Unknown => Some(format!(
+                "Unknown server version '{text}', assuming the API types {API_TYPES_VERSION}"
+            )),
+        }
+    }
+}
+
+/// Query the version of the server.
+pub fn server_info(client: &OfdbClient) -> crate::Result<ServerInfo> {
+    client.server_version().map(ServerInfo::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_versions() {
+        assert_eq!(Version::parse("0.12.7"), Some(Version::new(0, 12, 7)));
+        assert_eq!(Version::parse("v0.13"), Some(Version::new(0, 13, 0)));
+        assert_eq!(
+            Version::parse("openfairdb 1.2.3-dev+abc"),
+            Some(Version::new(1, 2, 3))
+        );
+        assert_eq!(Version::parse("unknown"), None);
+        assert_eq!(Version::parse("1.2.3.4"), None);
+    }
+
+    #[test]
+    fn compatibility_and_capabilities() {
+        let info = ServerInfo::new("0.12.3".to_string());
+        assert_eq!(info.compatibility, Compatibility::Compatible);
+        assert_eq!(info.warning(), None);
+        assert!(!info.capabilities.duplicate_search_params);
+
+        let info = ServerInfo::new("v0.13.1".to_string());
+        assert_eq!(info.compatibility, Compatibility::ServerNewer);
+        assert!(info.warning().is_some());
+        assert!(info.capabilities.duplicate_search_params);
+
+        let info = ServerInfo::new("0.11.0".to_string());
+        assert_eq!(info.compatibility, Compatibility::ServerOlder);
+        assert_eq!(
+            ServerInfo::new("dev".to_string()).capabilities,
+            Capabilities::default()
+        );
+    }
+
+    #[test]
+    fn api_types_version_matches_the_dependency() {
+        let manifest = include_str!("../Cargo.toml");
+        let rev = manifest
+            .lines()
+            .find(|l| l.starts_with("ofdb-boundary") && l.contains("rev ="))
+            .and_then(|l| l.split("rev =").nth(1))
+            .map(|rev| rev.trim_matches(|c| c == ' ' || c == '"' || c == '}'))
+            .unwrap();
+        assert_eq!(Version::parse(rev), Some(API_TYPES_VERSION));
+    }
+}
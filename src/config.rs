@@ -0,0 +1,311 @@
+use std::{collections::HashMap, env, ffi::OsString, fs, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+/// Environment variable to override the location of the config file.
+pub const CONFIG_FILE_ENV: &str = "OFDB_CONFIG";
+
+/// User configuration, read from `~/.config/ofdb/config.toml` by default.
+///
+/// Example:
+///
+/// ```toml
+/// [alias]
+/// archive-bochum = "review --email me@example.org archive-bochum.csv"
+///
+/// [smtp]
+/// host = "mail.example.org"
+/// username = "ofdb"
+/// from = "OpenFairDB Import <ofdb@example.org>"
+///
+/// [defaults]
+/// country = "Deutschland"
+/// license = "CC0-1.0"
+/// tags = ["bochum"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// User defined subcommand aliases.
+    pub alias: HashMap<String, String>,
+    /// Server to send reports by e-mail.
+    pub smtp: Option<SmtpConfig>,
+    /// Values of empty CSV columns (can be overridden by `--default-*`).
+    pub defaults: DefaultsConfig,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct DefaultsConfig {
+    pub country: Option<String>,
+    pub license: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Environment variable with the SMTP password
+/// (if it should not be stored in the config file).
+pub const SMTP_PASSWORD_ENV: &str = "OFDB_SMTP_PASSWORD";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// Defaults to the port of the security mode.
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sender of the e-mails.
+    pub from: String,
+}
+
+impl SmtpConfig {
+    pub fn password(&self) -> Option<String> {
+        self.password
+            .clone()
+            .or_else(|| env::var(SMTP_PASSWORD_ENV).ok())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Upgrade the connection with STARTTLS (port 587).
+    #[default]
+    Starttls,
+    /// Implicit TLS (port 465).
+    Tls,
+    /// Unencrypted, e.g. for a local relay (port 25).
+    None,
+}
+
+impl Config {
+    /// Load the config from the default location.
+    ///
+    /// A missing config file is not an error.
+    pub fn load() -> Result<Self> {
+        let Some(path) = config_file_path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        log::debug!("Read config from {}", path.display());
+        let content = fs::read_to_string(&path)?;
+        toml::from_str(&content)
+            .map_err(|err| anyhow!("Invalid config file {}: {err}", path.display()))
+    }
+
+    /// Replace the first user defined alias in the command line arguments
+    /// by its definition.
+    ///
+    /// `takes_value` tells if a global option (e.g. `--api-url`) is followed by a value,
+    /// so that the value is not mistaken for the subcommand.
+    pub fn expand_aliases(
+        &self,
+        args: Vec<OsString>,
+        takes_value: impl Fn(&str) -> bool,
+    ) -> Result<Vec<OsString>> {
+        if self.alias.is_empty() {
+            return Ok(args);
+        }
+        let mut expecting_value = false;
+        for (i, arg) in args.iter().enumerate().skip(1) {
+            if expecting_value {
+                expecting_value = false;
+                continue;
+            }
+            let Some(arg) = arg.to_str() else {
+                // Aliases and options are valid UTF-8.
+                break;
+            };
+            if let Some(definition) = self.alias.get(arg) {
+                let expanded = split_args(definition)?;
+                if expanded.is_empty() {
+                    return Err(anyhow!("The alias '{arg}' is empty"));
+                }
+                log::debug!("Expand alias '{arg}' to '{definition}'");
+                let mut expanded_args = args[..i].to_vec();
+                expanded_args.extend(expanded.into_iter().map(OsString::from));
+                expanded_args.extend_from_slice(&args[i + 1..]);
+                return Ok(expanded_args);
+            }
+            if arg.starts_with('-') {
+                expecting_value = !arg.contains('=') && takes_value(arg);
+                continue;
+            }
+            // This must be the (non-aliased) subcommand.
+            break;
+        }
+        Ok(args)
+    }
+}
+
+/// Path of the directory that contains the config and other user specific files.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("ofdb"))
+}
+
+fn config_file_path() -> Option<PathBuf> {
+    env::var_os(CONFIG_FILE_ENV)
+        .map(PathBuf::from)
+        .or_else(|| config_dir().map(|dir| dir.join("config.toml")))
+}
+
+/// Split a command line into arguments,
+/// respecting single and double quotes.
+fn split_args(s: &str) -> Result<Vec<String>> {
+    let mut args = vec![];
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    for c in s.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => {
+                quote = None;
+            }
+            (Some(_), c) => {
+                current.get_or_insert_with(String::new).push(c);
+            }
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            (None, c) => {
+                current.get_or_insert_with(String::new).push(c);
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err(anyhow!("Unbalanced quotes in '{s}'"));
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<OsString> {
+        s.split_whitespace().map(OsString::from).collect()
+    }
+
+    fn takes_value(flag: &str) -> bool {
+        flag == "--api-url"
+    }
+
+    fn config() -> Config {
+        let alias = [(
+            "archive-bochum".to_string(),
+            "review --email 'me@example.org' \"archive bochum.csv\"".to_string(),
+        )]
+        .into_iter()
+        .collect();
+        Config {
+            alias,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn split_quoted_args() {
+        assert_eq!(
+            split_args(r#"review --comment "foo bar" 'x y' z"#).unwrap(),
+            vec!["review", "--comment", "foo bar", "x y", "z"]
+        );
+        assert_eq!(split_args("a ''").unwrap(), vec!["a", ""]);
+        assert!(split_args("\"foo").is_err());
+    }
+
+    #[test]
+    fn parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [alias]
+            archive-bochum = "review --email me@example.org bochum.csv"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.alias.len(), 1);
+        assert!(config.smtp.is_none());
+
+        let config: Config = toml::from_str(
+            r#"
+            [smtp]
+            host = "localhost"
+            port = 2525
+            security = "none"
+            from = "ofdb@example.org"
+            "#,
+        )
+        .unwrap();
+        let smtp = config.smtp.unwrap();
+        assert_eq!(smtp.port, Some(2525));
+        assert_eq!(smtp.security, SmtpSecurity::None);
+        assert!(smtp.username.is_none());
+        assert!(config.defaults.country.is_none());
+
+        let config: Config = toml::from_str(
+            r#"
+            [defaults]
+            license = "CC0-1.0"
+            tags = ["bio", "regional"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.defaults.license.as_deref(), Some("CC0-1.0"));
+        assert_eq!(config.defaults.tags, vec!["bio", "regional"]);
+    }
+
+    #[test]
+    fn expand_alias() {
+        let expanded = config()
+            .expand_aliases(
+                args("ofdb --api-url http://localhost archive-bochum --password x"),
+                takes_value,
+            )
+            .unwrap();
+        assert_eq!(
+            expanded,
+            vec![
+                "ofdb",
+                "--api-url",
+                "http://localhost",
+                "review",
+                "--email",
+                "me@example.org",
+                "archive bochum.csv",
+                "--password",
+                "x"
+            ]
+        );
+    }
+
+    #[test]
+    fn only_expand_subcommands() {
+        let a = args("ofdb --api-url http://localhost read archive-bochum");
+        assert_eq!(config().expand_aliases(a.clone(), takes_value).unwrap(), a);
+        let a = args("ofdb --api-url archive-bochum read");
+        assert_eq!(config().expand_aliases(a.clone(), takes_value).unwrap(), a);
+    }
+
+    #[test]
+    fn expand_alias_after_boolean_flags() {
+        let expanded = config()
+            .expand_aliases(args("ofdb --summary-json archive-bochum"), takes_value)
+            .unwrap();
+        assert_eq!(
+            expanded,
+            args("ofdb --summary-json review --email me@example.org")
+                .into_iter()
+                .chain([OsString::from("archive bochum.csv")])
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Find the entries that contain an e-mail address or a phone number,
+//! e.g. to answer a request of a data subject (GDPR).
+
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use ofdb_boundary::Entry;
+use serde::Serialize;
+
+use crate::report::ENTRY_URL;
+
+/// Min. number of digits of a phone number,
+/// shorter numbers would match too many entries.
+const MIN_PHONE_DIGITS: usize = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Contact {
+    /// Lowercase e-mail address.
+    Email(String),
+    /// Digits of a phone number without the leading zeros.
+    Phone(String),
+}
+
+impl FromStr for Contact {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.contains('@') {
+            return Ok(Self::Email(s.to_lowercase()));
+        }
+        let digits = phone_digits(s);
+        if digits.len() < MIN_PHONE_DIGITS
+            || s.chars()
+                .any(|c| !c.is_ascii_digit() && !"+-/() ".contains(c))
+        {
+            bail!("'{s}' is neither an e-mail address nor a phone number");
+        }
+        Ok(Self::Phone(digits))
+    }
+}
+
+/// An entry that contains the contact.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContactMatch {
+    pub id: String,
+    pub title: String,
+    /// Fields that contain the contact.
+    pub fields: Vec<&'static str>,
+    pub url: String,
+}
+
+/// Find all entries that contain the contact.
+pub fn find_contact(entries: &[Entry], contact: &Contact) -> Vec<ContactMatch> {
+    entries
+        .iter()
+        .filter_map(|e| {
+            let fields = matching_fields(e, contact);
+            if fields.is_empty() {
+                return None;
+            }
+            Some(ContactMatch {
+                id: e.id.clone(),
+                title: e.title.clone(),
+                fields,
+                url: format!("{ENTRY_URL}{}", e.id),
+            })
+        })
+        .collect()
+}
+
+fn matching_fields(e: &Entry, contact: &Contact) -> Vec<&'static str> {
+    let mut fields = vec![];
+    match contact {
+        Contact::Email(email) => {
+            let contains = |text: &str| text.to_lowercase().contains(email.as_str());
+            if e.email.as_deref().is_some_and(contains) {
+                fields.push("email");
+            }
+            if contains(&e.description) {
+                fields.push("description");
+            }
+            if e.custom_links.iter().any(|l| contains(&l.url)) {
+                fields.push("links");
+            }
+        }
+        Contact::Phone(digits) => {
+            if e.telephone
+                .as_deref()
+                .is_some_and(|phone| same_phone_number(&phone_digits(phone), digits))
+            {
+                fields.push("telephone");
+            }
+        }
+    }
+    fields
+}
+
+/// Prefixes of phone numbers like `Tel.: 0234 123456`.
+const PHONE_PREFIXES: &[&str] = &["telefon", "phone", "mobil", "handy", "tel", "fon"];
+
+/// The parts of a combined contact like
+/// `Maria Muster <maria@example.org>, 0171 1234567`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ContactParts {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    /// The text that is neither the name, the e-mail address nor the phone number.
+    pub rest: Option<String>,
+}
+
+/// Split a combined contact into the name, the e-mail address and the phone number.
+///
+/// The parts are separated by `,`, `;` or new lines.
+/// Only the first name, e-mail address and phone number are used.
+pub fn split_contact(value: &str) -> ContactParts {
+    let mut parts = ContactParts::default();
+    let mut rest = vec![];
+    for part in value.split([',', ';', '\n']) {
+        let mut text = part.trim().to_string();
+        if parts.email.is_none() {
+            if let Some((email, remaining)) = take_email(&text) {
+                parts.email = Some(email);
+                text = remaining;
+            }
+        }
+        let text = text.trim_matches(|c: char| c.is_whitespace() || matches!(c, '"' | '\''));
+        if text.is_empty() {
+            continue;
+        }
+        if parts.phone.is_none() {
+            if let Some(phone) = phone_number(text) {
+                parts.phone = Some(phone.to_string());
+                continue;
+            }
+        }
+        if parts.name.is_none()
+            && text.chars().any(char::is_alphabetic)
+            && !text.chars().any(|c| c.is_ascii_digit())
+        {
+            parts.name = Some(text.to_string());
+            continue;
+        }
+        rest.push(text.to_string());
+    }
+    if !rest.is_empty() {
+        parts.rest = Some(rest.join(", "));
+    }
+    parts
+}
+
+/// Remove the e-mail address (`someone@example.org`, `<someone@example.org>`
+/// or `mailto:someone@example.org`) from the text.
+fn take_email(text: &str) -> Option<(String, String)> {
+    let word = text.split_whitespace().find(|w| w.contains('@'))?;
+    let email = word
+        .trim_matches(|c| matches!(c, '<' | '>' | '(' | ')' | '"'))
+        .trim_start_matches("mailto:");
+    let remaining = text.replacen(word, " ", 1);
+    Some((email.to_string(), remaining.trim().to_string()))
+}
+
+/// The phone number without a prefix like `Tel.:`.
+fn phone_number(text: &str) -> Option<&str> {
+    let lower = text.to_lowercase();
+    let number = PHONE_PREFIXES
+        .iter()
+        .find(|prefix| lower.starts_with(*prefix))
+        .and_then(|prefix| text.get(prefix.len()..))
+        .map(|number| number.trim_start_matches(|c: char| c.is_alphabetic() || ".: ".contains(c)))
+        .unwrap_or(text)
+        .trim();
+    let is_phone = phone_digits(number).len() >= MIN_PHONE_DIGITS
+        && number
+            .chars()
+            .all(|c| c.is_ascii_digit() || "+-/(). ".contains(c));
+    is_phone.then_some(number)
+}
+
+/// Digits of a phone number without the leading zeros,
+/// so that `+49 234 123456`, `0049 234 123456` and `0234 123456` can be compared.
+fn phone_digits(phone: &str) -> String {
+    phone
+        .chars()
+        .filter(char::is_ascii_digit)
+        .collect::<String>()
+        .trim_start_matches('0')
+        .to_string()
+}
+
+/// Numbers with and without country code are the same
+/// if the national number matches.
+fn same_phone_number(a: &str, b: &str) -> bool {
+    if a.len() < MIN_PHONE_DIGITS || b.len() < MIN_PHONE_DIGITS {
+        return false;
+    }
+    a.ends_with(b) || b.ends_with(a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_contacts() {
+        assert_eq!(
+            "Someone@Example.org ".parse::<Contact>().unwrap(),
+            Contact::Email("someone@example.org".to_string())
+        );
+        assert_eq!(
+            "+49 (234) 123-456".parse::<Contact>().unwrap(),
+            Contact::Phone("49234123456".to_string())
+        );
+        assert!("12 34".parse::<Contact>().is_err());
+        assert!("someone".parse::<Contact>().is_err());
+    }
+
+    #[test]
+    fn split_combined_contacts() {
+        assert_eq!(
+            split_contact("Maria Muster <maria@example.org>, 0171 1234567"),
+            ContactParts {
+                name: Some("Maria Muster".to_string()),
+                email: Some("maria@example.org".to_string()),
+                phone: Some("0171 1234567".to_string()),
+                rest: None,
+            }
+        );
+        assert_eq!(
+            split_contact("Tel.: +49 (234) 123-456; mailto:info@example.org\nMo-Fr 9-17"),
+            ContactParts {
+                name: None,
+                email: Some("info@example.org".to_string()),
+                phone: Some("+49 (234) 123-456".to_string()),
+                rest: Some("Mo-Fr 9-17".to_string()),
+            }
+        );
+        let parts = split_contact("\"Verein e.V.\", Herr Muster");
+        assert_eq!(parts.name.as_deref(), Some("Verein e.V."));
+        assert_eq!(parts.rest.as_deref(), Some("Herr Muster"));
+    }
+
+    #[test]
+    fn find_entries_with_email_or_phone_number() {
+        let mut entries = crate::fixtures::generate(3, 9).entries;
+        entries[0].email = Some("Someone@example.org".to_string());
+        entries[0].telephone = Some("0234 123456".to_string());
+        entries[1].email = None;
+        entries[1].description = "Kontakt: someone@example.org".to_string();
+        entries[2].email = Some("other@example.org".to_string());
+        entries[2].telephone = Some("0234 99123456".to_string());
+
+        let email = "someone@example.org".parse().unwrap();
+        let matches = find_contact(&entries, &email);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].fields, vec!["email"]);
+        assert_eq!(matches[1].fields, vec!["description"]);
+        assert!(matches[1].url.ends_with(&entries[1].id));
+
+        let phone = "+49 234 123456".parse().unwrap();
+        let matches = find_contact(&entries, &phone);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].id, entries[0].id);
+    }
+}
@@ -1,26 +1,48 @@
-use std::io::Read;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufRead, Read, Write},
+    marker::PhantomData,
+    sync::Arc,
+};
 
 use anyhow::{anyhow, Result};
-use csv::ReaderBuilder;
-use serde::Deserialize;
+use csv::{StringRecord, StringRecordsIntoIter, WriterBuilder};
+use email_address_parser::EmailAddress;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 use time::Date;
+use url::{Host, Url};
 use uuid::Uuid;
 
-use ofdb_boundary::{Address, CustomLink, Entry, NewPlace, Review, ReviewStatus};
+use ofdb_boundary::{
+    Address, CustomLink, Entry, MapBbox, MapPoint, NewPlace, NewPlaceRating, RatingContext, Review,
+    ReviewStatus,
+};
 use ofdb_core::gateways::geocode::GeoCodingGateway;
 use ofdb_gateways::opencage::*;
 
 use crate::{
+    address::AddressLocale,
+    contact,
+    diff::ProtectedFields,
+    filter::{RecordFilter, Selected},
+    geocoder_usage::GeocoderUsage,
+    headers::{self, struct_fields, ColumnReport, PLACE_SYNONYMS},
     import::{CsvImportError, CsvImportResult},
-    read_entries, Client,
+    opencage::{GeocodeMatch, GeocodeMatches},
+    opening_hours,
+    report::ERROR_COLUMN,
+    tags::{DescriptionHashtags, TagSeparator},
+    Client, OfdbApi, OfdbClient,
 };
 
 #[derive(Debug, Deserialize)]
 struct NewPlaceRecord {
     title: String,
     description: String,
+    #[serde(default, deserialize_with = "deserialize_coordinate")]
     lat: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_coordinate")]
     lng: Option<f64>,
     street: Option<String>,
     zip: Option<String>,
@@ -30,104 +52,919 @@ struct NewPlaceRecord {
     contact_name: Option<String>,
     contact_email: Option<String>,
     contact_phone: Option<String>,
+    /// Name, e-mail address and phone number in one column.
+    contact: Option<String>,
     opening_hours: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_date")]
     founded_on: Option<Date>,
+    #[serde(default)]
     tags: String,
     homepage: Option<String>,
+    #[serde(default)]
     license: String,
     image_url: Option<String>,
     image_link_url: Option<String>,
+    custom_link_title_0: Option<String>,
+    custom_link_title_1: Option<String>,
+    custom_link_title_2: Option<String>,
+    custom_link_title_3: Option<String>,
+    custom_link_title_4: Option<String>,
+    custom_link_description_0: Option<String>,
+    custom_link_description_1: Option<String>,
+    custom_link_description_2: Option<String>,
+    custom_link_description_3: Option<String>,
+    custom_link_description_4: Option<String>,
+    custom_link_url_0: Option<String>,
+    custom_link_url_1: Option<String>,
+    custom_link_url_2: Option<String>,
+    custom_link_url_3: Option<String>,
+    custom_link_url_4: Option<String>,
+    tag_0: Option<String>,
+    tag_1: Option<String>,
+    tag_2: Option<String>,
+    tag_3: Option<String>,
+    tag_4: Option<String>,
+    tag_5: Option<String>,
+    tag_6: Option<String>,
+    tag_7: Option<String>,
+    tag_8: Option<String>,
+    tag_9: Option<String>,
+}
+
+/// Values of empty (or missing) columns of new places,
+/// so they don't have to be repeated in every record.
+#[derive(Debug, Clone, Default)]
+pub struct RecordDefaults {
+    pub country: Option<String>,
+    pub license: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Options to read new places from CSV files.
+#[derive(Debug, Clone, Default)]
+pub struct CsvOptions {
+    /// The columns `lat` and `lng` are swapped.
+    ///
+    /// Without this option the coordinates are only swapped
+    /// if the latitude is out of range but the longitude is not.
+    pub swap_lat_lng: bool,
+    /// Places outside of this region are rejected,
+    /// e.g. if the geocoder found an address in another country.
+    pub expected_bbox: Option<MapBbox>,
+    /// Convert common human formats of the opening hours (e.g. `Mo-Fr 9-17 Uhr`)
+    /// into the OSM syntax.
+    pub fix_opening_hours: bool,
+    /// Reject opening hours that don't follow the OSM syntax instead of warning about them.
+    ///
+    /// The check only covers the commonly used subset of the syntax,
+    /// so some valid values are rejected.
+    pub strict_opening_hours: bool,
+    /// Max. length of text fields.
+    pub limits: FieldLimits,
+    /// Truncate text fields that are too long instead of rejecting them.
+    pub truncate: bool,
+    /// Only read the records that match this filter.
+    pub filter: RecordFilter,
+    /// Usage of the geocoder, to report records as deferred
+    /// if they were not geocoded because the budget was used up.
+    pub geocoder_usage: Option<Arc<GeocoderUsage>>,
+    /// Matches of the geocoder, to report the confidence of geocoded addresses.
+    pub geocode_matches: Option<Arc<GeocodeMatches>>,
+    /// Reject geocoded addresses with a lower confidence (1 - 10),
+    /// e.g. if only the city was found.
+    pub min_geocode_confidence: Option<u8>,
+    /// Clean up the addresses before they are geocoded.
+    pub normalize_addresses: Option<AddressLocale>,
+    pub defaults: RecordDefaults,
+    /// Add the hashtags of the description to the tags.
+    pub hashtags: DescriptionHashtags,
+    /// The separator of the `tags` column.
+    pub tag_separator: TagSeparator,
+    /// Reject files with unknown or missing columns.
+    pub strict_columns: bool,
+}
+
+/// A record of a CSV file.
+trait Record: DeserializeOwned {
+    /// Columns without a default value.
+    const REQUIRED_COLUMNS: &'static [&'static str];
+}
+
+impl Record for NewPlaceRecord {
+    const REQUIRED_COLUMNS: &'static [&'static str] = &["title", "description"];
+}
+
+impl Record for PlaceRecord {
+    const REQUIRED_COLUMNS: &'static [&'static str] = &[
+        "id",
+        "created",
+        "version",
+        "title",
+        "description",
+        "lat",
+        "lng",
+        "tags",
+        "license",
+    ];
+}
+
+impl Record for PatchPlaceRecord {
+    const REQUIRED_COLUMNS: &'static [&'static str] = &["id", "version"];
+}
+
+/// Read all records that match the filter together with their record number.
+fn filtered_records<R: Read, T: Record>(
+    r: R,
+    filter: &RecordFilter,
+    strict_columns: bool,
+) -> Result<Vec<(usize, csv::Result<T>)>> {
+    let mut records = FilteredRecords::new(r, filter.clone(), strict_columns)?;
+    let matching: Vec<_> = records.by_ref().collect();
+    if !filter.is_empty() {
+        log::info!(
+            "{} of {} records match the filter",
+            matching.len(),
+            records.total()
+        );
+    }
+    Ok(matching)
+}
+
+/// Records that match the filter, read one after another.
+struct FilteredRecords<R, T> {
+    rows: Selected<StringRecordsIntoIter<R>>,
+    headers: StringRecord,
+    record_type: PhantomData<T>,
+}
+
+impl<R: Read, T: Record> FilteredRecords<R, T> {
+    fn new(r: R, filter: RecordFilter, strict_columns: bool) -> Result<Self> {
+        let mut rdr = headers::reader(r, PLACE_SYNONYMS)?;
+        let headers = rdr.headers()?.clone();
+        let columns = ColumnReport::new(&headers, struct_fields::<T>(), T::REQUIRED_COLUMNS);
+        if columns.is_complete() {
+            log::info!("{columns}");
+        } else if strict_columns {
+            return Err(anyhow!("Invalid columns: {columns}"));
+        } else {
+            log::warn!("{columns}");
+        }
+        if !filter.is_empty() {
+            filter.check_columns(&headers)?;
+        }
+        Ok(Self {
+            rows: filter.select(&headers, rdr.into_records()),
+            headers,
+            record_type: PhantomData,
+        })
+    }
+
+    /// Number of records that were read so far.
+    fn total(&self) -> usize {
+        self.rows.total
+    }
+}
+
+impl<R: Read, T: DeserializeOwned> Iterator for FilteredRecords<R, T> {
+    type Item = (usize, csv::Result<T>);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (record_nr, record) = self.rows.next()?;
+        Some((
+            record_nr,
+            record.and_then(|record| record.deserialize(Some(&self.headers))),
+        ))
+    }
+}
+
+/// Max. length of the title of a place in the OpenFairDB.
+pub const MAX_TITLE_LEN: usize = 80;
+
+/// Max. length of the description of a place in the OpenFairDB.
+pub const MAX_DESCRIPTION_LEN: usize = 250;
+
+/// Max. number of characters of text fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldLimits {
+    pub title: usize,
+    pub description: usize,
+}
+
+impl Default for FieldLimits {
+    fn default() -> Self {
+        Self {
+            title: MAX_TITLE_LEN,
+            description: MAX_DESCRIPTION_LEN,
+        }
+    }
+}
+
+/// The (rough) bounding box of a country by its ISO 3166-1 alpha-2 code.
+pub fn country_bbox(code: &str) -> Option<MapBbox> {
+    let (sw_lat, sw_lng, ne_lat, ne_lng) = match code.to_uppercase().as_str() {
+        "AT" => (46.37, 9.53, 49.02, 17.16),
+        "BE" => (49.49, 2.54, 51.51, 6.41),
+        "CH" => (45.82, 5.96, 47.81, 10.49),
+        "DE" => (47.15, 5.72, 55.11, 15.09),
+        "DK" => (54.56, 8.07, 57.75, 15.20),
+        "FR" => (41.33, -5.14, 51.09, 9.56),
+        "LU" => (49.45, 5.73, 50.18, 6.53),
+        "NL" => (50.75, 3.36, 53.56, 7.23),
+        _ => return None,
+    };
+    Some(MapBbox {
+        sw: MapPoint {
+            lat: sw_lat,
+            lng: sw_lng,
+        },
+        ne: MapPoint {
+            lat: ne_lat,
+            lng: ne_lng,
+        },
+    })
+}
+
+/// Country codes that are supported by [country_bbox].
+pub const COUNTRY_CODES: &[&str] = &["AT", "BE", "CH", "DE", "DK", "FR", "LU", "NL"];
+
+fn check_expected_region(options: &CsvOptions, lat: f64, lng: f64) -> Result<()> {
+    let Some(MapBbox { sw, ne }) = &options.expected_bbox else {
+        return Ok(());
+    };
+    if (sw.lat..=ne.lat).contains(&lat) && (sw.lng..=ne.lng).contains(&lng) {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "The geo coordinates ({lat}, {lng}) are outside of the expected region"
+        ))
+    }
+}
+
+fn check_geocode_confidence(options: &CsvOptions, geocoding: Option<GeocodeMatch>) -> Result<()> {
+    match (geocoding, options.min_geocode_confidence) {
+        (Some(m), Some(min)) if m.confidence < min => Err(anyhow!(
+            "The address was only geocoded with confidence {} (min. {min})",
+            m.confidence
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Read a coordinate that may use a comma as decimal separator (e.g. `51,34`).
+fn deserialize_coordinate<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error as _;
+
+    let Some(s) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(None);
+    }
+    parse_coordinate(s)
+        .map(Some)
+        .ok_or_else(|| D::Error::custom(format!("invalid coordinate '{s}'")))
+}
+
+fn deserialize_required_coordinate<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error as _;
+
+    deserialize_coordinate(deserializer)?.ok_or_else(|| D::Error::custom("missing coordinate"))
+}
+
+fn parse_coordinate(s: &str) -> Option<f64> {
+    s.replace(',', ".")
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+}
+
+/// Read a date in one of the formats accepted by [parse_date].
+fn deserialize_date<'de, D>(deserializer: D) -> Result<Option<Date>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error as _;
+
+    let Some(s) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(None);
+    }
+    parse_date(s)
+        .map(Some)
+        .ok_or_else(|| D::Error::custom(format!("invalid date '{s}', {DATE_FORMATS}")))
+}
+
+const DATE_FORMATS: &str = "expected YYYY-MM-DD, DD.MM.YYYY or YYYY";
+
+/// Parse a date in the format `YYYY-MM-DD`, `DD.MM.YYYY` or `YYYY`
+/// (that is the first of January).
+pub(crate) fn parse_date(s: &str) -> Option<Date> {
+    let s = s.trim();
+    let (year, month, day) = if let Some((year, rest)) = s.split_once('-') {
+        let (month, day) = rest.split_once('-')?;
+        (year, month, day)
+    } else if let Some((day, rest)) = s.split_once('.') {
+        let (month, year) = rest.split_once('.')?;
+        (year, month, day)
+    } else {
+        (s, "1", "1")
+    };
+    if year.len() != 4 {
+        return None;
+    }
+    let year = year.parse().ok()?;
+    let month: u8 = month.parse().ok()?;
+    let day = day.parse().ok()?;
+    Date::from_calendar_date(year, month.try_into().ok()?, day).ok()
+}
+
+/// Check the records of new places without network access,
+/// i.e. addresses are not resolved.
+///
+/// The result of a valid record is its title.
+pub fn validate_new_places<R: Read>(
+    r: R,
+    options: &CsvOptions,
+) -> Result<Vec<CsvImportResult<String>>> {
+    log::info!("Validate entries from CSV");
+    let results =
+        filtered_records::<_, NewPlaceRecord>(r, &options.filter, options.strict_columns)?
+            .into_iter()
+            .map(|(record_nr, result)| {
+                let mut warnings = vec![];
+                let result = result
+                    .map_err(|err| CsvImportError::Record(err.to_string()))
+                    .and_then(|mut r| {
+                        warnings = prepare_new_place_record(&mut r, options)?;
+                        if let (Some(lat), Some(lng)) = (r.lat, r.lng) {
+                            check_expected_region(options, lat, lng).map_err(|err| {
+                                CsvImportError::AddressOrGeoCoordinates(err.to_string())
+                            })?;
+                        }
+                        Ok(r.title)
+                    });
+                CsvImportResult {
+                    warnings,
+                    ..CsvImportResult::new(record_nr, result)
+                }
+            })
+            .collect();
+    Ok(results)
+}
+
+/// Normalize and check a record before the address is resolved.
+///
+/// Returns the warnings of a valid record.
+fn prepare_new_place_record(
+    r: &mut NewPlaceRecord,
+    options: &CsvOptions,
+) -> Result<Vec<String>, CsvImportError> {
+    let tag_columns = [
+        r.tag_0.take(),
+        r.tag_1.take(),
+        r.tag_2.take(),
+        r.tag_3.take(),
+        r.tag_4.take(),
+        r.tag_5.take(),
+        r.tag_6.take(),
+        r.tag_7.take(),
+        r.tag_8.take(),
+        r.tag_9.take(),
+    ];
+    r.tags = options.tag_separator.split(&r.tags).join(",");
+    r.tags = merge_tag_columns(&r.tags, tag_columns);
+    apply_defaults(r, &options.defaults);
+    let hashtags = options.hashtags.apply(&mut r.description);
+    if !hashtags.is_empty() {
+        r.tags = [r.tags.as_str()]
+            .into_iter()
+            .chain(hashtags.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(",");
+    }
+    let mut warnings: Vec<_> = split_contact_column(r).into_iter().collect();
+    fix_coordinates(r, options);
+    warnings.extend(normalize_new_place_record_urls(r)?);
+    warnings.extend(check_opening_hours(&mut r.opening_hours, options)?);
+    check_field_lengths(r, options)?;
+    check_new_place_record(r)?;
+    Ok(warnings)
+}
+
+/// Fill the empty contact fields from a combined `contact` column
+/// and return the part of the contact that was not recognized.
+fn split_contact_column(r: &mut NewPlaceRecord) -> Option<String> {
+    let contact = r.contact.take()?;
+    let parts = contact::split_contact(&contact);
+    for (field, value) in [
+        (&mut r.contact_name, parts.name),
+        (&mut r.contact_email, parts.email),
+        (&mut r.contact_phone, parts.phone),
+    ] {
+        if field.is_none() {
+            *field = value;
+        }
+    }
+    parts
+        .rest
+        .map(|rest| format!("Unrecognized part of the contact '{contact}': {rest}"))
+}
+
+/// Add the tags of the columns `tag_0` … `tag_9` to the comma separated tags.
+fn merge_tag_columns(tags: &str, columns: [Option<String>; 10]) -> String {
+    let columns: Vec<_> = columns
+        .into_iter()
+        .flatten()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    if columns.is_empty() {
+        return tags.to_string();
+    }
+    Some(tags.to_string())
+        .filter(|tags| !tags.trim().is_empty())
+        .into_iter()
+        .chain(columns)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn apply_defaults(r: &mut NewPlaceRecord, defaults: &RecordDefaults) {
+    let is_empty = |value: &Option<String>| value.as_deref().map_or(true, |v| v.trim().is_empty());
+    if is_empty(&r.country) && defaults.country.is_some() {
+        r.country = defaults.country.clone();
+    }
+    if let Some(license) = &defaults.license {
+        if r.license.trim().is_empty() {
+            r.license = license.clone();
+        }
+    }
+    if !defaults.tags.is_empty() && normalize_tags(&r.tags).is_empty() {
+        r.tags = defaults.tags.join(",");
+    }
+}
+
+fn check_field_lengths(r: &mut NewPlaceRecord, options: &CsvOptions) -> Result<(), CsvImportError> {
+    let limits = options.limits;
+    for (name, value, max) in [
+        ("title", &mut r.title, limits.title),
+        ("description", &mut r.description, limits.description),
+    ] {
+        let len = value.chars().count();
+        if len <= max {
+            continue;
+        }
+        if !options.truncate {
+            return Err(CsvImportError::Validation(format!(
+                "The {name} is too long ({len} characters, max. {max})"
+            )));
+        }
+        log::warn!("Truncate the {name} '{value}' to {max} characters");
+        *value = truncate(value, max);
+    }
+    Ok(())
+}
+
+/// Shorten a text to `max` characters, ending with `…`.
+fn truncate(text: &str, max: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+    let mut truncated = text
+        .chars()
+        .take(max - 1)
+        .collect::<String>()
+        .trim_end()
+        .to_string();
+    truncated.push('…');
+    truncated
+}
+
+/// Returns a warning if the opening hours are invalid but not rejected.
+fn check_opening_hours(
+    field: &mut Option<String>,
+    options: &CsvOptions,
+) -> Result<Option<String>, CsvImportError> {
+    let Some(value) = field.take().filter(|v| !v.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let Err(err) = opening_hours::validate(&value) else {
+        *field = Some(value.trim().to_string());
+        return Ok(None);
+    };
+    let msg = match opening_hours::normalize(&value) {
+        Some(osm) if options.fix_opening_hours => {
+            log::info!("Convert opening hours '{value}' to '{osm}'");
+            *field = Some(osm);
+            return Ok(None);
+        }
+        Some(osm) => format!("Invalid opening hours '{value}' ({err}), did you mean '{osm}'?"),
+        None => format!("Invalid opening hours '{value}': {err}"),
+    };
+    if options.strict_opening_hours {
+        return Err(CsvImportError::Validation(msg));
+    }
+    *field = Some(value);
+    Ok(Some(msg))
+}
+
+fn fix_coordinates(r: &mut NewPlaceRecord, options: &CsvOptions) {
+    let (Some(lat), Some(lng)) = (r.lat, r.lng) else {
+        return;
+    };
+    let swap = if options.swap_lat_lng {
+        true
+    } else if lat.abs() > 90.0 && lng.abs() <= 90.0 {
+        log::warn!("Swap lat/lng of '{}' ({lat}, {lng})", r.title);
+        true
+    } else {
+        false
+    };
+    if swap {
+        r.lat = Some(lng);
+        r.lng = Some(lat);
+    }
+}
+
+fn check_new_place_record(r: &NewPlaceRecord) -> Result<(), CsvImportError> {
+    let invalid = |msg: String| Err(CsvImportError::Validation(msg));
+    for (name, value) in [
+        ("title", &r.title),
+        ("description", &r.description),
+        ("license", &r.license),
+    ] {
+        if value.trim().is_empty() {
+            return invalid(format!("The {name} is missing"));
+        }
+    }
+    if normalize_tags(&r.tags).is_empty() {
+        return invalid("At least one tag is required".to_string());
+    }
+    if let Some(email) = &r.contact_email {
+        if EmailAddress::parse(email.trim(), None).is_none() {
+            return invalid(format!("Invalid e-mail address '{email}'"));
+        }
+    }
+    let address_error = |msg: &str| Err(CsvImportError::AddressOrGeoCoordinates(msg.to_string()));
+    match (r.lat, r.lng) {
+        (Some(lat), Some(lng)) => {
+            if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lng) {
+                return address_error("The geo coordinates are out of range");
+            }
+        }
+        (Some(_), None) | (None, Some(_)) => {
+            return address_error("Both lat and lng are required");
+        }
+        (None, None) => {
+            if r.city.is_none() && r.zip.is_none() {
+                return address_error(
+                    "An address with city or zip code or geo coordinates (lat/lng) are required",
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Invalid custom links are skipped with a warning, the record is imported anyway.
+fn normalize_new_place_record_urls(r: &mut NewPlaceRecord) -> Result<Vec<String>, CsvImportError> {
+    normalize_url_field("homepage", &mut r.homepage)?;
+    normalize_url_field("image_url", &mut r.image_url)?;
+    normalize_url_field("image_link_url", &mut r.image_link_url)?;
+    let mut warnings = vec![];
+    for url in [
+        &mut r.custom_link_url_0,
+        &mut r.custom_link_url_1,
+        &mut r.custom_link_url_2,
+        &mut r.custom_link_url_3,
+        &mut r.custom_link_url_4,
+    ] {
+        if let Some(link) = url.take() {
+            match normalize_url(&link) {
+                Ok(normalized) => *url = normalized,
+                Err(err) => warnings.push(format!("Skip the invalid custom link {err}")),
+            }
+        }
+    }
+    Ok(warnings)
+}
+
+/// The custom links of the `custom_link_*_N` columns (links without URL are ignored).
+fn take_custom_links(r: &mut NewPlaceRecord) -> Vec<CustomLink> {
+    [
+        (
+            r.custom_link_url_0.take(),
+            r.custom_link_title_0.take(),
+            r.custom_link_description_0.take(),
+        ),
+        (
+            r.custom_link_url_1.take(),
+            r.custom_link_title_1.take(),
+            r.custom_link_description_1.take(),
+        ),
+        (
+            r.custom_link_url_2.take(),
+            r.custom_link_title_2.take(),
+            r.custom_link_description_2.take(),
+        ),
+        (
+            r.custom_link_url_3.take(),
+            r.custom_link_title_3.take(),
+            r.custom_link_description_3.take(),
+        ),
+        (
+            r.custom_link_url_4.take(),
+            r.custom_link_title_4.take(),
+            r.custom_link_description_4.take(),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(url, title, description)| construct_custom_link(url, title, description))
+    .collect()
+}
+
+/// Validate and normalize the URLs of an entry (see [normalize_url]).
+pub fn normalize_entry_urls(entry: &mut Entry) -> Result<(), CsvImportError> {
+    normalize_url_field("homepage", &mut entry.homepage)?;
+    normalize_url_field("image_url", &mut entry.image_url)?;
+    normalize_url_field("image_link_url", &mut entry.image_link_url)?;
+    for link in &mut entry.custom_links {
+        normalize_custom_link(link)?;
+    }
+    Ok(())
+}
+
+/// Validate and normalize only the URLs a patch changed,
+/// so that entries with already stored invalid URLs can still be patched.
+pub fn normalize_patched_urls(original: &Entry, patched: &mut Entry) -> Result<(), CsvImportError> {
+    if patched.homepage != original.homepage {
+        normalize_url_field("homepage", &mut patched.homepage)?;
+    }
+    if patched.image_url != original.image_url {
+        normalize_url_field("image_url", &mut patched.image_url)?;
+    }
+    if patched.image_link_url != original.image_link_url {
+        normalize_url_field("image_link_url", &mut patched.image_link_url)?;
+    }
+    for link in &mut patched.custom_links {
+        if !original.custom_links.iter().any(|l| l.url == link.url) {
+            normalize_custom_link(link)?;
+        }
+    }
+    Ok(())
+}
+
+fn normalize_custom_link(link: &mut CustomLink) -> Result<(), CsvImportError> {
+    link.url = normalize_url(&link.url)
+        .map_err(|err| CsvImportError::Validation(format!("Invalid custom link: {err}")))?
+        .ok_or_else(|| CsvImportError::Validation("Empty custom link URL".to_string()))?;
+    Ok(())
+}
+
+fn normalize_url_field(name: &str, field: &mut Option<String>) -> Result<(), CsvImportError> {
+    if let Some(url) = field.take() {
+        *field = normalize_url(&url)
+            .map_err(|err| CsvImportError::Validation(format!("Invalid {name}: {err}")))?;
+    }
+    Ok(())
+}
+
+/// Check that an URL is a valid `http` or `https` URL.
+///
+/// If the scheme is missing (e.g. `www.example.org`), `https://` is prepended.
+/// Empty values result in `None`.
+pub fn normalize_url(url: &str) -> Result<Option<String>, String> {
+    let url = url.trim();
+    if url.is_empty() {
+        return Ok(None);
+    }
+    let normalized = if url.contains("://") {
+        url.to_string()
+    } else {
+        let host = url.split(['/', '?', '#']).next().unwrap_or_default();
+        if !looks_like_a_domain(host) {
+            return Err(format!("'{url}' is not an URL"));
+        }
+        format!("https://{url}")
+    };
+    let parsed = Url::parse(&normalized).map_err(|err| format!("'{url}' ({err})"))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!(
+            "'{url}' (the scheme must be http or https, not '{}')",
+            parsed.scheme()
+        ));
+    }
+    match parsed.host() {
+        Some(Host::Domain(host)) if looks_like_a_domain(host) || host == "localhost" => {}
+        Some(Host::Ipv4(_) | Host::Ipv6(_)) => {}
+        _ => return Err(format!("'{url}' (invalid host)")),
+    }
+    Ok(Some(normalized))
+}
+
+fn looks_like_a_domain(host: &str) -> bool {
+    let host = host.split(':').next().unwrap_or_default();
+    host.contains('.')
+        && !host.starts_with('.')
+        && !host.ends_with('.')
+        && host
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '.' || c == '-')
+}
+
+/// Split comma separated tags and normalize them:
+/// lower case, without leading `#`, without duplicates.
+pub fn normalize_tags(tags: &str) -> Vec<String> {
+    let mut normalized: Vec<String> = vec![];
+    for tag in tags.split(',') {
+        let tag = tag.trim().trim_start_matches('#').trim().to_lowercase();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
 }
 
 pub fn new_places_from_reader<R: Read>(
     r: R,
     opencage_api_key: Option<String>,
 ) -> Result<Vec<CsvImportResult<NewPlace>>> {
-    log::info!("Read entries form CSV");
-    let mut rdr = ReaderBuilder::new().from_reader(r);
-
     if opencage_api_key.is_none() {
         log::warn!("No OpenCage API provided");
     }
-
     let geo_coding = OpenCage::new(opencage_api_key);
+    new_places_with_geo_coding(r, &geo_coding)
+}
 
-    let mut results = vec![];
+/// Read new places and resolve missing geo coordinates with the given gateway.
+pub fn new_places_with_geo_coding<R: Read>(
+    r: R,
+    geo_coding: &dyn GeoCodingGateway,
+) -> Result<Vec<CsvImportResult<NewPlace>>> {
+    new_places_with_options(r, geo_coding, &CsvOptions::default())
+}
 
-    for (record_nr, result) in rdr.deserialize().enumerate() {
-        match result {
-            Err(err) => {
-                results.push(CsvImportResult {
-                    record_nr,
-                    result: Err(CsvImportError::Record(err.to_string())),
-                });
-            }
-            Ok(r) => {
-                let NewPlaceRecord {
-                    title,
-                    street,
-                    zip,
-                    city,
-                    country,
-                    state,
-                    lat,
-                    lng,
-                    ..
-                } = r;
+/// Read new places with the given [CsvOptions].
+pub fn new_places_with_options<R: Read>(
+    r: R,
+    geo_coding: &dyn GeoCodingGateway,
+    options: &CsvOptions,
+) -> Result<Vec<CsvImportResult<NewPlace>>> {
+    log::info!("Read entries from CSV");
+    let mut places = stream_new_places(r, geo_coding, options.clone())?;
+    let results: Vec<_> = places.by_ref().collect();
+    if !options.filter.is_empty() {
+        log::info!(
+            "{} of {} records match the filter",
+            results.len(),
+            places.records.total()
+        );
+    }
+    Ok(results)
+}
 
-                log::info!(
-                    "Check address and geo location for entry '{}' ({:?})",
-                    title,
-                    city
-                );
-                let addr = Address {
-                    street,
-                    zip,
-                    city,
-                    country,
-                    state,
-                };
-                match check_address_and_geo_coordinates(&geo_coding, addr, lat, lng) {
-                    Ok((addr, (lat, lng))) => {
-                        let new_place = NewPlace {
-                            title,
-                            description: r.description,
-                            lat,
-                            lng,
-                            city: addr.city,
-                            country: addr.country,
-                            state: addr.state,
-                            street: addr.street,
-                            zip: addr.zip,
-                            contact_name: r.contact_name,
-                            email: r.contact_email,
-                            founded_on: r.founded_on,
-                            homepage: r.homepage,
-                            categories: vec![],
-                            license: r.license,
-                            links: vec![],
-                            opening_hours: r.opening_hours,
-                            tags: r.tags.split(',').map(ToString::to_string).collect(),
-                            telephone: r.contact_phone,
-                            image_url: r.image_url,
-                            image_link_url: r.image_link_url,
-                        };
-                        results.push(CsvImportResult {
-                            record_nr,
-                            result: Ok(new_place),
-                        });
-                    }
-                    Err(err) => {
-                        results.push(CsvImportResult {
-                            record_nr,
-                            result: Err(CsvImportError::AddressOrGeoCoordinates(err.to_string())),
-                        });
-                    }
-                }
-            }
+/// Read new places one after another,
+/// so that large files can be processed without holding all records in memory.
+///
+/// Records are only geocoded when they are requested from the iterator.
+pub fn stream_new_places<R: Read>(
+    r: R,
+    geo_coding: &dyn GeoCodingGateway,
+    options: CsvOptions,
+) -> Result<NewPlaces<'_, R>> {
+    let records = FilteredRecords::new(r, options.filter.clone(), options.strict_columns)?;
+    Ok(NewPlaces {
+        records,
+        geo_coding,
+        options,
+    })
+}
+
+/// New places of a CSV file, see [stream_new_places].
+pub struct NewPlaces<'g, R> {
+    records: FilteredRecords<R, NewPlaceRecord>,
+    geo_coding: &'g dyn GeoCodingGateway,
+    options: CsvOptions,
+}
+
+impl<R: Read> Iterator for NewPlaces<'_, R> {
+    type Item = CsvImportResult<NewPlace>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let (record_nr, result) = self.records.next()?;
+        Some(new_place_from_record(
+            record_nr,
+            result,
+            self.geo_coding,
+            &self.options,
+        ))
+    }
+}
+
+fn new_place_from_record(
+    record_nr: usize,
+    result: csv::Result<NewPlaceRecord>,
+    geo_coding: &dyn GeoCodingGateway,
+    options: &CsvOptions,
+) -> CsvImportResult<NewPlace> {
+    let mut r = match result {
+        Ok(r) => r,
+        Err(err) => {
+            return CsvImportResult::new(record_nr, Err(CsvImportError::Record(err.to_string())));
         }
+    };
+    let warnings = match prepare_new_place_record(&mut r, options) {
+        Ok(warnings) => warnings,
+        Err(err) => return CsvImportResult::new(record_nr, Err(err)),
+    };
+    let links = take_custom_links(&mut r);
+    let NewPlaceRecord {
+        title,
+        street,
+        zip,
+        city,
+        country,
+        state,
+        lat,
+        lng,
+        ..
+    } = r;
+
+    log::info!(
+        "Check address and geo location for entry '{}' ({:?})",
+        title,
+        city
+    );
+    let addr = Address {
+        street,
+        zip,
+        city,
+        country,
+        state,
+    };
+    // Only the query of the geocoder is normalized, the address of the entry is kept
+    let mut query = addr.clone();
+    if let Some(locale) = options.normalize_addresses {
+        crate::address::normalize(&mut query, locale);
+    }
+    let deferred = || options.geocoder_usage.as_ref().map(|u| u.deferred());
+    let deferred_before = deferred();
+    let geocoded = lat.zip(lng).is_none();
+    let mut geocoding = None;
+    let checked = check_address_and_geo_coordinates(geo_coding, query, lat, lng).and_then(
+        |(query, (lat, lng))| {
+            if geocoded {
+                geocoding = options
+                    .geocode_matches
+                    .as_ref()
+                    .and_then(|matches| matches.get(&query.into()));
+            }
+            check_geocode_confidence(options, geocoding)?;
+            check_expected_region(options, lat, lng)?;
+            Ok((lat, lng))
+        },
+    );
+    let result = match checked {
+        Ok((lat, lng)) => Ok(NewPlace {
+            title,
+            description: r.description,
+            lat,
+            lng,
+            city: addr.city,
+            country: addr.country,
+            state: addr.state,
+            street: addr.street,
+            zip: addr.zip,
+            contact_name: r.contact_name,
+            email: r.contact_email,
+            founded_on: r.founded_on,
+            homepage: r.homepage,
+            categories: vec![],
+            license: r.license,
+            links,
+            opening_hours: r.opening_hours,
+            tags: normalize_tags(&r.tags),
+            telephone: r.contact_phone,
+            image_url: r.image_url,
+            image_link_url: r.image_link_url,
+        }),
+        Err(_) if deferred() != deferred_before => Err(CsvImportError::GeocodingDeferred),
+        Err(err) => Err(CsvImportError::AddressOrGeoCoordinates(err.to_string())),
+    };
+    CsvImportResult {
+        record_nr,
+        result,
+        geocoding,
+        warnings,
     }
-    Ok(results)
 }
 
 #[derive(Debug, Deserialize)]
@@ -137,7 +974,9 @@ struct PlaceRecord {
     version: u64,
     title: String,
     description: String,
+    #[serde(deserialize_with = "deserialize_required_coordinate")]
     lat: f64,
+    #[serde(deserialize_with = "deserialize_required_coordinate")]
     lng: f64,
     street: Option<String>,
     zip: Option<String>,
@@ -148,7 +987,9 @@ struct PlaceRecord {
     contact_email: Option<String>,
     contact_phone: Option<String>,
     opening_hours: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_date")]
     founded_on: Option<Date>,
+    // A missing column would remove all tags of the entry
     tags: String,
     ratings: Vec<String>,
     homepage: Option<String>,
@@ -173,21 +1014,40 @@ struct PlaceRecord {
     custom_link_url_3: Option<String>,
     custom_link_url_4: Option<String>,
     custom_link_url_5: Option<String>,
+    tag_0: Option<String>,
+    tag_1: Option<String>,
+    tag_2: Option<String>,
+    tag_3: Option<String>,
+    tag_4: Option<String>,
+    tag_5: Option<String>,
+    tag_6: Option<String>,
+    tag_7: Option<String>,
+    tag_8: Option<String>,
+    tag_9: Option<String>,
 }
 
 pub fn places_from_reader<R: Read>(r: R) -> Result<Vec<CsvImportResult<Entry>>> {
+    places_with_filter(r, &RecordFilter::default(), TagSeparator::default(), false)
+}
+
+/// Read the entries that match the filter.
+pub fn places_with_filter<R: Read>(
+    r: R,
+    filter: &RecordFilter,
+    tag_separator: TagSeparator,
+    strict_columns: bool,
+) -> Result<Vec<CsvImportResult<Entry>>> {
     log::info!("Read entries form CSV");
-    let mut rdr = ReaderBuilder::new().from_reader(r);
     let mut results = vec![];
 
-    for (record_nr, result) in rdr.deserialize().enumerate() {
+    for (record_nr, result) in filtered_records::<_, PlaceRecord>(r, filter, strict_columns)? {
         match result {
             Err(err) => {
                 log::warn!("Invalid CSV entry: {err}");
-                results.push(CsvImportResult {
+                results.push(CsvImportResult::new(
                     record_nr,
-                    result: Err(CsvImportError::Record(err.to_string())),
-                });
+                    Err(CsvImportError::Record(err.to_string())),
+                ));
             }
             Ok(r) => {
                 let PlaceRecord {
@@ -228,6 +1088,16 @@ pub fn places_from_reader<R: Read>(r: R) -> Result<Vec<CsvImportResult<Entry>>>
                     custom_link_url_3,
                     custom_link_url_4,
                     custom_link_url_5,
+                    tag_0,
+                    tag_1,
+                    tag_2,
+                    tag_3,
+                    tag_4,
+                    tag_5,
+                    tag_6,
+                    tag_7,
+                    tag_8,
+                    tag_9,
                     ..
                 } = r;
 
@@ -235,7 +1105,15 @@ pub fn places_from_reader<R: Read>(r: R) -> Result<Vec<CsvImportResult<Entry>>>
                 let categories = vec![];
                 let telephone = r.contact_phone;
                 let email = r.contact_email;
-                let tags = r.tags.split(',').map(ToString::to_string).collect();
+                let tags = merge_tag_columns(
+                    &tag_separator.split(&r.tags).join(","),
+                    [
+                        tag_0, tag_1, tag_2, tag_3, tag_4, tag_5, tag_6, tag_7, tag_8, tag_9,
+                    ],
+                )
+                .split(',')
+                .map(ToString::to_string)
+                .collect();
 
                 if custom_link_url_5.is_some()
                     || custom_link_title_5.is_some()
@@ -275,7 +1153,7 @@ pub fn places_from_reader<R: Read>(r: R) -> Result<Vec<CsvImportResult<Entry>>>
                 .flatten()
                 .collect();
 
-                let place = Entry {
+                let mut place = Entry {
                     id,
                     created,
                     version,
@@ -302,16 +1180,71 @@ pub fn places_from_reader<R: Read>(r: R) -> Result<Vec<CsvImportResult<Entry>>>
                     image_link_url,
                     ratings,
                 };
-                results.push(CsvImportResult {
-                    record_nr,
-                    result: Ok(place),
-                });
+                let result = normalize_entry_urls(&mut place).map(|()| place);
+                results.push(CsvImportResult::new(record_nr, result));
             }
         }
     }
     Ok(results)
 }
 
+/// Turn all entries that are not known by the instance into failures.
+pub fn mark_unknown_ids(
+    results: Vec<CsvImportResult<Entry>>,
+    known_ids: &HashSet<Uuid>,
+) -> Vec<CsvImportResult<Entry>> {
+    results
+        .into_iter()
+        .map(|mut r| {
+            r.result = r.result.and_then(|entry| match entry.id.parse::<Uuid>() {
+                Ok(uuid) if known_ids.contains(&uuid) => Ok(entry),
+                _ => Err(CsvImportError::UnknownId(entry.id)),
+            });
+            r
+        })
+        .collect()
+}
+
+/// Reject all records that modify a protected field of the current entries.
+pub fn reject_protected_changes(
+    results: Vec<CsvImportResult<Entry>>,
+    current_entries: &[Entry],
+    protected: &ProtectedFields,
+) -> Vec<CsvImportResult<Entry>> {
+    results
+        .into_iter()
+        .map(|mut r| {
+            let record_nr = r.record_nr;
+            r.result = r.result.and_then(|entry| {
+                let Some(current) = current_entries.iter().find(|e| e.id == entry.id) else {
+                    return Ok(entry);
+                };
+                let modified = protected.modified(current, &entry);
+                if modified.is_empty() {
+                    return Ok(entry);
+                }
+                log::warn!("Record {record_nr} modifies protected fields: {modified:?}");
+                Err(CsvImportError::Validation(format!(
+                    "Protected fields can't be modified: {}",
+                    modified.join(", ")
+                )))
+            });
+            r
+        })
+        .collect()
+}
+
+/// IDs of all records that are not known by the instance.
+pub fn unknown_ids<T>(results: &[CsvImportResult<T>]) -> Vec<String> {
+    results
+        .iter()
+        .filter_map(|r| match &r.result {
+            Err(CsvImportError::UnknownId(id)) => Some(id.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
 fn construct_custom_link(
     url: Option<String>,
     title: Option<String>,
@@ -328,10 +1261,29 @@ pub fn patch_places_with_reader<R: Read>(
     r: R,
     api: &str,
     client: &Client,
+) -> Result<Vec<CsvImportResult<Entry>>> {
+    patch_places_with_api(r, &OfdbClient::with_client(api, client.clone()))
+}
+
+/// Read patches and apply them to the current state of the entries read via `api`.
+pub fn patch_places_with_api<R: Read>(
+    r: R,
+    api: &impl OfdbApi,
+) -> Result<Vec<CsvImportResult<Entry>>> {
+    patch_places_with_filter(r, api, &RecordFilter::default(), &PatchOptions::default())
+}
+
+/// Read the patches that match the filter and apply them (see [patch_places_with_api]).
+pub fn patch_places_with_filter<R: Read>(
+    r: R,
+    api: &impl OfdbApi,
+    filter: &RecordFilter,
+    options: &PatchOptions,
 ) -> Result<Vec<CsvImportResult<Entry>>> {
     log::info!("Read entries form CSV");
 
-    let (patch_place_records, mut results) = patches_from_reader(r)?;
+    let (patch_place_records, mut results) =
+        patches_from_reader(r, filter, options.strict_columns)?;
 
     let uuids: Vec<_> = patch_place_records
         .iter()
@@ -339,50 +1291,144 @@ pub fn patch_places_with_reader<R: Read>(
         .collect();
 
     log::info!("Read current state of all {} entries", uuids.len());
-    let mut original_entries = read_entries(api, client, uuids)?;
+    let mut original_entries = api.read_entries(uuids)?;
 
-    for (_, record_nr, record) in patch_place_records {
-        let index = original_entries
+    for (uuid, record_nr, record) in patch_place_records {
+        let Some(index) = original_entries
             .iter()
-            .position(|x| x.id == record.id)
-            .unwrap();
+            .position(|x| x.id.parse::<Uuid>().ok() == Some(uuid))
+        else {
+            log::warn!("Entry {uuid} does not exist");
+            results.push(CsvImportResult::new(
+                record_nr,
+                Err(CsvImportError::UnknownId(record.id)),
+            ));
+            continue;
+        };
         let original = original_entries.remove(index);
-        match patch_place(original, record) {
-            Ok(place) => {
-                results.push(CsvImportResult {
-                    record_nr,
-                    result: Ok(place),
-                });
+        match patch_place_with(original.clone(), record, options) {
+            Ok(mut place) => {
+                let result = normalize_patched_urls(&original, &mut place).map(|()| place);
+                results.push(CsvImportResult::new(record_nr, result));
             }
             Err(err) => {
-                results.push(CsvImportResult {
+                results.push(CsvImportResult::new(
                     record_nr,
-                    result: Err(CsvImportError::PatchRequest(err.to_string())),
-                });
+                    Err(CsvImportError::PatchRequest(err.to_string())),
+                ));
             }
         }
     }
     Ok(results)
 }
 
+/// Apply the single patch of a template to all entries (see [patch_places_with_api]).
+///
+/// The `id` and `version` columns of the template are optional
+/// because they are set for each entry.
+pub fn patch_places_with_template<R: Read>(
+    template: R,
+    uuids: Vec<Uuid>,
+    api: &impl OfdbApi,
+    options: &PatchOptions,
+) -> Result<Vec<CsvImportResult<Entry>>> {
+    let (headers, record) = template_from_reader(template)?;
+    log::info!("Read current state of all {} entries", uuids.len());
+    let original_entries = api.read_entries(uuids.clone())?;
+    let mut results = vec![];
+    for (record_nr, uuid) in uuids.into_iter().enumerate() {
+        let Some(original) = original_entries
+            .iter()
+            .find(|e| e.id.parse::<Uuid>().ok() == Some(uuid))
+        else {
+            log::warn!("Entry {uuid} does not exist");
+            results.push(CsvImportResult::new(
+                record_nr,
+                Err(CsvImportError::UnknownId(uuid.simple().to_string())),
+            ));
+            continue;
+        };
+        let result = patch_from_template(&headers, &record, original)
+            .and_then(|patch| patch_place_with(original.clone(), patch, options))
+            .map_err(|err| CsvImportError::PatchRequest(err.to_string()))
+            .and_then(|mut place| normalize_patched_urls(original, &mut place).map(|()| place));
+        results.push(CsvImportResult::new(record_nr, result));
+    }
+    Ok(results)
+}
+
+fn template_from_reader<R: Read>(r: R) -> Result<(StringRecord, StringRecord)> {
+    let mut rdr = headers::reader(r, PLACE_SYNONYMS)?;
+    let mut headers = rdr.headers()?.clone();
+    let mut records = rdr.records();
+    let Some(mut record) = records.next().transpose()? else {
+        return Err(anyhow!("The template contains no patch"));
+    };
+    if records.next().is_some() {
+        return Err(anyhow!("The template must contain exactly one patch"));
+    }
+    for column in ["id", "version"] {
+        if !headers.iter().any(|h| h == column) {
+            headers.push_field(column);
+            record.push_field("");
+        }
+    }
+    Ok((headers, record))
+}
+
+fn patch_from_template(
+    headers: &StringRecord,
+    template: &StringRecord,
+    original: &Entry,
+) -> Result<PatchPlaceRecord> {
+    let version = (original.version + 1).to_string();
+    let record: StringRecord = headers
+        .iter()
+        .zip(template)
+        .map(|(column, value)| match column {
+            "id" => original.id.as_str(),
+            "version" => &version,
+            _ => value,
+        })
+        .collect();
+    Ok(record.deserialize(Some(headers))?)
+}
+
+/// Read UUIDs (one per line, empty lines and lines starting with `#` are ignored).
+pub fn uuids_from_reader<R: BufRead>(r: R) -> Result<Vec<Uuid>> {
+    let mut uuids = vec![];
+    for (nr, line) in r.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let uuid = line
+            .parse()
+            .map_err(|err| anyhow!("Invalid UUID '{line}' in line {}: {err}", nr + 1))?;
+        uuids.push(uuid);
+    }
+    Ok(uuids)
+}
+
+type PatchPlaceRecords = Vec<(Uuid, usize, PatchPlaceRecord)>;
+
 fn patches_from_reader<R: Read>(
     r: R,
-) -> Result<(
-    Vec<(Uuid, usize, PatchPlaceRecord)>,
-    Vec<CsvImportResult<Entry>>,
-)> {
-    let mut rdr = ReaderBuilder::new().from_reader(r);
+    filter: &RecordFilter,
+    strict_columns: bool,
+) -> Result<(PatchPlaceRecords, Vec<CsvImportResult<Entry>>)> {
     let mut results = vec![];
     let mut patch_place_records = vec![];
 
-    for (record_nr, result) in rdr.deserialize::<PatchPlaceRecord>().enumerate() {
+    for (record_nr, result) in filtered_records::<_, PatchPlaceRecord>(r, filter, strict_columns)? {
         match result {
             Err(err) => {
                 log::warn!("Invalid CSV entry: {err}");
-                results.push(CsvImportResult {
+                results.push(CsvImportResult::new(
                     record_nr,
-                    result: Err(CsvImportError::Record(err.to_string())),
-                });
+                    Err(CsvImportError::Record(err.to_string())),
+                ));
             }
             Ok(record) => match record.id.parse::<Uuid>() {
                 Ok(uuid) => {
@@ -390,10 +1436,10 @@ fn patches_from_reader<R: Read>(
                 }
                 Err(err) => {
                     let err_msg = format!("Invalid entry ID: {err}");
-                    results.push(CsvImportResult {
+                    results.push(CsvImportResult::new(
                         record_nr,
-                        result: Err(CsvImportError::Record(err_msg)),
-                    });
+                        Err(CsvImportError::Record(err_msg)),
+                    ));
                 }
             },
         }
@@ -405,9 +1451,62 @@ const OP_APPEND: &str = "++";
 const OP_DELETE: &str = "--";
 const OP_REPLACE: &str = "==";
 
+/// A value that starts with this character is taken literally,
+/// e.g. `==\--foo` replaces a field with `--foo`.
+const ESCAPE: char = '\\';
+
 const APPEND_SEPERATOR: &str = " ";
 
-fn patch_place(mut original: Entry, record: PatchPlaceRecord) -> Result<Entry> {
+/// How text is added to a field with `++`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchOptions {
+    /// The text between the current value and the added text.
+    pub separator: String,
+    /// Add the text in front of the current value.
+    pub prepend: bool,
+    /// The fields `separator` and `prepend` apply to,
+    /// text is appended to all other fields with a space.
+    pub append_fields: Vec<String>,
+    /// Add the hashtags of a patched description to the tags.
+    pub hashtags: DescriptionHashtags,
+    /// The separator between the tag operations (e.g. `++bio;--regional`).
+    pub tag_separator: TagSeparator,
+    /// Reject files with unknown or missing columns.
+    pub strict_columns: bool,
+}
+
+impl Default for PatchOptions {
+    fn default() -> Self {
+        Self {
+            separator: APPEND_SEPERATOR.to_string(),
+            prepend: false,
+            append_fields: vec!["description".to_string()],
+            hashtags: DescriptionHashtags::default(),
+            tag_separator: TagSeparator::default(),
+            strict_columns: false,
+        }
+    }
+}
+
+impl PatchOptions {
+    fn add(&self, field_name: &str, field: &mut String, text: &str) {
+        if !self.append_fields.iter().any(|f| f == field_name) {
+            field.push_str(APPEND_SEPERATOR);
+            field.push_str(text);
+        } else if self.prepend {
+            *field = format!("{text}{}{field}", self.separator);
+        } else {
+            field.push_str(&self.separator);
+            field.push_str(text);
+        }
+    }
+}
+
+fn patch_place_with(
+    mut original: Entry,
+    record: PatchPlaceRecord,
+    options: &PatchOptions,
+) -> Result<Entry> {
     let PatchPlaceRecord {
         id,
         created,
@@ -472,30 +1571,59 @@ fn patch_place(mut original: Entry, record: PatchPlaceRecord) -> Result<Entry> {
         log::warn!("The ratings can't be modified.");
     }
 
-    patch_string_field("title", &mut original.title, title)?;
-    patch_string_field("description", &mut original.description, description)?;
+    patch_string_field("title", &mut original.title, title, options)?;
+    let description_patched = description.is_some();
+    patch_string_field(
+        "description",
+        &mut original.description,
+        description,
+        options,
+    )?;
+    if description_patched {
+        for tag in options.hashtags.apply(&mut original.description) {
+            if !original.tags.contains(&tag) {
+                original.tags.push(tag);
+            }
+        }
+    }
     patch_float_field("lat", &mut original.lat, lat)?;
     patch_float_field("lng", &mut original.lng, lng)?;
-    patch_optional_string_field("street", &mut original.street, street)?;
-    patch_optional_string_field("zip", &mut original.zip, zip)?;
-    patch_optional_string_field("city", &mut original.city, city)?;
-    patch_optional_string_field("country", &mut original.country, country)?;
-    patch_optional_string_field("state", &mut original.state, state)?;
-    patch_optional_string_field("contact_name", &mut original.contact_name, contact_name)?;
-    patch_optional_string_field("contact_email", &mut original.email, contact_email)?;
-    patch_optional_string_field("contact_phone", &mut original.telephone, contact_phone)?;
-    patch_optional_string_field("homepage", &mut original.homepage, homepage)?;
-    patch_optional_string_field("opening_hours", &mut original.opening_hours, opening_hours)?;
+    patch_optional_string_field("street", &mut original.street, street, options)?;
+    patch_optional_string_field("zip", &mut original.zip, zip, options)?;
+    patch_optional_string_field("city", &mut original.city, city, options)?;
+    patch_optional_string_field("country", &mut original.country, country, options)?;
+    patch_optional_string_field("state", &mut original.state, state, options)?;
+    patch_optional_string_field(
+        "contact_name",
+        &mut original.contact_name,
+        contact_name,
+        options,
+    )?;
+    patch_optional_string_field("contact_email", &mut original.email, contact_email, options)?;
+    patch_optional_string_field(
+        "contact_phone",
+        &mut original.telephone,
+        contact_phone,
+        options,
+    )?;
+    patch_optional_string_field("homepage", &mut original.homepage, homepage, options)?;
+    patch_optional_string_field(
+        "opening_hours",
+        &mut original.opening_hours,
+        opening_hours,
+        options,
+    )?;
     patch_optional_date_field("founded_on", &mut original.founded_on, founded_on)?;
-    patch_optional_string_field("image_url", &mut original.image_url, image_url)?;
+    patch_optional_string_field("image_url", &mut original.image_url, image_url, options)?;
     patch_optional_string_field(
         "image_link_url",
         &mut original.image_link_url,
         image_link_url,
+        options,
     )?;
 
     if let Some(tags) = tags {
-        for tag in tags.split(',') {
+        for tag in options.tag_separator.split(&tags) {
             match patch_op(tag) {
                 Ok(Some(PatchOp::Append(new_tag))) => {
                     original.tags.push(new_tag.to_string());
@@ -542,6 +1670,7 @@ fn patch_string_field(
     field_name: &str,
     field: &mut String,
     patch: Option<String>,
+    options: &PatchOptions,
 ) -> anyhow::Result<()> {
     log::debug!("Patch {field_name} with {patch:?}");
     if let Some(patch) = patch {
@@ -553,8 +1682,7 @@ fn patch_string_field(
                 *field = replace.to_string();
             }
             PatchOp::Append(append) => {
-                field.push_str(APPEND_SEPERATOR);
-                field.push_str(append);
+                options.add(field_name, field, append);
             }
             PatchOp::Delete(_) | PatchOp::DeleteAll => {
                 return Err(anyhow!("The field '{field_name}' can't be deleted."));
@@ -568,6 +1696,7 @@ fn patch_optional_string_field(
     field_name: &str,
     field: &mut Option<String>,
     patch: Option<String>,
+    options: &PatchOptions,
 ) -> anyhow::Result<()> {
     log::debug!("Patch optional {field_name} with {patch:?}");
     if let Some(patch) = patch {
@@ -580,8 +1709,7 @@ fn patch_optional_string_field(
             }
             PatchOp::Append(append) => match field {
                 Some(field) => {
-                    field.push_str(APPEND_SEPERATOR);
-                    field.push_str(append);
+                    options.add(field_name, field, append);
                 }
                 None => {
                     *field = Some(append.to_string());
@@ -610,7 +1738,8 @@ fn patch_optional_date_field(
         };
         match op {
             PatchOp::Replace(replace) => {
-                let date: Date = serde_json::from_str(replace)?;
+                let date = parse_date(replace)
+                    .ok_or_else(|| anyhow!("Invalid date '{replace}', {DATE_FORMATS}"))?;
                 *field = Some(date);
             }
             PatchOp::Append(_) => {
@@ -656,33 +1785,34 @@ fn patch_op(s: &str) -> Result<Option<PatchOp<'_>>, PatchOpError> {
         return Ok(None);
     }
 
-    if trimmed.starts_with(OP_DELETE) {
-        let delete = trimmed.split(OP_DELETE).nth(1).unwrap().trim();
+    if let Some(delete) = trimmed.strip_prefix(OP_DELETE) {
+        let delete = delete.trim();
         return Ok(Some(if delete.is_empty() {
             PatchOp::DeleteAll
         } else {
-            PatchOp::Delete(delete)
+            PatchOp::Delete(unescape(delete)?)
         }));
     }
 
-    if trimmed.starts_with(OP_APPEND) {
-        let append = trimmed.split(OP_APPEND).nth(1).unwrap().trim();
-        if append.is_empty() {
-            return Err(PatchOpError::EmptyString);
-        }
-        return Ok(Some(PatchOp::Append(append.trim())));
+    if let Some(append) = trimmed.strip_prefix(OP_APPEND) {
+        return Ok(Some(PatchOp::Append(unescape(append.trim())?)));
     }
 
-    if trimmed.starts_with(OP_REPLACE) {
-        let replace = trimmed.split(OP_REPLACE).nth(1).unwrap();
-        if replace.is_empty() {
-            return Err(PatchOpError::EmptyString);
-        }
-        return Ok(Some(PatchOp::Replace(replace.trim())));
+    if let Some(replace) = trimmed.strip_prefix(OP_REPLACE) {
+        return Ok(Some(PatchOp::Replace(unescape(replace.trim())?)));
     }
     Err(PatchOpError::NoOp)
 }
 
+/// Remove the [ESCAPE] character of a value.
+fn unescape(value: &str) -> Result<&str, PatchOpError> {
+    let value = value.strip_prefix(ESCAPE).unwrap_or(value);
+    if value.is_empty() {
+        return Err(PatchOpError::EmptyString);
+    }
+    Ok(value)
+}
+
 #[derive(Debug, Default, Deserialize)]
 struct PatchPlaceRecord {
     id: String,
@@ -760,6 +1890,328 @@ fn check_address_and_geo_coordinates(
     }
 }
 
+/// A custom link of an entry in the long format,
+/// i.e. one row per link instead of `custom_link_*_N` columns.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CustomLinkRecord {
+    pub entry_id: String,
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Convert the custom links of all entries into the long format.
+pub fn custom_link_records(entries: &[Entry]) -> Vec<CustomLinkRecord> {
+    entries
+        .iter()
+        .flat_map(|e| {
+            e.custom_links.iter().map(|l| CustomLinkRecord {
+                entry_id: e.id.clone(),
+                url: l.url.clone(),
+                title: l.title.clone(),
+                description: l.description.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Group custom links in the long format by (simple formatted) entry ID.
+pub fn custom_links_by_entry(
+    records: Vec<CustomLinkRecord>,
+) -> Result<HashMap<String, Vec<CustomLink>>> {
+    let mut links: HashMap<_, Vec<_>> = HashMap::new();
+    for CustomLinkRecord {
+        entry_id,
+        url,
+        title,
+        description,
+    } in records
+    {
+        let id = entry_id
+            .parse::<Uuid>()
+            .map_err(|err| anyhow!("Invalid entry ID '{entry_id}': {err}"))?
+            .simple()
+            .to_string();
+        links.entry(id).or_default().push(CustomLink {
+            url,
+            title,
+            description,
+        });
+    }
+    Ok(links)
+}
+
+/// Replace the custom links of the entries that are contained in `links`.
+///
+/// The links of other entries are kept and the applied links are removed from `links`.
+pub fn replace_custom_links<'a>(
+    entries: impl IntoIterator<Item = &'a mut Entry>,
+    links: &mut HashMap<String, Vec<CustomLink>>,
+) -> Result<()> {
+    for entry in entries {
+        let id = entry.id.parse::<Uuid>()?.simple().to_string();
+        if let Some(entry_links) = links.remove(&id) {
+            entry.custom_links = entry_links;
+        }
+    }
+    Ok(())
+}
+
+pub fn custom_link_records_from_reader<R: Read>(r: R) -> Result<Vec<CustomLinkRecord>> {
+    log::info!("Read custom links from CSV");
+    let mut rdr = headers::reader(r, &[])?;
+    let mut records = vec![];
+    for (record_nr, result) in rdr.deserialize().enumerate() {
+        let mut record: CustomLinkRecord =
+            result.map_err(|err| anyhow!("Unable to read record nr {record_nr}: {err}"))?;
+        record.url = normalize_url(&record.url)
+            .map_err(|err| anyhow!("Invalid URL in record nr {record_nr}: {err}"))?
+            .ok_or_else(|| anyhow!("The URL of record nr {record_nr} is missing"))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+pub fn write_custom_link_records<W: Write>(w: W, records: &[CustomLinkRecord]) -> Result<()> {
+    let mut wtr = WriterBuilder::new().from_writer(w);
+    for record in records {
+        wtr.serialize(record)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Max. number of custom links in the wide `custom_link_*_N` columns.
+const MAX_CUSTOM_LINK_COLUMNS: usize = 5;
+
+/// Columns of an export that are derived from an entry
+/// instead of being part of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComputedColumns {
+    pub days_since_created: Option<i64>,
+    pub days_since_last_update: Option<i64>,
+    pub review_status: Option<ReviewStatus>,
+}
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+impl ComputedColumns {
+    /// Derive the columns of an entry.
+    ///
+    /// `created` is the creation time (in seconds) of the first revision.
+    /// If it's unknown, it can only be derived from the first revision itself.
+    /// `now` is the current time in seconds.
+    pub fn new(
+        entry: &Entry,
+        created: Option<i64>,
+        review_status: Option<ReviewStatus>,
+        now: i64,
+    ) -> Self {
+        let created = created.or((entry.version == 0).then_some(entry.created));
+        let days_since = |at: i64| (now - at).max(0) / SECONDS_PER_DAY;
+        Self {
+            days_since_created: created.map(days_since),
+            days_since_last_update: Some(days_since(entry.created)),
+            review_status,
+        }
+    }
+}
+
+fn review_status_name(status: &ReviewStatus) -> &'static str {
+    match status {
+        ReviewStatus::Archived => "archived",
+        ReviewStatus::Confirmed => "confirmed",
+        ReviewStatus::Created => "created",
+        ReviewStatus::Rejected => "rejected",
+    }
+}
+
+const PLACE_COLUMNS: [&str; 22] = [
+    "id",
+    "created",
+    "version",
+    "title",
+    "description",
+    "lat",
+    "lng",
+    "street",
+    "zip",
+    "city",
+    "country",
+    "state",
+    "contact_name",
+    "contact_email",
+    "contact_phone",
+    "opening_hours",
+    "founded_on",
+    "tags",
+    "homepage",
+    "license",
+    "image_url",
+    "image_link_url",
+];
+
+const COMPUTED_COLUMNS: [&str; 3] = [
+    "days_since_created",
+    "days_since_last_update",
+    "review_status",
+];
+
+fn custom_link_columns() -> impl Iterator<Item = String> {
+    (0..MAX_CUSTOM_LINK_COLUMNS).flat_map(|i| {
+        [
+            format!("custom_link_title_{i}"),
+            format!("custom_link_description_{i}"),
+            format!("custom_link_url_{i}"),
+        ]
+    })
+}
+
+/// The fields of the `custom_link_*_N` columns.
+fn custom_link_fields<'a>(
+    links: &'a [CustomLink],
+    title: &str,
+) -> impl Iterator<Item = String> + 'a {
+    if links.len() > MAX_CUSTOM_LINK_COLUMNS {
+        log::warn!(
+            "'{title}' has more than {MAX_CUSTOM_LINK_COLUMNS} custom links, \
+             use the long format to export all of them"
+        );
+    }
+    (0..MAX_CUSTOM_LINK_COLUMNS).flat_map(move |i| {
+        let link = links.get(i);
+        [
+            link.and_then(|l| l.title.clone()).unwrap_or_default(),
+            link.and_then(|l| l.description.clone()).unwrap_or_default(),
+            link.map(|l| l.url.clone()).unwrap_or_default(),
+        ]
+    })
+}
+
+/// Write entries in the format that is read by [places_from_reader].
+///
+/// If `computed` is given, the [ComputedColumns] of each entry (by ID) are added.
+pub fn write_places<W: Write>(
+    w: W,
+    entries: &[Entry],
+    computed: Option<&HashMap<String, ComputedColumns>>,
+) -> Result<()> {
+    let mut wtr = WriterBuilder::new().from_writer(w);
+
+    let mut header: Vec<String> = PLACE_COLUMNS.iter().map(ToString::to_string).collect();
+    header.extend(custom_link_columns());
+    if computed.is_some() {
+        header.extend(COMPUTED_COLUMNS.iter().map(ToString::to_string));
+    }
+    // The reader expects the ratings in the last column
+    header.push("ratings".to_string());
+    wtr.write_record(&header)?;
+
+    for e in entries {
+        let opt = |v: &Option<String>| v.clone().unwrap_or_default();
+        let mut record = vec![
+            e.id.clone(),
+            e.created.to_string(),
+            e.version.to_string(),
+            e.title.clone(),
+            e.description.clone(),
+            e.lat.to_string(),
+            e.lng.to_string(),
+            opt(&e.street),
+            opt(&e.zip),
+            opt(&e.city),
+            opt(&e.country),
+            opt(&e.state),
+            opt(&e.contact_name),
+            opt(&e.email),
+            opt(&e.telephone),
+            opt(&e.opening_hours),
+            e.founded_on.map(|d| d.to_string()).unwrap_or_default(),
+            e.tags.join(","),
+            opt(&e.homepage),
+            opt(&e.license),
+            opt(&e.image_url),
+            opt(&e.image_link_url),
+        ];
+        record.extend(custom_link_fields(&e.custom_links, &e.title));
+        if let Some(computed) = computed {
+            let c = computed.get(&e.id).cloned().unwrap_or_default();
+            let num = |v: Option<i64>| v.map(|v| v.to_string()).unwrap_or_default();
+            record.push(num(c.days_since_created));
+            record.push(num(c.days_since_last_update));
+            record.push(
+                c.review_status
+                    .as_ref()
+                    .map(review_status_name)
+                    .unwrap_or_default()
+                    .to_string(),
+            );
+        }
+        record.push(e.ratings.join(","));
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Write new places in the format that is read by [new_places_from_reader].
+pub fn write_new_places<W: Write>(w: W, places: &[NewPlace]) -> Result<()> {
+    let mut wtr = WriterBuilder::new().from_writer(w);
+    wtr.write_record(new_place_columns())?;
+    for p in places {
+        wtr.write_record(new_place_fields(p))?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Write new places that could not be imported (see [write_new_places])
+/// with their error in the last column, so they can be fixed and imported again.
+pub fn write_failed_new_places<W: Write>(w: W, places: &[(NewPlace, String)]) -> Result<()> {
+    let mut wtr = WriterBuilder::new().from_writer(w);
+    wtr.write_record(new_place_columns().chain([ERROR_COLUMN.to_string()]))?;
+    for (p, error) in places {
+        wtr.write_record(new_place_fields(p).chain([error.clone()]))?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn new_place_columns() -> impl Iterator<Item = String> {
+    // Without id, created and version
+    PLACE_COLUMNS[3..]
+        .iter()
+        .map(ToString::to_string)
+        .chain(custom_link_columns())
+}
+
+fn new_place_fields(p: &NewPlace) -> impl Iterator<Item = String> + '_ {
+    let opt = |v: &Option<String>| v.clone().unwrap_or_default();
+    [
+        p.title.clone(),
+        p.description.clone(),
+        p.lat.to_string(),
+        p.lng.to_string(),
+        opt(&p.street),
+        opt(&p.zip),
+        opt(&p.city),
+        opt(&p.country),
+        opt(&p.state),
+        opt(&p.contact_name),
+        opt(&p.email),
+        opt(&p.telephone),
+        opt(&p.opening_hours),
+        p.founded_on.map(|d| d.to_string()).unwrap_or_default(),
+        p.tags.join(","),
+        opt(&p.homepage),
+        p.license.clone(),
+        opt(&p.image_url),
+        opt(&p.image_link_url),
+    ]
+    .into_iter()
+    .chain(custom_link_fields(&p.links, &p.title))
+}
+
 #[derive(Debug, Deserialize)]
 struct ReviewRecord {
     id: String,
@@ -768,11 +2220,20 @@ struct ReviewRecord {
 }
 
 pub fn reviews_from_reader<R: Read>(r: R) -> Result<Vec<(Uuid, Review)>> {
+    reviews_with_filter(r, &RecordFilter::default())
+}
+
+pub fn reviews_with_filter<R: Read>(r: R, filter: &RecordFilter) -> Result<Vec<(Uuid, Review)>> {
     log::info!("Read reviews form CSV");
-    let mut rdr = ReaderBuilder::new().from_reader(r);
+    let mut rdr = headers::reader(r, &[])?;
+    let headers = rdr.headers()?.clone();
+    if !filter.is_empty() {
+        filter.check_columns(&headers)?;
+    }
     let mut results = vec![];
 
-    for (record_nr, result) in rdr.deserialize().enumerate() {
+    for (record_nr, record) in filter.select(&headers, rdr.records()) {
+        let result = record.and_then(|record| record.deserialize::<ReviewRecord>(Some(&headers)));
         match result {
             Err(err) => {
                 log::warn!("Unable to read record nr {record_nr}): {}", err);
@@ -784,6 +2245,11 @@ pub fn reviews_from_reader<R: Read>(r: R) -> Result<Vec<(Uuid, Review)>> {
                     status,
                     comment,
                 } = r;
+                if status.trim().is_empty() {
+                    // e.g. an entry of an audit that should not be reviewed
+                    log::debug!("Skip record {record_nr} without status");
+                    continue;
+                }
                 if let Ok(id) = id.parse::<Uuid>() {
                     let status = match &*status.trim().to_lowercase() {
                         "archived" => ReviewStatus::Archived,
@@ -807,6 +2273,86 @@ pub fn reviews_from_reader<R: Read>(r: R) -> Result<Vec<(Uuid, Review)>> {
     Ok(results)
 }
 
+#[derive(Debug, Deserialize)]
+struct RatingRecord {
+    id: String,
+    context: String,
+    value: i8,
+    title: String,
+    comment: String,
+    source: Option<String>,
+}
+
+pub fn ratings_from_reader<R: Read>(r: R) -> Result<Vec<CsvImportResult<NewPlaceRating>>> {
+    log::info!("Read ratings from CSV");
+    let mut rdr = headers::reader(r, &[])?;
+    let mut results = vec![];
+
+    for (record_nr, result) in rdr.deserialize().enumerate() {
+        match result {
+            Err(err) => {
+                log::warn!("Invalid CSV entry: {err}");
+                results.push(CsvImportResult::new(
+                    record_nr,
+                    Err(CsvImportError::Record(err.to_string())),
+                ));
+            }
+            Ok(r) => {
+                let result = rating_from_record(r).map_err(|err| {
+                    log::warn!("Invalid rating in record {record_nr}: {err}");
+                    CsvImportError::Record(err.to_string())
+                });
+                results.push(CsvImportResult::new(record_nr, result));
+            }
+        }
+    }
+    Ok(results)
+}
+
+fn rating_from_record(record: RatingRecord) -> Result<NewPlaceRating> {
+    let RatingRecord {
+        id,
+        context,
+        value,
+        title,
+        comment,
+        source,
+    } = record;
+    let entry = id
+        .parse::<Uuid>()
+        .map_err(|err| anyhow!("Invalid entry ID: {err}"))?
+        .simple()
+        .to_string();
+    let context = match &*context.trim().to_lowercase() {
+        "diversity" => RatingContext::Diversity,
+        "renewable" => RatingContext::Renewable,
+        "fairness" => RatingContext::Fairness,
+        "humanity" => RatingContext::Humanity,
+        "transparency" => RatingContext::Transparency,
+        "solidarity" => RatingContext::Solidarity,
+        _ => {
+            return Err(anyhow!("Invalid rating context '{context}'"));
+        }
+    };
+    if !(-1..=2).contains(&value) {
+        return Err(anyhow!(
+            "Invalid rating value '{value}' (must be between -1 and 2)"
+        ));
+    }
+    if title.trim().is_empty() {
+        return Err(anyhow!("The rating title must not be empty"));
+    }
+    Ok(NewPlaceRating {
+        entry,
+        title,
+        value: value.into(),
+        context,
+        comment,
+        source,
+        user: None,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -817,6 +2363,757 @@ mod tests {
         let file = File::open("tests/review-example.csv").unwrap();
         let reviews = reviews_from_reader(file).unwrap();
         assert_eq!(reviews.len(), 3);
+
+        let file = File::open("tests/review-example.csv").unwrap();
+        let filter = RecordFilter {
+            offset: 1,
+            limit: Some(1),
+            ..Default::default()
+        };
+        let reviews = reviews_with_filter(file, &filter).unwrap();
+        assert_eq!(reviews.len(), 1);
+        assert_eq!(reviews[0].1.status, ReviewStatus::Rejected);
+    }
+
+    #[test]
+    fn read_ratings_from_csv_file() {
+        let file = File::open("tests/ratings-example.csv").unwrap();
+        let ratings = ratings_from_reader(file).unwrap();
+        assert_eq!(ratings.len(), 3);
+        let rating = ratings[0].result.as_ref().unwrap();
+        assert_eq!(rating.entry, "74030edff6034414a47a337c386913e1");
+        assert!(matches!(rating.context, RatingContext::Renewable));
+        assert_eq!(rating.source.as_deref(), Some("Umfrage 2023"));
+        assert!(ratings[1].result.is_ok());
+        assert!(ratings[2].result.is_err());
+    }
+
+    #[test]
+    fn read_custom_links_from_csv_file() {
+        let file = File::open("tests/custom-links-example.csv").unwrap();
+        let records = custom_link_records_from_reader(file).unwrap();
+        assert_eq!(records.len(), 3);
+        let links = custom_links_by_entry(records).unwrap();
+        assert_eq!(links.len(), 2);
+        let gls_links = &links["74030edff6034414a47a337c386913e1"];
+        assert_eq!(gls_links.len(), 2);
+        assert_eq!(gls_links[1].title, None);
+    }
+
+    #[test]
+    fn only_replace_the_custom_links_of_entries_in_the_links_file() {
+        let file = File::open("tests/update-example.csv").unwrap();
+        let mut entries: Vec<_> = places_from_reader(file)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.result.unwrap())
+            .collect();
+        let mut other = entries[0].clone();
+        other.id = "481ffc16da7d48f090ec5a7cc3a8831e".to_string();
+        other.custom_links = vec![CustomLink {
+            url: "https://example.org/".to_string(),
+            title: None,
+            description: None,
+        }];
+        entries.push(other);
+        let file = File::open("tests/custom-links-example.csv").unwrap();
+        let records = custom_link_records_from_reader(file).unwrap();
+        let mut links = custom_links_by_entry(records).unwrap();
+        replace_custom_links(&mut entries, &mut links).unwrap();
+        assert_eq!(entries[0].custom_links.len(), 2);
+        assert_eq!(entries[1].custom_links.len(), 1);
+        assert_eq!(entries[1].custom_links[0].url, "https://example.org/");
+        assert_eq!(links.len(), 1);
+    }
+
+    #[test]
+    fn convert_custom_links_to_long_format_and_back() {
+        let file = File::open("tests/update-example.csv").unwrap();
+        let entries: Vec<_> = places_from_reader(file)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.result.unwrap())
+            .collect();
+        let records = custom_link_records(&entries);
+        assert_eq!(records.len(), 1);
+
+        let mut csv = vec![];
+        write_custom_link_records(&mut csv, &records).unwrap();
+        let read_records = custom_link_records_from_reader(&csv[..]).unwrap();
+        assert_eq!(records, read_records);
+
+        let links = custom_links_by_entry(read_records).unwrap();
+        let urls = |links: &[CustomLink]| links.iter().map(|l| l.url.clone()).collect::<Vec<_>>();
+        assert_eq!(urls(&links[&entries[0].id]), urls(&entries[0].custom_links));
+    }
+
+    #[test]
+    fn normalize_urls() {
+        let ok = |url: &str| normalize_url(url).unwrap().unwrap();
+        assert_eq!(ok("https://example.org"), "https://example.org");
+        assert_eq!(ok(" http://example.org/a?b=c "), "http://example.org/a?b=c");
+        assert_eq!(ok("www.example.org"), "https://www.example.org");
+        assert_eq!(ok("example.org/kontakt"), "https://example.org/kontakt");
+        assert_eq!(normalize_url("  ").unwrap(), None);
+        assert!(normalize_url("htp://broken").is_err());
+        assert!(normalize_url("https://").is_err());
+        assert!(normalize_url("https://broken").is_err());
+        assert!(normalize_url("keine Webseite").is_err());
+        assert!(normalize_url("mailto:info@example.org").is_err());
+        assert_eq!(ok("http://192.168.0.1:8080/"), "http://192.168.0.1:8080/");
+        assert_eq!(
+            ok("http://[2001:db8::1]/karte"),
+            "http://[2001:db8::1]/karte"
+        );
+    }
+
+    #[test]
+    fn report_invalid_urls_per_record() {
+        let mut entry = crate::fixtures::generate(1, 0).entries.remove(0);
+        entry.homepage = Some("www.example.org".to_string());
+        entry.custom_links = vec![CustomLink {
+            url: "example.org/termine".to_string(),
+            title: None,
+            description: None,
+        }];
+        normalize_entry_urls(&mut entry).unwrap();
+        assert_eq!(entry.homepage.as_deref(), Some("https://www.example.org"));
+        assert_eq!(entry.custom_links[0].url, "https://example.org/termine");
+
+        entry.image_url = Some("htp://broken".to_string());
+        let err = normalize_entry_urls(&mut entry).unwrap_err();
+        assert!(err.to_string().contains("image_url"), "{err}");
+    }
+
+    #[test]
+    fn validate_only_patched_urls() {
+        let mut original = crate::fixtures::generate(1, 0).entries.remove(0);
+        original.homepage = Some("keine Webseite".to_string());
+        original.image_url = None;
+
+        let mut patched = original.clone();
+        patched.image_url = Some("example.org/logo.png".to_string());
+        normalize_patched_urls(&original, &mut patched).unwrap();
+        assert_eq!(patched.homepage, original.homepage);
+        assert_eq!(
+            patched.image_url.as_deref(),
+            Some("https://example.org/logo.png")
+        );
+
+        let mut patched = original.clone();
+        patched.homepage = Some("htp://broken".to_string());
+        let err = normalize_patched_urls(&original, &mut patched).unwrap_err();
+        assert!(err.to_string().contains("homepage"), "{err}");
+    }
+
+    #[test]
+    fn normalize_comma_separated_tags() {
+        assert_eq!(
+            normalize_tags(" Bio, #regional,,bio , Zero Waste"),
+            vec!["bio", "regional", "zero waste"]
+        );
+        assert!(normalize_tags(" , ").is_empty());
+    }
+
+    #[test]
+    fn read_coordinates_with_comma_or_swapped() {
+        let csv = "\
+title,description,lat,lng,street,zip,city,country,state,contact_name,contact_email,contact_phone,opening_hours,founded_on,tags,homepage,license,image_url,image_link_url
+Comma,A place,\"51,34\",\"7,2\",,,,,,,,,,,bio,,CC0-1.0,,
+Swapped,A place,-122.4,37.8,,,,,,,,,,,bio,,CC0-1.0,,
+";
+        let geo_coding = OpenCage::new(None);
+        let places = |options| {
+            new_places_with_options(csv.as_bytes(), &geo_coding, &options)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.result.map(|p| (p.lat, p.lng)).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            places(CsvOptions::default()),
+            vec![(51.34, 7.2), (37.8, -122.4)]
+        );
+        let options = CsvOptions {
+            swap_lat_lng: true,
+            ..Default::default()
+        };
+        assert_eq!(places(options), vec![(7.2, 51.34), (37.8, -122.4)]);
+        assert_eq!(parse_coordinate("1,234.5"), None);
+        assert_eq!(parse_coordinate("NaN"), None);
+    }
+
+    #[test]
+    fn filter_records() {
+        let csv = "\
+title,description,lat,lng,street,zip,city,country,state,contact_name,contact_email,contact_phone,opening_hours,founded_on,tags,homepage,license,image_url,image_link_url
+A,A place,53.5,10.0,,,Hamburg,,,,,,,,solar,,CC0-1.0,,
+B,A place,51.4,7.2,,,Bochum,,,,,,,,solar,,CC0-1.0,,
+C,A place,53.5,10.0,,,Hamburg,,,,,,,,bio,,CC0-1.0,,
+D,A place,53.5,10.0,,,hamburg,,,,,,,,solarenergie,,CC0-1.0,,
+";
+        let options = |conditions: &[&str], rows: Option<&str>| CsvOptions {
+            filter: RecordFilter {
+                conditions: conditions.iter().map(|c| c.parse().unwrap()).collect(),
+                rows: rows.map(|r| r.parse().unwrap()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let record_nrs = |options: CsvOptions| {
+            new_places_with_options(csv.as_bytes(), &OpenCage::new(None), &options)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.record_nr)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(record_nrs(options(&[], None)), vec![0, 1, 2, 3]);
+        assert_eq!(
+            record_nrs(options(&["city=Hamburg", "tags~solar"], None)),
+            vec![0, 3]
+        );
+        assert_eq!(record_nrs(options(&["city=Hamburg"], Some("1-2"))), vec![2]);
+        assert!(new_places_with_options(
+            csv.as_bytes(),
+            &OpenCage::new(None),
+            &options(&["foo=bar"], None)
+        )
+        .is_err());
+        let titles: Vec<_> =
+            validate_new_places(csv.as_bytes(), &options(&["city!=Hamburg"], None))
+                .unwrap()
+                .into_iter()
+                .map(|r| r.result.unwrap())
+                .collect();
+        assert_eq!(titles, vec!["B"]);
+    }
+
+    #[test]
+    fn check_field_lengths() {
+        let csv = format!(
+            "\
+title,description,lat,lng,street,zip,city,country,state,contact_name,contact_email,contact_phone,opening_hours,founded_on,tags,homepage,license,image_url,image_link_url
+Short,A place,51.4,7.2,,,,,,,,,,,bio,,CC0-1.0,,
+{},A place,51.4,7.2,,,,,,,,,,,bio,,CC0-1.0,,
+Long description,{},51.4,7.2,,,,,,,,,,,bio,,CC0-1.0,,
+",
+            "Ä".repeat(MAX_TITLE_LEN + 1),
+            "x".repeat(MAX_DESCRIPTION_LEN + 1)
+        );
+        let results = validate_new_places(csv.as_bytes(), &CsvOptions::default()).unwrap();
+        assert!(results[0].result.is_ok());
+        let Err(CsvImportError::Validation(msg)) = &results[1].result else {
+            panic!("unexpected result");
+        };
+        assert_eq!(msg, "The title is too long (81 characters, max. 80)");
+        assert!(results[2].result.is_err());
+
+        let options = CsvOptions {
+            limits: FieldLimits {
+                title: 4,
+                ..Default::default()
+            },
+            truncate: true,
+            ..Default::default()
+        };
+        let results =
+            new_places_with_options(csv.as_bytes(), &OpenCage::new(None), &options).unwrap();
+        let place = |nr: usize| results[nr].result.as_ref().unwrap();
+        assert_eq!(place(0).title, "Sho…");
+        assert_eq!(place(1).title, "ÄÄÄ…");
+        assert_eq!(place(2).title, "Lon…");
+        assert_eq!(place(2).description.chars().count(), MAX_DESCRIPTION_LEN);
+        assert!(place(2).description.ends_with("x…"));
+    }
+
+    #[test]
+    fn only_normalize_the_query_of_the_geocoder() {
+        struct StreetGeoCoding(std::cell::RefCell<Vec<Option<String>>>);
+        impl GeoCodingGateway for StreetGeoCoding {
+            fn resolve_address_lat_lng(
+                &self,
+                addr: &ofdb_entities::address::Address,
+            ) -> Option<(f64, f64)> {
+                self.0.borrow_mut().push(addr.street.clone());
+                Some((51.48, 7.22))
+            }
+        }
+        let csv = "\
+title,description,lat,lng,street,zip,city,tags,license
+Geocoded,A place,,,\"c/o Foo, Hauptstr.1, Hinterhaus\",44787,Bochum,bio,CC0-1.0
+Located,A place,51.4,7.2,\"Hauptstr.1, Hinterhaus\",44787,Bochum,bio,CC0-1.0
+";
+        let geo_coding = StreetGeoCoding(Default::default());
+        let options = CsvOptions {
+            normalize_addresses: Some(AddressLocale::De),
+            ..Default::default()
+        };
+        let results = new_places_with_options(csv.as_bytes(), &geo_coding, &options).unwrap();
+        let street = |i: usize| results[i].result.as_ref().unwrap().street.clone();
+        assert_eq!(
+            street(0).as_deref(),
+            Some("c/o Foo, Hauptstr.1, Hinterhaus")
+        );
+        assert_eq!(street(1).as_deref(), Some("Hauptstr.1, Hinterhaus"));
+        assert_eq!(
+            *geo_coding.0.borrow(),
+            vec![Some("Hauptstraße 1".to_string())]
+        );
+    }
+
+    #[test]
+    fn geocode_streamed_records_on_demand() {
+        struct CountingGeoCoding(std::cell::Cell<usize>);
+        impl GeoCodingGateway for CountingGeoCoding {
+            fn resolve_address_lat_lng(
+                &self,
+                _: &ofdb_entities::address::Address,
+            ) -> Option<(f64, f64)> {
+                self.0.set(self.0.get() + 1);
+                Some((51.48, 7.22))
+            }
+        }
+        let csv = "\
+title,description,lat,lng,street,zip,city,country,state,contact_name,contact_email,contact_phone,opening_hours,founded_on,tags,homepage,license,image_url,image_link_url
+First,A place,,,,,Bochum,,,,,,,,bio,,CC0-1.0,,
+Invalid,A place,,,,,,,,,,,,,bio,,CC0-1.0,,
+Third,A place,,,,,Bochum,,,,,,,,bio,,CC0-1.0,,
+";
+        let geo_coding = CountingGeoCoding(Default::default());
+        let mut places =
+            stream_new_places(csv.as_bytes(), &geo_coding, CsvOptions::default()).unwrap();
+        let first = places.next().unwrap();
+        assert_eq!(first.result.unwrap().lat, 51.48);
+        assert_eq!(geo_coding.0.get(), 1);
+        let rest: Vec<_> = places.collect();
+        assert_eq!(rest.len(), 2);
+        assert_eq!(rest[0].record_nr, 1);
+        assert!(rest[0].result.is_err());
+        assert!(rest[1].result.is_ok());
+        assert_eq!(geo_coding.0.get(), 2);
+    }
+
+    #[test]
+    fn defer_records_when_the_geocoding_budget_is_used_up() {
+        use crate::geocoder_usage::{BudgetedGeoCoding, GeocoderUsage};
+
+        struct FixedGeoCoding;
+        impl GeoCodingGateway for FixedGeoCoding {
+            fn resolve_address_lat_lng(
+                &self,
+                _: &ofdb_entities::address::Address,
+            ) -> Option<(f64, f64)> {
+                Some((51.48, 7.22))
+            }
+        }
+        let csv = "\
+title,description,lat,lng,street,zip,city,country,state,contact_name,contact_email,contact_phone,opening_hours,founded_on,tags,homepage,license,image_url,image_link_url
+First,A place,,,,,Bochum,,,,,,,,bio,,CC0-1.0,,
+Second,A place,,,,,Bochum,,,,,,,,bio,,CC0-1.0,,
+Third,A place,51.4,7.2,,,Bochum,,,,,,,,bio,,CC0-1.0,,
+";
+        let path = std::env::temp_dir().join("ofdb-csv-geocoder-usage-test.json");
+        let usage = Arc::new(GeocoderUsage::open_file(path, Some(1), "2024-05-01").unwrap());
+        let geo_coding = BudgetedGeoCoding::new(FixedGeoCoding, Arc::clone(&usage));
+        let options = CsvOptions {
+            geocoder_usage: Some(usage),
+            ..Default::default()
+        };
+        let results = new_places_with_options(csv.as_bytes(), &geo_coding, &options).unwrap();
+        assert!(results[0].result.is_ok());
+        assert!(matches!(
+            results[1].result,
+            Err(CsvImportError::GeocodingDeferred)
+        ));
+        assert!(results[2].result.is_ok());
+    }
+
+    #[test]
+    fn reject_addresses_with_a_low_geocoding_confidence() {
+        struct MatchingGeoCoding(Arc<GeocodeMatches>);
+        impl GeoCodingGateway for MatchingGeoCoding {
+            fn resolve_address_lat_lng(
+                &self,
+                addr: &ofdb_entities::address::Address,
+            ) -> Option<(f64, f64)> {
+                // Only the city was found if there's no street
+                let confidence = if addr.street.is_some() { 9 } else { 5 };
+                let m = GeocodeMatch {
+                    lat: 51.48,
+                    lng: 7.22,
+                    confidence,
+                    bounds: None,
+                };
+                self.0.insert(addr, m);
+                Some((m.lat, m.lng))
+            }
+        }
+        let csv = "\
+title,description,lat,lng,street,zip,city,country,state,contact_name,contact_email,contact_phone,opening_hours,founded_on,tags,homepage,license,image_url,image_link_url
+Street,A place,,,Hauptstraße 1,,Bochum,,,,,,,,bio,,CC0-1.0,,
+City,A place,,,,,Bochum,,,,,,,,bio,,CC0-1.0,,
+Coordinates,A place,51.4,7.2,,,Bochum,,,,,,,,bio,,CC0-1.0,,
+";
+        let matches = Arc::new(GeocodeMatches::default());
+        let geo_coding = MatchingGeoCoding(Arc::clone(&matches));
+        let options = CsvOptions {
+            geocode_matches: Some(Arc::clone(&matches)),
+            ..Default::default()
+        };
+        let results = new_places_with_options(csv.as_bytes(), &geo_coding, &options).unwrap();
+        assert_eq!(results[0].geocoding.unwrap().confidence, 9);
+        assert_eq!(results[1].geocoding.unwrap().confidence, 5);
+        assert!(results[1].result.is_ok());
+        assert_eq!(results[2].geocoding, None);
+
+        let options = CsvOptions {
+            min_geocode_confidence: Some(7),
+            ..options
+        };
+        let results = new_places_with_options(csv.as_bytes(), &geo_coding, &options).unwrap();
+        assert!(results[0].result.is_ok());
+        let Err(CsvImportError::AddressOrGeoCoordinates(msg)) = &results[1].result else {
+            panic!("unexpected result");
+        };
+        assert!(msg.contains("confidence 5"));
+        assert!(results[2].result.is_ok());
+    }
+
+    #[test]
+    fn check_and_fix_opening_hours() {
+        let csv = "\
+title,description,lat,lng,street,zip,city,country,state,contact_name,contact_email,contact_phone,opening_hours,founded_on,tags,homepage,license,image_url,image_link_url
+Valid,A place,51.4,7.2,,,,,,,,,Mo-Fr 09:00-17:00,,bio,,CC0-1.0,,
+Human,A place,51.4,7.2,,,,,,,,,Mo-Fr 9-17 Uhr,,bio,,CC0-1.0,,
+Unknown,A place,51.4,7.2,,,,,,,,,nach Vereinbarung,,bio,,CC0-1.0,,
+";
+        let strict = CsvOptions {
+            strict_opening_hours: true,
+            ..Default::default()
+        };
+        let results = validate_new_places(csv.as_bytes(), &strict).unwrap();
+        assert!(results[0].result.is_ok());
+        let Err(CsvImportError::Validation(msg)) = &results[1].result else {
+            panic!("unexpected result");
+        };
+        assert!(msg.contains("did you mean 'Mo-Fr 09:00-17:00'?"), "{msg}");
+        assert!(results[2].result.is_err());
+
+        // Without --strict-opening-hours invalid values are only reported
+        let results =
+            new_places_with_options(csv.as_bytes(), &OpenCage::new(None), &CsvOptions::default())
+                .unwrap();
+        assert!(results.iter().all(|r| r.result.is_ok()));
+        assert!(results[0].warnings.is_empty());
+        assert!(results[1].warnings[0].contains("did you mean"));
+        let opening_hours = |nr: usize| results[nr].result.as_ref().unwrap().opening_hours.clone();
+        assert_eq!(opening_hours(2).as_deref(), Some("nach Vereinbarung"));
+
+        let options = CsvOptions {
+            fix_opening_hours: true,
+            strict_opening_hours: true,
+            ..Default::default()
+        };
+        let results =
+            new_places_with_options(csv.as_bytes(), &OpenCage::new(None), &options).unwrap();
+        let opening_hours = |nr: usize| results[nr].result.as_ref().unwrap().opening_hours.clone();
+        assert_eq!(opening_hours(0).as_deref(), Some("Mo-Fr 09:00-17:00"));
+        assert_eq!(opening_hours(1).as_deref(), Some("Mo-Fr 09:00-17:00"));
+        assert!(results[2].result.is_err());
+    }
+
+    #[test]
+    fn reject_places_outside_of_the_expected_region() {
+        let csv = "\
+title,description,lat,lng,street,zip,city,country,state,contact_name,contact_email,contact_phone,opening_hours,founded_on,tags,homepage,license,image_url,image_link_url
+Bochum,A place,51.48,7.22,,,,,,,,,,,bio,,CC0-1.0,,
+New York,A place,40.71,-74.0,,,,,,,,,,,bio,,CC0-1.0,,
+";
+        let options = CsvOptions {
+            expected_bbox: country_bbox("de"),
+            ..Default::default()
+        };
+        let results =
+            new_places_with_options(csv.as_bytes(), &OpenCage::new(None), &options).unwrap();
+        assert!(results[0].result.is_ok());
+        assert!(matches!(
+            results[1].result,
+            Err(CsvImportError::AddressOrGeoCoordinates(_))
+        ));
+        let results = validate_new_places(csv.as_bytes(), &options).unwrap();
+        assert!(results[0].result.is_ok());
+        assert!(results[1].result.is_err());
+        assert!(country_bbox("XX").is_none());
+    }
+
+    #[test]
+    fn validate_new_places_offline() {
+        let csv = "\
+title,description,lat,lng,street,zip,city,country,state,contact_name,contact_email,contact_phone,opening_hours,founded_on,tags,homepage,license,image_url,image_link_url
+Ok,A place,51.4,7.2,,,,,,,,,,,bio,,CC0-1.0,,
+Address,A place,,,Hauptstr. 1,44787,,,,,,,,,bio,,CC0-1.0,,
+No title,,51.4,7.2,,,,,,,,,,,bio,,CC0-1.0,,
+No location,A place,,,Hauptstr. 1,,,,,,,,,,bio,,CC0-1.0,,
+Bad email,A place,51.4,7.2,,,,,,,foo@,,,,bio,,CC0-1.0,,
+Bad lat,A place,91,181,,,,,,,,,,,bio,,CC0-1.0,,
+No tags,A place,51.4,7.2,,,,,,,,,,,\"#, \",,CC0-1.0,,
+Bad date,A place,51.4,7.2,,,,,,,,,,05/2019,bio,,CC0-1.0,,
+";
+        let results = validate_new_places(csv.as_bytes(), &CsvOptions::default()).unwrap();
+        assert_eq!(results.len(), 8);
+        assert_eq!(results[0].result.as_deref().unwrap(), "Ok");
+        assert_eq!(results[1].result.as_deref().unwrap(), "Address");
+        for r in &results[2..] {
+            assert!(r.result.is_err(), "{}", r.record_nr);
+        }
+        assert!(matches!(
+            results[3].result,
+            Err(CsvImportError::AddressOrGeoCoordinates(_))
+        ));
+        assert!(matches!(results[7].result, Err(CsvImportError::Record(_))));
+    }
+
+    #[test]
+    fn split_a_combined_contact_column() {
+        let csv = "\
+title,description,lat,lng,tags,license,contact_email,contact
+Combined,A place,51.4,7.2,bio,CC0-1.0,,\"Maria Muster <maria@example.org>, 0171 1234567\"
+Given,A place,51.4,7.2,bio,CC0-1.0,info@example.org,\"Maria <maria@example.org>, Mo-Fr\"
+";
+        let results =
+            new_places_with_options(csv.as_bytes(), &OpenCage::new(None), &CsvOptions::default())
+                .unwrap();
+        let combined = results[0].result.as_ref().unwrap();
+        assert_eq!(combined.contact_name.as_deref(), Some("Maria Muster"));
+        assert_eq!(combined.email.as_deref(), Some("maria@example.org"));
+        assert_eq!(combined.telephone.as_deref(), Some("0171 1234567"));
+        assert!(results[0].warnings.is_empty());
+        let given = results[1].result.as_ref().unwrap();
+        assert_eq!(given.email.as_deref(), Some("info@example.org"));
+        assert_eq!(given.contact_name.as_deref(), Some("Maria"));
+        assert_eq!(results[1].warnings.len(), 1);
+        assert!(results[1].warnings[0].ends_with(": Mo-Fr"));
+    }
+
+    #[test]
+    fn extract_hashtags_of_the_description() {
+        let csv = "\
+title,description,lat,lng,tags,license
+Repair,Repair café #Repair #zerowaste,51.4,7.2,,CC0-1.0
+";
+        let options = CsvOptions {
+            hashtags: DescriptionHashtags::Extract,
+            ..Default::default()
+        };
+        let results =
+            new_places_with_options(csv.as_bytes(), &OpenCage::new(None), &options).unwrap();
+        let place = results[0].result.as_ref().unwrap();
+        assert_eq!(place.tags, vec!["repair", "zerowaste"]);
+        assert_eq!(place.description, "Repair café #Repair #zerowaste");
+    }
+
+    #[test]
+    fn fill_empty_columns_with_default_values() {
+        let csv = "\
+title,description,lat,lng,country,tags
+Empty,A place,51.4,7.2,,
+Given,A place,51.4,7.2,Österreich,bio
+";
+        let results = validate_new_places(csv.as_bytes(), &CsvOptions::default()).unwrap();
+        assert!(results[0].result.is_err());
+        let options = CsvOptions {
+            defaults: RecordDefaults {
+                country: Some("Deutschland".to_string()),
+                license: Some("CC0-1.0".to_string()),
+                tags: vec!["regional".to_string()],
+            },
+            ..Default::default()
+        };
+        let results =
+            new_places_with_options(csv.as_bytes(), &OpenCage::new(None), &options).unwrap();
+        let empty = results[0].result.as_ref().unwrap();
+        assert_eq!(empty.country.as_deref(), Some("Deutschland"));
+        assert_eq!(empty.license, "CC0-1.0");
+        assert_eq!(empty.tags, vec!["regional"]);
+        let given = results[1].result.as_ref().unwrap();
+        assert_eq!(given.country.as_deref(), Some("Österreich"));
+        assert_eq!(given.tags, vec!["bio"]);
+    }
+
+    #[test]
+    fn parse_dates() {
+        let date = |s| parse_date(s).map(|d| d.to_string());
+        assert_eq!(date("2001-02-03").as_deref(), Some("2001-02-03"));
+        assert_eq!(date("01.05.2019").as_deref(), Some("2019-05-01"));
+        assert_eq!(date("1.5.2019").as_deref(), Some("2019-05-01"));
+        assert_eq!(date(" 2019 ").as_deref(), Some("2019-01-01"));
+        assert_eq!(date("2001-02-30"), None);
+        assert_eq!(date("01.05.19"), None);
+        assert_eq!(date("05/2019"), None);
+        assert_eq!(date("19"), None);
+    }
+
+    #[test]
+    fn patch_founded_on_in_german_format() {
+        let mut founded_on = None;
+        patch_optional_date_field("founded_on", &mut founded_on, Some("==01.05.2019".into()))
+            .unwrap();
+        assert_eq!(founded_on.unwrap().to_string(), "2019-05-01");
+        assert!(
+            patch_optional_date_field("founded_on", &mut founded_on, Some("==Mai".into())).is_err()
+        );
+    }
+
+    #[test]
+    fn write_new_places_and_read_them_again() {
+        let places = crate::fixtures::generate(10, 1).new_places;
+        let mut csv = vec![];
+        write_new_places(&mut csv, &places).unwrap();
+        let read_places: Vec<_> = new_places_from_reader(&csv[..], None)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.result.unwrap())
+            .collect();
+        assert_eq!(read_places.len(), places.len());
+        for (read, place) in read_places.iter().zip(&places) {
+            assert_eq!(read.title, place.title);
+            assert_eq!(read.lat, place.lat);
+            assert_eq!(read.city, place.city);
+            assert_eq!(read.tags, place.tags);
+            assert_eq!(read.founded_on, place.founded_on);
+            assert_eq!(read.links.len(), place.links.len());
+            for (read, link) in read.links.iter().zip(&place.links) {
+                assert_eq!(read.url, link.url);
+                assert_eq!(read.title, link.title);
+                assert_eq!(read.description, link.description);
+            }
+        }
+        assert!(places.iter().any(|p| !p.links.is_empty()));
+    }
+
+    #[test]
+    fn read_custom_link_columns_of_new_places() {
+        let csv = "\
+title,description,lat,lng,license,tags,custom_link_title_0,custom_link_url_0,custom_link_url_1,custom_link_description_2
+A,Foo,1.0,2.0,CC0-1.0,bio,Events,www.example.org/events,https://example.org/shop,No URL
+";
+        let results = new_places_from_reader(csv.as_bytes(), None).unwrap();
+        let place = results[0].result.as_ref().unwrap();
+        assert_eq!(place.links.len(), 2);
+        assert_eq!(place.links[0].url, "https://www.example.org/events");
+        assert_eq!(place.links[0].title.as_deref(), Some("Events"));
+        assert_eq!(place.links[1].url, "https://example.org/shop");
+
+        let csv = "title,description,lat,lng,license,tags,custom_link_url_0\nA,Foo,1.0,2.0,CC0-1.0,bio,foo\n";
+        let results = new_places_from_reader(csv.as_bytes(), None).unwrap();
+        assert!(results[0].result.as_ref().unwrap().links.is_empty());
+        assert!(results[0].warnings[0].contains("'foo'"));
+    }
+
+    #[test]
+    fn write_failed_new_places_and_read_them_again() {
+        let places = crate::fixtures::generate(2, 3).new_places;
+        let failed: Vec<_> = places
+            .into_iter()
+            .map(|p| (p, "Invalid tags".to_string()))
+            .collect();
+        let mut csv = vec![];
+        write_failed_new_places(&mut csv, &failed).unwrap();
+        let text = String::from_utf8(csv.clone()).unwrap();
+        assert!(text
+            .lines()
+            .next()
+            .unwrap()
+            .ends_with(",custom_link_url_4,import_error"));
+        assert!(text.lines().nth(1).unwrap().ends_with(",Invalid tags"));
+        let read: Vec<_> = new_places_from_reader(&csv[..], None)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.result.unwrap())
+            .collect();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[1].title, failed[1].0.title);
+    }
+
+    #[test]
+    fn write_places_with_computed_columns_and_read_them_again() {
+        let file = File::open("tests/update-example.csv").unwrap();
+        let entries: Vec<_> = places_from_reader(file)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.result.unwrap())
+            .collect();
+        let now = entries[0].created + 10 * SECONDS_PER_DAY + 5;
+        let computed: HashMap<_, _> = entries
+            .iter()
+            .map(|e| {
+                let columns = ComputedColumns::new(e, None, Some(ReviewStatus::Confirmed), now);
+                (e.id.clone(), columns)
+            })
+            .collect();
+
+        let mut csv = vec![];
+        write_places(&mut csv, &entries, Some(&computed)).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .ends_with("days_since_created,days_since_last_update,review_status,ratings"));
+        assert!(lines.next().unwrap().contains(",,10,confirmed,"));
+
+        let read_entries: Vec<_> = places_from_reader(csv.as_bytes())
+            .unwrap()
+            .into_iter()
+            .map(|r| r.result.unwrap())
+            .collect();
+        assert_eq!(read_entries.len(), entries.len());
+        assert_eq!(read_entries[0].id, entries[0].id);
+        assert_eq!(read_entries[0].tags, entries[0].tags);
+        assert_eq!(read_entries[0].ratings, entries[0].ratings);
+        assert_eq!(
+            read_entries[0].custom_links[0].url,
+            entries[0].custom_links[0].url
+        );
+    }
+
+    #[test]
+    fn detect_unknown_ids() {
+        let file = File::open("tests/update-example.csv").unwrap();
+        let results = places_from_reader(file).unwrap();
+        let id = results[0].result.as_ref().unwrap().id.parse().unwrap();
+
+        let checked = mark_unknown_ids(results.clone(), &HashSet::from([id]));
+        assert!(checked[0].result.is_ok());
+        assert!(unknown_ids(&checked).is_empty());
+
+        let checked = mark_unknown_ids(results, &HashSet::new());
+        assert!(matches!(
+            checked[0].result,
+            Err(CsvImportError::UnknownId(_))
+        ));
+        assert_eq!(unknown_ids(&checked), vec![id.simple().to_string()]);
+    }
+
+    #[test]
+    fn compute_entry_age() {
+        let mut entry = places_from_reader(File::open("tests/update-example.csv").unwrap())
+            .unwrap()
+            .remove(0)
+            .result
+            .unwrap();
+        let now = entry.created + 3 * SECONDS_PER_DAY;
+        let columns = ComputedColumns::new(&entry, None, None, now);
+        assert_eq!(columns.days_since_created, None);
+        assert_eq!(columns.days_since_last_update, Some(3));
+
+        let first_created = entry.created - 7 * SECONDS_PER_DAY;
+        let columns = ComputedColumns::new(&entry, Some(first_created), None, now);
+        assert_eq!(columns.days_since_created, Some(10));
+
+        entry.version = 0;
+        let columns = ComputedColumns::new(&entry, None, None, now);
+        assert_eq!(columns.days_since_created, Some(3));
     }
 
     #[test]
@@ -836,14 +3133,150 @@ mod tests {
         assert!(updates[0].result.is_ok());
     }
 
+    #[test]
+    fn merge_tag_columns_into_the_tags() {
+        let csv = "\
+title,description,lat,lng,license,tags,tag_0,tag_1,tag_9
+Both,A place,51.4,7.2,CC0-1.0,bio,Repair,,#zerowaste
+Columns,A place,51.4,7.2,CC0-1.0,,fair,,
+";
+        let results = validate_new_places(csv.as_bytes(), &CsvOptions::default()).unwrap();
+        assert!(results[1].result.is_ok());
+        let results =
+            new_places_with_options(csv.as_bytes(), &OpenCage::new(None), &CsvOptions::default())
+                .unwrap();
+        let tags = |i: usize| results[i].result.as_ref().unwrap().tags.clone();
+        assert_eq!(tags(0), vec!["bio", "repair", "zerowaste"]);
+        assert_eq!(tags(1), vec!["fair"]);
+
+        // The ratings have to be the last column
+        let update = std::fs::read_to_string("tests/update-example.csv").unwrap();
+        let update = update
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                let (fields, ratings) = line.rsplit_once(',').unwrap();
+                let tag = if i == 0 { "tag_0" } else { "solar" };
+                format!("{fields},{tag},{ratings}\n")
+            })
+            .collect::<String>();
+        let entry = places_from_reader(update.as_bytes()).unwrap()[0]
+            .result
+            .clone()
+            .unwrap();
+        assert_eq!(entry.tags, vec!["bank", "geld", "commercial", "solar"]);
+    }
+
+    #[test]
+    fn reject_updates_without_tags_column() {
+        let file = File::open("tests/update-example.csv").unwrap();
+        let mut rdr = ::csv::Reader::from_reader(file);
+        let headers = rdr.headers().unwrap().clone();
+        let tags = headers.iter().position(|h| h == "tags").unwrap();
+        let without_tags = |record: &StringRecord| -> StringRecord {
+            record
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != tags)
+                .map(|(_, field)| field)
+                .collect()
+        };
+        let mut wtr = ::csv::WriterBuilder::new().from_writer(vec![]);
+        wtr.write_record(&without_tags(&headers)).unwrap();
+        for record in rdr.records() {
+            wtr.write_record(&without_tags(&record.unwrap())).unwrap();
+        }
+        let csv = wtr.into_inner().unwrap();
+        let results = places_from_reader(&csv[..]).unwrap();
+        assert!(results[0].result.is_err());
+    }
+
+    #[test]
+    fn read_headers_with_other_spellings() {
+        let csv = "\
+ Title ,Beschreibung,Latitude,Longitude,LICENSE,TAGS,E-Mail
+Headers,A place,51.4,7.2,CC0-1.0,bio,mail@example.org
+";
+        let results = validate_new_places(csv.as_bytes(), &CsvOptions::default()).unwrap();
+        assert!(results[0].result.is_ok());
+        let place =
+            new_places_with_options(csv.as_bytes(), &OpenCage::new(None), &CsvOptions::default())
+                .unwrap()
+                .remove(0)
+                .result
+                .unwrap();
+        assert_eq!(place.title, "Headers");
+        assert_eq!(place.email.as_deref(), Some("mail@example.org"));
+    }
+
+    #[test]
+    fn reject_unknown_columns_if_strict() {
+        let csv = "title,description,lat,lng,license,tags,tgas\nA,A place,51.4,7.2,CC0-1.0,bio,\n";
+        let results = validate_new_places(csv.as_bytes(), &CsvOptions::default()).unwrap();
+        assert!(results[0].result.is_ok());
+        let options = CsvOptions {
+            strict_columns: true,
+            ..Default::default()
+        };
+        let err = validate_new_places(csv.as_bytes(), &options).unwrap_err();
+        assert!(
+            err.to_string().contains("'tgas' (did you mean 'tags'?)"),
+            "{err}"
+        );
+        let file = File::open("tests/update-example.csv").unwrap();
+        assert!(
+            places_with_filter(file, &RecordFilter::default(), TagSeparator::Comma, true).is_ok()
+        );
+    }
+
+    #[test]
+    fn read_tags_with_other_separators() {
+        let csv = "\
+title,description,lat,lng,license,tags
+Semicolon,A place,51.4,7.2,CC0-1.0,Bio; Regional
+Hashtags,A place,51.4,7.2,CC0-1.0,#repair #zerowaste
+Words,A place,51.4,7.2,CC0-1.0,zero waste
+";
+        let tags = |options: &CsvOptions| {
+            new_places_with_options(csv.as_bytes(), &OpenCage::new(None), options)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.result.unwrap().tags)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            tags(&CsvOptions::default()),
+            vec![
+                vec!["bio", "regional"],
+                vec!["repair", "zerowaste"],
+                vec!["zero waste"]
+            ]
+        );
+        let options = CsvOptions {
+            tag_separator: TagSeparator::Whitespace,
+            ..Default::default()
+        };
+        assert_eq!(tags(&options)[2], vec!["zero", "waste"]);
+    }
+
     #[test]
     fn read_patch_updates_from_csv_file() {
         let file = File::open("tests/update-patch-example.csv").unwrap();
-        let (patches, failures) = patches_from_reader(file).unwrap();
+        let (patches, failures) =
+            patches_from_reader(file, &RecordFilter::default(), false).unwrap();
         assert_eq!(patches.len(), 4);
         assert_eq!(failures.len(), 0);
     }
 
+    #[test]
+    fn read_uuids() {
+        let ids = "# Zukunftsorte\n5d6e2d4ac4a84a5b9b3b5d5e5f5a5b5c\n\n 0c0b0ed1-d2d5-4e2a-8a4b-b1e6c4d0e4b5 \n";
+        let uuids = uuids_from_reader(ids.as_bytes()).unwrap();
+        assert_eq!(uuids.len(), 2);
+        let err = uuids_from_reader("foo\n".as_bytes()).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid UUID 'foo' in line 1"));
+    }
+
     mod patch {
 
         use super::*;
@@ -878,6 +3311,10 @@ mod tests {
             }
         }
 
+        fn patch_place(original: Entry, record: PatchPlaceRecord) -> Result<Entry> {
+            patch_place_with(original, record, &PatchOptions::default())
+        }
+
         #[test]
         fn append() {
             assert_eq!(patch_op("++foo"), Ok(Some(PatchOp::Append("foo"))));
@@ -907,6 +3344,22 @@ mod tests {
             );
         }
 
+        #[test]
+        fn values_that_look_like_operators() {
+            assert_eq!(
+                patch_op("==== Title ===="),
+                Ok(Some(PatchOp::Replace("== Title ====")))
+            );
+            assert_eq!(
+                patch_op("==\\-- not deleted"),
+                Ok(Some(PatchOp::Replace("-- not deleted")))
+            );
+            assert_eq!(patch_op("++\\==foo"), Ok(Some(PatchOp::Append("==foo"))));
+            assert_eq!(patch_op("--\\++foo"), Ok(Some(PatchOp::Delete("++foo"))));
+            assert_eq!(patch_op("==\\\\foo"), Ok(Some(PatchOp::Replace("\\foo"))));
+            assert_eq!(patch_op("==\\"), Err(PatchOpError::EmptyString));
+        }
+
         #[test]
         fn do_nothing() {
             assert_eq!(patch_op(""), Ok(None));
@@ -972,6 +3425,53 @@ mod tests {
             assert_eq!(patched.tags, vec!["foo", "bar", "baz", "boing"]);
         }
 
+        #[test]
+        fn prepend_description_with_separator() {
+            let original = Entry {
+                title: "Foo".to_string(),
+                description: "Foo".to_string(),
+                homepage: None,
+                ..default_entry()
+            };
+            let record = PatchPlaceRecord {
+                version: original.version + 1,
+                title: Some("++Bar".to_string()),
+                description: Some("++Bar".to_string()),
+                homepage: Some("++https://example.org".to_string()),
+                ..Default::default()
+            };
+            let options = PatchOptions {
+                separator: "\n\n".to_string(),
+                prepend: true,
+                ..Default::default()
+            };
+            let patched = patch_place_with(original, record, &options).unwrap();
+            assert_eq!(patched.title, "Foo Bar");
+            assert_eq!(patched.description, "Bar\n\nFoo");
+            assert_eq!(patched.homepage.as_deref(), Some("https://example.org"));
+        }
+
+        #[test]
+        fn move_hashtags_of_a_patched_description() {
+            let original = Entry {
+                description: "Foo".to_string(),
+                tags: vec!["repair".to_string()],
+                ..default_entry()
+            };
+            let record = PatchPlaceRecord {
+                version: original.version + 1,
+                description: Some("++#Repair #zerowaste".to_string()),
+                ..Default::default()
+            };
+            let options = PatchOptions {
+                hashtags: DescriptionHashtags::Move,
+                ..Default::default()
+            };
+            let patched = patch_place_with(original, record, &options).unwrap();
+            assert_eq!(patched.description, "Foo");
+            assert_eq!(patched.tags, vec!["repair", "zerowaste"]);
+        }
+
         #[test]
         fn remove_tags() {
             let original = Entry {
@@ -987,6 +3487,33 @@ mod tests {
             assert_eq!(patched.tags, vec!["bar"]);
         }
 
+        #[test]
+        fn patch_tags_with_other_separators() {
+            let original = Entry {
+                tags: vec!["foo".to_string(), "bar".to_string()],
+                ..default_entry()
+            };
+            let record = PatchPlaceRecord {
+                version: original.version + 1,
+                tags: Some("--foo;++baz".to_string()),
+                ..Default::default()
+            };
+            let patched = patch_place(original.clone(), record).unwrap();
+            assert_eq!(patched.tags, vec!["bar", "baz"]);
+
+            let record = PatchPlaceRecord {
+                version: original.version + 1,
+                tags: Some("--foo ++baz".to_string()),
+                ..Default::default()
+            };
+            let options = PatchOptions {
+                tag_separator: TagSeparator::Whitespace,
+                ..Default::default()
+            };
+            let patched = patch_place_with(original, record, &options).unwrap();
+            assert_eq!(patched.tags, vec!["bar", "baz"]);
+        }
+
         #[test]
         fn remove_and_append_tags() {
             let original = Entry {
@@ -1001,5 +3528,29 @@ mod tests {
             let patched = patch_place(original, record).unwrap();
             assert_eq!(patched.tags, vec!["foo", "baz"]);
         }
+
+        #[test]
+        fn apply_a_template() {
+            let template = "tags,homepage\n++zukunftsorte,==https://example.org\n";
+            let (headers, record) = template_from_reader(template.as_bytes()).unwrap();
+            let original = Entry {
+                id: "5d6e2d4ac4a84a5b9b3b5d5e5f5a5b5c".to_string(),
+                version: 7,
+                tags: vec!["foo".to_string()],
+                ..default_entry()
+            };
+            let patch = patch_from_template(&headers, &record, &original).unwrap();
+            let patched = patch_place(original, patch).unwrap();
+            assert_eq!(patched.version, 8);
+            assert_eq!(patched.tags, vec!["foo", "zukunftsorte"]);
+            assert_eq!(patched.homepage.as_deref(), Some("https://example.org"));
+        }
+
+        #[test]
+        fn reject_templates_with_several_patches() {
+            let template = "tags\n++foo\n++bar\n";
+            assert!(template_from_reader(template.as_bytes()).is_err());
+            assert!(template_from_reader("tags\n".as_bytes()).is_err());
+        }
     }
 }
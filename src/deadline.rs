@@ -0,0 +1,41 @@
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+/// Exit code of the CLI if a run was stopped because of its maximum duration
+/// (same as the `timeout` command).
+pub const EXIT_CODE_DEADLINE_EXCEEDED: i32 = 124;
+
+#[derive(Debug, Error)]
+#[error("The maximum duration of the run was exceeded")]
+pub struct DeadlineExceeded;
+
+/// Point in time after which a run should gracefully stop.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    pub fn after(duration: Option<Duration>) -> Self {
+        Self(duration.map(|d| Instant::now() + d))
+    }
+
+    pub const fn none() -> Self {
+        Self(None)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.0.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expire() {
+        assert!(!Deadline::none().is_expired());
+        assert!(!Deadline::after(Some(Duration::from_secs(60))).is_expired());
+        assert!(Deadline::after(Some(Duration::ZERO)).is_expired());
+    }
+}
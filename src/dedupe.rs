@@ -0,0 +1,184 @@
+//! Find clusters of existing entries that are probably duplicates.
+
+use std::io::Write;
+
+use anyhow::Result;
+use ofdb_boundary::Entry;
+use serde::Serialize;
+
+use crate::matching::{distance_in_meters, title_similarity};
+
+/// Approximate length of one degree of latitude in meters.
+const METERS_PER_DEGREE: f64 = 111_195.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DedupeOptions {
+    /// Min. similarity of the titles (`0.0` to `1.0`).
+    pub min_similarity: f64,
+    /// Max. distance in meters.
+    pub max_distance: f64,
+}
+
+impl Default for DedupeOptions {
+    fn default() -> Self {
+        Self {
+            min_similarity: 0.8,
+            max_distance: 100.0,
+        }
+    }
+}
+
+/// A row of the report.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateRecord {
+    pub cluster: usize,
+    pub id: String,
+    pub title: String,
+    /// `keep` for the oldest entry of a cluster, `merge` for the others.
+    pub suggestion: &'static str,
+    /// Similarity of the title to the title of the entry to keep.
+    pub similarity: f64,
+    /// Distance to the entry to keep in meters.
+    pub distance: f64,
+}
+
+/// Group entries with similar titles that are close to each other.
+///
+/// Returns the indices of the entries of each cluster,
+/// the oldest entry first.
+pub fn clusters(entries: &[Entry], options: DedupeOptions) -> Vec<Vec<usize>> {
+    let mut by_lat: Vec<_> = (0..entries.len()).collect();
+    by_lat.sort_by(|a, b| entries[*a].lat.total_cmp(&entries[*b].lat));
+    let max_lat_diff = options.max_distance / METERS_PER_DEGREE;
+
+    let mut parents: Vec<_> = (0..entries.len()).collect();
+    for (pos, &a) in by_lat.iter().enumerate() {
+        for &b in &by_lat[pos + 1..] {
+            let (ea, eb) = (&entries[a], &entries[b]);
+            if eb.lat - ea.lat > max_lat_diff {
+                break;
+            }
+            if distance_in_meters((ea.lat, ea.lng), (eb.lat, eb.lng)) <= options.max_distance
+                && title_similarity(&ea.title, &eb.title) >= options.min_similarity
+            {
+                let (root_a, root_b) = (root(&mut parents, a), root(&mut parents, b));
+                parents[root_a] = root_b;
+            }
+        }
+    }
+
+    let mut clusters: Vec<Vec<usize>> = vec![];
+    let mut cluster_of_root: Vec<Option<usize>> = vec![None; entries.len()];
+    for i in 0..entries.len() {
+        let r = root(&mut parents, i);
+        match cluster_of_root[r] {
+            Some(c) => clusters[c].push(i),
+            None => {
+                cluster_of_root[r] = Some(clusters.len());
+                clusters.push(vec![i]);
+            }
+        }
+    }
+    clusters.retain(|c| c.len() > 1);
+    for c in &mut clusters {
+        c.sort_by_key(|i| (entries[*i].created, *i));
+    }
+    clusters
+}
+
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Create the merge suggestions.
+pub fn duplicates(entries: &[Entry], options: DedupeOptions) -> Vec<DuplicateRecord> {
+    let mut records = vec![];
+    for (nr, cluster) in clusters(entries, options).into_iter().enumerate() {
+        let keep = &entries[cluster[0]];
+        for (pos, i) in cluster.into_iter().enumerate() {
+            let e = &entries[i];
+            let distance = distance_in_meters((keep.lat, keep.lng), (e.lat, e.lng));
+            records.push(DuplicateRecord {
+                cluster: nr + 1,
+                id: e.id.clone(),
+                title: e.title.clone(),
+                suggestion: if pos == 0 { "keep" } else { "merge" },
+                similarity: (title_similarity(&keep.title, &e.title) * 100.0).round() / 100.0,
+                distance: distance.round(),
+            });
+        }
+    }
+    records
+}
+
+pub fn write_duplicates<W: Write>(w: W, records: &[DuplicateRecord]) -> Result<()> {
+    let mut wtr = csv::WriterBuilder::new().from_writer(w);
+    for r in records {
+        wtr.serialize(r)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<Entry> {
+        let mut entries = crate::fixtures::generate(5, 7).entries;
+        let places = [
+            ("Repair Café Bochum", 51.4818, 7.2162, 3),
+            ("Unverpackt Bochum", 51.4819, 7.2163, 1),
+            ("Repaircafe Bochum e.V.", 51.4820, 7.2162, 2),
+            ("Repair Café Bochum", 51.5136, 7.4653, 0),
+            ("Repair-Café Bochum", 51.4818, 7.2161, 4),
+        ];
+        for (e, (title, lat, lng, created)) in entries.iter_mut().zip(places) {
+            e.title = title.to_string();
+            e.lat = lat;
+            e.lng = lng;
+            e.created = created;
+        }
+        entries
+    }
+
+    #[test]
+    fn cluster_similar_entries_nearby() {
+        let entries = entries();
+        let clusters = clusters(&entries, DedupeOptions::default());
+        assert_eq!(clusters, vec![vec![2, 0, 4]]);
+    }
+
+    #[test]
+    fn use_the_max_distance() {
+        let entries = entries();
+        let options = DedupeOptions {
+            max_distance: 20_000.0,
+            ..Default::default()
+        };
+        assert_eq!(clusters(&entries, options), vec![vec![3, 2, 0, 4]]);
+    }
+
+    #[test]
+    fn suggest_to_keep_the_oldest_entry() {
+        let entries = entries();
+        let records = duplicates(&entries, DedupeOptions::default());
+        let rows: Vec<_> = records
+            .iter()
+            .map(|r| (r.cluster, r.title.as_str(), r.suggestion, r.similarity))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (1, "Repaircafe Bochum e.V.", "keep", 1.0),
+                (1, "Repair Café Bochum", "merge", 0.91),
+                (1, "Repair-Café Bochum", "merge", 0.91),
+            ]
+        );
+        assert_eq!(records[1].distance, 22.0);
+    }
+}
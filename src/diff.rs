@@ -0,0 +1,222 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+use ofdb_boundary::Entry;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::json_patch::ENTRY_FIELDS;
+
+/// Fields of an entry that can't be modified by an update.
+const READ_ONLY_ENTRY_FIELDS: &[&str] = &["id", "created", "version", "ratings", "license"];
+
+/// A single field that differs between two versions of a place.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FieldChange {
+    /// Name of the field, nested fields are separated with a dot
+    /// (e.g. `location.address.city`).
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// The differences between the current state of an entry on the server
+/// and a local (updated) version of it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EntryDiff {
+    pub id: String,
+    pub title: String,
+    pub local_version: u64,
+    pub server_version: Option<u64>,
+    /// The entry was modified on the server after the local file was exported.
+    pub outdated: bool,
+    pub changes: Vec<FieldChange>,
+}
+
+impl EntryDiff {
+    pub fn not_found(local: &Entry) -> Self {
+        Self {
+            id: local.id.clone(),
+            title: local.title.clone(),
+            local_version: local.version,
+            server_version: None,
+            outdated: false,
+            changes: vec![],
+        }
+    }
+}
+
+/// Compare the server state of an entry with a local version.
+pub fn diff_entries(server: &Entry, local: &Entry) -> EntryDiff {
+    let server_value = serde_json::to_value(server).unwrap_or_default();
+    let local_value = serde_json::to_value(local).unwrap_or_default();
+    EntryDiff {
+        id: local.id.clone(),
+        title: local.title.clone(),
+        local_version: local.version,
+        server_version: Some(server.version),
+        outdated: server.version > local.version,
+        changes: diff_json(&server_value, &local_value, READ_ONLY_ENTRY_FIELDS),
+    }
+}
+
+/// Fields of an entry that must not be modified by an update,
+/// e.g. `title,license,founded_on`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProtectedFields(Vec<String>);
+
+impl ProtectedFields {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, field: &str) -> bool {
+        self.0.iter().any(|f| f == field)
+    }
+
+    /// The protected fields that differ between the server state and the local version.
+    pub fn modified(&self, server: &Entry, local: &Entry) -> Vec<String> {
+        let server_value = serde_json::to_value(server).unwrap_or_default();
+        let local_value = serde_json::to_value(local).unwrap_or_default();
+        let mut modified: Vec<String> = diff_json(&server_value, &local_value, &[])
+            .into_iter()
+            .map(|change| match change.field.split_once('.') {
+                Some((field, _)) => field.to_string(),
+                None => change.field,
+            })
+            .filter(|field| self.contains(field))
+            .collect();
+        modified.dedup();
+        modified
+    }
+}
+
+impl FromStr for ProtectedFields {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = vec![];
+        for field in s.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            // The names of the CSV columns can be used as well
+            let field = match field {
+                "contact_email" => "email",
+                "contact_phone" => "telephone",
+                "custom_links" => "links",
+                field => field,
+            };
+            if !ENTRY_FIELDS.contains(&field) && !READ_ONLY_ENTRY_FIELDS.contains(&field) {
+                bail!("Unknown field '{field}'");
+            }
+            fields.push(field.to_string());
+        }
+        Ok(Self(fields))
+    }
+}
+
+/// Compare two JSON values field by field.
+///
+/// Top-level fields listed in `ignored_fields` are skipped.
+pub fn diff_json(old: &Value, new: &Value, ignored_fields: &[&str]) -> Vec<FieldChange> {
+    let mut changes = vec![];
+    diff_values("", old, new, ignored_fields, &mut changes);
+    changes
+}
+
+fn diff_values(
+    path: &str,
+    old: &Value,
+    new: &Value,
+    ignored_fields: &[&str],
+    changes: &mut Vec<FieldChange>,
+) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut keys: Vec<_> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                if path.is_empty() && ignored_fields.contains(&key.as_str()) {
+                    continue;
+                }
+                let nested_path = if path.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{path}.{key}")
+                };
+                diff_values(
+                    &nested_path,
+                    old.get(key).unwrap_or(&Value::Null),
+                    new.get(key).unwrap_or(&Value::Null),
+                    ignored_fields,
+                    changes,
+                );
+            }
+        }
+        (old, new) => {
+            if old != new {
+                changes.push(FieldChange {
+                    field: path.to_string(),
+                    old: old.clone(),
+                    new: new.clone(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fields(changes: Vec<FieldChange>) -> Vec<String> {
+        changes.into_iter().map(|c| c.field).collect()
+    }
+
+    #[test]
+    fn report_nested_changes() {
+        let old = json!({
+            "revision": 1,
+            "title": "Foo",
+            "location": { "lat": 1.0, "lng": 2.0, "address": { "city": "Bochum" } },
+            "tags": ["a", "b"]
+        });
+        let new = json!({
+            "revision": 2,
+            "title": "Foo",
+            "location": { "lat": 1.0, "lng": 2.0, "address": { "city": "Berlin" } },
+            "tags": ["a"]
+        });
+        let changes = diff_json(&old, &new, &["revision"]);
+        assert_eq!(changes[0].old, json!("Bochum"));
+        assert_eq!(changes[0].new, json!("Berlin"));
+        assert_eq!(fields(changes), vec!["location.address.city", "tags"]);
+    }
+
+    #[test]
+    fn detect_modified_protected_fields() {
+        let server = crate::fixtures::generate(1, 3).entries.remove(0);
+        let mut local = server.clone();
+        local.version += 1;
+        local.title = "New title".to_string();
+        local.telephone = Some("0234 123456".to_string());
+        local.description = "New description".to_string();
+        let protected: ProtectedFields = "title, license, contact_phone".parse().unwrap();
+        assert!(protected.contains("telephone"));
+        assert_eq!(
+            protected.modified(&server, &local),
+            vec!["telephone", "title"]
+        );
+        assert!(protected.modified(&server, &server).is_empty());
+        assert!("title,foo".parse::<ProtectedFields>().is_err());
+    }
+
+    #[test]
+    fn report_added_and_removed_fields() {
+        let old = json!({ "opening_hours": null, "founded_on": "2019-01-01" });
+        let new = json!({ "opening_hours": "Mo-Fr 09:00-17:00" });
+        assert_eq!(
+            fields(diff_json(&old, &new, &[])),
+            vec!["founded_on", "opening_hours"]
+        );
+    }
+}
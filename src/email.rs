@@ -0,0 +1,142 @@
+//! Send reports by e-mail,
+//! e.g. to coordinators who never look at the server where a cron job runs.
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use lettre::{
+    message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart},
+    transport::smtp::authentication::Credentials,
+    Message, SmtpTransport, Transport,
+};
+
+use crate::{
+    config::{SmtpConfig, SmtpSecurity},
+    report::ReportFormat,
+};
+
+pub struct ReportMail {
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    transport: SmtpTransport,
+}
+
+impl ReportMail {
+    pub fn new(config: &SmtpConfig, to: &[String]) -> Result<Self> {
+        let from = config
+            .from
+            .parse()
+            .with_context(|| format!("Invalid sender address '{}'", config.from))?;
+        let to = to
+            .iter()
+            .map(|addr| {
+                addr.parse()
+                    .with_context(|| format!("Invalid e-mail address '{addr}'"))
+            })
+            .collect::<Result<_>>()?;
+        let mut builder = match config.security {
+            SmtpSecurity::Starttls => SmtpTransport::starttls_relay(&config.host)?,
+            SmtpSecurity::Tls => SmtpTransport::relay(&config.host)?,
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(&config.host),
+        };
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = &config.username {
+            let password = config.password().unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+        Ok(Self {
+            from,
+            to,
+            transport: builder.build(),
+        })
+    }
+
+    /// Send the text with the report file as attachment.
+    pub fn send(
+        &self,
+        subject: &str,
+        text: &str,
+        report: &Path,
+        format: ReportFormat,
+    ) -> Result<()> {
+        let content = fs::read(report)?;
+        let file_name = report
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "report".to_string());
+        let message = message(
+            self.from.clone(),
+            &self.to,
+            subject,
+            text,
+            (file_name, content, format),
+        )?;
+        self.transport.send(&message)?;
+        log::info!(
+            "Sent the report to {}",
+            self.to
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        Ok(())
+    }
+}
+
+fn message(
+    from: Mailbox,
+    to: &[Mailbox],
+    subject: &str,
+    text: &str,
+    (file_name, content, format): (String, Vec<u8>, ReportFormat),
+) -> Result<Message> {
+    let mut builder = Message::builder().from(from).subject(subject);
+    for to in to {
+        builder = builder.to(to.clone());
+    }
+    let content_type = match format {
+        ReportFormat::Json => "application/json",
+        ReportFormat::Html => "text/html; charset=utf-8",
+        ReportFormat::Csv => "text/csv; charset=utf-8",
+    };
+    let attachment = Attachment::new(file_name).body(content, ContentType::parse(content_type)?);
+    let message = builder.multipart(
+        MultiPart::mixed()
+            .singlepart(SinglePart::plain(text.to_string()))
+            .singlepart(attachment),
+    )?;
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attach_report() {
+        let message = message(
+            "ofdb@example.org".parse().unwrap(),
+            &[
+                "a@example.org".parse().unwrap(),
+                "b@example.org".parse().unwrap(),
+            ],
+            "Import report",
+            "Successes: 3",
+            (
+                "report.csv".to_string(),
+                b"record_nr,status\n0,success\n".to_vec(),
+                ReportFormat::Csv,
+            ),
+        )
+        .unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        assert!(formatted.contains("To: a@example.org, b@example.org"));
+        assert!(formatted.contains("Subject: Import report"));
+        assert!(formatted.contains("Successes: 3"));
+        assert!(formatted.contains("filename=\"report.csv\""));
+        assert!(formatted.contains("Content-Type: text/csv"));
+    }
+}
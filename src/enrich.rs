@@ -0,0 +1,376 @@
+//! Propose values for empty fields of entries from external sources.
+//!
+//! The proposals are written as a CSV file for `update --patch`,
+//! so they can be reviewed (and edited) before they are applied.
+
+use std::{fmt, io::Write, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+use ofdb_boundary::Entry;
+use reqwest::{blocking::Client, Url};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::matching::{distance_in_meters, title_similarity};
+
+const WIKIDATA_API: &str = "https://www.wikidata.org/w/api.php";
+const WIKIDATA_ENTITY_URL: &str = "https://www.wikidata.org/wiki/";
+const COMMONS_FILE_URL: &str = "https://commons.wikimedia.org/wiki/Special:FilePath/";
+
+/// Max. number of search results that are compared with an entry.
+const MAX_CANDIDATES: usize = 5;
+
+// Wikidata properties
+const OFFICIAL_WEBSITE: &str = "P856";
+const INCEPTION: &str = "P571";
+const IMAGE: &str = "P18";
+const COORDINATE_LOCATION: &str = "P625";
+
+/// Precision of a Wikidata time value.
+const PRECISION_DAY: u64 = 11;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    #[default]
+    Wikidata,
+}
+
+impl FromStr for Source {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "wikidata" => Self::Wikidata,
+            _ => bail!("Unknown source '{s}' (expected 'wikidata')"),
+        })
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Wikidata => "wikidata",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EnrichOptions {
+    /// Language of the labels that are searched.
+    pub language: String,
+    /// Min. similarity of the titles (0.0 - 1.0).
+    pub min_similarity: f64,
+    /// Max. distance in meters.
+    pub max_distance: f64,
+}
+
+impl Default for EnrichOptions {
+    fn default() -> Self {
+        Self {
+            language: "de".to_string(),
+            min_similarity: 0.8,
+            max_distance: 500.0,
+        }
+    }
+}
+
+/// An item of the source that might represent an entry.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Candidate {
+    pub id: String,
+    pub label: String,
+    pub lat_lng: Option<(f64, f64)>,
+    pub homepage: Option<String>,
+    /// `YYYY-MM-DD` or only `YYYY` if the day is unknown.
+    pub founded_on: Option<String>,
+    pub image_url: Option<String>,
+}
+
+/// A row of the CSV file for `update --patch`.
+///
+/// `source` and `source_title` are ignored by the update
+/// and only help to review the proposals.
+#[derive(Debug, Clone, Serialize)]
+pub struct Proposal {
+    pub id: String,
+    pub version: u64,
+    pub homepage: String,
+    pub founded_on: String,
+    pub image_url: String,
+    pub source: String,
+    pub source_title: String,
+}
+
+/// Look up each entry and propose values for its empty fields.
+///
+/// `on_checked` is called for each entry with `true` if the lookup succeeded.
+pub fn enrich(
+    http: &Client,
+    source: Source,
+    entries: &[Entry],
+    options: &EnrichOptions,
+    mut on_checked: impl FnMut(bool),
+) -> Vec<Proposal> {
+    let mut proposals = vec![];
+    for entry in entries {
+        if !has_empty_fields(entry) {
+            on_checked(true);
+            continue;
+        }
+        let candidates = match source {
+            Source::Wikidata => wikidata_candidates(http, &entry.title, &options.language),
+        };
+        match candidates {
+            Ok(candidates) => {
+                on_checked(true);
+                if let Some(proposal) = propose(entry, &candidates, options) {
+                    log::debug!("Found '{}' for '{}'", proposal.source, entry.title);
+                    proposals.push(proposal);
+                }
+            }
+            Err(err) => {
+                on_checked(false);
+                log::warn!("Unable to look up '{}': {err}", entry.title);
+            }
+        }
+    }
+    proposals
+}
+
+fn has_empty_fields(entry: &Entry) -> bool {
+    is_empty(&entry.homepage) || entry.founded_on.is_none() || is_empty(&entry.image_url)
+}
+
+fn is_empty(field: &Option<String>) -> bool {
+    field.as_deref().map_or(true, |v| v.trim().is_empty())
+}
+
+/// Find the best matching candidate and propose its values for the empty fields.
+///
+/// A candidate has to have a similar title and a location close to the entry.
+pub fn propose(
+    entry: &Entry,
+    candidates: &[Candidate],
+    options: &EnrichOptions,
+) -> Option<Proposal> {
+    let candidate = candidates
+        .iter()
+        .filter_map(|c| {
+            let lat_lng = c.lat_lng?;
+            let similarity = title_similarity(&entry.title, &c.label);
+            let distance = distance_in_meters((entry.lat, entry.lng), lat_lng);
+            (similarity >= options.min_similarity && distance <= options.max_distance)
+                .then_some((c, similarity))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(c, _)| c)?;
+    // The values of the patch have to be prefixed with the replace operator
+    let replace = |empty: bool, value: &Option<String>| match value {
+        Some(value) if empty => format!("=={value}"),
+        _ => String::new(),
+    };
+    let proposal = Proposal {
+        id: entry.id.clone(),
+        version: entry.version + 1,
+        homepage: replace(is_empty(&entry.homepage), &candidate.homepage),
+        founded_on: replace(entry.founded_on.is_none(), &candidate.founded_on),
+        image_url: replace(is_empty(&entry.image_url), &candidate.image_url),
+        source: format!("{WIKIDATA_ENTITY_URL}{}", candidate.id),
+        source_title: candidate.label.clone(),
+    };
+    if proposal.homepage.is_empty()
+        && proposal.founded_on.is_empty()
+        && proposal.image_url.is_empty()
+    {
+        return None;
+    }
+    Some(proposal)
+}
+
+fn wikidata_candidates(http: &Client, title: &str, language: &str) -> Result<Vec<Candidate>> {
+    let limit = MAX_CANDIDATES.to_string();
+    let search: Value = http
+        .get(WIKIDATA_API)
+        .query(&[
+            ("action", "wbsearchentities"),
+            ("format", "json"),
+            ("type", "item"),
+            ("search", title),
+            ("language", language),
+            ("uselang", language),
+            ("limit", &limit),
+        ])
+        .send()?
+        .error_for_status()?
+        .json()?;
+    let ids: Vec<_> = search["search"]
+        .as_array()
+        .ok_or_else(|| anyhow!("Unexpected search response"))?
+        .iter()
+        .filter_map(|item| item["id"].as_str())
+        .collect();
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let languages = format!("{language}|en");
+    let entities: Value = http
+        .get(WIKIDATA_API)
+        .query(&[
+            ("action", "wbgetentities"),
+            ("format", "json"),
+            ("props", "labels|claims"),
+            ("ids", &ids.join("|")),
+            ("languages", &languages),
+        ])
+        .send()?
+        .error_for_status()?
+        .json()?;
+    Ok(wikidata_entities(&entities, language))
+}
+
+/// Read the candidates of a `wbgetentities` response.
+pub fn wikidata_entities(response: &Value, language: &str) -> Vec<Candidate> {
+    let Some(entities) = response["entities"].as_object() else {
+        return vec![];
+    };
+    entities
+        .iter()
+        .map(|(id, entity)| {
+            let labels = &entity["labels"];
+            let label = labels[language]["value"]
+                .as_str()
+                .or_else(|| labels["en"]["value"].as_str())
+                .unwrap_or_default();
+            let claims = &entity["claims"];
+            Candidate {
+                id: id.clone(),
+                label: label.to_string(),
+                lat_lng: claim(claims, COORDINATE_LOCATION)
+                    .and_then(|v| Some((v["latitude"].as_f64()?, v["longitude"].as_f64()?))),
+                homepage: claim(claims, OFFICIAL_WEBSITE)
+                    .and_then(Value::as_str)
+                    .map(ToString::to_string),
+                founded_on: claim(claims, INCEPTION).and_then(wikidata_date),
+                image_url: claim(claims, IMAGE)
+                    .and_then(Value::as_str)
+                    .and_then(commons_file_url),
+            }
+        })
+        .collect()
+}
+
+/// The value of the first claim of a property that is not deprecated
+/// (preferred claims first).
+fn claim<'a>(claims: &'a Value, property: &str) -> Option<&'a Value> {
+    let claims = claims[property].as_array()?;
+    let rank = |c: &Value| c["rank"].as_str().unwrap_or("normal").to_string();
+    claims
+        .iter()
+        .find(|c| rank(c) == "preferred")
+        .or_else(|| claims.iter().find(|c| rank(c) != "deprecated"))
+        .map(|c| &c["mainsnak"]["datavalue"]["value"])
+        .filter(|v| !v.is_null())
+}
+
+/// Convert a time value (e.g. `+1998-05-01T00:00:00Z`).
+fn wikidata_date(value: &Value) -> Option<String> {
+    let time = value["time"].as_str()?.strip_prefix('+')?;
+    let date = time.split('T').next()?;
+    let year = date.get(..4)?;
+    if value["precision"].as_u64() >= Some(PRECISION_DAY) {
+        Some(date.to_string())
+    } else {
+        Some(year.to_string())
+    }
+}
+
+fn commons_file_url(file_name: &str) -> Option<String> {
+    let mut url = Url::parse(COMMONS_FILE_URL).ok()?;
+    url.path_segments_mut()
+        .ok()?
+        .pop()
+        .push(&file_name.replace(' ', "_"));
+    Some(url.to_string())
+}
+
+pub fn write_proposals<W: Write>(w: W, proposals: &[Proposal]) -> Result<()> {
+    let mut wtr = csv::WriterBuilder::new().from_writer(w);
+    for proposal in proposals {
+        wtr.serialize(proposal)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn response() -> Value {
+        json!({
+          "entities": {
+            "Q123": {
+              "labels": { "de": { "language": "de", "value": "Repair Café Bochum" } },
+              "claims": {
+                "P625": [{ "mainsnak": { "datavalue": { "value": { "latitude": 51.4818, "longitude": 7.2162 } } }, "rank": "normal" }],
+                "P856": [
+                  { "mainsnak": { "datavalue": { "value": "https://old.example.org" } }, "rank": "deprecated" },
+                  { "mainsnak": { "datavalue": { "value": "https://example.org" } }, "rank": "normal" }
+                ],
+                "P571": [{ "mainsnak": { "datavalue": { "value": { "time": "+2014-00-00T00:00:00Z", "precision": 9 } } }, "rank": "normal" }],
+                "P18": [{ "mainsnak": { "datavalue": { "value": "Repair Café.jpg" } }, "rank": "normal" }]
+              }
+            }
+          }
+        })
+    }
+
+    #[test]
+    fn read_wikidata_entities() {
+        let candidates = wikidata_entities(&response(), "de");
+        assert_eq!(
+            candidates,
+            vec![Candidate {
+                id: "Q123".to_string(),
+                label: "Repair Café Bochum".to_string(),
+                lat_lng: Some((51.4818, 7.2162)),
+                homepage: Some("https://example.org".to_string()),
+                founded_on: Some("2014".to_string()),
+                image_url: Some(
+                    "https://commons.wikimedia.org/wiki/Special:FilePath/Repair_Caf%C3%A9.jpg"
+                        .to_string()
+                ),
+            }]
+        );
+        let date = json!({ "time": "+2014-05-03T00:00:00Z", "precision": 11 });
+        assert_eq!(wikidata_date(&date).as_deref(), Some("2014-05-03"));
+    }
+
+    #[test]
+    fn propose_only_empty_fields_of_matching_entries() {
+        let mut entry = crate::fixtures::generate(1, 3).entries.remove(0);
+        entry.title = "Repair-Café Bochum".to_string();
+        entry.version = 4;
+        entry.lat = 51.4820;
+        entry.lng = 7.2160;
+        entry.homepage = Some("https://repaircafe-bochum.de".to_string());
+        entry.founded_on = None;
+        entry.image_url = None;
+        let candidates = wikidata_entities(&response(), "de");
+        let options = EnrichOptions::default();
+
+        let proposal = propose(&entry, &candidates, &options).unwrap();
+        assert_eq!(proposal.version, 5);
+        assert_eq!(proposal.homepage, "");
+        assert_eq!(proposal.founded_on, "==2014");
+        assert!(proposal.image_url.starts_with("==https://commons"));
+        assert_eq!(proposal.source, "https://www.wikidata.org/wiki/Q123");
+
+        entry.lat = 52.0;
+        assert!(propose(&entry, &candidates, &options).is_none());
+        entry.lat = 51.4820;
+        entry.title = "Kleidertausch".to_string();
+        assert!(propose(&entry, &candidates, &options).is_none());
+    }
+}
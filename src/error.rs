@@ -0,0 +1,94 @@
+use reqwest::StatusCode;
+use thiserror::Error;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors of the OpenFairDB API client.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Permission denied: {0}")]
+    Forbidden(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Version conflict: {0}")]
+    VersionConflict(String),
+    #[error("Too many requests: {0}")]
+    RateLimited(String),
+    #[error("Could not decode the response: {0}")]
+    Decode(String),
+    #[error(
+        "The server did not return JSON (status {status}, content type '{content_type}'): {snippet}{}",
+        .hint.as_ref().map(|h| format!(" — {h}")).unwrap_or_default()
+    )]
+    NotJson {
+        status: u16,
+        content_type: String,
+        snippet: String,
+        hint: Option<String>,
+    },
+    #[error("Could not connect to the server: {0}")]
+    Transport(#[from] reqwest::Error),
+    #[error("The server responded with {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("No recorded response for {0}")]
+    NotRecorded(String),
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+    #[error("Invalid certificate file {0}")]
+    InvalidCertificate(String),
+}
+
+impl Error {
+    /// Create an error from an unsuccessful HTTP response.
+    pub fn from_status(status: StatusCode, message: String) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => Self::Unauthorized(message),
+            StatusCode::FORBIDDEN => Self::Forbidden(message),
+            StatusCode::NOT_FOUND => Self::NotFound(message),
+            StatusCode::CONFLICT => Self::VersionConflict(message),
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited(message),
+            _ => Self::Api {
+                status: status.as_u16(),
+                message,
+            },
+        }
+    }
+
+    /// The HTTP status code of the response, if there was one.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::Unauthorized(_) => Some(StatusCode::UNAUTHORIZED.as_u16()),
+            Self::Forbidden(_) => Some(StatusCode::FORBIDDEN.as_u16()),
+            Self::NotFound(_) => Some(StatusCode::NOT_FOUND.as_u16()),
+            Self::VersionConflict(_) => Some(StatusCode::CONFLICT.as_u16()),
+            Self::RateLimited(_) => Some(StatusCode::TOO_MANY_REQUESTS.as_u16()),
+            Self::Api { status, .. } | Self::NotJson { status, .. } => Some(*status),
+            Self::Decode(_)
+            | Self::NotRecorded(_)
+            | Self::InvalidUrl(_)
+            | Self::InvalidCertificate(_) => None,
+            Self::Transport(err) => err.status().map(|s| s.as_u16()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_status_codes() {
+        let err = |code| Error::from_status(StatusCode::from_u16(code).unwrap(), "msg".into());
+        assert!(matches!(err(401), Error::Unauthorized(_)));
+        assert!(matches!(err(403), Error::Forbidden(_)));
+        assert!(matches!(err(404), Error::NotFound(_)));
+        assert!(matches!(err(409), Error::VersionConflict(_)));
+        assert!(matches!(err(429), Error::RateLimited(_)));
+        assert!(matches!(err(500), Error::Api { status: 500, .. }));
+        for code in [401, 403, 404, 409, 429, 400, 500] {
+            assert_eq!(err(code).status(), Some(code));
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Read complete datasets from the CSV export endpoint of the server
+//! instead of searching and reading the entries.
+//!
+//! The export requires a login as scout or admin
+//! (or the token of an organization for its own entries).
+
+use std::io::Read;
+
+use crate::{csv::parse_date, Error, Result};
+use csv::StringRecord;
+use ofdb_boundary::{CustomLink, Entry};
+use serde::Deserialize;
+
+/// A row of the CSV export of the server.
+#[derive(Debug, Deserialize)]
+struct ExportRecord {
+    id: String,
+    #[serde(default)]
+    created_at: i64,
+    version: u64,
+    title: String,
+    description: String,
+    lat: f64,
+    lng: f64,
+    street: Option<String>,
+    zip: Option<String>,
+    city: Option<String>,
+    country: Option<String>,
+    state: Option<String>,
+    contact_name: Option<String>,
+    contact_email: Option<String>,
+    contact_phone: Option<String>,
+    opening_hours: Option<String>,
+    founded_on: Option<String>,
+    homepage: Option<String>,
+    image_url: Option<String>,
+    image_link_url: Option<String>,
+    #[serde(default)]
+    categories: String,
+    #[serde(default)]
+    tags: String,
+    license: Option<String>,
+    /// IDs of the ratings (only in the CSV files of the CLI).
+    #[serde(default)]
+    ratings: String,
+}
+
+impl TryFrom<ExportRecord> for Entry {
+    type Error = Error;
+
+    fn try_from(r: ExportRecord) -> Result<Self> {
+        let list = |s: &str| {
+            s.split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(ToString::to_string)
+                .collect()
+        };
+        let founded_on = match r.founded_on.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(date) => Some(parse_date(date).ok_or_else(|| {
+                Error::Decode(format!("Invalid founded_on '{date}' of the entry {}", r.id))
+            })?),
+        };
+        Ok(Self {
+            id: r.id,
+            created: r.created_at,
+            version: r.version,
+            title: r.title,
+            description: r.description,
+            lat: r.lat,
+            lng: r.lng,
+            street: r.street,
+            zip: r.zip,
+            city: r.city,
+            country: r.country,
+            state: r.state,
+            contact_name: r.contact_name,
+            email: r.contact_email,
+            telephone: r.contact_phone,
+            homepage: r.homepage,
+            opening_hours: r.opening_hours,
+            founded_on,
+            categories: list(&r.categories),
+            tags: list(&r.tags),
+            ratings: list(&r.ratings),
+            license: r.license,
+            image_url: r.image_url,
+            image_link_url: r.image_link_url,
+            custom_links: vec![],
+        })
+    }
+}
+
+/// The links of the `custom_link_*_N` columns (links without URL are ignored).
+fn custom_links(headers: &StringRecord, record: &StringRecord) -> Vec<CustomLink> {
+    let field = |name: String| {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .and_then(|i| record.get(i))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(ToString::to_string)
+    };
+    (0..)
+        .map_while(|i| {
+            let columns = [
+                format!("custom_link_url_{i}"),
+                format!("custom_link_title_{i}"),
+                format!("custom_link_description_{i}"),
+            ];
+            columns
+                .iter()
+                .any(|c| headers.iter().any(|h| h == c))
+                .then(|| columns.map(field))
+        })
+        .filter_map(|[url, title, description]| {
+            url.map(|url| CustomLink {
+                url,
+                title,
+                description,
+            })
+        })
+        .collect()
+}
+
+/// The entries of an export, deserialized while they are received.
+pub struct ExportedPlaces<R: Read> {
+    headers: Option<StringRecord>,
+    records: csv::StringRecordsIntoIter<R>,
+}
+
+impl<R: Read> ExportedPlaces<R> {
+    pub fn from_reader(r: R) -> Self {
+        let mut reader = csv::Reader::from_reader(r);
+        let headers = reader.headers().ok().cloned();
+        Self {
+            headers,
+            records: reader.into_records(),
+        }
+    }
+
+    fn entry(&self, record: csv::Result<StringRecord>) -> Result<Entry> {
+        let decode = |err: csv::Error| Error::Decode(err.to_string());
+        let headers = self
+            .headers
+            .as_ref()
+            .ok_or_else(|| Error::Decode("The export has no header".to_string()))?;
+        let record = record.map_err(decode)?;
+        let export_record: ExportRecord = record.deserialize(Some(headers)).map_err(decode)?;
+        let mut entry = Entry::try_from(export_record)?;
+        entry.custom_links = custom_links(headers, &record);
+        Ok(entry)
+    }
+}
+
+impl<R: Read> Iterator for ExportedPlaces<R> {
+    type Item = Result<Entry>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.records.next()?;
+        Some(self.entry(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_exported_entries() {
+        let csv = "\
+id,osm_node,created_at,created_by,version,title,description,lat,lng,street,zip,city,country,state,contact_name,contact_email,contact_phone,opening_hours,founded_on,homepage,image_url,image_link_url,categories,tags,license,avg_rating
+a1,,1600000000,,3,Repair Café,Reparieren,51.48,7.22,,44787,Bochum,DE,,,mail@example.org,,,2014-05-03,https://example.org,,,2cd00bebec0c48ba9db761da48678134,\"repaircafe,bochum\",CC0-1.0,0.0
+b2,,1600000000,,0,Broken,,north,7.22,,,,,,,,,,,,,,,,CC0-1.0,0.0
+";
+        let entries: Vec<_> = ExportedPlaces::from_reader(csv.as_bytes()).collect();
+        assert_eq!(entries.len(), 2);
+        let entry = entries[0].as_ref().unwrap();
+        assert_eq!(entry.title, "Repair Café");
+        assert_eq!(entry.version, 3);
+        assert_eq!(entry.email.as_deref(), Some("mail@example.org"));
+        assert_eq!(entry.tags, vec!["repaircafe", "bochum"]);
+        assert_eq!(entry.founded_on.unwrap().to_string(), "2014-05-03");
+        assert!(matches!(entries[1], Err(Error::Decode(_))));
+    }
+
+    #[test]
+    fn read_links_and_ratings_and_report_invalid_dates() {
+        let csv = "\
+id,version,title,description,lat,lng,founded_on,tags,custom_link_title_0,custom_link_description_0,custom_link_url_0,custom_link_title_1,custom_link_description_1,custom_link_url_1,ratings
+a1,3,Repair Café,Reparieren,51.48,7.22,,repaircafe,Termine,,https://example.org/termine,,,,\"r1,r2\"
+b2,1,Unknown founding,,51.48,7.22,someday,,,,,,,,
+";
+        let entries: Vec<_> = ExportedPlaces::from_reader(csv.as_bytes()).collect();
+        let entry = entries[0].as_ref().unwrap();
+        assert_eq!(entry.ratings, vec!["r1", "r2"]);
+        assert_eq!(entry.custom_links.len(), 1);
+        assert_eq!(entry.custom_links[0].url, "https://example.org/termine");
+        assert_eq!(entry.custom_links[0].title.as_deref(), Some("Termine"));
+        assert_eq!(entry.founded_on, None);
+        let err = entries[1].as_ref().unwrap_err();
+        assert!(err.to_string().contains("someday"), "{err}");
+    }
+}
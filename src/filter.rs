@@ -0,0 +1,372 @@
+//! Select the records of a CSV file that should be processed,
+//! e.g. `--filter 'city=Hamburg' --rows 100-200` or `--offset 50 --limit 10`.
+
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use anyhow::{anyhow, bail};
+use csv::StringRecord;
+
+use crate::headers::{normalize_header, PLACE_SYNONYMS};
+
+/// A condition on a column, e.g. `city=Hamburg`, `city!=Hamburg` or `tags~solar`.
+///
+/// Values are compared case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub column: String,
+    pub op: Op,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// `=`
+    Equals,
+    /// `!=`
+    NotEquals,
+    /// `~`
+    Contains,
+}
+
+impl Condition {
+    fn matches(&self, cell: &str) -> bool {
+        let cell = cell.trim().to_lowercase();
+        let value = self.value.to_lowercase();
+        match self.op {
+            Op::Equals => cell == value,
+            Op::NotEquals => cell != value,
+            Op::Contains => cell.contains(&value),
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pos = s.find(['=', '~']).ok_or_else(|| {
+            anyhow!("Invalid filter '{s}' (expected e.g. 'city=Hamburg' or 'tags~solar')")
+        })?;
+        let (column, op) = match (&s[..pos], &s[pos..pos + 1]) {
+            (column, "=") => match column.strip_suffix('!') {
+                Some(column) => (column, Op::NotEquals),
+                None => (column, Op::Equals),
+            },
+            (column, _) => (column, Op::Contains),
+        };
+        let column = column.trim();
+        if column.is_empty() {
+            bail!("The filter '{s}' has no column");
+        }
+        Ok(Self {
+            column: normalize_header(column, PLACE_SYNONYMS),
+            op,
+            value: s[pos + 1..].trim().to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.op {
+            Op::Equals => "=",
+            Op::NotEquals => "!=",
+            Op::Contains => "~",
+        };
+        write!(f, "{}{op}{}", self.column, self.value)
+    }
+}
+
+/// A range of record numbers (as used in the reports), e.g. `100-200`, `100-` or `-200`.
+///
+/// Ranges like in Rust are supported as well: `50..100` (without `100`), `50..=100`, `50..` or `..100`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rows {
+    pub first: usize,
+    pub last: Option<usize>,
+}
+
+impl Rows {
+    fn contains(&self, record_nr: usize) -> bool {
+        record_nr >= self.first && self.last.map_or(true, |last| record_nr <= last)
+    }
+}
+
+impl FromStr for Rows {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("Invalid rows '{s}' (expected e.g. '100-200')");
+        let number = |n: &str| n.trim().parse::<usize>().map_err(|_| invalid());
+        if let Some((first, end)) = s.split_once("..") {
+            let first = if first.trim().is_empty() {
+                0
+            } else {
+                number(first)?
+            };
+            let last = match end.strip_prefix('=') {
+                Some(last) => Some(number(last)?),
+                None if end.trim().is_empty() => None,
+                None => Some(number(end)?.checked_sub(1).ok_or_else(invalid)?),
+            };
+            if last.is_some_and(|last| last < first) {
+                return Err(invalid());
+            }
+            return Ok(Self { first, last });
+        }
+        let rows = match s.split_once('-') {
+            Some((first, last)) => Self {
+                first: if first.trim().is_empty() {
+                    0
+                } else {
+                    number(first)?
+                },
+                last: if last.trim().is_empty() {
+                    None
+                } else {
+                    Some(number(last)?)
+                },
+            },
+            None => {
+                let nr = number(s)?;
+                Self {
+                    first: nr,
+                    last: Some(nr),
+                }
+            }
+        };
+        if rows.last.is_some_and(|last| last < rows.first) {
+            return Err(invalid());
+        }
+        Ok(rows)
+    }
+}
+
+/// All conditions and the range of rows a record has to match.
+#[derive(Debug, Clone, Default)]
+pub struct RecordFilter {
+    pub conditions: Vec<Condition>,
+    pub rows: Option<Rows>,
+    /// Only these record numbers, e.g. the failed records of a previous run.
+    pub records: Option<BTreeSet<usize>>,
+    /// Record numbers that are skipped, e.g. the processed records of an interrupted run.
+    pub skip: BTreeSet<usize>,
+    /// Number of matching records that are skipped.
+    pub offset: usize,
+    /// Max. number of matching records (after the offset).
+    pub limit: Option<usize>,
+}
+
+impl RecordFilter {
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+            && self.rows.is_none()
+            && self.records.is_none()
+            && self.skip.is_empty()
+            && self.offset == 0
+            && self.limit.is_none()
+    }
+
+    /// The records that match the filter together with their record number.
+    ///
+    /// The offset and the limit are applied to the matching records,
+    /// no more records are read after the limit is reached.
+    /// Records that can't be read are returned if their row is included.
+    pub fn select<I>(&self, headers: &StringRecord, records: I) -> Selected<I>
+    where
+        I: Iterator<Item = csv::Result<StringRecord>>,
+    {
+        Selected {
+            filter: self.clone(),
+            headers: headers.clone(),
+            records,
+            total: 0,
+            matching: 0,
+        }
+    }
+
+    /// Check that the columns of all conditions exist.
+    pub fn check_columns(&self, headers: &StringRecord) -> anyhow::Result<()> {
+        for condition in &self.conditions {
+            if !headers.iter().any(|h| h == condition.column) {
+                bail!(
+                    "Unknown column '{}' in filter '{condition}'",
+                    condition.column
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn includes_row(&self, record_nr: usize) -> bool {
+        !self.skip.contains(&record_nr)
+            && self.rows.map_or(true, |rows| rows.contains(record_nr))
+            && self
+                .records
+                .as_ref()
+                .map_or(true, |records| records.contains(&record_nr))
+    }
+
+    /// The items of a list (e.g. of a JSON file) at the selected positions,
+    /// together with their position.
+    ///
+    /// The conditions on columns are ignored.
+    pub fn select_items<T>(&self, items: Vec<T>) -> Vec<(usize, T)> {
+        items
+            .into_iter()
+            .enumerate()
+            .filter(|(nr, _)| self.includes_row(*nr))
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    pub fn matches(&self, record_nr: usize, headers: &StringRecord, record: &StringRecord) -> bool {
+        if !self.includes_row(record_nr) {
+            return false;
+        }
+        self.conditions.iter().all(|condition| {
+            let cell = headers
+                .iter()
+                .position(|h| h == condition.column)
+                .and_then(|i| record.get(i))
+                .unwrap_or_default();
+            condition.matches(cell)
+        })
+    }
+}
+
+/// See [RecordFilter::select].
+pub struct Selected<I> {
+    filter: RecordFilter,
+    headers: StringRecord,
+    records: I,
+    /// Number of records that were read so far.
+    pub total: usize,
+    /// Number of matching records so far (including the skipped ones).
+    matching: usize,
+}
+
+impl<I> Iterator for Selected<I>
+where
+    I: Iterator<Item = csv::Result<StringRecord>>,
+{
+    type Item = (usize, csv::Result<StringRecord>);
+    fn next(&mut self) -> Option<Self::Item> {
+        let RecordFilter { offset, limit, .. } = self.filter;
+        loop {
+            if limit.is_some_and(|limit| self.matching >= offset + limit) {
+                return None;
+            }
+            let record = self.records.next()?;
+            let record_nr = self.total;
+            self.total += 1;
+            let selected = match &record {
+                Ok(record) => self.filter.matches(record_nr, &self.headers, record),
+                Err(_) => self.filter.includes_row(record_nr),
+            };
+            if !selected {
+                continue;
+            }
+            self.matching += 1;
+            if self.matching > offset {
+                return Some((record_nr, record));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_conditions() {
+        let c: Condition = "city=Hamburg".parse().unwrap();
+        assert_eq!(
+            (c.column.as_str(), c.op, c.value.as_str()),
+            ("city", Op::Equals, "Hamburg")
+        );
+        let c: Condition = "city != Hamburg".parse().unwrap();
+        assert_eq!((c.column.as_str(), c.op), ("city", Op::NotEquals));
+        let c: Condition = "tags~solar".parse().unwrap();
+        assert_eq!((c.column.as_str(), c.op), ("tags", Op::Contains));
+        assert_eq!(c.to_string(), "tags~solar");
+        assert!("city".parse::<Condition>().is_err());
+        assert!("=Hamburg".parse::<Condition>().is_err());
+        assert!("!=Hamburg".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn parse_rows() {
+        let rows = |s: &str| s.parse::<Rows>().map(|r| (r.first, r.last)).ok();
+        assert_eq!(rows("100-200"), Some((100, Some(200))));
+        assert_eq!(rows("100-"), Some((100, None)));
+        assert_eq!(rows("-200"), Some((0, Some(200))));
+        assert_eq!(rows("42"), Some((42, Some(42))));
+        assert_eq!(rows("200-100"), None);
+        assert_eq!(rows("a-b"), None);
+        assert_eq!(rows("50..100"), Some((50, Some(99))));
+        assert_eq!(rows("50..=100"), Some((50, Some(100))));
+        assert_eq!(rows("50.."), Some((50, None)));
+        assert_eq!(rows("..10"), Some((0, Some(9))));
+        assert_eq!(rows("..0"), None);
+        assert_eq!(rows("100..50"), None);
+    }
+
+    #[test]
+    fn match_records() {
+        let headers = StringRecord::from(vec!["title", "city", "tags"]);
+        let record = StringRecord::from(vec!["Solar e.V.", " hamburg", "solar,energie"]);
+        let filter = |conditions: &[&str], rows: Option<&str>| RecordFilter {
+            conditions: conditions.iter().map(|c| c.parse().unwrap()).collect(),
+            rows: rows.map(|r| r.parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(filter(&[], None).matches(7, &headers, &record));
+        assert!(filter(&["city=Hamburg", "tags~SOLAR"], None).matches(7, &headers, &record));
+        assert!(!filter(&["city!=Hamburg"], None).matches(7, &headers, &record));
+        assert!(!filter(&["tags~wind"], None).matches(7, &headers, &record));
+        assert!(filter(&["city=Hamburg"], Some("5-10")).matches(7, &headers, &record));
+        assert!(!filter(&["city=Hamburg"], Some("8-")).matches(7, &headers, &record));
+        let failed = RecordFilter {
+            records: Some([3, 7].into()),
+            ..filter(&["city=Hamburg"], Some("5-10"))
+        };
+        assert!(failed.matches(7, &headers, &record));
+        assert!(!failed.matches(6, &headers, &record));
+        assert!(filter(&["tags~solar"], None)
+            .check_columns(&headers)
+            .is_ok());
+        assert!(filter(&["zip=20357"], None)
+            .check_columns(&headers)
+            .is_err());
+    }
+
+    #[test]
+    fn select_with_offset_and_limit() {
+        let headers = StringRecord::from(vec!["title", "city"]);
+        let records = ["A,Bochum", "B,Essen", "C,Bochum", "D,Bochum", "E,Bochum"]
+            .map(|r| Ok(StringRecord::from(r.split(',').collect::<Vec<_>>())));
+        let filter = RecordFilter {
+            conditions: vec!["city=Bochum".parse().unwrap()],
+            offset: 1,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let mut selected = filter.select(&headers, records.into_iter());
+        let record_nrs: Vec<_> = selected.by_ref().map(|(nr, _)| nr).collect();
+        assert_eq!(record_nrs, [2, 3]);
+        // The last record is not read
+        assert_eq!(selected.total, 4);
+
+        let filter = RecordFilter {
+            rows: Some("1-".parse().unwrap()),
+            offset: 1,
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.select_items(vec!["a", "b", "c", "d", "e"]),
+            [(2, "c"), (3, "d")]
+        );
+    }
+}
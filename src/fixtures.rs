@@ -0,0 +1,387 @@
+//! Realistic fake entries for demos, trainings and integration tests.
+//!
+//! The generated data only depends on the seed,
+//! so the same seed always results in the same fixtures.
+
+use ofdb_boundary::{CustomLink, Entry, MapBbox, NewPlace};
+use time::Date;
+use uuid::{Builder, Uuid};
+
+use crate::{
+    category::{CATEGORY_COMPANY, CATEGORY_INITIATIVE},
+    matching::distance_in_meters,
+    mirror::new_place_from_entry,
+};
+
+/// 2020-01-01T00:00:00Z
+const MIN_CREATED: i64 = 1_577_836_800;
+
+/// Max. age of a created entry relative to [MIN_CREATED].
+const MAX_CREATED_OFFSET: u64 = 4 * 365 * 24 * 60 * 60;
+
+/// Max. distance (in meters) of a place within a custom bounding box
+/// to the center of a city to use its name and zip code.
+const MAX_CITY_DISTANCE: f64 = 30_000.0;
+
+/// City, zip code and coordinates.
+const CITIES: &[(&str, &str, f64, f64)] = &[
+    ("Berlin", "10997", 52.4996, 13.4183),
+    ("Bochum", "44787", 51.4818, 7.2162),
+    ("Dresden", "01067", 51.0504, 13.7373),
+    ("Freiburg im Breisgau", "79098", 47.9990, 7.8421),
+    ("Hamburg", "20357", 53.5627, 9.9629),
+    ("Kassel", "34117", 51.3127, 9.4797),
+    ("Köln", "50667", 50.9375, 6.9603),
+    ("Leipzig", "04109", 51.3397, 12.3731),
+    ("München", "80331", 48.1351, 11.5820),
+    ("Stuttgart", "70173", 48.7758, 9.1829),
+];
+
+const STREETS: &[&str] = &[
+    "Hauptstraße",
+    "Bahnhofstraße",
+    "Gartenweg",
+    "Lindenallee",
+    "Marktplatz",
+    "Schulstraße",
+    "Am Mühlbach",
+    "Wiesenweg",
+];
+
+/// Kind of place, category and tags.
+const KINDS: &[(&str, &str, &[&str])] = &[
+    (
+        "Repair Café",
+        CATEGORY_INITIATIVE,
+        &["repaircafe", "reparieren", "nachhaltigkeit"],
+    ),
+    (
+        "Unverpackt-Laden",
+        CATEGORY_COMPANY,
+        &["zerowaste", "unverpackt", "bio"],
+    ),
+    (
+        "Solidarische Landwirtschaft",
+        CATEGORY_INITIATIVE,
+        &["solawi", "regional", "landwirtschaft"],
+    ),
+    (
+        "Weltladen",
+        CATEGORY_COMPANY,
+        &["fairtrade", "weltladen", "fairerhandel"],
+    ),
+    (
+        "Foodsharing Fairteiler",
+        CATEGORY_INITIATIVE,
+        &["foodsharing", "lebensmittelretten"],
+    ),
+    (
+        "Gemeinschaftsgarten",
+        CATEGORY_INITIATIVE,
+        &["urbangardening", "garten", "gemeinschaft"],
+    ),
+    (
+        "Bioladen",
+        CATEGORY_COMPANY,
+        &["bio", "regional", "lebensmittel"],
+    ),
+    (
+        "Lastenrad-Verleih",
+        CATEGORY_INITIATIVE,
+        &["lastenrad", "mobilität", "teilen"],
+    ),
+];
+
+const NAMES: &[&str] = &[
+    "Sonnenblume",
+    "Löwenzahn",
+    "Grüne Welle",
+    "Kleeblatt",
+    "Morgenrot",
+    "Wurzelwerk",
+    "Regenbogen",
+    "Apfelbaum",
+];
+
+const CONTACTS: &[&str] = &[
+    "Alex Schmidt",
+    "Sam Becker",
+    "Kim Wagner",
+    "Robin Fischer",
+    "Jo Hoffmann",
+    "Charlie Weber",
+];
+
+const OPENING_HOURS: &[&str] = &[
+    "Mo-Fr 09:00-18:00",
+    "Mo-Sa 10:00-19:00",
+    "Tu,Th 15:00-19:00",
+    "Sa 10:00-14:00",
+];
+
+/// Generated places and the corresponding entries.
+#[derive(Debug, Clone)]
+pub struct Fixtures {
+    /// Places in the format of the import.
+    pub new_places: Vec<NewPlace>,
+    /// Places in the format of the export and update.
+    pub entries: Vec<Entry>,
+}
+
+/// Options of the generated entries.
+#[derive(Debug, Clone, Default)]
+pub struct FixtureOptions {
+    /// Spread the places within the bounding box
+    /// instead of around some German cities.
+    pub bbox: Option<MapBbox>,
+    /// Additional tags of all entries.
+    pub tags: Vec<String>,
+}
+
+/// Generate `count` fake entries.
+pub fn generate(count: usize, seed: u64) -> Fixtures {
+    generate_with(count, seed, &FixtureOptions::default())
+}
+
+/// Generate `count` fake entries with custom options.
+pub fn generate_with(count: usize, seed: u64, options: &FixtureOptions) -> Fixtures {
+    let mut rng = Rng::new(seed);
+    let entries: Vec<_> = (0..count)
+        .map(|nr| {
+            let mut entry = fake_entry(&mut rng, nr);
+            if let Some(bbox) = &options.bbox {
+                move_into_bbox(&mut entry, &mut rng, bbox);
+            }
+            for tag in &options.tags {
+                let tag = tag.trim().to_lowercase();
+                if !tag.is_empty() && !entry.tags.contains(&tag) {
+                    entry.tags.push(tag);
+                }
+            }
+            entry
+        })
+        .collect();
+    let new_places = entries.iter().cloned().map(new_place_from_entry).collect();
+    Fixtures {
+        new_places,
+        entries,
+    }
+}
+
+fn fake_entry(rng: &mut Rng, nr: usize) -> Entry {
+    let (kind, category, tags) = *rng.pick(KINDS);
+    let name = *rng.pick(NAMES);
+    let (city, zip, lat, lng) = *rng.pick(CITIES);
+    let street = format!("{} {}", rng.pick(STREETS), 1 + rng.below(120));
+    let title = format!("{kind} {name}");
+    let slug = format!("{}-{}", slug(&title), nr + 1);
+    let homepage = format!("https://{slug}.example.org");
+
+    let mut id = [0; 16];
+    id[..8].copy_from_slice(&rng.next_u64().to_be_bytes());
+    id[8..].copy_from_slice(&rng.next_u64().to_be_bytes());
+    let id: Uuid = Builder::from_random_bytes(id).into_uuid();
+
+    let custom_links = if rng.below(3) == 0 {
+        vec![CustomLink {
+            url: format!("{homepage}/termine"),
+            title: Some("Termine".to_string()),
+            description: None,
+        }]
+    } else {
+        vec![]
+    };
+    let founded_on =
+        Date::from_ordinal_date(1990 + rng.below(34) as i32, 1 + rng.below(365) as u16)
+            .ok()
+            .filter(|_| rng.below(2) == 0);
+
+    Entry {
+        id: id.simple().to_string(),
+        created: MIN_CREATED + rng.below_u64(MAX_CREATED_OFFSET) as i64,
+        version: rng.below(5) as u64,
+        title,
+        description: format!("{kind} in {city}: Komm vorbei und mach mit!"),
+        // Spread the places within ~5 km around the city center
+        lat: lat + (rng.float() - 0.5) * 0.09,
+        lng: lng + (rng.float() - 0.5) * 0.14,
+        street: Some(street),
+        zip: Some(zip.to_string()),
+        city: Some(city.to_string()),
+        country: Some("Deutschland".to_string()),
+        state: None,
+        contact_name: Some(rng.pick(CONTACTS).to_string()),
+        email: Some(format!("kontakt@{slug}.example.org")),
+        telephone: Some(format!(
+            "0{:03} {:06}",
+            100 + rng.below(900),
+            rng.below(1_000_000)
+        )),
+        homepage: Some(homepage),
+        opening_hours: Some(rng.pick(OPENING_HOURS).to_string()),
+        founded_on,
+        categories: vec![category.to_string()],
+        tags: tags.iter().map(ToString::to_string).collect(),
+        ratings: vec![],
+        license: Some("CC0-1.0".to_string()),
+        image_url: None,
+        image_link_url: None,
+        custom_links,
+    }
+}
+
+/// Place the entry at a random position within the bounding box.
+///
+/// The city and zip code are only kept if a known city is nearby.
+fn move_into_bbox(entry: &mut Entry, rng: &mut Rng, bbox: &MapBbox) {
+    entry.lat = bbox.sw.lat + rng.float() * (bbox.ne.lat - bbox.sw.lat);
+    entry.lng = bbox.sw.lng + rng.float() * (bbox.ne.lng - bbox.sw.lng);
+    let nearest = CITIES.iter().min_by(|a, b| {
+        let distance =
+            |c: &&(_, _, f64, f64)| distance_in_meters((entry.lat, entry.lng), (c.2, c.3));
+        distance(a).total_cmp(&distance(b))
+    });
+    let old_city = entry.city.take().unwrap_or_default();
+    match nearest {
+        Some((city, zip, lat, lng))
+            if distance_in_meters((entry.lat, entry.lng), (*lat, *lng)) <= MAX_CITY_DISTANCE =>
+        {
+            entry.description = entry.description.replace(&old_city, city);
+            entry.city = Some(city.to_string());
+            entry.zip = Some(zip.to_string());
+        }
+        _ => {
+            entry.description = entry.description.replace(&format!(" in {old_city}"), "");
+            entry.zip = None;
+            entry.country = None;
+        }
+    }
+}
+
+fn slug(title: &str) -> String {
+    title
+        .to_lowercase()
+        .replace('ä', "ae")
+        .replace('ö', "oe")
+        .replace('ü', "ue")
+        .replace('é', "e")
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// A small pseudo random number generator (SplitMix64).
+///
+/// It's used instead of an external crate
+/// to guarantee the same fixtures across versions and platforms.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below_u64(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        self.below_u64(n as u64) as usize
+    }
+
+    /// A number in `[0, 1)`.
+    fn float(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ofdb_boundary::MapPoint;
+
+    #[test]
+    fn same_seed_same_fixtures() {
+        let json = |f: &Fixtures| serde_json::to_string(&(&f.new_places, &f.entries)).unwrap();
+        let a = generate(20, 42);
+        assert_eq!(a.entries.len(), 20);
+        assert_eq!(a.new_places.len(), 20);
+        assert_eq!(json(&a), json(&generate(20, 42)));
+        assert_ne!(json(&a), json(&generate(20, 43)));
+    }
+
+    #[test]
+    fn generate_valid_entries() {
+        let Fixtures { entries, .. } = generate(50, 7);
+        let mut ids: Vec<_> = entries.iter().map(|e| e.id.clone()).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 50);
+        for e in &entries {
+            assert!(e.id.parse::<Uuid>().is_ok());
+            assert!((47.0..55.5).contains(&e.lat), "{}", e.lat);
+            assert!((5.5..15.5).contains(&e.lng), "{}", e.lng);
+            assert!(!e.tags.is_empty());
+            assert!(e.created >= MIN_CREATED);
+        }
+    }
+
+    #[test]
+    fn generate_within_bbox() {
+        let options = FixtureOptions {
+            bbox: Some(MapBbox {
+                sw: MapPoint {
+                    lat: 51.3,
+                    lng: 12.2,
+                },
+                ne: MapPoint {
+                    lat: 51.4,
+                    lng: 12.5,
+                },
+            }),
+            tags: vec!["Test".to_string(), "demo".to_string(), " ".to_string()],
+        };
+        let Fixtures { entries, .. } = generate_with(20, 1, &options);
+        for e in &entries {
+            assert!((51.3..=51.4).contains(&e.lat), "{}", e.lat);
+            assert!((12.2..=12.5).contains(&e.lng), "{}", e.lng);
+            assert_eq!(e.city.as_deref(), Some("Leipzig"));
+            assert!(e.description.contains("in Leipzig:"), "{}", e.description);
+            assert!(e.tags.ends_with(&["test".to_string(), "demo".to_string()]));
+        }
+
+        let options = FixtureOptions {
+            bbox: Some(MapBbox {
+                sw: MapPoint { lat: 0.0, lng: 0.0 },
+                ne: MapPoint { lat: 1.0, lng: 1.0 },
+            }),
+            ..Default::default()
+        };
+        let Fixtures { entries, .. } = generate_with(1, 1, &options);
+        assert_eq!(entries[0].city, None);
+        assert_eq!(entries[0].zip, None);
+        assert!(!entries[0].description.contains(" in "));
+    }
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slug("Repair Café Löwenzahn"), "repair-cafe-loewenzahn");
+        assert_eq!(
+            slug("Unverpackt-Laden Grüne Welle"),
+            "unverpackt-laden-gruene-welle"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Fill the coordinates of a CSV file with addresses,
+//! e.g. to geocode a file once and import or share it later.
+
+use std::{
+    fmt,
+    io::{Read, Write},
+};
+
+use anyhow::{bail, Result};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use ofdb_boundary::Address;
+use ofdb_core::gateways::geocode::GeoCodingGateway;
+
+use crate::address::{normalize, AddressLocale};
+
+const ADDRESS_COLUMNS: [&str; 5] = ["street", "zip", "city", "country", "state"];
+
+/// Number of rows by outcome.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GeocodeSummary {
+    pub geocoded: usize,
+    /// Rows that already have coordinates.
+    pub skipped: usize,
+    /// Rows without an address or with an address that was not found.
+    pub not_found: usize,
+}
+
+impl fmt::Display for GeocodeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} rows geocoded, {} rows already had coordinates, {} addresses not found",
+            self.geocoded, self.skipped, self.not_found
+        )
+    }
+}
+
+/// Copy the CSV file and fill the empty `lat` and `lng` columns
+/// with the coordinates of the address.
+///
+/// All other columns are copied as they are
+/// (the address is only normalized for the geocoder).
+/// The columns `lat` and `lng` are appended if they don't exist.
+pub fn geocode_csv<R: Read, W: Write>(
+    r: R,
+    w: W,
+    geo_coding: &dyn GeoCodingGateway,
+    locale: Option<AddressLocale>,
+) -> Result<GeocodeSummary> {
+    let mut reader = ReaderBuilder::new().flexible(true).from_reader(r);
+    let mut headers = reader.headers()?.clone();
+    let column = |headers: &StringRecord, name: &str| {
+        headers
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+    };
+    let address_columns = ADDRESS_COLUMNS.map(|name| column(&headers, name));
+    if address_columns.iter().all(Option::is_none) {
+        bail!(
+            "The file has no address columns ({})",
+            ADDRESS_COLUMNS.join(", ")
+        );
+    }
+    let mut lat_lng_column = |name| match column(&headers, name) {
+        Some(idx) => idx,
+        None => {
+            headers.push_field(name);
+            headers.len() - 1
+        }
+    };
+    let lat_column = lat_lng_column("lat");
+    let lng_column = lat_lng_column("lng");
+
+    let mut writer = WriterBuilder::new().flexible(true).from_writer(w);
+    writer.write_record(&headers)?;
+    let mut summary = GeocodeSummary::default();
+    for (record_nr, record) in reader.records().enumerate() {
+        let record = record?;
+        let mut fields: Vec<_> = record.iter().map(ToString::to_string).collect();
+        if fields.len() < headers.len() {
+            fields.resize(headers.len(), String::new());
+        }
+        if !fields[lat_column].trim().is_empty() && !fields[lng_column].trim().is_empty() {
+            summary.skipped += 1;
+            writer.write_record(&fields)?;
+            continue;
+        }
+        let values = address_columns.map(|idx| {
+            idx.map(|idx| fields[idx].trim())
+                .filter(|value| !value.is_empty())
+                .map(ToString::to_string)
+        });
+        let has_address = values.iter().any(Option::is_some);
+        let [street, zip, city, country, state] = values;
+        let mut addr = Address {
+            street,
+            zip,
+            city,
+            country,
+            state,
+        };
+        if let Some(locale) = locale {
+            normalize(&mut addr, locale);
+        }
+        let lat_lng = if has_address {
+            geo_coding.resolve_address_lat_lng(&addr.clone().into())
+        } else {
+            None
+        };
+        match lat_lng {
+            Some((lat, lng)) => {
+                fields[lat_column] = lat.to_string();
+                fields[lng_column] = lng.to_string();
+                summary.geocoded += 1;
+            }
+            None => {
+                log::warn!("Record {record_nr}: unable to find the address {addr:?}");
+                summary.not_found += 1;
+            }
+        }
+        writer.write_record(&fields)?;
+    }
+    writer.flush()?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CityGeoCoding;
+
+    impl GeoCodingGateway for CityGeoCoding {
+        fn resolve_address_lat_lng(
+            &self,
+            addr: &ofdb_entities::address::Address,
+        ) -> Option<(f64, f64)> {
+            match addr.city.as_deref() {
+                Some("Bochum") => Some((51.48, 7.22)),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn fill_missing_coordinates() {
+        let csv = "\
+name,City,lat,lng,note
+A,Bochum,,,\"a, b\"
+B,Bochum,1.5,2.5,
+C,Nowhere,,,
+D,,,,
+";
+        let mut out = vec![];
+        let summary = geocode_csv(csv.as_bytes(), &mut out, &CityGeoCoding, None).unwrap();
+        assert_eq!(
+            summary,
+            GeocodeSummary {
+                geocoded: 1,
+                skipped: 1,
+                not_found: 2
+            }
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+name,City,lat,lng,note
+A,Bochum,51.48,7.22,\"a, b\"
+B,Bochum,1.5,2.5,
+C,Nowhere,,,
+D,,,,
+"
+        );
+    }
+
+    #[test]
+    fn append_the_coordinate_columns() {
+        let csv = "city,zip\nBochum,44787\n";
+        let mut out = vec![];
+        geocode_csv(csv.as_bytes(), &mut out, &CityGeoCoding, None).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "city,zip,lat,lng\nBochum,44787,51.48,7.22\n"
+        );
+        assert!(geocode_csv("name\nA\n".as_bytes(), vec![], &CityGeoCoding, None).is_err());
+
+        // Only the query of the geocoder is normalized
+        let csv = "street,city\nHauptstr.1,44787 Bochum\n";
+        let mut out = vec![];
+        geocode_csv(
+            csv.as_bytes(),
+            &mut out,
+            &CityGeoCoding,
+            Some(AddressLocale::De),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "street,city,lat,lng\nHauptstr.1,44787 Bochum,51.48,7.22\n"
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Daily usage of the geocoder and an optional budget,
+//! e.g. to stay within the 2500 requests per day of the OpenCage free tier.
+//!
+//! The usage is stored in the state directory, so it's counted across runs.
+//! Records that can't be geocoded because the budget is used up are deferred
+//! and can be imported again on the next day.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::Result;
+use ofdb_core::gateways::geocode::GeoCodingGateway;
+use ofdb_entities::address::Address;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::session::state_dir;
+
+const USAGE_FILE: &str = "geocoder-usage.json";
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct StoredUsage {
+    /// Day (`YYYY-MM-DD`, UTC) of the requests.
+    day: String,
+    requests: u64,
+}
+
+/// Number of geocoding requests of the current day.
+#[derive(Debug)]
+pub struct GeocoderUsage {
+    path: PathBuf,
+    day: String,
+    requests: AtomicU64,
+    deferred: AtomicU64,
+    budget: Option<u64>,
+}
+
+impl GeocoderUsage {
+    /// Use the usage of the state directory.
+    pub fn open(budget: Option<u64>) -> Result<Self> {
+        let dir =
+            state_dir().ok_or_else(|| anyhow::anyhow!("Unable to find the state directory"))?;
+        Self::open_file(dir.join(USAGE_FILE), budget, &today())
+    }
+
+    pub fn open_file(path: PathBuf, budget: Option<u64>, day: &str) -> Result<Self> {
+        let stored: StoredUsage = if path.exists() {
+            serde_json::from_slice(&fs::read(&path)?)?
+        } else {
+            StoredUsage::default()
+        };
+        // The quota is reset every day
+        let requests = if stored.day == day {
+            stored.requests
+        } else {
+            0
+        };
+        Ok(Self {
+            path,
+            day: day.to_string(),
+            requests: AtomicU64::new(requests),
+            deferred: AtomicU64::default(),
+            budget,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let usage = StoredUsage {
+            day: self.day.clone(),
+            requests: self.requests(),
+        };
+        fs::write(&self.path, serde_json::to_vec(&usage)?)?;
+        Ok(())
+    }
+
+    /// Requests of the current day (including previous runs).
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// Addresses that were not geocoded in this run because of the budget.
+    pub fn deferred(&self) -> u64 {
+        self.deferred.load(Ordering::Relaxed)
+    }
+
+    /// Count a request if the budget allows it.
+    fn acquire(&self) -> bool {
+        let allowed = self
+            .requests
+            .fetch_update(
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+                |requests| match self.budget {
+                    Some(budget) if requests >= budget => None,
+                    _ => Some(requests + 1),
+                },
+            )
+            .is_ok();
+        if !allowed {
+            self.deferred.fetch_add(1, Ordering::Relaxed);
+        }
+        allowed
+    }
+}
+
+impl fmt::Display for GeocoderUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} geocoding requests today", self.requests())?;
+        if let Some(budget) = self.budget {
+            write!(
+                f,
+                " (budget: {budget}, remaining: {})",
+                budget.saturating_sub(self.requests())
+            )?;
+        }
+        let deferred = self.deferred();
+        if deferred > 0 {
+            write!(f, ", {deferred} addresses deferred")?;
+        }
+        Ok(())
+    }
+}
+
+fn today() -> String {
+    OffsetDateTime::now_utc().date().to_string()
+}
+
+/// Geocoding that counts the requests and stops when the budget is used up.
+pub struct BudgetedGeoCoding<G> {
+    inner: G,
+    usage: Arc<GeocoderUsage>,
+}
+
+impl<G> BudgetedGeoCoding<G> {
+    pub fn new(inner: G, usage: Arc<GeocoderUsage>) -> Self {
+        Self { inner, usage }
+    }
+}
+
+impl<G: GeoCodingGateway> GeoCodingGateway for BudgetedGeoCoding<G> {
+    fn resolve_address_lat_lng(&self, addr: &Address) -> Option<(f64, f64)> {
+        if !self.usage.acquire() {
+            log::debug!("The geocoding budget is used up");
+            return None;
+        }
+        self.inner.resolve_address_lat_lng(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    struct FixedGeoCoding;
+
+    impl GeoCodingGateway for FixedGeoCoding {
+        fn resolve_address_lat_lng(&self, _: &Address) -> Option<(f64, f64)> {
+            Some((51.48, 7.22))
+        }
+    }
+
+    #[test]
+    fn stop_geocoding_when_the_budget_is_used_up() {
+        let path = env::temp_dir().join(format!(
+            "ofdb-geocoder-usage-test-{}.json",
+            std::process::id()
+        ));
+        let usage =
+            Arc::new(GeocoderUsage::open_file(path.clone(), Some(3), "2024-05-01").unwrap());
+        let geo_coding = BudgetedGeoCoding::new(FixedGeoCoding, Arc::clone(&usage));
+        let addr = Address::default();
+        assert!(geo_coding.resolve_address_lat_lng(&addr).is_some());
+        assert!(geo_coding.resolve_address_lat_lng(&addr).is_some());
+        usage.save().unwrap();
+
+        let usage =
+            Arc::new(GeocoderUsage::open_file(path.clone(), Some(3), "2024-05-01").unwrap());
+        assert_eq!(usage.requests(), 2);
+        let geo_coding = BudgetedGeoCoding::new(FixedGeoCoding, Arc::clone(&usage));
+        assert!(geo_coding.resolve_address_lat_lng(&addr).is_some());
+        assert!(geo_coding.resolve_address_lat_lng(&addr).is_none());
+        assert_eq!(usage.requests(), 3);
+        assert_eq!(usage.deferred(), 1);
+        assert_eq!(
+            usage.to_string(),
+            "3 geocoding requests today (budget: 3, remaining: 0), 1 addresses deferred"
+        );
+        usage.save().unwrap();
+
+        let usage = GeocoderUsage::open_file(path.clone(), Some(3), "2024-05-02").unwrap();
+        assert_eq!(usage.requests(), 0);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+//! A persistent cache of resolved addresses,
+//! so that repeated runs over the same file don't use up the geocoder quota.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use ofdb_core::gateways::geocode::GeoCodingGateway;
+use ofdb_entities::address::Address;
+
+use crate::session::state_dir;
+
+const CACHE_FILE: &str = "geocoding-cache.json";
+
+/// Geocoding with a cache in front of it.
+///
+/// Addresses that could not be resolved are not cached.
+pub struct CachedGeoCoding<'a> {
+    inner: &'a dyn GeoCodingGateway,
+    path: PathBuf,
+    entries: RefCell<HashMap<String, (f64, f64)>>,
+}
+
+impl<'a> CachedGeoCoding<'a> {
+    /// Use the cache in the state directory.
+    pub fn open(inner: &'a dyn GeoCodingGateway) -> Result<Self> {
+        let dir =
+            state_dir().ok_or_else(|| anyhow::anyhow!("Unable to find the state directory"))?;
+        Self::open_file(inner, dir.join(CACHE_FILE))
+    }
+
+    pub fn open_file(inner: &'a dyn GeoCodingGateway, path: PathBuf) -> Result<Self> {
+        let entries = if path.exists() {
+            serde_json::from_slice(&fs::read(&path)?)?
+        } else {
+            HashMap::new()
+        };
+        log::debug!(
+            "Loaded {} cached addresses from {}",
+            entries.len(),
+            path.display()
+        );
+        Ok(Self {
+            inner,
+            path,
+            entries: RefCell::new(entries),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_vec(&*self.entries.borrow())?)?;
+        Ok(())
+    }
+}
+
+impl GeoCodingGateway for CachedGeoCoding<'_> {
+    fn resolve_address_lat_lng(&self, addr: &Address) -> Option<(f64, f64)> {
+        let key = cache_key(addr);
+        if let Some(lat_lng) = self.entries.borrow().get(&key) {
+            return Some(*lat_lng);
+        }
+        let lat_lng = self.inner.resolve_address_lat_lng(addr)?;
+        self.entries.borrow_mut().insert(key, lat_lng);
+        Some(lat_lng)
+    }
+}
+
+pub(crate) fn cache_key(addr: &Address) -> String {
+    [
+        &addr.street,
+        &addr.zip,
+        &addr.city,
+        &addr.country,
+        &addr.state,
+    ]
+    .map(|field| field.as_deref().unwrap_or_default().trim().to_lowercase())
+    .join("|")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, env};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingGeoCoding {
+        requests: Cell<usize>,
+    }
+
+    impl GeoCodingGateway for CountingGeoCoding {
+        fn resolve_address_lat_lng(&self, addr: &Address) -> Option<(f64, f64)> {
+            self.requests.set(self.requests.get() + 1);
+            addr.city.as_ref().map(|_| (51.48, 7.22))
+        }
+    }
+
+    #[test]
+    fn resolve_each_address_only_once() {
+        let path = env::temp_dir().join(format!(
+            "ofdb-geocoding-cache-test-{}.json",
+            std::process::id()
+        ));
+        let inner = CountingGeoCoding::default();
+        let addr = Address {
+            city: Some("Bochum".to_string()),
+            ..Default::default()
+        };
+        let cache = CachedGeoCoding::open_file(&inner, path.clone()).unwrap();
+        assert_eq!(cache.resolve_address_lat_lng(&addr), Some((51.48, 7.22)));
+        assert_eq!(cache.resolve_address_lat_lng(&addr), Some((51.48, 7.22)));
+        assert_eq!(inner.requests.get(), 1);
+        assert_eq!(cache.resolve_address_lat_lng(&Address::default()), None);
+        assert_eq!(cache.resolve_address_lat_lng(&Address::default()), None);
+        assert_eq!(inner.requests.get(), 3);
+        cache.save().unwrap();
+
+        let cache = CachedGeoCoding::open_file(&inner, path.clone()).unwrap();
+        let addr = Address {
+            city: Some(" bochum".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(cache.resolve_address_lat_lng(&addr), Some((51.48, 7.22)));
+        assert_eq!(inner.requests.get(), 3);
+        fs::remove_file(path).unwrap();
+    }
+}
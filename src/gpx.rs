@@ -0,0 +1,56 @@
+//! Entries as waypoints of a GPX file, e.g. for GPS devices.
+
+use std::io::Write;
+
+use anyhow::Result;
+use ofdb_boundary::Entry;
+
+use crate::{address::address_line, report::entry_url, xml::escape};
+
+/// Write the entries as waypoints with their title, description and a link to the map.
+///
+/// The address is the comment of a waypoint.
+pub fn write_entries<W: Write>(mut w: W, entries: &[Entry]) -> Result<()> {
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        w,
+        r#"<gpx version="1.1" creator="ofdb-cli" xmlns="http://www.topografix.com/GPX/1/1">"#
+    )?;
+    for e in entries {
+        writeln!(w, r#"<wpt lat="{}" lon="{}">"#, e.lat, e.lng)?;
+        writeln!(w, "<name>{}</name>", escape(&e.title))?;
+        let address = address_line(e, true);
+        if !address.is_empty() {
+            writeln!(w, "<cmt>{}</cmt>", escape(&address))?;
+        }
+        writeln!(w, "<desc>{}</desc>", escape(e.description.trim()))?;
+        writeln!(
+            w,
+            r#"<link href="{}"><text>Karte von morgen</text></link>"#,
+            escape(&entry_url(&e.id))
+        )?;
+        writeln!(w, "</wpt>")?;
+    }
+    writeln!(w, "</gpx>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_waypoints() {
+        let mut entry = crate::fixtures::generate(1, 0).entries.remove(0);
+        entry.description = "<b>Bio</b>".to_string();
+        entry.lat = 51.5;
+        entry.lng = 7.25;
+        let mut gpx = vec![];
+        write_entries(&mut gpx, &[entry.clone()]).unwrap();
+        let gpx = String::from_utf8(gpx).unwrap();
+        assert!(gpx.contains(r#"<wpt lat="51.5" lon="7.25">"#));
+        assert!(gpx.contains("<desc>&lt;b&gt;Bio&lt;/b&gt;</desc>"));
+        assert!(gpx.contains(&format!(r#"<link href="{}">"#, entry_url(&entry.id))));
+        assert!(gpx.trim_end().ends_with("</gpx>"));
+    }
+}
@@ -0,0 +1,264 @@
+//! Normalize the column names of CSV files,
+//! e.g. `Title`, ` title ` or `E-Mail` of files that were edited by hand,
+//! and report the columns that are unknown or missing.
+
+use std::{fmt, io::Read};
+
+use csv::{Reader, ReaderBuilder, StringRecord};
+use serde::{
+    de::{self, Visitor},
+    forward_to_deserialize_any, Deserialize, Deserializer,
+};
+
+/// Common names of the columns of places (after [normalize_header]).
+pub const PLACE_SYNONYMS: &[(&str, &str)] = &[
+    ("titel", "title"),
+    ("name", "title"),
+    ("beschreibung", "description"),
+    ("latitude", "lat"),
+    ("longitude", "lng"),
+    ("lon", "lng"),
+    ("long", "lng"),
+    ("strasse", "street"),
+    ("straße", "street"),
+    ("postcode", "zip"),
+    ("postal_code", "zip"),
+    ("zip_code", "zip"),
+    ("plz", "zip"),
+    ("ort", "city"),
+    ("stadt", "city"),
+    ("town", "city"),
+    ("land", "country"),
+    ("email", "contact_email"),
+    ("e_mail", "contact_email"),
+    ("mail", "contact_email"),
+    ("phone", "contact_phone"),
+    ("telephone", "contact_phone"),
+    ("telefon", "contact_phone"),
+    ("website", "homepage"),
+    ("webseite", "homepage"),
+    ("lizenz", "license"),
+];
+
+/// The lowercase name with `_` instead of spaces and dashes
+/// (`Contact Name` → `contact_name`), or the column of a synonym.
+pub fn normalize_header(header: &str, synonyms: &[(&str, &str)]) -> String {
+    let name = base_name(header);
+    synonym(&name, synonyms).map_or(name, ToString::to_string)
+}
+
+/// Normalize all headers, a synonym is only replaced
+/// if the file doesn't have the column itself.
+pub fn normalize_headers(headers: &StringRecord, synonyms: &[(&str, &str)]) -> StringRecord {
+    let names: Vec<_> = headers.iter().map(base_name).collect();
+    names
+        .iter()
+        .map(|name| match synonym(name, synonyms) {
+            Some(column) if !names.iter().any(|n| n == column) => column,
+            _ => name.as_str(),
+        })
+        .collect()
+}
+
+/// A CSV reader with normalized headers.
+pub fn reader<R: Read>(r: R, synonyms: &[(&str, &str)]) -> csv::Result<Reader<R>> {
+    let mut rdr = ReaderBuilder::new().from_reader(r);
+    let headers = normalize_headers(rdr.headers()?, synonyms);
+    rdr.set_headers(headers);
+    Ok(rdr)
+}
+
+/// How the columns of a file map onto the fields of a record.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnReport {
+    pub mapped: Vec<String>,
+    /// Unknown columns and the most similar known column.
+    pub ignored: Vec<(String, Option<String>)>,
+    /// Required columns that don't exist.
+    pub missing: Vec<String>,
+}
+
+impl ColumnReport {
+    pub fn new(headers: &StringRecord, known: &[&str], required: &[&str]) -> Self {
+        let mut report = Self::default();
+        for header in headers.iter().filter(|h| !h.is_empty()) {
+            if known.contains(&header) {
+                report.mapped.push(header.to_string());
+            } else {
+                let suggestion = suggestion(header, known).map(ToString::to_string);
+                report.ignored.push((header.to_string(), suggestion));
+            }
+        }
+        report.missing = required
+            .iter()
+            .filter(|column| !headers.iter().any(|h| h == **column))
+            .map(ToString::to_string)
+            .collect();
+        report
+    }
+
+    /// All columns are known and no required column is missing.
+    pub fn is_complete(&self) -> bool {
+        self.ignored.is_empty() && self.missing.is_empty()
+    }
+}
+
+impl fmt::Display for ColumnReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} columns mapped", self.mapped.len())?;
+        if !self.ignored.is_empty() {
+            let ignored: Vec<_> = self
+                .ignored
+                .iter()
+                .map(|(column, suggestion)| match suggestion {
+                    Some(s) => format!("'{column}' (did you mean '{s}'?)"),
+                    None => format!("'{column}'"),
+                })
+                .collect();
+            write!(f, ", unknown columns ignored: {}", ignored.join(", "))?;
+        }
+        if !self.missing.is_empty() {
+            write!(f, ", missing columns: '{}'", self.missing.join("', '"))?;
+        }
+        Ok(())
+    }
+}
+
+/// The known column that is at most two edits away.
+fn suggestion<'a>(column: &str, known: &[&'a str]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|k| (edit_distance(column, k), *k))
+        .filter(|(distance, _)| *distance <= 2 && *distance < column.chars().count())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, k)| k)
+}
+
+/// The Levenshtein distance of two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<_> = b.chars().collect();
+    let mut prev: Vec<_> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            current.push((prev[j] + cost).min(prev[j + 1] + 1).min(current[j] + 1));
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+/// The names of the fields of a struct that is deserialized from a CSV record.
+pub fn struct_fields<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// A deserializer that only records the field names of a struct.
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+impl<'de, 'a> Deserializer<'de> for FieldNames<'a> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only structs are supported"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("only the field names are read"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+fn base_name(header: &str) -> String {
+    header
+        .trim()
+        .to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+fn synonym<'a>(name: &str, synonyms: &[(&str, &'a str)]) -> Option<&'a str> {
+    synonyms
+        .iter()
+        .find(|(synonym, _)| *synonym == name)
+        .map(|(_, column)| *column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_place_headers() {
+        let headers = StringRecord::from(vec![" Title ", "TAGS", "E-Mail", "Contact Name", "PLZ"]);
+        assert_eq!(
+            normalize_headers(&headers, PLACE_SYNONYMS),
+            StringRecord::from(vec![
+                "title",
+                "tags",
+                "contact_email",
+                "contact_name",
+                "zip"
+            ])
+        );
+        // The column itself wins over a synonym
+        let headers = StringRecord::from(vec!["email", "contact_email"]);
+        assert_eq!(
+            normalize_headers(&headers, PLACE_SYNONYMS),
+            StringRecord::from(vec!["email", "contact_email"])
+        );
+        assert_eq!(normalize_header("Email", &[]), "email");
+    }
+
+    #[test]
+    fn report_unknown_and_missing_columns() {
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Record {
+            title: String,
+            description: String,
+            homepage: Option<String>,
+        }
+        let known = struct_fields::<Record>();
+        assert_eq!(known, ["title", "description", "homepage"]);
+        let headers = StringRecord::from(vec!["title", "homepgae", "foo"]);
+        let report = ColumnReport::new(&headers, known, &["title", "description"]);
+        assert!(!report.is_complete());
+        assert_eq!(
+            report.to_string(),
+            "1 columns mapped, unknown columns ignored: 'homepgae' (did you mean 'homepage'?), 'foo', \
+             missing columns: 'description'"
+        );
+        let headers = StringRecord::from(vec!["description", "title"]);
+        assert!(ColumnReport::new(&headers, known, &["title"]).is_complete());
+    }
+
+    #[test]
+    fn read_with_normalized_headers() {
+        let csv = "ID, Status \n42,ok\n";
+        let mut rdr = reader(csv.as_bytes(), &[]).unwrap();
+        assert_eq!(
+            rdr.headers().unwrap(),
+            &StringRecord::from(vec!["id", "status"])
+        );
+        let record: std::collections::HashMap<String, String> =
+            rdr.deserialize().next().unwrap().unwrap();
+        assert_eq!(record["status"], "ok");
+    }
+}
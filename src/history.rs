@@ -0,0 +1,60 @@
+use ofdb_boundary::PlaceRevision;
+
+use crate::diff::diff_json;
+
+/// Fields that change with every revision and are therefore not reported.
+const IGNORED_FIELDS: &[&str] = &["revision", "created"];
+
+/// Names of all fields that differ between two revisions of a place.
+///
+/// Nested fields are separated with a dot (e.g. `location.address.city`).
+pub fn changed_fields(previous: &PlaceRevision, current: &PlaceRevision) -> Vec<String> {
+    let previous = serde_json::to_value(previous).unwrap_or_default();
+    let current = serde_json::to_value(current).unwrap_or_default();
+    diff_json(&previous, &current, IGNORED_FIELDS)
+        .into_iter()
+        .map(|change| change.field)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn diff(previous: Value, current: Value) -> Vec<String> {
+        diff_json(&previous, &current, IGNORED_FIELDS)
+            .into_iter()
+            .map(|change| change.field)
+            .collect()
+    }
+
+    #[test]
+    fn report_nested_changes() {
+        let previous = json!({
+            "revision": 1,
+            "created": { "at": 1, "by": "foo@bar.tld" },
+            "title": "Foo",
+            "location": { "lat": 1.0, "lng": 2.0, "address": { "city": "Bochum" } },
+            "tags": ["a", "b"]
+        });
+        let current = json!({
+            "revision": 2,
+            "created": { "at": 2, "by": "bar@foo.tld" },
+            "title": "Foo",
+            "location": { "lat": 1.0, "lng": 2.0, "address": { "city": "Berlin" } },
+            "tags": ["a"]
+        });
+        assert_eq!(
+            diff(previous, current),
+            vec!["location.address.city", "tags"]
+        );
+    }
+
+    #[test]
+    fn report_added_and_removed_fields() {
+        let previous = json!({ "opening_hours": null, "founded_on": "2019-01-01" });
+        let current = json!({ "opening_hours": "Mo-Fr 09:00-17:00" });
+        assert_eq!(diff(previous, current), vec!["founded_on", "opening_hours"]);
+    }
+}
@@ -0,0 +1,90 @@
+//! Settings of the HTTP clients,
+//! e.g. for networks with a proxy that intercepts TLS connections.
+
+use std::{fmt, fs, path::Path, path::PathBuf, time::Duration};
+
+use reqwest::{blocking::ClientBuilder, Certificate, Proxy};
+
+use crate::{Error, Result};
+
+/// Timeout of a request to the API (except for streamed downloads like the CSV export).
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Timeout of establishing a connection.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Default)]
+pub struct HttpOptions {
+    /// Timeout of a whole request (the default is [DEFAULT_TIMEOUT]).
+    pub timeout: Option<Duration>,
+    /// Timeout of a streamed download, e.g. of the CSV export
+    /// (downloads are not limited by default because they take long on large instances).
+    pub stream_timeout: Option<Duration>,
+    /// Proxy for all requests
+    /// (without it the `HTTP_PROXY` and `HTTPS_PROXY` environment variables are used).
+    pub proxy: Option<String>,
+    /// PEM file with additional root certificates.
+    pub ca_cert: Option<PathBuf>,
+}
+
+impl HttpOptions {
+    /// Apply the options to a client for other services than the API.
+    pub fn apply(&self, builder: ClientBuilder) -> Result<ClientBuilder> {
+        let builder = self.apply_connection(builder)?;
+        Ok(builder.timeout(self.timeout.unwrap_or(DEFAULT_TIMEOUT)))
+    }
+
+    /// Apply the options that don't limit the duration of a request
+    /// (the API client limits each request on its own).
+    pub(crate) fn apply_connection(&self, mut builder: ClientBuilder) -> Result<ClientBuilder> {
+        builder = builder.connect_timeout(DEFAULT_CONNECT_TIMEOUT);
+        if let Some(proxy) = &self.proxy {
+            let proxy =
+                Proxy::all(proxy).map_err(|err| Error::InvalidUrl(format!("{proxy} ({err})")))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(path) = &self.ca_cert {
+            for cert in certificates(path)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        Ok(builder)
+    }
+}
+
+/// All certificates of a PEM file (e.g. a CA bundle).
+fn certificates(path: &Path) -> Result<Vec<Certificate>> {
+    let invalid =
+        |err: &dyn fmt::Display| Error::InvalidCertificate(format!("{}: {err}", path.display()));
+    let pem = fs::read(path).map_err(|err| invalid(&err))?;
+    let certs = Certificate::from_pem_bundle(&pem).map_err(|err| invalid(&err))?;
+    if certs.is_empty() {
+        return Err(invalid(&"No certificate found"));
+    }
+    Ok(certs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_options() {
+        let options = HttpOptions {
+            proxy: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            options.apply(reqwest::blocking::Client::builder()),
+            Err(Error::InvalidUrl(_))
+        ));
+        let path = std::env::temp_dir().join(format!("ofdb-http-test-{}.pem", std::process::id()));
+        fs::write(&path, "no certificate").unwrap();
+        assert!(matches!(
+            certificates(&path),
+            Err(Error::InvalidCertificate(_))
+        ));
+        fs::remove_file(&path).unwrap();
+        assert!(certificates(&path).is_err());
+    }
+}
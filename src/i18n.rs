@@ -0,0 +1,215 @@
+//! Translations of the most frequent messages and of the reports,
+//! since most users of Karte von morgen speak German.
+//!
+//! The English text is the key of a message, so untranslated messages
+//! are shown in English.
+
+use std::{fmt, str::FromStr, sync::OnceLock};
+
+use anyhow::bail;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+}
+
+impl FromStr for Lang {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_str() {
+            "en" => Self::En,
+            "de" => Self::De,
+            _ => bail!("Unsupported language '{s}' (expected 'en' or 'de')"),
+        })
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::En => "en",
+            Self::De => "de",
+        })
+    }
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Set the language of the messages once at the start of the CLI.
+pub fn set_lang(lang: Lang) {
+    if LANG.set(lang).is_err() {
+        log::debug!("The language is already set");
+    }
+}
+
+/// The language of the messages (English if it was not set).
+pub fn lang() -> Lang {
+    LANG.get().copied().unwrap_or_default()
+}
+
+/// German translations of the messages and labels.
+const DE: &[(&str, &str)] = &[
+    // Import
+    (
+        "Successfully imported {count} places",
+        "{count} Einträge erfolgreich importiert",
+    ),
+    (
+        "Found {count} places with possible duplicates",
+        "{count} Einträge mit möglichen Dubletten gefunden",
+    ),
+    ("{count} places contain errors", "{count} Einträge enthalten Fehler"),
+    (
+        "{count} csv records contain errors",
+        "{count} CSV-Datensätze enthalten Fehler",
+    ),
+    (
+        "Ignore duplicates: create a new entry, even if it becomes a duplicate",
+        "Dubletten werden ignoriert: auch mögliche Dubletten werden als neue Einträge angelegt",
+    ),
+    (
+        "{count} records contain errors (see {path})",
+        "{count} Datensätze enthalten Fehler (siehe {path})",
+    ),
+    ("All {count} records are valid", "Alle {count} Datensätze sind gültig"),
+    // Update
+    (
+        "Stop after updating {done} of {total} places",
+        "Abbruch nach {done} von {total} aktualisierten Einträgen",
+    ),
+    (
+        "Could not update '{title}': {error}",
+        "'{title}' konnte nicht aktualisiert werden: {error}",
+    ),
+    (
+        "No permission to update {count} places",
+        "Keine Berechtigung, {count} Einträge zu aktualisieren",
+    ),
+    (
+        "{count} places could not be updated",
+        "{count} Einträge konnten nicht aktualisiert werden",
+    ),
+    // Review
+    (
+        "{count} reviews where found in CSV file",
+        "{count} Bewertungen in der CSV-Datei gefunden",
+    ),
+    (
+        "{count} entries will be archived or rejected and hidden on the map. Continue?",
+        "{count} Einträge werden archiviert oder abgelehnt und auf der Karte ausgeblendet. Fortfahren?",
+    ),
+    ("Unable to review: {error}", "Review fehlgeschlagen: {error}"),
+    // Reports
+    ("Report", "Bericht"),
+    ("Successes", "Erfolgreich"),
+    ("Duplicates", "Dubletten"),
+    ("Failures", "Fehlgeschlagen"),
+    ("Conflicts", "Konflikte"),
+    ("Field", "Feld"),
+    ("Exported", "Exportiert"),
+    ("Current", "Aktuell"),
+    ("File", "Datei"),
+    ("Invalid records", "Ungültige Datensätze"),
+    ("Warnings", "Warnungen"),
+    ("Unknown IDs", "Unbekannte IDs"),
+    ("Title", "Titel"),
+    ("Description", "Beschreibung"),
+    ("Address", "Adresse"),
+    ("Map", "Karte"),
+    ("Other", "Sonstige"),
+    ("Import ID", "Import-ID"),
+    ("Entry", "Eintrag"),
+    ("Possible duplicates", "Mögliche Dubletten"),
+    ("Kind", "Art"),
+    ("Error", "Fehler"),
+    ("Record", "Datensatz"),
+    ("Warning", "Warnung"),
+    ("other", "sonstiger Fehler"),
+    ("permission denied", "keine Berechtigung"),
+    ("file", "datei"),
+    ("record_nr", "datensatz"),
+    ("title", "titel"),
+    ("error", "fehler"),
+    ("duplicate_ids", "dubletten_ids"),
+    ("warnings", "warnungen"),
+];
+
+impl Lang {
+    /// The translation of an English message or label.
+    pub fn tr(self, msg: &'static str) -> &'static str {
+        let catalog = match self {
+            Self::En => return msg,
+            Self::De => DE,
+        };
+        catalog
+            .iter()
+            .find(|(en, _)| *en == msg)
+            .map_or(msg, |(_, translation)| translation)
+    }
+}
+
+/// Translate the message and replace the placeholders (`{name}`) with the values.
+pub fn translate(lang: Lang, msg: &'static str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    let mut text = lang.tr(msg).to_string();
+    for (name, value) in args {
+        text = text.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+    text
+}
+
+/// Translate a message into the language of the CLI,
+/// e.g. `tr!("All {count} records are valid", count = 42)`.
+#[macro_export]
+macro_rules! tr {
+    ($msg:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::translate(
+            $crate::i18n::lang(),
+            $msg,
+            &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),*],
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translate_messages() {
+        let args: &[(&str, &dyn fmt::Display)] = &[("count", &3), ("path", &"report.json")];
+        let msg = "{count} records contain errors (see {path})";
+        assert_eq!(
+            translate(Lang::De, msg, args),
+            "3 Datensätze enthalten Fehler (siehe report.json)"
+        );
+        assert_eq!(
+            translate(Lang::En, msg, args),
+            "3 records contain errors (see report.json)"
+        );
+        assert_eq!(Lang::De.tr("Not translated"), "Not translated");
+        assert_eq!(
+            tr!("All {count} records are valid", count = 2),
+            "All 2 records are valid"
+        );
+        assert_eq!("DE".parse::<Lang>().unwrap(), Lang::De);
+        assert!("fr".parse::<Lang>().is_err());
+    }
+
+    #[test]
+    fn all_placeholders_are_translated() {
+        let placeholders = |s: &str| {
+            let mut names: Vec<_> = s
+                .split('{')
+                .skip(1)
+                .filter_map(|part| part.split_once('}').map(|(name, _)| name.to_string()))
+                .collect();
+            names.sort();
+            names
+        };
+        for (en, de) in DE {
+            assert_eq!(placeholders(en), placeholders(de), "{en}");
+        }
+    }
+}
@@ -1,17 +1,44 @@
 use anyhow::Result;
-use ofdb_boundary::{Entry, NewPlace, PlaceSearchResult};
+use ofdb_boundary::{Entry, NewPlace, NewPlaceRating, PlaceSearchResult};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{convert::TryFrom, result};
 use thiserror::Error;
 
+use crate::{
+    deadline::Deadline, metrics::Metrics, opencage::GeocodeMatch, remerge::FieldConflict, OfdbApi,
+};
+
+/// Version of the structure of the [Report].
+///
+/// It is increased whenever fields are renamed, removed or change their meaning,
+/// so that tools that read the reports can detect incompatible changes.
+/// Reports without a version were written before the versioning was introduced.
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// A place, entry or rating in the format of the OpenFairDB API
+/// (only used for the JSON schema of the report).
+type ApiObject = serde_json::Map<String, serde_json::Value>;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Found possible duplicates")]
     Duplicates(Vec<PlaceSearchResult>),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
     #[error("Could not import place: {0}")]
     Other(String),
 }
 
+impl From<crate::Error> for Error {
+    fn from(err: crate::Error) -> Self {
+        match err {
+            crate::Error::Forbidden(msg) => Self::PermissionDenied(msg),
+            err => Self::Other(err.to_string()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Error)]
 pub enum CsvImportError {
     #[error("Could not read CSV record: {0}")]
@@ -20,6 +47,16 @@ pub enum CsvImportError {
     AddressOrGeoCoordinates(String),
     #[error("Invalid patch request: {0}")]
     PatchRequest(String),
+    #[error("Validation failed: {0}")]
+    Validation(String),
+    #[error("Unknown entry ID {0} (does the entry exist on this instance?)")]
+    UnknownId(String),
+    #[error("Geocoding deferred: the geocoding budget is used up")]
+    GeocodingDeferred,
+    #[error("No entry found with {0}")]
+    NoMatch(String),
+    #[error("Several entries found with {query}: {} (add the id column to choose one)", .ids.join(", "))]
+    AmbiguousMatch { query: String, ids: Vec<String> },
 }
 
 type PlaceId = String;
@@ -38,10 +75,50 @@ pub struct UpdateResult<'a> {
     pub result: result::Result<PlaceId, Error>,
 }
 
+#[derive(Debug)]
+pub struct RatingResult<'a> {
+    pub rating: &'a NewPlaceRating,
+    pub import_id: Option<String>,
+    pub result: result::Result<(), Error>,
+}
+
 #[derive(Debug, Clone)]
 pub struct CsvImportResult<T> {
     pub record_nr: usize,
     pub result: result::Result<T, CsvImportError>,
+    /// The match of the geocoder if the address was geocoded.
+    pub geocoding: Option<GeocodeMatch>,
+    /// Problems of a valid record, e.g. text that could not be assigned to a field.
+    pub warnings: Vec<String>,
+}
+
+impl<T> CsvImportResult<T> {
+    pub fn new(record_nr: usize, result: result::Result<T, CsvImportError>) -> Self {
+        Self {
+            record_nr,
+            result,
+            geocoding: None,
+            warnings: vec![],
+        }
+    }
+
+    /// The record is valid, but could not be geocoded because the budget is used up.
+    pub fn is_deferred(&self) -> bool {
+        matches!(self.result, Err(CsvImportError::GeocodingDeferred))
+    }
+}
+
+/// The warnings of all records.
+pub fn csv_import_warnings<T>(results: &[CsvImportResult<T>]) -> Vec<CsvImportWarningReport> {
+    results
+        .iter()
+        .flat_map(|r| {
+            r.warnings.iter().map(|warning| CsvImportWarningReport {
+                record_nr: r.record_nr,
+                warning: warning.clone(),
+            })
+        })
+        .collect()
 }
 
 impl ImportResult<'_> {
@@ -56,65 +133,193 @@ impl ImportResult<'_> {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[schemars(bound = "", rename = "FailureReport")]
 pub struct FailureReport<T> {
+    #[schemars(with = "ApiObject")]
     pub place: T,
     pub import_id: Option<String>,
     pub error: String,
+    #[serde(default)]
+    pub kind: FailureKind,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Category of a failure, so that users know
+/// which records can be retried and which have to be escalated.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    #[default]
+    Other,
+    /// The user has no rights to modify the entry.
+    PermissionDenied,
+}
+
+impl Error {
+    fn failure(&self) -> Option<(FailureKind, &str)> {
+        match self {
+            Self::Duplicates(_) => None,
+            Self::PermissionDenied(msg) => Some((FailureKind::PermissionDenied, msg)),
+            Self::Other(msg) => Some((FailureKind::Other, msg)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct DuplicateReport {
+    #[schemars(with = "ApiObject")]
     pub new_place: NewPlace,
     pub import_id: Option<String>,
+    #[schemars(with = "Vec<ApiObject>")]
     pub duplicates: Vec<PlaceSearchResult>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[schemars(bound = "", rename = "SuccessReport")]
 pub struct SuccessReport<T> {
+    #[schemars(with = "ApiObject")]
     pub place: T,
     pub import_id: Option<String>,
     pub uuid: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[schemars(bound = "", rename = "CsvImportSuccessReport")]
 pub struct CsvImportSuccessReport<T> {
     pub record_nr: usize,
+    #[schemars(with = "ApiObject")]
     pub place: T,
+    /// The match of the geocoder if the address was geocoded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geocoding: Option<GeocodeMatch>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct CsvImportFailureReport {
     pub record_nr: usize,
     pub error: String,
+    /// The record was not geocoded because the geocoding budget was used up.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deferred: bool,
 }
 
-#[derive(Debug, Default, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct CsvImportWarningReport {
+    pub record_nr: usize,
+    pub warning: String,
+}
+
+/// An entry that was edited by someone else with changes
+/// that can't be merged with the changes of the record.
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ConflictReport {
+    pub import_id: Option<String>,
+    pub entry_id: String,
+    pub title: String,
+    /// The version that the record is based on (e.g. the exported version).
+    pub base_version: u64,
+    /// The current version on the server.
+    pub current_version: u64,
+    /// The fields that were changed on both sides.
+    pub fields: Vec<FieldConflict>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[schemars(bound = "S: JsonSchema", rename = "Report")]
 pub struct Report<T, S> {
+    /// See [REPORT_SCHEMA_VERSION].
+    #[serde(default)]
+    #[schemars(
+        description = "Version of the structure of the report (0 if the report was written before the versioning)"
+    )]
+    pub schema_version: u32,
     pub duplicates: Vec<DuplicateReport>,
     pub failures: Vec<FailureReport<T>>,
     pub successes: Vec<S>,
     pub csv_import_successes: Vec<CsvImportSuccessReport<T>>,
     pub csv_import_failures: Vec<CsvImportFailureReport>,
+    #[serde(default)]
+    pub csv_import_warnings: Vec<CsvImportWarningReport>,
+    /// IDs that don't exist on the instance.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unknown_ids: Vec<String>,
+    /// Updates that were rejected because of conflicting changes by others.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<ConflictReport>,
+    /// Timing of the run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<Metrics>,
 }
 
 impl TryFrom<&ImportResult<'_>> for FailureReport<NewPlace> {
     type Error = ();
     fn try_from(res: &ImportResult) -> Result<Self, Self::Error> {
         res.err()
-            .and_then(|e| match e {
-                Error::Other(msg) => Some(msg),
-                _ => None,
-            })
-            .map(|e| FailureReport {
+            .and_then(Error::failure)
+            .map(|(kind, msg)| FailureReport {
                 place: res.place().to_owned(),
                 import_id: res.import_id.clone(),
-                error: e.to_string(),
+                error: msg.to_string(),
+                kind,
+            })
+            .ok_or(())
+    }
+}
+
+impl TryFrom<&UpdateResult<'_>> for FailureReport<Entry> {
+    type Error = ();
+    fn try_from(res: &UpdateResult) -> Result<Self, Self::Error> {
+        res.result
+            .as_ref()
+            .err()
+            .and_then(Error::failure)
+            .map(|(kind, msg)| FailureReport {
+                place: res.place.to_owned(),
+                import_id: res.import_id.clone(),
+                error: msg.to_string(),
+                kind,
             })
             .ok_or(())
     }
 }
 
+impl TryFrom<&UpdateResult<'_>> for SuccessReport<Entry> {
+    type Error = ();
+    fn try_from(res: &UpdateResult) -> Result<Self, Self::Error> {
+        res.result
+            .as_ref()
+            .ok()
+            .map(|id| Self {
+                place: res.place.to_owned(),
+                import_id: res.import_id.clone(),
+                uuid: id.to_owned(),
+            })
+            .ok_or(())
+    }
+}
+
+impl From<Vec<UpdateResult<'_>>> for Report<Entry, SuccessReport<Entry>> {
+    fn from(results: Vec<UpdateResult>) -> Self {
+        let failures = results
+            .iter()
+            .map(FailureReport::try_from)
+            .filter_map(Result::ok)
+            .collect();
+
+        let successes = results
+            .iter()
+            .map(SuccessReport::try_from)
+            .filter_map(Result::ok)
+            .collect();
+
+        Self {
+            failures,
+            successes,
+            ..Default::default()
+        }
+    }
+}
+
 impl TryFrom<&ImportResult<'_>> for DuplicateReport {
     type Error = ();
     fn try_from(res: &ImportResult) -> Result<Self, Self::Error> {
@@ -151,12 +356,18 @@ where
 {
     type Error = ();
     fn try_from(res: &CsvImportResult<T>) -> Result<Self, Self::Error> {
-        let CsvImportResult { record_nr, result } = res;
+        let CsvImportResult {
+            record_nr,
+            result,
+            geocoding,
+            ..
+        } = res;
         result
             .as_ref()
             .map(|place| CsvImportSuccessReport {
                 record_nr: *record_nr,
                 place: place.clone(),
+                geocoding: *geocoding,
             })
             .map_err(|_| ())
     }
@@ -165,13 +376,13 @@ where
 impl<T> TryFrom<&CsvImportResult<T>> for CsvImportFailureReport {
     type Error = ();
     fn try_from(res: &CsvImportResult<T>) -> Result<Self, Self::Error> {
-        let CsvImportResult { record_nr, result } = res;
-        result
+        res.result
             .as_ref()
             .err()
             .map(|err| CsvImportFailureReport {
-                record_nr: *record_nr,
+                record_nr: res.record_nr,
                 error: err.to_string(),
+                deferred: matches!(err, CsvImportError::GeocodingDeferred),
             })
             .ok_or(())
     }
@@ -201,14 +412,16 @@ impl From<Vec<ImportResult<'_>>> for Report<NewPlace, SuccessReport<NewPlace>> {
             duplicates,
             failures,
             successes,
-            csv_import_failures: Default::default(),
-            csv_import_successes: Default::default(),
+            ..Default::default()
         }
     }
 }
 
-impl From<Vec<CsvImportResult<NewPlace>>> for Report<NewPlace, SuccessReport<NewPlace>> {
-    fn from(results: Vec<CsvImportResult<NewPlace>>) -> Self {
+impl<T> From<Vec<CsvImportResult<T>>> for Report<T, SuccessReport<T>>
+where
+    T: Clone,
+{
+    fn from(results: Vec<CsvImportResult<T>>) -> Self {
         let csv_import_failures = results
             .iter()
             .map(CsvImportFailureReport::try_from)
@@ -224,33 +437,374 @@ impl From<Vec<CsvImportResult<NewPlace>>> for Report<NewPlace, SuccessReport<New
         Self {
             csv_import_failures,
             csv_import_successes,
-            duplicates: Default::default(),
-            failures: Default::default(),
-            successes: Default::default(),
+            csv_import_warnings: csv_import_warnings(&results),
+            ..Default::default()
         }
     }
 }
 
-impl From<Vec<CsvImportResult<Entry>>> for Report<Entry, SuccessReport<Entry>> {
-    fn from(results: Vec<CsvImportResult<Entry>>) -> Self {
-        let csv_import_failures = results
+impl TryFrom<&RatingResult<'_>> for FailureReport<NewPlaceRating> {
+    type Error = ();
+    fn try_from(res: &RatingResult) -> Result<Self, Self::Error> {
+        res.result
+            .as_ref()
+            .err()
+            .map(|e| FailureReport {
+                place: res.rating.to_owned(),
+                import_id: res.import_id.clone(),
+                error: e.to_string(),
+                kind: e.failure().map(|(kind, _)| kind).unwrap_or_default(),
+            })
+            .ok_or(())
+    }
+}
+
+impl TryFrom<&RatingResult<'_>> for SuccessReport<NewPlaceRating> {
+    type Error = ();
+    fn try_from(res: &RatingResult) -> Result<Self, Self::Error> {
+        res.result
+            .as_ref()
+            .ok()
+            .map(|_| Self {
+                place: res.rating.to_owned(),
+                import_id: res.import_id.clone(),
+                uuid: res.rating.entry.clone(),
+            })
+            .ok_or(())
+    }
+}
+
+impl From<Vec<RatingResult<'_>>> for Report<NewPlaceRating, SuccessReport<NewPlaceRating>> {
+    fn from(results: Vec<RatingResult>) -> Self {
+        let failures = results
             .iter()
-            .map(CsvImportFailureReport::try_from)
+            .map(FailureReport::try_from)
             .filter_map(Result::ok)
             .collect();
 
-        let csv_import_successes = results
+        let successes = results
             .iter()
-            .map(CsvImportSuccessReport::try_from)
+            .map(SuccessReport::try_from)
             .filter_map(Result::ok)
             .collect();
 
         Self {
-            csv_import_failures,
-            csv_import_successes,
-            duplicates: Default::default(),
-            failures: Default::default(),
-            successes: Default::default(),
+            failures,
+            successes,
+            ..Default::default()
+        }
+    }
+}
+
+impl<T, S> Default for Report<T, S> {
+    fn default() -> Self {
+        Self {
+            schema_version: REPORT_SCHEMA_VERSION,
+            duplicates: vec![],
+            failures: vec![],
+            successes: vec![],
+            csv_import_successes: vec![],
+            csv_import_failures: vec![],
+            csv_import_warnings: vec![],
+            unknown_ids: vec![],
+            conflicts: vec![],
+            metrics: None,
+        }
+    }
+}
+
+impl<T, S> Report<T, S> {
+    /// Add the entries of another report, e.g. of the next record of a stream.
+    pub fn append(&mut self, mut other: Self) {
+        self.duplicates.append(&mut other.duplicates);
+        self.failures.append(&mut other.failures);
+        self.successes.append(&mut other.successes);
+        self.csv_import_successes
+            .append(&mut other.csv_import_successes);
+        self.csv_import_failures
+            .append(&mut other.csv_import_failures);
+        self.csv_import_warnings
+            .append(&mut other.csv_import_warnings);
+        self.unknown_ids.append(&mut other.unknown_ids);
+        self.conflicts.append(&mut other.conflicts);
+        self.metrics = self.metrics.take().or(other.metrics);
+    }
+
+    /// Number of records with a result.
+    pub fn record_count(&self) -> usize {
+        self.successes.len()
+            + self.duplicates.len()
+            + self.failures.len()
+            + self.csv_import_failures.len()
+    }
+}
+
+/// Add a tag (e.g. the tag that is owned by an organization) to all places.
+pub fn add_tag(results: &mut [CsvImportResult<NewPlace>], tag: &str) {
+    let tag = tag.trim().trim_start_matches('#').to_lowercase();
+    for place in results.iter_mut().filter_map(|r| r.result.as_mut().ok()) {
+        if !place.tags.contains(&tag) {
+            place.tags.push(tag.clone());
         }
     }
 }
+
+/// Results of importing places.
+#[derive(Debug)]
+pub struct PlacesImport<'a> {
+    pub results: Vec<ImportResult<'a>>,
+    /// The import was stopped before all places were imported.
+    pub deadline_exceeded: bool,
+}
+
+/// Create new places unless there are possible duplicates.
+pub fn import_places<'a, A: OfdbApi>(
+    api: &A,
+    places: &'a [NewPlace],
+    ignore_duplicates: bool,
+    deadline: Deadline,
+) -> crate::Result<PlacesImport<'a>> {
+    let places: Vec<_> = places
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (Some(i.to_string()), p))
+        .collect();
+    let check_duplicates = |new_place: &NewPlace| {
+        if ignore_duplicates {
+            Ok(None)
+        } else {
+            api.search_duplicates(new_place)
+        }
+    };
+    import_places_with(api, places, check_duplicates, deadline, |_| {})
+}
+
+/// Create new places (with their import IDs)
+/// unless `check_duplicates` finds possible duplicates.
+///
+/// `on_result` is called with the result of each place.
+pub fn import_places_with<'a, A, D, R>(
+    api: &A,
+    places: Vec<(Option<String>, &'a NewPlace)>,
+    check_duplicates: D,
+    deadline: Deadline,
+    mut on_result: R,
+) -> crate::Result<PlacesImport<'a>>
+where
+    A: OfdbApi,
+    D: Fn(&NewPlace) -> crate::Result<Option<Vec<PlaceSearchResult>>>,
+    R: FnMut(&ImportResult<'a>),
+{
+    log::debug!("Import {} places", places.len());
+    let count = places.len();
+    let mut results = vec![];
+    let mut deadline_exceeded = false;
+    for (i, (import_id, new_place)) in places.into_iter().enumerate() {
+        if deadline.is_expired() {
+            log::warn!("Stop after importing {i} of {count} places");
+            deadline_exceeded = true;
+            break;
+        }
+        let possible_duplicates = check_duplicates(new_place)?;
+
+        if let Some(possible_duplicates) = possible_duplicates {
+            log::warn!(
+                "Found {} possible duplicates for '{}':",
+                possible_duplicates.len(),
+                new_place.title
+            );
+            for p in &possible_duplicates {
+                log::warn!(" - {} (id: {})", p.title, p.id);
+            }
+            let result = ImportResult {
+                new_place,
+                import_id,
+                result: Err(Error::Duplicates(possible_duplicates)),
+            };
+            on_result(&result);
+            results.push(result);
+            continue;
+        }
+        let result = match api.create_place(new_place) {
+            Ok(id) => {
+                log::debug!("Successfully imported '{}' with ID={}", new_place.title, id);
+                Ok(id)
+            }
+            Err(err) => {
+                log::warn!("Could not import '{}': {}", new_place.title, err);
+                Err(Error::from(err))
+            }
+        };
+        let result = ImportResult {
+            new_place,
+            import_id,
+            result,
+        };
+        on_result(&result);
+        results.push(result);
+    }
+    Ok(PlacesImport {
+        results,
+        deadline_exceeded,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ofdb_boundary::{EntrySearchRatings, MapBbox, Review, SearchResponse, UpdatePlace};
+    use std::cell::RefCell;
+    use uuid::Uuid;
+
+    /// In-memory API that rejects places with an empty title
+    /// and reports places with the title "Duplicate" as duplicates.
+    #[derive(Default)]
+    struct FakeApi {
+        created: RefCell<Vec<String>>,
+    }
+
+    impl OfdbApi for FakeApi {
+        fn create_place(&self, new_place: &NewPlace) -> crate::Result<String> {
+            if new_place.title.is_empty() {
+                return Err(crate::Error::Api {
+                    status: 400,
+                    message: "Empty title".to_string(),
+                });
+            }
+            let id = Uuid::new_v4().simple().to_string();
+            self.created.borrow_mut().push(new_place.title.clone());
+            Ok(id)
+        }
+        fn search_duplicates(
+            &self,
+            new_place: &NewPlace,
+        ) -> crate::Result<Option<Vec<PlaceSearchResult>>> {
+            if new_place.title != "Duplicate" {
+                return Ok(None);
+            }
+            Ok(Some(vec![PlaceSearchResult {
+                id: "existing".to_string(),
+                status: None,
+                lat: new_place.lat,
+                lng: new_place.lng,
+                title: new_place.title.clone(),
+                description: new_place.description.clone(),
+                categories: vec![],
+                tags: vec![],
+                ratings: EntrySearchRatings {
+                    total: 0.0,
+                    diversity: 0.0,
+                    fairness: 0.0,
+                    humanity: 0.0,
+                    renewable: 0.0,
+                    solidarity: 0.0,
+                    transparency: 0.0,
+                },
+            }]))
+        }
+        fn update_place(&self, _: &str, _: &UpdatePlace) -> crate::Result<String> {
+            Err(crate::not_supported("update_place"))
+        }
+        fn read_entries(&self, _: Vec<Uuid>) -> crate::Result<Vec<Entry>> {
+            Err(crate::not_supported("read_entries"))
+        }
+        fn search(&self, _: &str, _: &MapBbox) -> crate::Result<SearchResponse> {
+            Err(crate::not_supported("search"))
+        }
+        fn review(&self, _: Vec<Uuid>, _: Review) -> crate::Result<()> {
+            Err(crate::not_supported("review"))
+        }
+    }
+
+    fn new_place(title: &str) -> NewPlace {
+        NewPlace {
+            title: title.to_string(),
+            description: "A place".to_string(),
+            lat: 51.5,
+            lng: 7.2,
+            street: None,
+            zip: None,
+            city: None,
+            country: None,
+            state: None,
+            contact_name: None,
+            email: None,
+            telephone: None,
+            homepage: None,
+            opening_hours: None,
+            founded_on: None,
+            categories: vec![],
+            tags: vec![],
+            license: "CC0-1.0".to_string(),
+            image_url: None,
+            image_link_url: None,
+            links: vec![],
+        }
+    }
+
+    #[test]
+    fn import_places_with_fake_api() {
+        let api = FakeApi::default();
+        let places = vec![new_place("Foo"), new_place("Duplicate"), new_place("")];
+        let import = import_places(&api, &places, false, Deadline::none()).unwrap();
+        assert!(!import.deadline_exceeded);
+        assert_eq!(*api.created.borrow(), vec!["Foo"]);
+
+        let report = Report::from(import.results);
+        assert_eq!(report.successes.len(), 1);
+        assert_eq!(report.duplicates.len(), 1);
+        assert_eq!(report.failures.len(), 1);
+    }
+
+    #[test]
+    fn report_permission_errors() {
+        let err = Error::from(crate::Error::Forbidden("moderated".to_string()));
+        assert!(matches!(err, Error::PermissionDenied(_)));
+        let place = new_place("Foo");
+        let result = ImportResult {
+            new_place: &place,
+            import_id: None,
+            result: Err(err),
+        };
+        let report = Report::from(vec![result]);
+        assert_eq!(report.failures[0].kind, FailureKind::PermissionDenied);
+        assert_eq!(report.failures[0].error, "moderated");
+
+        let err = Error::from(crate::Error::NotFound("foo".to_string()));
+        assert!(matches!(err, Error::Other(_)));
+    }
+
+    #[test]
+    fn import_duplicates_if_they_are_ignored() {
+        let api = FakeApi::default();
+        let places = vec![new_place("Duplicate")];
+        let import = import_places(&api, &places, true, Deadline::none()).unwrap();
+        assert_eq!(import.results.len(), 1);
+        assert_eq!(*api.created.borrow(), vec!["Duplicate"]);
+    }
+
+    #[test]
+    fn add_the_tag_of_an_organization() {
+        let mut tagged = new_place("Foo");
+        tagged.tags = vec!["solar".to_string(), "my-org".to_string()];
+        let mut results = vec![
+            CsvImportResult::new(0, Ok(new_place("Bar"))),
+            CsvImportResult::new(1, Ok(tagged)),
+            CsvImportResult::new(2, Err(CsvImportError::Record("invalid".to_string()))),
+        ];
+        add_tag(&mut results, "#My-Org");
+        let tags = |r: &CsvImportResult<NewPlace>| r.result.as_ref().unwrap().tags.clone();
+        assert_eq!(tags(&results[0]), vec!["my-org"]);
+        assert_eq!(tags(&results[1]), vec!["solar", "my-org"]);
+    }
+
+    #[test]
+    fn stop_import_after_deadline() {
+        let api = FakeApi::default();
+        let places = vec![new_place("Foo")];
+        let deadline = Deadline::after(Some(std::time::Duration::ZERO));
+        let import = import_places(&api, &places, false, deadline).unwrap();
+        assert!(import.deadline_exceeded);
+        assert!(import.results.is_empty());
+    }
+}
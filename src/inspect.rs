@@ -0,0 +1,161 @@
+//! Show how the first records of a CSV file are read,
+//! e.g. to check the mapping of the columns before a long import.
+
+use std::{io::Write, sync::Mutex};
+
+use anyhow::Result;
+use ofdb_boundary::NewPlace;
+use ofdb_core::gateways::geocode::GeoCodingGateway;
+use ofdb_entities::address::Address;
+
+use crate::import::CsvImportResult;
+
+/// A geocoder without any requests, to only use the geocoding cache.
+pub struct NoGeoCoding;
+
+impl GeoCodingGateway for NoGeoCoding {
+    fn resolve_address_lat_lng(&self, _: &Address) -> Option<(f64, f64)> {
+        None
+    }
+}
+
+/// Geocoding that remembers the resolved coordinates,
+/// to tell them apart from the coordinates of the file.
+pub struct RecordingGeoCoding<G> {
+    inner: G,
+    resolved: Mutex<Vec<(f64, f64)>>,
+}
+
+impl<G> RecordingGeoCoding<G> {
+    pub fn new(inner: G) -> Self {
+        Self {
+            inner,
+            resolved: Mutex::default(),
+        }
+    }
+
+    fn is_resolved(&self, lat: f64, lng: f64) -> bool {
+        self.resolved.lock().unwrap().contains(&(lat, lng))
+    }
+}
+
+impl<G: GeoCodingGateway> GeoCodingGateway for RecordingGeoCoding<G> {
+    fn resolve_address_lat_lng(&self, addr: &Address) -> Option<(f64, f64)> {
+        let lat_lng = self.inner.resolve_address_lat_lng(addr)?;
+        self.resolved.lock().unwrap().push(lat_lng);
+        Some(lat_lng)
+    }
+}
+
+/// Write the fields of the places that would be imported
+/// (empty fields are omitted) or the errors of the records.
+pub fn write_records<W: Write, G>(
+    mut w: W,
+    results: &[CsvImportResult<NewPlace>],
+    geo_coding: &RecordingGeoCoding<G>,
+) -> Result<()> {
+    for r in results {
+        let place = match &r.result {
+            Ok(place) => place,
+            Err(err) => {
+                writeln!(w, "Record {}: {err}\n", r.record_nr)?;
+                continue;
+            }
+        };
+        writeln!(w, "Record {}: {}", r.record_nr, place.title)?;
+        let source = if geo_coding.is_resolved(place.lat, place.lng) {
+            "geocoding cache"
+        } else {
+            "file"
+        };
+        let coordinates = format!("{}, {} ({source})", place.lat, place.lng);
+        let address = [
+            &place.street,
+            &place.zip,
+            &place.city,
+            &place.state,
+            &place.country,
+        ]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+        let founded_on = place.founded_on.map(|date| date.to_string());
+        let links = place
+            .links
+            .iter()
+            .map(|link| link.url.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        for (name, value) in [
+            ("description", Some(place.description.as_str())),
+            ("coordinates", Some(coordinates.as_str())),
+            ("address", Some(address.as_str())),
+            ("contact_name", place.contact_name.as_deref()),
+            ("email", place.email.as_deref()),
+            ("telephone", place.telephone.as_deref()),
+            ("homepage", place.homepage.as_deref()),
+            ("opening_hours", place.opening_hours.as_deref()),
+            ("founded_on", founded_on.as_deref()),
+            ("tags", Some(place.tags.join(", ").as_str())),
+            ("license", Some(place.license.as_str())),
+            ("image_url", place.image_url.as_deref()),
+            ("image_link_url", place.image_link_url.as_deref()),
+            ("links", Some(links.as_str())),
+        ] {
+            match value {
+                Some(value) if !value.is_empty() => writeln!(w, "  {name}: {value}")?,
+                _ => {}
+            }
+        }
+        for warning in &r.warnings {
+            writeln!(w, "  warning: {warning}")?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv::{new_places_with_options, CsvOptions};
+
+    struct CityGeoCoding;
+
+    impl GeoCodingGateway for CityGeoCoding {
+        fn resolve_address_lat_lng(&self, addr: &Address) -> Option<(f64, f64)> {
+            (addr.city.as_deref() == Some("Bochum")).then_some((51.48, 7.22))
+        }
+    }
+
+    #[test]
+    fn show_the_mapped_fields() {
+        let csv = "\
+title,description,lat,lng,city,license,tags,founded_on
+File,A place,51.4,7.2,,CC0-1.0,Bio; #Regional,01.05.2019
+Cache,A place,,,Bochum,CC0-1.0,bio,
+Missing,A place,,,,CC0-1.0,bio,
+";
+        let geo_coding = RecordingGeoCoding::new(CityGeoCoding);
+        let results =
+            new_places_with_options(csv.as_bytes(), &geo_coding, &CsvOptions::default()).unwrap();
+        let mut out = vec![];
+        write_records(&mut out, &results, &geo_coding).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with(
+            "\
+Record 0: File
+  description: A place
+  coordinates: 51.4, 7.2 (file)
+  founded_on: 2019-05-01
+  tags: bio, regional
+  license: CC0-1.0
+"
+        ));
+        assert!(out.contains("Record 1: Cache\n"));
+        assert!(out.contains("  coordinates: 51.48, 7.22 (geocoding cache)\n"));
+        assert!(out.contains("Record 2: "));
+    }
+}
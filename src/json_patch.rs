@@ -0,0 +1,257 @@
+//! Update entries with JSON merge patches (RFC 7386).
+//!
+//! Each patch is an object with the `id` of the entry
+//! and only the fields that should be changed:
+//!
+//! ```json
+//! [{ "id": "…", "homepage": null, "tags": ["solar"] }]
+//! ```
+//!
+//! - `null` deletes a field
+//! - arrays (`tags`, `categories` and `links`) are appended,
+//!   unless the field name ends with `!` (e.g. `"tags!": ["solar"]`)
+//! - the `version` is optional, by default the next version is used
+
+use std::io::Read;
+
+use anyhow::{anyhow, bail, Result};
+use ofdb_boundary::Entry;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    csv::normalize_patched_urls,
+    filter::RecordFilter,
+    import::{CsvImportError, CsvImportResult},
+    OfdbApi,
+};
+
+/// Fields that can't be modified.
+const READ_ONLY_FIELDS: &[&str] = &["created", "license", "ratings"];
+
+/// Fields of an entry that can be modified by a patch or an update.
+pub(crate) const ENTRY_FIELDS: &[&str] = &[
+    "title",
+    "description",
+    "lat",
+    "lng",
+    "street",
+    "zip",
+    "city",
+    "country",
+    "state",
+    "contact_name",
+    "email",
+    "telephone",
+    "homepage",
+    "opening_hours",
+    "founded_on",
+    "categories",
+    "tags",
+    "image_url",
+    "image_link_url",
+    "links",
+];
+
+/// Fields that are required and therefore can't be deleted.
+const REQUIRED_FIELDS: &[&str] = &["title", "description", "lat", "lng"];
+
+/// Read the patches and apply them to the current state of the entries read via `api`.
+pub fn patch_places_from_json<R: Read>(
+    r: R,
+    api: &impl OfdbApi,
+    filter: &RecordFilter,
+) -> Result<Vec<CsvImportResult<Entry>>> {
+    let patches = match serde_json::from_reader(r)? {
+        Value::Array(patches) => patches,
+        patch @ Value::Object(_) => vec![patch],
+        _ => bail!("Expected a JSON array of patches"),
+    };
+    log::debug!("Read {} patches from JSON file", patches.len());
+    let mut results = vec![];
+    let mut valid_patches = vec![];
+    for (record_nr, patch) in filter.select_items(patches) {
+        match parse_patch(patch) {
+            Ok((uuid, patch)) => valid_patches.push((record_nr, uuid, patch)),
+            Err(err) => results.push(CsvImportResult::new(
+                record_nr,
+                Err(CsvImportError::Record(err.to_string())),
+            )),
+        }
+    }
+    let uuids = valid_patches.iter().map(|(_, uuid, _)| *uuid).collect();
+    log::info!("Read current state of all {} entries", valid_patches.len());
+    let original_entries = api.read_entries(uuids)?;
+    for (record_nr, uuid, patch) in valid_patches {
+        let Some(original) = original_entries
+            .iter()
+            .find(|e| e.id.parse::<Uuid>().ok() == Some(uuid))
+        else {
+            log::warn!("Entry {uuid} does not exist");
+            results.push(CsvImportResult::new(
+                record_nr,
+                Err(CsvImportError::UnknownId(uuid.simple().to_string())),
+            ));
+            continue;
+        };
+        let result = apply_patch(original.clone(), &patch)
+            .map_err(|err| CsvImportError::PatchRequest(err.to_string()))
+            .and_then(|mut entry| normalize_patched_urls(original, &mut entry).map(|()| entry));
+        results.push(CsvImportResult::new(record_nr, result));
+    }
+    results.sort_by_key(|r| r.record_nr);
+    Ok(results)
+}
+
+fn parse_patch(patch: Value) -> Result<(Uuid, Map<String, Value>)> {
+    let Value::Object(patch) = patch else {
+        bail!("A patch has to be a JSON object");
+    };
+    let id = patch
+        .get("id")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("The patch has no 'id'"))?;
+    let uuid = id
+        .parse::<Uuid>()
+        .map_err(|err| anyhow!("Invalid entry ID: {err}"))?;
+    Ok((uuid, patch))
+}
+
+/// Apply a merge patch to an entry.
+pub fn apply_patch(original: Entry, patch: &Map<String, Value>) -> Result<Entry> {
+    let version = original.version;
+    let Value::Object(mut entry) = serde_json::to_value(original)? else {
+        unreachable!("An entry is a JSON object");
+    };
+    for (key, value) in patch {
+        let (field, replace) = match key.strip_suffix('!') {
+            Some(field) => (field, true),
+            None => (key.as_str(), false),
+        };
+        match field {
+            "id" => {}
+            "version" => {
+                if value.as_u64() != Some(version + 1) {
+                    bail!("Invalid entry version");
+                }
+            }
+            _ if READ_ONLY_FIELDS.contains(&field) => {
+                log::warn!("The field '{field}' can't be modified.");
+            }
+            _ => {
+                if !ENTRY_FIELDS.contains(&field) {
+                    bail!("Unknown field '{field}'");
+                }
+                if value.is_null() && REQUIRED_FIELDS.contains(&field) {
+                    bail!("The field '{field}' can't be deleted");
+                }
+                let current = entry.entry(field).or_insert(Value::Null);
+                match (current, value) {
+                    (Value::Array(current), Value::Null) => current.clear(),
+                    (Value::Array(current), Value::Array(values)) if !replace => {
+                        for v in values {
+                            if !current.contains(v) {
+                                current.push(v.clone());
+                            }
+                        }
+                    }
+                    (current, value) => merge(current, value),
+                }
+            }
+        }
+    }
+    entry.insert("version".to_string(), Value::from(version + 1));
+    serde_json::from_value(Value::Object(entry)).map_err(|err| anyhow!("Invalid patch: {err}"))
+}
+
+/// The merge algorithm of RFC 7386.
+fn merge(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(target) = target else {
+        unreachable!();
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn entry() -> Entry {
+        let mut entry = crate::fixtures::generate(1, 5).entries.remove(0);
+        entry.version = 2;
+        entry.tags = vec!["foo".to_string(), "bar".to_string()];
+        entry.homepage = Some("https://example.org".to_string());
+        entry
+    }
+
+    fn patch(value: Value) -> Map<String, Value> {
+        let Value::Object(patch) = value else {
+            panic!();
+        };
+        patch
+    }
+
+    #[test]
+    fn change_and_delete_fields() {
+        let original = entry();
+        let patched = apply_patch(
+            original.clone(),
+            &patch(json!({ "id": original.id, "title": "New", "homepage": null })),
+        )
+        .unwrap();
+        assert_eq!(patched.version, 3);
+        assert_eq!(patched.title, "New");
+        assert_eq!(patched.homepage, None);
+        assert_eq!(patched.description, original.description);
+    }
+
+    #[test]
+    fn append_or_replace_tags() {
+        let patched = apply_patch(entry(), &patch(json!({ "tags": ["bar", "baz"] }))).unwrap();
+        assert_eq!(patched.tags, vec!["foo", "bar", "baz"]);
+        let patched = apply_patch(entry(), &patch(json!({ "tags!": ["baz"] }))).unwrap();
+        assert_eq!(patched.tags, vec!["baz"]);
+        let patched = apply_patch(entry(), &patch(json!({ "tags": null }))).unwrap();
+        assert!(patched.tags.is_empty());
+    }
+
+    #[test]
+    fn reject_invalid_patches() {
+        let err = |value| apply_patch(entry(), &patch(value)).unwrap_err().to_string();
+        assert_eq!(err(json!({ "version": 2 })), "Invalid entry version");
+        assert_eq!(err(json!({ "foo": 1 })), "Unknown field 'foo'");
+        assert_eq!(
+            err(json!({ "title": null })),
+            "The field 'title' can't be deleted"
+        );
+        assert!(err(json!({ "lat": "north" })).starts_with("Invalid patch"));
+        assert!(apply_patch(entry(), &patch(json!({ "version": 3 }))).is_ok());
+    }
+
+    #[test]
+    fn parse_patches() {
+        assert!(parse_patch(json!({ "title": "Foo" })).is_err());
+        assert!(parse_patch(json!({ "id": "foo" })).is_err());
+        assert!(parse_patch(json!(["foo"])).is_err());
+        let (uuid, _) = parse_patch(json!({ "id": "5d6e2d4ac4a84a5b9b3b5d5e5f5a5b5c" })).unwrap();
+        assert_eq!(
+            uuid.simple().to_string(),
+            "5d6e2d4ac4a84a5b9b3b5d5e5f5a5b5c"
+        );
+    }
+}
@@ -0,0 +1,357 @@
+//! Entries as placemarks of a KML file, e.g. for offline maps like Organic Maps,
+//! and placemarks of a KML file (e.g. of Google My Maps) as records of the CSV import.
+
+use std::{io::Write, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+use ofdb_boundary::Entry;
+use roxmltree::Node;
+
+use crate::{
+    address::{address_line, split_zip_city},
+    report::entry_url,
+    tags::TagSeparator,
+    xml::escape,
+};
+
+/// The columns of a placemark, the fields of its extended data follow.
+const PLACEMARK_COLUMNS: [&str; 9] = [
+    "title",
+    "description",
+    "lat",
+    "lng",
+    "street",
+    "zip",
+    "city",
+    "country",
+    "tags",
+];
+
+/// The pseudo field with the name of the folder (layer) of a placemark.
+pub const FOLDER_FIELD: &str = "folder";
+
+/// The tags of a value of a tag field, e.g. `Unverpackt=zero-waste,unverpackt`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagMapping {
+    pub value: String,
+    pub tags: Vec<String>,
+}
+
+impl FromStr for TagMapping {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((value, tags)) = s.split_once('=') else {
+            bail!("Invalid tag mapping '{s}' (expected 'VALUE=TAG,TAG')");
+        };
+        let tags: Vec<_> = tags
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(ToString::to_string)
+            .collect();
+        if value.trim().is_empty() || tags.is_empty() {
+            bail!("Invalid tag mapping '{s}' (expected 'VALUE=TAG,TAG')");
+        }
+        Ok(Self {
+            value: value.trim().to_string(),
+            tags,
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct KmlOptions {
+    /// Fields of the extended data (or [FOLDER_FIELD]) with the tags of a placemark.
+    pub tag_fields: Vec<String>,
+    /// Values of the tag fields that are replaced by other tags.
+    pub tag_mappings: Vec<TagMapping>,
+    /// The separator of the `tags` column of the CSV import.
+    pub tag_separator: TagSeparator,
+}
+
+impl KmlOptions {
+    fn tags(&self, value: &str) -> Vec<String> {
+        value
+            .split([',', ';'])
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .flat_map(|v| {
+                self.tag_mappings
+                    .iter()
+                    .find(|m| m.value.eq_ignore_ascii_case(v))
+                    .map_or_else(|| vec![v.to_string()], |m| m.tags.clone())
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Placemark {
+    name: String,
+    description: String,
+    address: String,
+    /// `lat,lng` of a point, other geometries have no coordinates.
+    coordinates: Option<(f64, f64)>,
+    folder: Option<String>,
+    data: Vec<(String, String)>,
+}
+
+/// Convert the placemarks into the records of a CSV file of new places
+/// (the [PLACEMARK_COLUMNS] and the fields of the extended data),
+/// so they are read, geocoded and checked for duplicates like any other import file.
+pub fn placemarks_to_csv(kml: &str, options: &KmlOptions) -> Result<Vec<u8>> {
+    let doc = roxmltree::Document::parse(kml).map_err(|err| anyhow!("Invalid KML file: {err}"))?;
+    let placemarks = doc
+        .descendants()
+        .filter(|n| n.tag_name().name() == "Placemark")
+        .map(placemark)
+        .collect::<Result<Vec<_>>>()?;
+    log::info!("Read {} placemarks from KML file", placemarks.len());
+
+    let mut fields: Vec<&str> = vec![];
+    for (name, _) in placemarks.iter().flat_map(|p| &p.data) {
+        if !fields.contains(&name.as_str()) && !PLACEMARK_COLUMNS.contains(&name.as_str()) {
+            fields.push(name);
+        }
+    }
+    let mut wtr = ::csv::Writer::from_writer(vec![]);
+    wtr.write_record(PLACEMARK_COLUMNS.iter().chain(&fields))?;
+    for p in &placemarks {
+        let value = |field: &str| {
+            if field == FOLDER_FIELD && !p.data.iter().any(|(name, _)| name == field) {
+                return p.folder.as_deref();
+            }
+            p.data
+                .iter()
+                .find(|(name, _)| name == field)
+                .map(|(_, value)| value.as_str())
+        };
+        let mut tags: Vec<String> = vec![];
+        for tag in options
+            .tag_fields
+            .iter()
+            .filter_map(|f| value(f))
+            .flat_map(|v| options.tags(v))
+        {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        let (lat, lng) = p
+            .coordinates
+            .map(|(lat, lng)| (lat.to_string(), lng.to_string()))
+            .unwrap_or_default();
+        let mut record = vec![p.name.clone(), p.description.clone(), lat, lng];
+        record.extend(split_address(&p.address));
+        record.push(options.tag_separator.join(&tags));
+        record.extend(
+            fields
+                .iter()
+                .map(|f| value(f).unwrap_or_default().to_string()),
+        );
+        wtr.write_record(&record)?;
+    }
+    Ok(wtr.into_inner()?)
+}
+
+fn placemark(node: Node) -> Result<Placemark> {
+    let text = |node: Option<Node>| {
+        node.and_then(|n| n.text())
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    };
+    let coordinates = node
+        .descendants()
+        .find(|n| n.tag_name().name() == "Point")
+        .and_then(|point| child(point, "coordinates"))
+        .and_then(|c| {
+            let first = c
+                .text()
+                .unwrap_or_default()
+                .split_whitespace()
+                .next()
+                .unwrap_or_default();
+            let mut values = first.split(',').map(|v| v.trim().parse::<f64>());
+            match (values.next(), values.next()) {
+                (Some(Ok(lng)), Some(Ok(lat))) => Some((lat, lng)),
+                _ => {
+                    // The record is geocoded by its address or rejected by the import
+                    log::warn!("Ignore the invalid coordinates '{first}' of a placemark");
+                    None
+                }
+            }
+        });
+    let mut data = vec![];
+    if let Some(extended_data) = child(node, "ExtendedData") {
+        for d in extended_data.descendants() {
+            match d.tag_name().name() {
+                "Data" => data.push((attribute_name(d), text(child(d, "value")))),
+                "SimpleData" => data.push((attribute_name(d), text(Some(d)))),
+                _ => {}
+            }
+        }
+    }
+    let folder = node
+        .ancestors()
+        .find(|n| n.tag_name().name() == "Folder")
+        .map(|folder| text(child(folder, "name")));
+    Ok(Placemark {
+        name: text(child(node, "name")),
+        description: strip_html(&text(child(node, "description"))),
+        address: text(child(node, "address")),
+        coordinates,
+        folder,
+        data,
+    })
+}
+
+/// The street, zip, city and country of an address in one line,
+/// e.g. `Hauptstr. 1, 44787 Bochum, Deutschland`.
+fn split_address(address: &str) -> [String; 4] {
+    let mut parts = address.split(',').map(str::trim).filter(|p| !p.is_empty());
+    let street = parts.next().unwrap_or_default().to_string();
+    let (mut zip, mut city, mut country) = (String::new(), String::new(), String::new());
+    for part in parts {
+        if let Some((z, c)) = split_zip_city(part).filter(|_| city.is_empty()) {
+            zip = z.to_string();
+            city = c.to_string();
+        } else if city.is_empty() {
+            city = part.to_string();
+        } else {
+            country = part.to_string();
+        }
+    }
+    [street, zip, city, country]
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.tag_name().name() == name)
+}
+
+fn attribute_name(node: Node) -> String {
+    node.attribute("name")
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// The text of an HTML description (Google My Maps uses `<br>` for line breaks).
+fn strip_html(html: &str) -> String {
+    let html = html
+        .replace("<br>", "\n")
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n");
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+/// Write the entries as placemarks with their title, description, address and a link to the map.
+pub fn write_entries<W: Write>(mut w: W, entries: &[Entry]) -> Result<()> {
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(w, r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#)?;
+    writeln!(w, "<Document>")?;
+    writeln!(w, "<name>Karte von morgen</name>")?;
+    for e in entries {
+        writeln!(w, "<Placemark>")?;
+        writeln!(w, "<name>{}</name>", escape(&e.title))?;
+        let description = format!("{}\n\n{}", e.description.trim(), entry_url(&e.id));
+        writeln!(w, "<description>{}</description>", escape(&description))?;
+        let address = address_line(e, true);
+        if !address.is_empty() {
+            writeln!(w, "<address>{}</address>", escape(&address))?;
+        }
+        writeln!(
+            w,
+            "<Point><coordinates>{},{}</coordinates></Point>",
+            e.lng, e.lat
+        )?;
+        writeln!(w, "</Placemark>")?;
+    }
+    writeln!(w, "</Document>")?;
+    writeln!(w, "</kml>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_placemarks() {
+        let mut entry = crate::fixtures::generate(1, 0).entries.remove(0);
+        entry.title = "Obst & Gemüse".to_string();
+        entry.lat = 51.5;
+        entry.lng = 7.25;
+        let mut kml = vec![];
+        write_entries(&mut kml, &[entry.clone()]).unwrap();
+        let kml = String::from_utf8(kml).unwrap();
+        assert!(kml.contains("<name>Obst &amp; Gemüse</name>"));
+        assert!(kml.contains("<Point><coordinates>7.25,51.5</coordinates></Point>"));
+        assert!(kml.contains(&entry_url(&entry.id)));
+        assert!(kml.trim_end().ends_with("</kml>"));
+    }
+
+    const MY_MAPS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2">
+  <Document>
+    <name>Nachhaltiges Bochum</name>
+    <Folder>
+      <name>Unverpackt</name>
+      <Placemark>
+        <name>Unverpackt Laden</name>
+        <description><![CDATA[Lose Lebensmittel<br>Mo-Fr]]></description>
+        <ExtendedData>
+          <Data name="Kategorie"><value>Unverpackt; Bio</value></Data>
+          <Data name="Website"><value>https://example.org</value></Data>
+        </ExtendedData>
+        <Point><coordinates>7.2162,51.4818,0</coordinates></Point>
+      </Placemark>
+      <Placemark>
+        <name>Repair Café</name>
+        <address>Hauptstr. 1, 44787 Bochum, Deutschland</address>
+        <ExtendedData><Data name="Kategorie"><value>Reparatur</value></Data></ExtendedData>
+        <LineString><coordinates>7.21,51.48,0 7.22,51.49,0</coordinates></LineString>
+      </Placemark>
+    </Folder>
+  </Document>
+</kml>"#;
+
+    #[test]
+    fn read_placemarks_as_csv_records() {
+        let mut options = KmlOptions {
+            tag_fields: vec!["Kategorie".to_string(), FOLDER_FIELD.to_string()],
+            tag_mappings: vec!["unverpackt=zero-waste,unverpackt".parse().unwrap()],
+            ..Default::default()
+        };
+        let csv = String::from_utf8(placemarks_to_csv(MY_MAPS, &options).unwrap()).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "title,description,lat,lng,street,zip,city,country,tags,Kategorie,Website"
+        );
+        assert_eq!(
+            csv.split_once('\n').unwrap().1,
+            "Unverpackt Laden,\"Lose Lebensmittel\nMo-Fr\",51.4818,7.2162,,,,,\"zero-waste,unverpackt,Bio\",Unverpackt; Bio,https://example.org\n\
+             Repair Café,,,,Hauptstr. 1,44787,Bochum,Deutschland,\"Reparatur,zero-waste,unverpackt\",Reparatur,\n"
+        );
+        assert!(placemarks_to_csv("<kml>", &options).is_err());
+        assert!("Bio".parse::<TagMapping>().is_err());
+
+        options.tag_separator = TagSeparator::Semicolon;
+        let broken = MY_MAPS.replace("7.2162,51.4818,0", "7.2162;51.4818");
+        let csv = String::from_utf8(placemarks_to_csv(&broken, &options).unwrap()).unwrap();
+        assert!(csv.contains(
+            "Unverpackt Laden,\"Lose Lebensmittel\nMo-Fr\",,,,,,,zero-waste;unverpackt;Bio,"
+        ));
+    }
+}
@@ -1,50 +1,91 @@
-use anyhow::Result;
 use ofdb_boundary::{
-    Credentials, Entry, Error, MapBbox, NewPlace, PlaceSearchResult, Review, SearchResponse,
-    UpdatePlace,
+    Credentials, Entry, MapBbox, NewPlace, NewPlaceRating, PlaceHistory, PlaceSearchResult, Rating,
+    Review, SearchResponse, TagFrequency, UpdatePlace,
 };
-use reqwest::blocking::{Client, Response};
+use reqwest::blocking::Client;
 use uuid::Uuid;
 
+pub mod address;
+mod api;
+pub mod audit;
+pub mod category;
+pub mod clearance;
+mod client;
+pub mod compat;
+pub mod config;
+pub mod contact;
 pub mod csv;
+pub mod deadline;
+pub mod dedupe;
+pub mod diff;
+pub mod email;
+pub mod enrich;
+mod error;
+pub mod export;
+pub mod filter;
+pub mod fixtures;
+pub mod geocode;
+pub mod geocoder_usage;
+pub mod geocoding_cache;
+pub mod gpx;
+pub mod headers;
+pub mod history;
+pub mod http;
+pub mod i18n;
 pub mod import;
+pub mod inspect;
+pub mod json_patch;
+pub mod kml;
+pub mod link_check;
+pub mod lookup;
+pub mod markdown;
+pub mod matching;
+pub mod merge;
+pub mod metrics;
+pub mod mirror;
+#[cfg(feature = "mock-server")]
+pub mod mock_server;
+pub mod opencage;
+pub mod opening_hours;
+#[cfg(feature = "parquet")]
+pub mod parquet_export;
+pub mod pipeline;
+pub mod preview;
+pub mod rate_limit;
+pub mod remerge;
+pub mod replay;
+pub mod report;
 pub mod review;
+pub mod roundtrip;
+pub mod search;
+pub mod session;
+pub mod spreadsheet;
+pub mod state;
+pub mod stats;
+pub mod tags;
+pub mod trace;
+pub mod users;
+pub mod webhook;
+pub mod xml;
+
+pub use api::{not_supported, OfdbApi};
+pub use client::{OfdbClient, DEFAULT_READ_CHUNK_SIZE};
+pub use error::{Error, Result};
+
+fn ofdb_client(api: &str, client: &Client) -> OfdbClient {
+    OfdbClient::with_client(api, client.clone())
+}
 
 pub fn create_new_place(api: &str, client: &Client, new_place: &NewPlace) -> Result<String> {
-    let url = format!("{}/entries", api);
-    let res = client.post(url).json(&new_place).send()?;
-    handle_response(res)
+    ofdb_client(api, client).create_place(new_place)
 }
 
 pub fn update_place(api: &str, client: &Client, id: &str, place: &UpdatePlace) -> Result<String> {
-    let mut place = place.clone();
-    place.version += 1;
-    let url = format!("{}/entries/{}", api, id);
-    let res = client.put(url).json(&place).send()?;
-    handle_response(res)
+    ofdb_client(api, client).update_place(id, place)
 }
 
 pub fn read_entries(api: &str, client: &Client, uuids: Vec<Uuid>) -> Result<Vec<Entry>> {
-    log::debug!("Read {} places", uuids.len());
-
-    let chunks = uuids.chunks(50).collect::<Vec<&[Uuid]>>();
-
-    let mut all_entries = vec![];
-
-    for uuids in chunks {
-        let ids = uuids
-            .iter()
-            .copied()
-            .map(Uuid::simple)
-            .map(|s| s.to_string())
-            .collect::<Vec<_>>()
-            .join(",");
-        let url = format!("{}/entries/{}", api, ids);
-        let res = client.get(url).send()?;
-        let mut entries = handle_response(res)?;
-        all_entries.append(&mut entries);
-    }
-    Ok(all_entries)
+    ofdb_client(api, client).read_entries(uuids)
 }
 
 /// Login
@@ -54,42 +95,128 @@ pub fn read_entries(api: &str, client: &Client, uuids: Vec<Uuid>) -> Result<Vec<
 /// [cookie store](https://docs.rs/reqwest/0.11.1/reqwest/struct.ClientBuilder.html#method.cookie_store)
 /// should be enabled.  
 pub fn login(api: &str, client: &Client, req: &Credentials) -> Result<()> {
-    let url = format!("{}/login", api);
-    log::info!("Try to login with '{}' ", req.email);
-    let res = client
-        .post(url)
-        .header("Access-Control-Allow-Credentials", "true")
-        .json(&req)
-        .send()?;
-    handle_response(res)
+    ofdb_client(api, client).login(req)
 }
 
 pub fn review_places(api: &str, client: &Client, uuids: Vec<Uuid>, review: Review) -> Result<()> {
-    let url = format!(
-        "{}/places/{}/review",
-        api,
-        uuids
-            .into_iter()
-            .map(Uuid::simple)
-            .map(|s| s.to_string())
-            .collect::<Vec<_>>()
-            .join(",")
-    );
-    let json_string = serde_json::to_string(&review).unwrap();
-    log::debug!("Send review {json_string} to {url}");
-    let res = client.post(&url).json(&review).send()?;
-    handle_response(res)
+    ofdb_client(api, client).review(uuids, review)
+}
+
+/// Read all revisions of a place.
+///
+/// Important:
+/// This requires a login with scout or admin rights.
+pub fn place_history(api: &str, client: &Client, uuid: Uuid) -> Result<PlaceHistory> {
+    ofdb_client(api, client).place_history(uuid)
+}
+
+pub fn create_rating(api: &str, client: &Client, rating: &NewPlaceRating) -> Result<()> {
+    ofdb_client(api, client).create_rating(rating)
+}
+
+pub fn read_ratings(api: &str, client: &Client, ids: Vec<String>) -> Result<Vec<Rating>> {
+    ofdb_client(api, client).read_ratings(ids)
 }
 
 pub fn search(api: &str, client: &Client, txt: &str, bbox: &MapBbox) -> Result<SearchResponse> {
-    let url = format!("{}/search", api);
-    let MapBbox { sw, ne } = bbox;
-    let bbox_string = format!("{},{},{},{}", sw.lat, sw.lng, ne.lat, ne.lng);
-    let res = client
-        .get(url)
-        .query(&[("text", txt), ("bbox", &bbox_string)])
-        .send()?;
-    handle_response(res)
+    ofdb_client(api, client).search(txt, bbox)
+}
+
+/// Count all entries of the instance.
+pub fn count_entries(api: &str, client: &Client) -> Result<u64> {
+    ofdb_client(api, client).count_entries()
+}
+
+/// Count the different tags of the instance.
+pub fn count_tags(api: &str, client: &Client) -> Result<u64> {
+    ofdb_client(api, client).count_tags()
+}
+
+/// Read all entries within the bounding box from the export endpoint.
+///
+/// The entries are deserialized while they are received.
+///
+/// Important:
+/// This requires a login as scout or admin.
+pub fn export_places(
+    api: &str,
+    client: &Client,
+    bbox: &MapBbox,
+    text: &str,
+) -> Result<export::ExportedPlaces<reqwest::blocking::Response>> {
+    ofdb_client(api, client).export_places(bbox, text)
+}
+
+/// Copy the CSV export of all entries within the bounding box to `w`.
+///
+/// Important:
+/// This requires a login as scout or admin.
+pub fn export_places_csv(
+    api: &str,
+    client: &Client,
+    bbox: &MapBbox,
+    text: &str,
+    w: &mut impl std::io::Write,
+) -> Result<u64> {
+    ofdb_client(api, client).export_places_csv(bbox, text, w)
+}
+
+/// Read the most popular tags.
+pub fn most_popular_tags(
+    api: &str,
+    client: &Client,
+    query: &tags::TagQuery,
+) -> Result<Vec<TagFrequency>> {
+    ofdb_client(api, client).most_popular_tags(query)
+}
+
+/// Read the entries with changes that are waiting for a clearance.
+///
+/// Important:
+/// This requires the token of an organization.
+pub fn pending_clearances(
+    api: &str,
+    client: &Client,
+    org_token: &str,
+    offset: Option<u64>,
+    limit: Option<u64>,
+) -> Result<Vec<clearance::PendingClearance>> {
+    ofdb_client(api, client)
+        .with_org_token(org_token.to_string())
+        .pending_clearances(offset, limit)
+}
+
+/// Accept the changes of the entries up to the given revisions.
+///
+/// Important:
+/// This requires the token of an organization.
+pub fn clear_places(
+    api: &str,
+    client: &Client,
+    org_token: &str,
+    clearances: &[clearance::Clearance],
+) -> Result<u64> {
+    ofdb_client(api, client)
+        .with_org_token(org_token.to_string())
+        .clear_places(clearances)
+}
+
+/// Search with all parameters (categories, tags, review status and limit).
+pub fn search_with(
+    api: &str,
+    client: &Client,
+    query: &search::SearchQuery,
+) -> Result<SearchResponse> {
+    ofdb_client(api, client).search_with(query)
+}
+
+/// Read all results of a search, even if the server limits the number of results.
+pub fn search_all(
+    api: &str,
+    client: &Client,
+    query: search::SearchQuery,
+) -> Result<Vec<PlaceSearchResult>> {
+    ofdb_client(api, client).search_all(query).collect()
 }
 
 pub fn search_duplicates(
@@ -97,20 +224,5 @@ pub fn search_duplicates(
     client: &Client,
     new_place: &NewPlace,
 ) -> Result<Option<Vec<PlaceSearchResult>>> {
-    let url = format!("{}/search/duplicates", api);
-    let res = client.post(url).json(&new_place).send()?;
-    let res: Vec<PlaceSearchResult> = handle_response(res)?;
-    Ok(if res.is_empty() { None } else { Some(res) })
-}
-
-fn handle_response<T>(res: Response) -> Result<T>
-where
-    T: for<'de> serde::Deserialize<'de>,
-{
-    if res.status().is_success() {
-        Ok(res.json::<T>()?)
-    } else {
-        let err: Error = res.json()?;
-        Err(anyhow::anyhow!(err.message))
-    }
+    ofdb_client(api, client).search_duplicates(new_place)
 }
@@ -0,0 +1,251 @@
+//! Find broken homepages, image URLs and custom links of existing entries.
+
+use std::{
+    io::Write,
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+use anyhow::Result;
+use ofdb_boundary::Entry;
+use reqwest::blocking::Client;
+use serde::Serialize;
+
+/// Status codes that are often returned if a server does not support `HEAD` requests.
+const HEAD_NOT_SUPPORTED: &[u16] = &[403, 405, 501];
+
+/// A URL of an entry.
+#[derive(Debug, Clone)]
+pub struct Link {
+    pub entry_id: String,
+    pub version: u64,
+    pub title: String,
+    /// `homepage`, `image_url`, `image_link_url` or `custom_link`.
+    pub field: &'static str,
+    pub url: String,
+}
+
+/// A row of the report.
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokenLink {
+    pub entry_id: String,
+    #[serde(skip)]
+    pub version: u64,
+    pub title: String,
+    pub field: &'static str,
+    pub url: String,
+    /// HTTP status code, if the server responded at all.
+    pub status: Option<u16>,
+    pub error: String,
+}
+
+/// All URLs of the entries.
+pub fn links(entries: &[Entry]) -> Vec<Link> {
+    let mut links = vec![];
+    for e in entries {
+        let mut push = |field, url: &str| {
+            if !url.trim().is_empty() {
+                links.push(Link {
+                    entry_id: e.id.clone(),
+                    version: e.version,
+                    title: e.title.clone(),
+                    field,
+                    url: url.trim().to_string(),
+                });
+            }
+        };
+        for (field, url) in [
+            ("homepage", &e.homepage),
+            ("image_url", &e.image_url),
+            ("image_link_url", &e.image_link_url),
+        ] {
+            if let Some(url) = url {
+                push(field, url);
+            }
+        }
+        for link in &e.custom_links {
+            push("custom_link", &link.url);
+        }
+    }
+    links
+}
+
+/// Request all links with up to `concurrency` parallel requests.
+///
+/// `on_checked` is called for each link with `true` if it is OK.
+pub fn check_links(
+    http: &Client,
+    links: Vec<Link>,
+    concurrency: usize,
+    mut on_checked: impl FnMut(bool),
+) -> Vec<BrokenLink> {
+    let queue = Mutex::new(links.into_iter().enumerate());
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|s| {
+        for _ in 0..concurrency.max(1) {
+            let tx = tx.clone();
+            let queue = &queue;
+            s.spawn(move || loop {
+                let Some((nr, link)) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let result = check_url(http, &link.url);
+                if tx.send((nr, link, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+        let mut broken = vec![];
+        for (nr, link, result) in rx {
+            on_checked(result.is_ok());
+            if let Err((status, error)) = result {
+                log::debug!("Broken link {} of '{}': {error}", link.url, link.title);
+                let Link {
+                    entry_id,
+                    version,
+                    title,
+                    field,
+                    url,
+                } = link;
+                broken.push((
+                    nr,
+                    BrokenLink {
+                        entry_id,
+                        version,
+                        title,
+                        field,
+                        url,
+                        status,
+                        error,
+                    },
+                ));
+            }
+        }
+        broken.sort_by_key(|(nr, _)| *nr);
+        broken.into_iter().map(|(_, link)| link).collect()
+    })
+}
+
+fn check_url(http: &Client, url: &str) -> Result<(), (Option<u16>, String)> {
+    let res = match http.head(url).send() {
+        Ok(res) if HEAD_NOT_SUPPORTED.contains(&res.status().as_u16()) => http.get(url).send(),
+        res => res,
+    };
+    match res {
+        Ok(res) if res.status().is_success() => Ok(()),
+        Ok(res) => Err((Some(res.status().as_u16()), res.status().to_string())),
+        Err(err) => Err((None, err.to_string())),
+    }
+}
+
+pub fn write_report<W: Write>(w: W, broken: &[BrokenLink]) -> Result<()> {
+    let mut wtr = csv::WriterBuilder::new().from_writer(w);
+    for link in broken {
+        wtr.serialize(link)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// A row of a CSV file for `update --patch`.
+#[derive(Debug, Default, Serialize)]
+struct PatchRecord<'a> {
+    id: &'a str,
+    version: u64,
+    homepage: &'static str,
+    image_url: &'static str,
+    image_link_url: &'static str,
+}
+
+/// Write patches that remove the broken URLs (custom links are not supported).
+pub fn write_patches<W: Write>(w: W, broken: &[BrokenLink]) -> Result<()> {
+    const DELETE: &str = "--";
+    let mut patches: Vec<PatchRecord> = vec![];
+    for link in broken {
+        let index = match patches.iter().position(|p| p.id == link.entry_id) {
+            Some(index) => index,
+            None => {
+                patches.push(PatchRecord {
+                    id: &link.entry_id,
+                    // A patch has to contain the next version
+                    version: link.version + 1,
+                    ..Default::default()
+                });
+                patches.len() - 1
+            }
+        };
+        let patch = &mut patches[index];
+        match link.field {
+            "homepage" => patch.homepage = DELETE,
+            "image_url" => patch.image_url = DELETE,
+            "image_link_url" => patch.image_link_url = DELETE,
+            _ => {}
+        }
+    }
+    let mut wtr = csv::WriterBuilder::new().from_writer(w);
+    for patch in patches {
+        if patch.homepage.is_empty()
+            && patch.image_url.is_empty()
+            && patch.image_link_url.is_empty()
+        {
+            continue;
+        }
+        wtr.serialize(patch)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_links_of_entries() {
+        let entries = crate::fixtures::generate(10, 1).entries;
+        let links = links(&entries);
+        let homepages = links.iter().filter(|l| l.field == "homepage").count();
+        assert_eq!(homepages, 10);
+        let custom_links = entries.iter().map(|e| e.custom_links.len()).sum::<usize>();
+        assert_eq!(links.len(), homepages + custom_links);
+    }
+
+    #[test]
+    fn report_unreachable_links() {
+        let entries = crate::fixtures::generate(2, 1).entries;
+        let mut links = links(&entries);
+        links.truncate(1);
+        links[0].url = "http://127.0.0.1:9/".to_string();
+        let mut checked = vec![];
+        let broken = check_links(&Client::new(), links, 4, |ok| checked.push(ok));
+        assert_eq!(checked, vec![false]);
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].entry_id, entries[0].id);
+        assert_eq!(broken[0].status, None);
+    }
+
+    #[test]
+    fn write_patches_to_remove_broken_links() {
+        let link = |id: &str, field| BrokenLink {
+            entry_id: id.to_string(),
+            version: 3,
+            title: "Foo".to_string(),
+            field,
+            url: "https://example.org".to_string(),
+            status: Some(404),
+            error: "404 Not Found".to_string(),
+        };
+        let broken = vec![
+            link("a", "homepage"),
+            link("b", "custom_link"),
+            link("a", "image_url"),
+        ];
+        let mut csv = vec![];
+        write_patches(&mut csv, &broken).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "id,version,homepage,image_url,image_link_url\na,4,--,--,\n"
+        );
+    }
+}
@@ -0,0 +1,468 @@
+//! Find the entries of an update file without IDs,
+//! e.g. the updated list of a partner, by their title and address.
+
+use std::{fmt, io::Read, str::FromStr};
+
+use anyhow::{bail, Result};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use ofdb_boundary::Entry;
+use uuid::Uuid;
+
+use crate::{
+    csv::{places_with_filter, write_places},
+    filter::RecordFilter,
+    headers::{self, PLACE_SYNONYMS},
+    import::{CsvImportError, CsvImportResult},
+    matching::is_same_title,
+    search::world_bbox,
+    tags::TagSeparator,
+    OfdbApi,
+};
+
+/// A column that identifies an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchField {
+    Title,
+    Street,
+    Zip,
+    City,
+    Country,
+}
+
+impl MatchField {
+    const ALL: [Self; 5] = [
+        Self::Title,
+        Self::Street,
+        Self::Zip,
+        Self::City,
+        Self::Country,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Street => "street",
+            Self::Zip => "zip",
+            Self::City => "city",
+            Self::Country => "country",
+        }
+    }
+
+    fn value(self, entry: &Entry) -> Option<&str> {
+        match self {
+            Self::Title => Some(&entry.title),
+            Self::Street => entry.street.as_deref(),
+            Self::Zip => entry.zip.as_deref(),
+            Self::City => entry.city.as_deref(),
+            Self::Country => entry.country.as_deref(),
+        }
+    }
+
+    fn matches(self, entry: &Entry, value: &str) -> bool {
+        let normalize = |s: &str| {
+            s.split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase()
+        };
+        match self {
+            Self::Title => is_same_title(&entry.title, value),
+            _ => normalize(self.value(entry).unwrap_or_default()) == normalize(value),
+        }
+    }
+}
+
+/// The columns to find an entry, e.g. `title,city`.
+///
+/// The title is always required because the entries are searched by their title.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchBy(Vec<MatchField>);
+
+impl FromStr for MatchBy {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = vec![];
+        for name in s.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let name = name.to_lowercase();
+            let Some(field) = MatchField::ALL.into_iter().find(|f| f.name() == name) else {
+                let names: Vec<_> = MatchField::ALL.iter().map(|f| f.name()).collect();
+                bail!("Unknown column '{name}' (expected {})", names.join(", "));
+            };
+            if !fields.contains(&field) {
+                fields.push(field);
+            }
+        }
+        if !fields.contains(&MatchField::Title) {
+            bail!("The title is required to find the entries (e.g. 'title,city')");
+        }
+        Ok(Self(fields))
+    }
+}
+
+impl fmt::Display for MatchBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<_> = self.0.iter().map(|field| field.name()).collect();
+        f.write_str(&names.join(","))
+    }
+}
+
+/// Columns that are taken from the entry instead of the file.
+const ENTRY_COLUMNS: [&str; 4] = ["id", "created", "version", "ratings"];
+
+/// Columns that keep the value of the entry if they are empty in the file.
+const REQUIRED_COLUMNS: [&str; 3] = ["lat", "lng", "license"];
+
+/// Read the records of a file without IDs and find their entries.
+///
+/// A record is only applied if exactly one entry has the same title
+/// (see [is_same_title]) and the same values in the other columns of `match_by`.
+/// Records without or with several matching entries are failures of the result.
+///
+/// The columns of the file replace the fields of the entry,
+/// fields without a column keep their current value.
+pub fn match_entries<R: Read>(
+    r: R,
+    api: &impl OfdbApi,
+    match_by: &MatchBy,
+    filter: &RecordFilter,
+    tag_separator: TagSeparator,
+    strict_columns: bool,
+) -> Result<Vec<CsvImportResult<Entry>>> {
+    let mut rdr = headers::reader(r, PLACE_SYNONYMS)?;
+    let headers = rdr.headers()?.clone();
+    if headers.iter().any(|h| h == "id") {
+        bail!("The file has an id column, entries with IDs don't have to be matched");
+    }
+    for field in &match_by.0 {
+        if !headers.iter().any(|h| h == field.name()) {
+            bail!("The column '{}' is missing", field.name());
+        }
+    }
+    if !filter.is_empty() {
+        filter.check_columns(&headers)?;
+    }
+
+    let mut results = vec![];
+    let mut matched: Vec<(usize, Entry, StringRecord)> = vec![];
+    for (record_nr, record) in filter.select(&headers, rdr.records()) {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                let err = CsvImportError::Record(err.to_string());
+                results.push(CsvImportResult::new(record_nr, Err(err)));
+                continue;
+            }
+        };
+        let result = match find_entry(api, match_by, &headers, &record)? {
+            Ok(entry) => match matched.iter().find(|(_, e, _)| e.id == entry.id) {
+                Some((nr, _, _)) => Err(CsvImportError::Validation(format!(
+                    "The entry {} is already updated by record {nr}",
+                    entry.id
+                ))),
+                None => Ok(entry),
+            },
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(entry) => matched.push((record_nr, entry, record)),
+            Err(err) => {
+                log::warn!("Record {record_nr}: {err}");
+                results.push(CsvImportResult::new(record_nr, Err(err)));
+            }
+        }
+    }
+    log::info!("Found the entries of {} records", matched.len());
+
+    let csv = merge_records(&headers, &matched)?;
+    for mut r in places_with_filter(
+        &csv[..],
+        &RecordFilter::default(),
+        tag_separator,
+        strict_columns,
+    )? {
+        r.record_nr = matched[r.record_nr].0;
+        results.push(r);
+    }
+    results.sort_by_key(|r| r.record_nr);
+    Ok(results)
+}
+
+/// The only entry that matches the record.
+fn find_entry(
+    api: &impl OfdbApi,
+    match_by: &MatchBy,
+    headers: &StringRecord,
+    record: &StringRecord,
+) -> Result<Result<Entry, CsvImportError>> {
+    let values: Vec<_> = match_by
+        .0
+        .iter()
+        .map(|field| {
+            let value = headers
+                .iter()
+                .position(|h| h == field.name())
+                .and_then(|i| record.get(i))
+                .unwrap_or_default()
+                .trim();
+            (*field, value)
+        })
+        .collect();
+    let query = values
+        .iter()
+        .map(|(field, value)| format!("{} '{value}'", field.name()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let title = values
+        .iter()
+        .find(|(field, _)| *field == MatchField::Title)
+        .map(|(_, title)| *title)
+        .unwrap_or_default();
+    if title.is_empty() {
+        return Ok(Err(CsvImportError::Validation(
+            "The title is required to find the entry".to_string(),
+        )));
+    }
+    let response = api.search(title, &world_bbox())?;
+    let uuids: Vec<Uuid> = response
+        .visible
+        .iter()
+        .chain(&response.invisible)
+        .filter(|place| is_same_title(&place.title, title))
+        .filter_map(|place| place.id.parse().ok())
+        .collect();
+    let mut entries: Vec<_> = if uuids.is_empty() {
+        vec![]
+    } else {
+        api.read_entries(uuids)?
+            .into_iter()
+            .filter(|entry| {
+                values
+                    .iter()
+                    .all(|(field, value)| field.matches(entry, value))
+            })
+            .collect()
+    };
+    entries.dedup_by(|a, b| a.id == b.id);
+    Ok(match entries.len() {
+        0 => Err(CsvImportError::NoMatch(query)),
+        1 => Ok(entries.remove(0)),
+        _ => Err(CsvImportError::AmbiguousMatch {
+            query,
+            ids: entries.into_iter().map(|e| e.id).collect(),
+        }),
+    })
+}
+
+/// The records in the format of an update file:
+/// the entry (see [write_places]) with the columns of the record.
+fn merge_records(
+    headers: &StringRecord,
+    matched: &[(usize, Entry, StringRecord)],
+) -> Result<Vec<u8>> {
+    let entries: Vec<_> = matched.iter().map(|(_, entry, _)| entry.clone()).collect();
+    let mut csv = vec![];
+    write_places(&mut csv, &entries, None)?;
+    let mut rdr = ReaderBuilder::new().from_reader(&csv[..]);
+    let entry_headers = rdr.headers()?.clone();
+
+    let mut wtr = WriterBuilder::new().from_writer(vec![]);
+    // Columns that are not part of the export, e.g. `tag_0`
+    let extra_columns: Vec<_> = headers
+        .iter()
+        .enumerate()
+        .filter(|(_, h)| !entry_headers.iter().any(|e| e == *h))
+        .collect();
+    wtr.write_record(with_extra_columns(
+        entry_headers.iter().collect(),
+        extra_columns.iter().map(|(_, h)| *h).collect(),
+    ))?;
+    for (entry_record, (_, _, record)) in rdr.records().zip(matched) {
+        let entry_record = entry_record?;
+        let fields = entry_headers
+            .iter()
+            .zip(&entry_record)
+            .map(|(column, current)| {
+                if ENTRY_COLUMNS.contains(&column) {
+                    return current;
+                }
+                match headers
+                    .iter()
+                    .position(|h| h == column)
+                    .and_then(|i| record.get(i))
+                {
+                    Some(value)
+                        if value.trim().is_empty() && REQUIRED_COLUMNS.contains(&column) =>
+                    {
+                        current
+                    }
+                    Some(value) => value,
+                    None => current,
+                }
+            })
+            .collect();
+        let extra = extra_columns
+            .iter()
+            .map(|(i, _)| record.get(*i).unwrap_or_default())
+            .collect();
+        wtr.write_record(with_extra_columns(fields, extra))?;
+    }
+    Ok(wtr.into_inner()?)
+}
+
+/// Insert the extra columns before the ratings,
+/// because the reader expects the ratings in the last column.
+fn with_extra_columns<'a>(mut fields: Vec<&'a str>, extra: Vec<&'a str>) -> Vec<&'a str> {
+    let ratings = fields.pop();
+    fields.extend(extra);
+    fields.extend(ratings);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ofdb_boundary::{
+        EntrySearchRatings, MapBbox, NewPlace, PlaceSearchResult, Review, SearchResponse,
+        UpdatePlace,
+    };
+
+    /// Entries that are found by the words of their title.
+    struct FakeApi(Vec<Entry>);
+
+    impl OfdbApi for FakeApi {
+        fn read_entries(&self, uuids: Vec<Uuid>) -> crate::Result<Vec<Entry>> {
+            Ok(self
+                .0
+                .iter()
+                .filter(|e| uuids.contains(&e.id.parse().unwrap()))
+                .cloned()
+                .collect())
+        }
+        fn search(&self, txt: &str, _: &MapBbox) -> crate::Result<SearchResponse> {
+            let word = txt
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_lowercase();
+            let visible = self
+                .0
+                .iter()
+                .filter(|e| e.title.to_lowercase().contains(&word))
+                .map(|e| PlaceSearchResult {
+                    id: e.id.clone(),
+                    status: None,
+                    lat: e.lat,
+                    lng: e.lng,
+                    title: e.title.clone(),
+                    description: e.description.clone(),
+                    categories: vec![],
+                    tags: e.tags.clone(),
+                    ratings: EntrySearchRatings {
+                        total: 0.0,
+                        diversity: 0.0,
+                        fairness: 0.0,
+                        humanity: 0.0,
+                        renewable: 0.0,
+                        solidarity: 0.0,
+                        transparency: 0.0,
+                    },
+                })
+                .collect();
+            Ok(SearchResponse {
+                visible,
+                invisible: vec![],
+            })
+        }
+        fn create_place(&self, _: &NewPlace) -> crate::Result<String> {
+            Err(crate::not_supported("create_place"))
+        }
+        fn update_place(&self, _: &str, _: &UpdatePlace) -> crate::Result<String> {
+            Err(crate::not_supported("update_place"))
+        }
+        fn review(&self, _: Vec<Uuid>, _: Review) -> crate::Result<()> {
+            Err(crate::not_supported("review"))
+        }
+    }
+
+    fn entries() -> Vec<Entry> {
+        let mut entries = crate::fixtures::generate(3, 1).entries;
+        for (e, (title, city)) in entries.iter_mut().zip([
+            ("Repair Café e.V.", "Bochum"),
+            ("Repair Café", "Essen"),
+            ("Repair Café", "Essen"),
+        ]) {
+            e.title = title.to_string();
+            e.city = Some(city.to_string());
+        }
+        entries
+    }
+
+    #[test]
+    fn parse_match_by() {
+        let match_by: MatchBy = "Title, city".parse().unwrap();
+        assert_eq!(match_by, MatchBy(vec![MatchField::Title, MatchField::City]));
+        assert_eq!(match_by.to_string(), "title,city");
+        assert!("city".parse::<MatchBy>().is_err());
+        assert!("title,phone".parse::<MatchBy>().is_err());
+    }
+
+    #[test]
+    fn update_the_only_matching_entry() {
+        let entries = entries();
+        let api = FakeApi(entries.clone());
+        let csv = "\
+Name,City,Homepage,tag_0
+repair cafe,bochum,https://repair.example.org,reparieren
+Repair Café,Essen,,
+Repair Café,Dortmund,,
+";
+        let match_by = "title,city".parse().unwrap();
+        let results = match_entries(
+            csv.as_bytes(),
+            &api,
+            &match_by,
+            &RecordFilter::default(),
+            TagSeparator::default(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(results.len(), 3);
+
+        let entry = results[0].result.as_ref().unwrap();
+        assert_eq!(entry.id, entries[0].id);
+        assert_eq!(entry.version, entries[0].version);
+        assert_eq!(entry.title, "repair cafe");
+        assert_eq!(
+            entry.homepage.as_deref(),
+            Some("https://repair.example.org")
+        );
+        assert!(entry.tags.contains(&"reparieren".to_string()));
+        // Columns that the file doesn't have are kept
+        assert_eq!(entry.description, entries[0].description);
+        assert_eq!(entry.lat, entries[0].lat);
+        assert_eq!(entry.email, entries[0].email);
+
+        let err = results[1].result.as_ref().unwrap_err().to_string();
+        assert!(err.contains(&entries[1].id) && err.contains(&entries[2].id));
+        assert!(matches!(results[2].result, Err(CsvImportError::NoMatch(_))));
+        assert_eq!(results[2].record_nr, 2);
+    }
+
+    #[test]
+    fn reject_files_with_ids() {
+        let api = FakeApi(vec![]);
+        let match_by = "title".parse().unwrap();
+        let read = |csv: &str| {
+            match_entries(
+                csv.as_bytes(),
+                &api,
+                &match_by,
+                &RecordFilter::default(),
+                TagSeparator::default(),
+                false,
+            )
+        };
+        assert!(read("id,title\n1,A\n").is_err());
+        assert!(read("city\nBochum\n").is_err());
+    }
+}
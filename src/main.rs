@@ -1,21 +1,44 @@
 use std::{
-    env,
+    collections::{BTreeSet, HashMap},
+    env, fmt,
     fs::File,
-    io,
+    io::{self, BufRead, IsTerminal, Read, Write},
     path::{Path, PathBuf},
+    process,
+    rc::Rc,
     str::FromStr,
+    sync::Arc,
 };
 
 use anyhow::{anyhow, bail, Result};
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use email_address_parser::EmailAddress;
-use ofdb_boundary::{Credentials, Entry, NewPlace, UpdatePlace};
-use ofdb_cli::*;
-use reqwest::blocking::Client;
+use ofdb_boundary::{Credentials, Entry, MapBbox, MapPoint, PlaceRevision, UpdatePlace};
+use ofdb_cli::{
+    deadline::{Deadline, DeadlineExceeded, EXIT_CODE_DEADLINE_EXCEEDED},
+    geocoder_usage::{BudgetedGeoCoding, GeocoderUsage},
+    opencage::{GeocodeMatches, OpenCageGeocoder},
+    rate_limit::{RateLimit, RateLimitedGeoCoding},
+    report::ReportFormat,
+    search::world_bbox,
+    *,
+};
+use ofdb_core::gateways::geocode::GeoCodingGateway;
+use ofdb_gateways::opencage::OpenCage;
 use serde::Serialize;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
-use crate::import::*;
+use crate::{
+    cli::{
+        bench, progress,
+        review::review,
+        update::{patch_template, update, UpdateOptions},
+    },
+    import::*,
+};
+
+mod cli;
 
 #[derive(Parser)]
 #[clap(name = "ofdb", about = "CLI for OpenFairDB", author)]
@@ -29,332 +52,3770 @@ struct Cli {
 #[derive(Args)]
 struct Opt {
     #[clap(long = "api-url", help = "The URL of the JSON API")]
-    api: String,
+    api: Option<String>,
+    #[clap(
+        long = "max-duration",
+        help = "Gracefully stop the run after this duration (e.g. '2h' or '30min')"
+    )]
+    max_duration: Option<humantime::Duration>,
+    #[clap(
+        long = "store-credentials",
+        help = "Store the password to login again if the session expires during a run"
+    )]
+    store_credentials: bool,
+    #[clap(
+        long = "rate-limit",
+        help = "Max. number of API requests (e.g. '5/s' or '100/min')"
+    )]
+    rate_limit: Option<RateLimit>,
+    #[clap(
+        long = "geocoder-rate-limit",
+        help = "Max. number of geocoding requests (e.g. '1/s')"
+    )]
+    geocoder_rate_limit: Option<RateLimit>,
+    #[clap(
+        long = "geocode-budget",
+        help = "Max. number of geocoding requests per day (e.g. '2000'), further records are deferred"
+    )]
+    geocode_budget: Option<u64>,
+    #[clap(
+        long = "lang",
+        env = "OFDB_LANG",
+        help = "Language of the messages and reports (en or de)",
+        default_value = "en"
+    )]
+    lang: i18n::Lang,
+    #[clap(
+        long = "org-token",
+        env = "OFDB_ORG_TOKEN",
+        hide_env_values = true,
+        help = "API token of an organization (e.g. to create entries with its owned tags)"
+    )]
+    org_token: Option<String>,
+    #[clap(
+        long = "read-chunk-size",
+        default_value_t = DEFAULT_READ_CHUNK_SIZE,
+        help = "Max. number of entries that are read with one request"
+    )]
+    read_chunk_size: usize,
+    #[clap(
+        long = "email-report",
+        help = "Send the report to this e-mail address (requires the SMTP settings in the config file)"
+    )]
+    email_report: Vec<String>,
+    #[clap(
+        long = "summary-json",
+        help = "Print a summary of the run as one line of JSON to stdout (e.g. for scripts)"
+    )]
+    summary_json: bool,
+    #[clap(
+        long = "yes",
+        help = "Don't ask before archiving, rejecting or deleting (required if stdin is not a terminal)"
+    )]
+    yes: bool,
+    #[clap(
+        long = "timeout",
+        help = "Timeout of an HTTP request (e.g. '90s' or '5min'; default: 30s, exports are only limited if the timeout is given)"
+    )]
+    timeout: Option<humantime::Duration>,
+    #[clap(
+        long = "proxy",
+        help = "Send all HTTP requests via this proxy (e.g. 'http://proxy.example.org:3128')"
+    )]
+    proxy: Option<String>,
+    #[clap(
+        long = "ca-cert",
+        help = "PEM file with additional root certificates (e.g. of a proxy that intercepts TLS)"
+    )]
+    ca_cert: Option<PathBuf>,
+    #[clap(
+        long = "trace-http",
+        help = "Log method, URL, status and duration of each API request"
+    )]
+    trace_http: bool,
+    #[clap(
+        long = "trace-bodies",
+        help = "Log the API requests with their bodies (secrets and contact data are redacted)"
+    )]
+    trace_bodies: bool,
+    #[clap(
+        long = "record",
+        conflicts_with = "replay",
+        help = "Record the API requests and responses to this JSON file"
+    )]
+    record: Option<PathBuf>,
+    #[clap(
+        long = "replay",
+        help = "Respond to the API requests with the responses of a recording instead of sending them"
+    )]
+    replay: Option<PathBuf>,
+    #[clap(skip)]
+    recording: Option<Arc<replay::Recording>>,
+    #[clap(skip)]
+    replayed: Option<Arc<replay::Replay>>,
+}
+
+impl Opt {
+    fn load_fixtures(&mut self) -> Result<()> {
+        if let Some(path) = &self.record {
+            self.recording = Some(Arc::new(replay::Recording::new(path.clone())));
+        }
+        if let Some(path) = &self.replay {
+            self.replayed = Some(Arc::new(replay::Replay::load(path)?));
+        }
+        Ok(())
+    }
+
+    fn api(&self) -> Result<&str> {
+        self.api
+            .as_deref()
+            .ok_or_else(|| anyhow!("The URL of the JSON API is required (--api-url)"))
+    }
+
+    fn client(&self) -> Result<OfdbClient> {
+        self.client_for(self.api()?)
+    }
+
+    fn client_for(&self, api: &str) -> Result<OfdbClient> {
+        let client = OfdbClient::with_http_options(api, &self.http())?;
+        Ok(self.configured(client))
+    }
+
+    /// A client that uses the cookies of a login session.
+    fn session_client(&self, session: &session::Session) -> Result<OfdbClient> {
+        Ok(self.configured(session.client(&self.http())?))
+    }
+
+    fn configured(&self, client: OfdbClient) -> OfdbClient {
+        let mut client = client.with_read_chunk_size(self.read_chunk_size);
+        if let Some(token) = &self.org_token {
+            client = client.with_org_token(token.clone());
+        }
+        if let Some(recording) = &self.recording {
+            client = client.with_recording(Arc::clone(recording));
+        }
+        if let Some(replay) = &self.replayed {
+            client = client.with_replay(Arc::clone(replay));
+        }
+        if self.trace_http || self.trace_bodies {
+            client = client.with_http_trace(trace::HttpTrace {
+                bodies: self.trace_bodies,
+            });
+        }
+        match self.rate_limit {
+            Some(limit) => client.with_rate_limit(limit),
+            None => client,
+        }
+    }
+
+    fn http(&self) -> http::HttpOptions {
+        http::HttpOptions {
+            timeout: self.timeout.map(Into::into),
+            stream_timeout: self.timeout.map(Into::into),
+            proxy: self.proxy.clone(),
+            ca_cert: self.ca_cert.clone(),
+        }
+    }
+
+    fn http_client(&self) -> Result<reqwest::blocking::Client> {
+        http_client(&self.http())
+    }
+
+    fn geo_coding(
+        &self,
+        opencage_api_key: Option<String>,
+        usage: &Arc<GeocoderUsage>,
+        matches: &Arc<GeocodeMatches>,
+    ) -> Result<Box<dyn GeoCodingGateway>> {
+        let Some(key) = opencage_api_key else {
+            log::warn!("No OpenCage API provided");
+            return Ok(self.limited_geo_coding(OpenCage::new(None), usage));
+        };
+        let geo_coding = OpenCageGeocoder::new(key, self.http_client()?, Arc::clone(matches));
+        Ok(self.limited_geo_coding(geo_coding, usage))
+    }
+
+    fn limited_geo_coding<G: GeoCodingGateway + 'static>(
+        &self,
+        geo_coding: G,
+        usage: &Arc<GeocoderUsage>,
+    ) -> Box<dyn GeoCodingGateway> {
+        let usage = Arc::clone(usage);
+        match self.geocoder_rate_limit {
+            Some(limit) => Box::new(BudgetedGeoCoding::new(
+                RateLimitedGeoCoding::new(geo_coding, limit),
+                usage,
+            )),
+            None => Box::new(BudgetedGeoCoding::new(geo_coding, usage)),
+        }
+    }
+
+    fn geocoder_usage(&self) -> Result<Arc<GeocoderUsage>> {
+        Ok(Arc::new(GeocoderUsage::open(self.geocode_budget)?))
+    }
 }
 
 #[derive(Subcommand)]
 enum SubCommand {
-    #[clap(about = "Import new entries")]
-    Import {
-        #[clap(help = "JSON or CSV file with entries")]
+    #[clap(about = "Import new entries", visible_alias = "imp")]
+    Import(cli::import::ImportArgs),
+    #[clap(about = "Check a CSV file of new entries without network access")]
+    Validate {
+        #[clap(help = "CSV file with entries")]
         file: PathBuf,
+        #[clap(flatten)]
+        csv: CsvArgs,
+        #[clap(flatten)]
+        filter: FilterArgs,
         #[clap(
             long = "report-file",
-            help = "File with the import report",
-            default_value = "import-report.json"
+            help = "File with the validation report",
+            default_value = "validation-report.json"
         )]
         report_file: PathBuf,
+        #[clap(
+            long = "report-format",
+            help = "Format of the report (json, html or csv)",
+            default_value = "json"
+        )]
+        report_format: ReportFormat,
+    },
+    #[clap(about = "Show the records of a CSV file on a map before the import")]
+    Preview {
+        #[clap(help = "CSV file with entries")]
+        file: PathBuf,
+        #[clap(
+            long = "out",
+            help = "HTML file with the map",
+            default_value = "preview.html"
+        )]
+        out: PathBuf,
+        #[clap(long = "opencage-api-key", help = "OpenCage API key")]
+        opencage_api_key: Option<String>,
+        #[clap(
+            long = "no-duplicate-check",
+            help = "Don't search for possible duplicates on the server"
+        )]
+        no_duplicate_check: bool,
+        #[clap(flatten)]
+        csv: CsvArgs,
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    #[clap(about = "Show how the first records of a CSV file are read, without network access")]
+    Inspect {
+        #[clap(help = "CSV file with entries")]
+        file: PathBuf,
+        #[clap(
+            long = "limit",
+            visible_alias = "rows",
+            help = "Number of records to show",
+            default_value = "5",
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        limit: u64,
+        #[clap(flatten)]
+        csv: CsvArgs,
+    },
+    #[clap(
+        about = "Check that no values are lost by reading, writing and reading a CSV file again"
+    )]
+    Roundtrip {
+        #[clap(help = "CSV file with new places or entries")]
+        file: PathBuf,
+        #[clap(flatten)]
+        csv: CsvArgs,
+    },
+    #[clap(about = "Fill the coordinates of a CSV file with addresses")]
+    Geocode {
+        #[clap(help = "CSV file with the columns street, zip, city, country and state")]
+        file: PathBuf,
+        #[clap(long = "out", help = "CSV file with the coordinates")]
+        out: PathBuf,
         #[clap(long = "opencage-api-key", help = "OpenCage API key")]
         opencage_api_key: Option<String>,
-
         #[clap(
-            long = "ignore-duplicates",
-            help = "create a new entry, even if it becomes a duplicate"
+            long = "normalize-addresses",
+            help = "Clean up the addresses before geocoding with the rules of a locale (de or en)"
         )]
-        ignore_duplicates: bool,
+        normalize_addresses: Option<address::AddressLocale>,
     },
     #[clap(about = "Read entry")]
     Read {
         #[clap(required = true, num_args = 1.., help = "UUID")]
         uuids: Vec<Uuid>,
     },
-    #[clap(about = "Update entries")]
+    #[clap(about = "Export entries as CSV")]
+    Export {
+        #[clap(
+            long = "bbox",
+            help = "Bounding box (sw_lat,sw_lng,ne_lat,ne_lng), default is the whole world",
+            value_parser = parse_bbox
+        )]
+        bbox: Option<MapBbox>,
+        #[clap(long = "text", help = "Search text", default_value = "")]
+        text: String,
+        #[clap(
+            long = "out",
+            help = "File with the entries (default: 'entries.csv', 'entries.md', ...)"
+        )]
+        out: Option<PathBuf>,
+        #[clap(
+            long = "format",
+            help = "Format of the file (csv, md, kml, gpx or parquet)",
+            default_value = "csv"
+        )]
+        format: ExportFormat,
+        #[clap(
+            long = "markdown-style",
+            help = "Markdown as table or list",
+            default_value = "table"
+        )]
+        markdown_style: markdown::Style,
+        #[clap(
+            long = "group-by",
+            help = "Group the entries of the Markdown file by tag or city"
+        )]
+        group_by: Option<markdown::GroupBy>,
+        #[clap(
+            long = "with-computed-columns",
+            help = "Add the columns days_since_created, days_since_last_update and review_status"
+        )]
+        with_computed_columns: bool,
+        #[clap(
+            long = "server-export",
+            requires = "email",
+            help = "Read the entries from the export endpoint of the server (requires a login as scout or admin)"
+        )]
+        server_export: bool,
+        #[clap(
+            long = "email",
+            env = "OFDB_EMAIL",
+            help = "E-Mail address to read the history of entries (days_since_created)"
+        )]
+        email: Option<String>,
+        #[clap(
+            long = "password",
+            env = "OFDB_PASSWORD",
+            hide_env_values = true,
+            requires = "email",
+            help = "Password (prompted if omitted)"
+        )]
+        password: Option<String>,
+    },
+    #[clap(about = "Update entries", visible_alias = "up")]
     Update {
-        #[clap(help = "JSON or CSV file with entries")]
-        file: PathBuf,
+        #[clap(
+            required = true,
+            num_args = 1..,
+            help = "JSON or CSV files with entries (glob patterns like 'data/*.csv' are supported)"
+        )]
+        files: Vec<PathBuf>,
         #[clap(
             long = "report-file",
             help = "File with the update report",
             default_value = "update-report.json"
         )]
         report_file: PathBuf,
+        #[clap(
+            long = "report-format",
+            help = "Format of the report (json, html or csv)",
+            default_value = "json"
+        )]
+        report_format: ReportFormat,
         #[clap(
             long = "patch",
-            help = "use (non-standard) diff syntax to update fields"
+            help = "use (non-standard) diff syntax (CSV) or JSON merge patches to update fields"
         )]
         patch: bool,
+        #[clap(
+            long = "validate-webhook",
+            help = "URL of an external service to validate each record"
+        )]
+        validate_webhook: Option<String>,
+        #[clap(
+            long = "links-file",
+            help = "CSV file with custom links (entry_id,url,title,description) that replace the links of the updated entries"
+        )]
+        links_file: Option<PathBuf>,
+        #[clap(
+            long = "ignore-unknown-ids",
+            help = "Update the other entries if the file contains IDs that don't exist on this instance"
+        )]
+        ignore_unknown_ids: bool,
+        #[clap(
+            long = "protect",
+            help = "Reject records that modify one of these fields (e.g. 'title,license,founded_on')"
+        )]
+        protect: Option<diff::ProtectedFields>,
+        #[clap(
+            long = "match-by",
+            conflicts_with = "patch",
+            help = "Find the entries of a CSV file without IDs by these columns (e.g. 'title,city')"
+        )]
+        match_by: Option<lookup::MatchBy>,
+        #[clap(
+            long = "no-remerge",
+            help = "Don't re-apply the changes to the current version of entries that were edited in the meantime"
+        )]
+        no_remerge: bool,
+        #[clap(
+            long = "state-file",
+            help = "File with the processed records to resume an interrupted run (e.g. 'run.state.json')"
+        )]
+        state_file: Option<PathBuf>,
+        #[clap(flatten)]
+        filter: FilterArgs,
+        #[clap(flatten)]
+        patch_args: PatchArgs,
     },
-    #[clap(about = "Review entries")]
-    Review {
-        #[clap(long = "email", required = true, help = "E-Mail address")]
+    #[clap(about = "Update entries with patches (JSON merge patches or the CSV patch syntax)")]
+    Patch {
+        #[clap(
+            required_unless_present = "template",
+            conflicts_with = "template",
+            num_args = 1..,
+            help = "JSON or CSV files with patches (glob patterns like 'data/*.json' are supported)"
+        )]
+        files: Vec<PathBuf>,
+        #[clap(
+            long = "template",
+            requires = "ids_file",
+            help = "CSV file with a single patch (without id and version) that is applied to all entries of the IDs file"
+        )]
+        template: Option<PathBuf>,
+        #[clap(
+            long = "ids-file",
+            requires = "template",
+            help = "File with the IDs of the entries (one per line)"
+        )]
+        ids_file: Option<PathBuf>,
+        #[clap(
+            long = "report-file",
+            help = "File with the update report",
+            default_value = "update-report.json"
+        )]
+        report_file: PathBuf,
+        #[clap(
+            long = "report-format",
+            help = "Format of the report (json, html or csv)",
+            default_value = "json"
+        )]
+        report_format: ReportFormat,
+        #[clap(
+            long = "validate-webhook",
+            help = "URL of an external service to validate each record"
+        )]
+        validate_webhook: Option<String>,
+        #[clap(
+            long = "ignore-unknown-ids",
+            help = "Update the other entries if the file contains IDs that don't exist on this instance"
+        )]
+        ignore_unknown_ids: bool,
+        #[clap(
+            long = "protect",
+            help = "Reject records that modify one of these fields (e.g. 'title,license,founded_on')"
+        )]
+        protect: Option<diff::ProtectedFields>,
+        #[clap(
+            long = "no-remerge",
+            help = "Don't re-apply the changes to the current version of entries that were edited in the meantime"
+        )]
+        no_remerge: bool,
+        #[clap(
+            long = "state-file",
+            help = "File with the processed records to resume an interrupted run (e.g. 'run.state.json')"
+        )]
+        state_file: Option<PathBuf>,
+        #[clap(flatten)]
+        filter: FilterArgs,
+        #[clap(flatten)]
+        patch_args: PatchArgs,
+    },
+    #[clap(about = "Find broken homepages, image URLs and custom links of existing entries")]
+    CheckLinks {
+        #[clap(
+            long = "bbox",
+            help = "Bounding box (sw_lat,sw_lng,ne_lat,ne_lng), default is the whole world",
+            value_parser = parse_bbox
+        )]
+        bbox: Option<MapBbox>,
+        #[clap(long = "tag", help = "Only check entries with this tag")]
+        tags: Vec<String>,
+        #[clap(long = "text", help = "Search text", default_value = "")]
+        text: String,
+        #[clap(
+            long = "concurrency",
+            help = "Max. number of parallel requests",
+            default_value = "8"
+        )]
+        concurrency: usize,
+        #[clap(
+            long = "timeout",
+            help = "Timeout of a single request",
+            default_value = "10s"
+        )]
+        timeout: humantime::Duration,
+        #[clap(
+            long = "out",
+            help = "CSV file with the broken links",
+            default_value = "broken-links.csv"
+        )]
+        out: PathBuf,
+        #[clap(
+            long = "patch-file",
+            help = "Write a CSV file for 'update --patch' that removes the broken homepages and image URLs"
+        )]
+        patch_file: Option<PathBuf>,
+    },
+    #[clap(about = "Find suspicious entries (e.g. spam) and write a CSV file for 'review'")]
+    Audit {
+        #[clap(
+            long = "bbox",
+            help = "Bounding box (sw_lat,sw_lng,ne_lat,ne_lng), default is the whole world",
+            value_parser = parse_bbox
+        )]
+        bbox: Option<MapBbox>,
+        #[clap(long = "tag", help = "Only check entries with this tag")]
+        tags: Vec<String>,
+        #[clap(long = "text", help = "Search text", default_value = "")]
+        text: String,
+        #[clap(
+            long = "min-score",
+            help = "Only report entries with at least this score",
+            default_value = "1"
+        )]
+        min_score: u32,
+        #[clap(
+            long = "out",
+            help = "CSV file with the suspicious entries",
+            default_value = "audit.csv"
+        )]
+        out: PathBuf,
+    },
+    #[clap(about = "Show statistics of the entries in a region")]
+    Stats {
+        #[clap(
+            long = "bbox",
+            help = "Bounding box (sw_lat,sw_lng,ne_lat,ne_lng), default is the whole world",
+            value_parser = parse_bbox
+        )]
+        bbox: Option<MapBbox>,
+        #[clap(long = "tag", help = "Only count entries with this tag")]
+        tags: Vec<String>,
+        #[clap(long = "text", help = "Search text", default_value = "")]
+        text: String,
+        #[clap(
+            long = "top",
+            help = "Number of the most frequent tags, cities and categories to show",
+            default_value = "20"
+        )]
+        top: usize,
+        #[clap(long = "json", help = "Print the statistics as JSON")]
+        json: bool,
+    },
+    #[clap(about = "Find the entries that contain an e-mail address or a phone number")]
+    FindContact {
+        #[clap(help = "E-mail address or phone number")]
+        contact: contact::Contact,
+        #[clap(
+            long = "bbox",
+            help = "Bounding box (sw_lat,sw_lng,ne_lat,ne_lng), default is the whole world",
+            value_parser = parse_bbox
+        )]
+        bbox: Option<MapBbox>,
+        #[clap(long = "tag", help = "Only check entries with this tag")]
+        tags: Vec<String>,
+        #[clap(long = "text", help = "Search text", default_value = "")]
+        text: String,
+        #[clap(
+            long = "file",
+            help = "Check the entries of an exported JSON or CSV file instead of searching them",
+            conflicts_with_all = ["bbox", "tags", "text"]
+        )]
+        file: Option<PathBuf>,
+        #[clap(long = "json", help = "Print the entries as JSON")]
+        json: bool,
+    },
+    #[clap(about = "Find entries that are probably duplicates and suggest which ones to merge")]
+    Dedupe {
+        #[clap(
+            long = "bbox",
+            help = "Bounding box (sw_lat,sw_lng,ne_lat,ne_lng), default is the whole world",
+            value_parser = parse_bbox
+        )]
+        bbox: Option<MapBbox>,
+        #[clap(long = "tag", help = "Only check entries with this tag")]
+        tags: Vec<String>,
+        #[clap(long = "text", help = "Search text", default_value = "")]
+        text: String,
+        #[clap(
+            long = "min-similarity",
+            help = "Min. similarity of the titles (0.0 - 1.0)",
+            default_value = "0.8"
+        )]
+        min_similarity: f64,
+        #[clap(
+            long = "max-distance",
+            help = "Max. distance of the entries in meters",
+            default_value = "100"
+        )]
+        max_distance: f64,
+        #[clap(
+            long = "out",
+            help = "CSV file with the merge suggestions",
+            default_value = "duplicates.csv"
+        )]
+        out: PathBuf,
+    },
+    #[clap(about = "Merge a duplicate into another entry and archive the duplicate")]
+    Merge {
+        #[clap(
+            long = "email",
+            env = "OFDB_EMAIL",
+            help = "E-Mail address (default: the logged in user)"
+        )]
+        email: Option<String>,
+        #[clap(
+            long = "password",
+            env = "OFDB_PASSWORD",
+            hide_env_values = true,
+            help = "Password (prompted if omitted and no credentials are stored)"
+        )]
+        password: Option<String>,
+        #[clap(
+            long = "concat-descriptions",
+            help = "Append the description of the duplicate instead of keeping the longer one"
+        )]
+        concat_descriptions: bool,
+        #[clap(
+            long = "dry-run",
+            help = "Only show the changes without updating or archiving an entry"
+        )]
+        dry_run: bool,
+        #[clap(help = "UUID of the entry to keep")]
+        keep: Uuid,
+        #[clap(help = "UUID of the duplicate")]
+        duplicate: Uuid,
+    },
+    #[clap(
+        about = "Propose values for empty fields from Wikidata as a CSV file for 'update --patch'"
+    )]
+    Enrich {
+        #[clap(long = "ids-file", help = "File with one entry ID per line")]
+        ids_file: PathBuf,
+        #[clap(
+            long = "source",
+            help = "Source of the values ('wikidata')",
+            default_value = "wikidata"
+        )]
+        source: enrich::Source,
+        #[clap(
+            long = "language",
+            help = "Language of the titles",
+            default_value = "de"
+        )]
+        language: String,
+        #[clap(
+            long = "min-similarity",
+            help = "Min. similarity of the titles (0.0 - 1.0)",
+            default_value = "0.8"
+        )]
+        min_similarity: f64,
+        #[clap(
+            long = "max-distance",
+            help = "Max. distance to the entry in meters",
+            default_value = "500"
+        )]
+        max_distance: f64,
+        #[clap(
+            long = "out",
+            help = "CSV file with the proposed patches",
+            default_value = "enrich-patch.csv"
+        )]
+        out: PathBuf,
+    },
+    #[clap(about = "Compare entries of a file with the current state on the server")]
+    Diff {
+        #[clap(help = "JSON or CSV file with entries")]
+        file: PathBuf,
+        #[clap(long = "json", help = "Print the differences as JSON")]
+        json: bool,
+    },
+    #[clap(about = "Login and keep the session for the following commands")]
+    Login {
+        #[clap(long = "email", env = "OFDB_EMAIL", help = "E-Mail address")]
         email: String,
-        #[clap(long = "password", required = true, help = "Password")]
-        password: String,
+        #[clap(
+            long = "password",
+            env = "OFDB_PASSWORD",
+            hide_env_values = true,
+            help = "Password (prompted if omitted and no credentials are stored)"
+        )]
+        password: Option<String>,
+    },
+    #[clap(about = "Logout and delete the stored session")]
+    Logout {
+        #[clap(
+            long = "email",
+            env = "OFDB_EMAIL",
+            help = "E-Mail address (default: the logged in user)"
+        )]
+        email: Option<String>,
+    },
+    #[clap(about = "Review entries", visible_alias = "rev")]
+    Review {
+        #[clap(
+            long = "email",
+            env = "OFDB_EMAIL",
+            help = "E-Mail address (default: the logged in user)"
+        )]
+        email: Option<String>,
+        #[clap(
+            long = "password",
+            env = "OFDB_PASSWORD",
+            hide_env_values = true,
+            help = "Password (prompted if omitted and no credentials are stored)"
+        )]
+        password: Option<String>,
         #[clap(required = true, help = "CSV file")]
         file: PathBuf,
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    #[clap(about = "Show the history of an entry")]
+    History {
+        #[clap(
+            long = "email",
+            env = "OFDB_EMAIL",
+            help = "E-Mail address (default: the logged in user)"
+        )]
+        email: Option<String>,
+        #[clap(
+            long = "password",
+            env = "OFDB_PASSWORD",
+            hide_env_values = true,
+            help = "Password (prompted if omitted and no credentials are stored)"
+        )]
+        password: Option<String>,
+        #[clap(long = "json", help = "Print the raw history as JSON")]
+        json: bool,
+        #[clap(required = true, help = "UUID")]
+        uuid: Uuid,
+    },
+    #[clap(about = "Mirror entries from one instance to another")]
+    Mirror {
+        #[clap(long = "source-api", help = "The URL of the JSON API to read from")]
+        source_api: String,
+        #[clap(long = "target-api", help = "The URL of the JSON API to write to")]
+        target_api: String,
+        #[clap(long = "tag", help = "Mirror all entries with this tag")]
+        tag: String,
+        #[clap(
+            long = "id-mapping",
+            help = "File that maps source IDs to target IDs",
+            default_value = "id-mapping.json"
+        )]
+        id_mapping: PathBuf,
+        #[clap(
+            long = "report-file",
+            help = "File with the mirror report",
+            default_value = "mirror-report.json"
+        )]
+        report_file: PathBuf,
+    },
+    #[clap(about = "Generate shell completions")]
+    Completions {
+        #[clap(help = "Shell (bash, zsh, fish, powershell or elvish)")]
+        shell: clap_complete::Shell,
+    },
+    #[clap(about = "Check the connectivity and configuration")]
+    Doctor {
+        #[clap(long = "opencage-api-key", help = "OpenCage API key")]
+        opencage_api_key: Option<String>,
+        #[clap(long = "email", env = "OFDB_EMAIL", help = "E-Mail address")]
+        email: Option<String>,
+        #[clap(
+            long = "password",
+            env = "OFDB_PASSWORD",
+            hide_env_values = true,
+            requires = "email",
+            help = "Password (prompted if omitted)"
+        )]
+        password: Option<String>,
+    },
+    #[clap(about = "Manage ratings")]
+    Ratings {
+        #[clap(subcommand)]
+        cmd: RatingsCommand,
+    },
+    #[clap(about = "Manage custom links")]
+    Links {
+        #[clap(subcommand)]
+        cmd: LinksCommand,
+    },
+    #[clap(about = "Generate test data")]
+    Fixtures {
+        #[clap(subcommand)]
+        cmd: FixturesCommand,
+    },
+    #[clap(about = "Generate fake places in the format of the import")]
+    Generate {
+        #[clap(long = "count", help = "Number of places", default_value = "50")]
+        count: usize,
+        #[clap(
+            long = "bbox",
+            help = "Bounding box (sw_lat,sw_lng,ne_lat,ne_lng) of the places",
+            value_parser = parse_bbox
+        )]
+        bbox: Option<MapBbox>,
+        #[clap(
+            long = "tags",
+            help = "Comma separated tags that are added to all places",
+            value_delimiter = ','
+        )]
+        tags: Vec<String>,
+        #[clap(
+            long = "seed",
+            help = "The same seed always generates the same places",
+            default_value = "0"
+        )]
+        seed: u64,
+        #[clap(long = "out", help = "Output file (CSV or JSON)")]
+        out: PathBuf,
+    },
+    #[clap(about = "Measure the latency of create, read and search requests")]
+    Bench {
+        #[clap(
+            long = "email",
+            env = "OFDB_EMAIL",
+            help = "E-Mail address of a user that can archive the created entries"
+        )]
+        email: Option<String>,
+        #[clap(
+            long = "password",
+            env = "OFDB_PASSWORD",
+            hide_env_values = true,
+            help = "Password (prompted if omitted and no credentials are stored)",
+            requires = "email"
+        )]
+        password: Option<String>,
+        #[clap(
+            long = "requests",
+            help = "Number of requests per operation",
+            default_value = "100"
+        )]
+        requests: usize,
+        #[clap(
+            long = "concurrency",
+            help = "Max. number of parallel requests",
+            default_value = "8"
+        )]
+        concurrency: usize,
+        #[clap(
+            long = "seed",
+            help = "Seed of the generated places (default: a different seed for each run)"
+        )]
+        seed: Option<u64>,
+        #[clap(long = "json", help = "Print the latencies as JSON")]
+        json: bool,
+    },
+    #[clap(about = "Run a mock of the OpenFairDB API for workshops, demos and tests")]
+    MockServer {
+        #[clap(
+            long = "host",
+            help = "Address to listen on",
+            default_value = "127.0.0.1"
+        )]
+        host: String,
+        #[clap(long = "port", help = "Port to listen on", default_value = "8080")]
+        port: u16,
+        #[clap(
+            long = "seed",
+            help = "JSON file with entries (e.g. of 'fixtures generate')"
+        )]
+        seed: Option<PathBuf>,
+    },
+    #[clap(about = "Count the entries and tags of the instance")]
+    Count {
+        #[clap(long = "json", help = "Print the counts as JSON")]
+        json: bool,
+    },
+    #[clap(about = "Show the tags that are in use")]
+    Tags {
+        #[clap(subcommand)]
+        cmd: TagsCommand,
+    },
+    #[clap(about = "Manage user accounts")]
+    User {
+        #[clap(subcommand)]
+        cmd: UserCommand,
+    },
+    #[clap(about = "Review pending changes of entries with tags of an organization")]
+    Clearance {
+        #[clap(subcommand)]
+        cmd: ClearanceCommand,
+    },
+    #[clap(about = "Work with the reports of previous runs")]
+    Report {
+        #[clap(subcommand)]
+        cmd: ReportCommand,
     },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum FileType {
-    Json,
-    Csv,
+#[derive(Subcommand)]
+enum TagsCommand {
+    #[clap(about = "List the most popular tags with the number of entries")]
+    List {
+        #[clap(long = "min-count", help = "Min. number of entries with the tag")]
+        min_count: Option<u64>,
+        #[clap(long = "max-count", help = "Max. number of entries with the tag")]
+        max_count: Option<u64>,
+        #[clap(long = "contains", help = "Only show tags that contain this text")]
+        contains: Option<String>,
+        #[clap(long = "offset", help = "Skip the first tags")]
+        offset: Option<u64>,
+        #[clap(long = "limit", help = "Max. number of tags")]
+        limit: Option<u64>,
+        #[clap(long = "json", help = "Print the tags as JSON", conflicts_with = "csv")]
+        json: bool,
+        #[clap(long = "csv", help = "Print the tags as CSV")]
+        csv: bool,
+    },
 }
 
-impl FromStr for FileType {
-    type Err = anyhow::Error;
-    fn from_str(t: &str) -> Result<Self, Self::Err> {
-        match &*t.to_lowercase() {
-            "json" => Ok(Self::Json),
-            "csv" => Ok(Self::Csv),
-            _ => Err(anyhow::anyhow!("Unsupported file type")),
+#[derive(Subcommand)]
+enum UserCommand {
+    #[clap(about = "Register new users (they have to confirm their e-mail address)")]
+    Register {
+        #[clap(
+            long = "email",
+            required_unless_present = "file",
+            help = "E-Mail address of the new user"
+        )]
+        email: Option<String>,
+        #[clap(
+            long = "password",
+            requires = "email",
+            help = "Password of the new user (prompted if omitted)"
+        )]
+        password: Option<String>,
+        #[clap(
+            long = "file",
+            conflicts_with = "email",
+            help = "CSV file with the columns 'email' and 'password'"
+        )]
+        file: Option<PathBuf>,
+    },
+    #[clap(about = "Delete user accounts (requires a login as admin)")]
+    Delete {
+        #[clap(
+            long = "email",
+            env = "OFDB_EMAIL",
+            help = "E-Mail address (default: the logged in user)"
+        )]
+        email: Option<String>,
+        #[clap(
+            long = "password",
+            env = "OFDB_PASSWORD",
+            hide_env_values = true,
+            help = "Password (prompted if omitted and no credentials are stored)"
+        )]
+        password: Option<String>,
+        #[clap(required = true, num_args = 1.., help = "E-Mail addresses of the users")]
+        users: Vec<String>,
+    },
+    #[clap(
+        about = "Request an e-mail to reset the password of users",
+        long_about = "Request an e-mail to reset the password of users.\n\n\
+            Resetting the password with the link of the e-mail also confirms the address, \
+            so this helps users that lost the confirmation e-mail."
+    )]
+    ResetPassword {
+        #[clap(required = true, num_args = 1.., help = "E-Mail addresses of the users")]
+        users: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ClearanceCommand {
+    #[clap(about = "List the entries with changes that are waiting for a clearance")]
+    List {
+        #[clap(long = "offset", help = "Skip the first entries")]
+        offset: Option<u64>,
+        #[clap(long = "limit", help = "Max. number of entries")]
+        limit: Option<u64>,
+        #[clap(long = "json", help = "Print the entries as JSON")]
+        json: bool,
+    },
+    #[clap(about = "Accept the changes of entries")]
+    Resolve {
+        #[clap(
+            long = "until",
+            help = "Accept the changes up to this revision (default: the current revision of each entry)"
+        )]
+        until: Option<u64>,
+        #[clap(required = true, num_args = 1.., help = "UUID")]
+        uuids: Vec<Uuid>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReportCommand {
+    #[clap(about = "Print the JSON schema of the import, update and ratings reports")]
+    Schema {
+        #[clap(
+            long = "several-files",
+            help = "Schema of a report of several input files"
+        )]
+        several_files: bool,
+    },
+    #[clap(about = "Print the totals of one or more JSON reports")]
+    Summarize {
+        #[clap(
+            required = true,
+            num_args = 1..,
+            help = "JSON reports (glob patterns like 'reports/*.json' are supported)"
+        )]
+        files: Vec<PathBuf>,
+        #[clap(long = "json", help = "Print the totals as JSON")]
+        json: bool,
+    },
+    #[clap(about = "Combine the JSON reports of several runs into a single report")]
+    Merge {
+        #[clap(
+            required = true,
+            num_args = 1..,
+            help = "JSON reports (glob patterns like 'reports/*.json' are supported)"
+        )]
+        files: Vec<PathBuf>,
+        #[clap(
+            long = "report-file",
+            help = "File with the merged report",
+            default_value = "merged-report.json"
+        )]
+        report_file: PathBuf,
+        #[clap(
+            long = "report-format",
+            help = "Format of the merged report (json, html or csv)",
+            default_value = "json"
+        )]
+        report_format: ReportFormat,
+    },
+    #[clap(
+        about = "Copy the failed records of the source file into a CSV file to fix and import them again"
+    )]
+    FailuresToCsv {
+        #[clap(help = "JSON report")]
+        report: PathBuf,
+        #[clap(
+            long = "source",
+            help = "CSV file that was imported or updated (without it the failed new places of the report are written)"
+        )]
+        source: Option<PathBuf>,
+        #[clap(
+            long = "file",
+            help = "Section of a report of several files (defaults to the source file)"
+        )]
+        file: Option<String>,
+        #[clap(
+            long = "out",
+            help = "CSV file with the failed records",
+            default_value = "failed-records.csv"
+        )]
+        out: PathBuf,
+        #[clap(
+            long = "with-duplicates",
+            help = "Also copy the records with possible duplicates"
+        )]
+        with_duplicates: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum LinksCommand {
+    #[clap(about = "Export custom links of entries (one row per link)")]
+    Export {
+        #[clap(required = true, num_args = 1.., help = "UUID")]
+        uuids: Vec<Uuid>,
+        #[clap(
+            long = "out",
+            help = "CSV file with the links",
+            default_value = "custom-links.csv"
+        )]
+        out: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum FixturesCommand {
+    #[clap(about = "Generate fake entries as JSON and CSV files")]
+    Generate {
+        #[clap(long = "entries", help = "Number of entries", default_value = "50")]
+        entries: usize,
+        #[clap(
+            long = "seed",
+            help = "The same seed always generates the same entries",
+            default_value = "0"
+        )]
+        seed: u64,
+        #[clap(
+            long = "bbox",
+            help = "Bounding box (sw_lat,sw_lng,ne_lat,ne_lng) of the entries",
+            value_parser = parse_bbox
+        )]
+        bbox: Option<MapBbox>,
+        #[clap(
+            long = "tags",
+            help = "Comma separated tags that are added to all entries",
+            value_delimiter = ','
+        )]
+        tags: Vec<String>,
+        #[clap(long = "out", help = "Output directory", default_value = "fixtures")]
+        out: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum RatingsCommand {
+    #[clap(about = "Import new ratings")]
+    Import {
+        #[clap(required = true, help = "CSV file with ratings")]
+        file: PathBuf,
+        #[clap(
+            long = "report-file",
+            help = "File with the import report",
+            default_value = "ratings-report.json"
+        )]
+        report_file: PathBuf,
+        #[clap(
+            long = "report-format",
+            help = "Format of the report (json, html or csv)",
+            default_value = "json"
+        )]
+        report_format: ReportFormat,
+    },
+}
+
+#[derive(Args)]
+struct CsvArgs {
+    #[clap(
+        long = "swap-latlng",
+        help = "The columns lat and lng of the CSV file are swapped"
+    )]
+    swap_latlng: bool,
+    #[clap(
+        long = "expect-bbox",
+        help = "Reject places outside of this bounding box (sw_lat,sw_lng,ne_lat,ne_lng)",
+        value_parser = parse_bbox
+    )]
+    expect_bbox: Option<MapBbox>,
+    #[clap(
+        long = "expect-country",
+        help = "Reject places outside of this country (e.g. DE)",
+        conflicts_with = "expect_bbox",
+        value_parser = parse_country
+    )]
+    expect_country: Option<MapBbox>,
+    #[clap(
+        long = "fix-opening-hours",
+        help = "Convert opening hours like 'Mo-Fr 9-17 Uhr' into the OSM syntax"
+    )]
+    fix_opening_hours: bool,
+    #[clap(
+        long = "strict-opening-hours",
+        help = "Reject opening hours that don't follow the OSM syntax (only the common subset is known)"
+    )]
+    strict_opening_hours: bool,
+    #[clap(
+        long = "max-title-length",
+        help = "Max. number of characters of a title",
+        default_value_t = csv::MAX_TITLE_LEN
+    )]
+    max_title_length: usize,
+    #[clap(
+        long = "max-description-length",
+        help = "Max. number of characters of a description",
+        default_value_t = csv::MAX_DESCRIPTION_LEN
+    )]
+    max_description_length: usize,
+    #[clap(
+        long = "truncate",
+        help = "Truncate texts that are too long instead of rejecting the entry"
+    )]
+    truncate: bool,
+    #[clap(
+        long = "min-geocode-confidence",
+        help = "Reject addresses that the geocoder only found with a lower confidence (1 - 10)",
+        value_parser = clap::value_parser!(u8).range(1..=10)
+    )]
+    min_geocode_confidence: Option<u8>,
+    #[clap(
+        long = "normalize-addresses",
+        help = "Clean up the addresses before geocoding with the rules of a locale (de or en)"
+    )]
+    normalize_addresses: Option<address::AddressLocale>,
+    #[clap(
+        long = "default-country",
+        help = "Country of the records with an empty country column (e.g. Deutschland)"
+    )]
+    default_country: Option<String>,
+    #[clap(
+        long = "default-license",
+        help = "License of the records with an empty license column (e.g. CC0-1.0)"
+    )]
+    default_license: Option<String>,
+    #[clap(
+        long = "default-tags",
+        help = "Comma separated tags of the records with an empty tags column",
+        value_delimiter = ','
+    )]
+    default_tags: Vec<String>,
+    #[clap(flatten)]
+    hashtags: HashtagArgs,
+    #[clap(
+        long = "tag-separator",
+        help = "Separator of the tags column (auto, ',', ';' or space)",
+        default_value = "auto"
+    )]
+    tag_separator: tags::TagSeparator,
+    #[clap(
+        long = "strict-columns",
+        help = "Reject files with unknown or missing columns instead of ignoring them"
+    )]
+    strict_columns: bool,
+}
+
+/// Online tables that are imported like CSV files.
+#[derive(Args)]
+struct SpreadsheetArgs {
+    #[clap(
+        long = "from-airtable",
+        requires = "airtable_token",
+        help = "Import the records of an Airtable table (BASE/TABLE)"
+    )]
+    from_airtable: Vec<spreadsheet::Table>,
+    #[clap(
+        long = "airtable-token",
+        env = "OFDB_AIRTABLE_TOKEN",
+        hide_env_values = true,
+        help = "Personal access token of Airtable"
+    )]
+    airtable_token: Option<String>,
+    #[clap(
+        long = "from-google-sheet",
+        requires = "google_api_key",
+        help = "Import the rows of a shared Google sheet (SPREADSHEET_ID or SPREADSHEET_ID/SHEET)"
+    )]
+    from_google_sheet: Vec<spreadsheet::Table>,
+    #[clap(
+        long = "google-api-key",
+        env = "OFDB_GOOGLE_API_KEY",
+        hide_env_values = true,
+        help = "API key of Google Sheets"
+    )]
+    google_api_key: Option<String>,
+}
+
+#[derive(Args)]
+struct FilterArgs {
+    #[clap(
+        long = "filter",
+        help = "Only process records that match (e.g. 'city=Hamburg', 'city!=Hamburg' or 'tags~solar')"
+    )]
+    filters: Vec<filter::Condition>,
+    #[clap(
+        long = "rows",
+        help = "Only process these record numbers (e.g. '100-200', '100-', '-200' or '50..100' without 100)"
+    )]
+    rows: Option<filter::Rows>,
+    #[clap(
+        long = "offset",
+        help = "Skip this number of the selected records",
+        default_value = "0"
+    )]
+    offset: usize,
+    #[clap(
+        long = "limit",
+        help = "Max. number of records to process (after the offset)"
+    )]
+    limit: Option<usize>,
+}
+
+#[derive(Args)]
+struct PatchArgs {
+    #[clap(
+        long = "append-separator",
+        help = "Text between the current value and the text added with '++' (escape sequences like '\\n' are supported)",
+        default_value = " "
+    )]
+    append_separator: String,
+    #[clap(
+        long = "prepend",
+        help = "Add the text of '++' in front of the current value"
+    )]
+    prepend: bool,
+    #[clap(
+        long = "append-fields",
+        help = "Comma separated fields '--append-separator' and '--prepend' apply to",
+        value_delimiter = ',',
+        default_value = "description"
+    )]
+    append_fields: Vec<String>,
+    #[clap(flatten)]
+    hashtags: HashtagArgs,
+    #[clap(
+        long = "tag-separator",
+        help = "Separator of the tags column (auto, ',', ';' or space)",
+        default_value = "auto"
+    )]
+    tag_separator: tags::TagSeparator,
+    #[clap(
+        long = "strict-columns",
+        help = "Reject files with unknown or missing columns instead of ignoring them"
+    )]
+    strict_columns: bool,
+}
+
+#[derive(Args)]
+struct HashtagArgs {
+    #[clap(
+        long = "extract-hashtags",
+        help = "Add the hashtags of the description (e.g. '#repair') to the tags"
+    )]
+    extract_hashtags: bool,
+    #[clap(
+        long = "remove-hashtags",
+        help = "Remove the extracted hashtags from the description",
+        requires = "extract_hashtags"
+    )]
+    remove_hashtags: bool,
+}
+
+impl From<HashtagArgs> for tags::DescriptionHashtags {
+    fn from(args: HashtagArgs) -> Self {
+        match (args.extract_hashtags, args.remove_hashtags) {
+            (false, _) => Self::Keep,
+            (true, false) => Self::Extract,
+            (true, true) => Self::Move,
+        }
+    }
+}
+
+impl From<PatchArgs> for csv::PatchOptions {
+    fn from(args: PatchArgs) -> Self {
+        Self {
+            separator: args
+                .append_separator
+                .replace("\\n", "\n")
+                .replace("\\t", "\t"),
+            prepend: args.prepend,
+            append_fields: args.append_fields,
+            hashtags: args.hashtags.into(),
+            tag_separator: args.tag_separator,
+            strict_columns: args.strict_columns,
+        }
+    }
+}
+
+impl From<FilterArgs> for filter::RecordFilter {
+    fn from(args: FilterArgs) -> Self {
+        Self {
+            conditions: args.filters,
+            rows: args.rows,
+            records: None,
+            skip: BTreeSet::new(),
+            offset: args.offset,
+            limit: args.limit,
+        }
+    }
+}
+
+impl SubCommand {
+    fn csv_args_mut(&mut self) -> Option<&mut CsvArgs> {
+        match self {
+            Self::Import(cli::import::ImportArgs { csv, .. })
+            | Self::Validate { csv, .. }
+            | Self::Preview { csv, .. }
+            | Self::Inspect { csv, .. }
+            | Self::Roundtrip { csv, .. } => Some(csv),
+            _ => None,
+        }
+    }
+
+    /// The command writes a report that can be sent by e-mail.
+    fn writes_report(&self) -> bool {
+        matches!(
+            self,
+            Self::Import(_)
+                | Self::Validate { .. }
+                | Self::Update { .. }
+                | Self::Patch { .. }
+                | Self::Ratings {
+                    cmd: RatingsCommand::Import { .. }
+                }
+                | Self::Report {
+                    cmd: ReportCommand::Merge { .. }
+                }
+        )
+    }
+}
+
+impl CsvArgs {
+    /// Use the defaults of the config file unless they are given as arguments.
+    fn apply_config_defaults(&mut self, defaults: &config::DefaultsConfig) {
+        if self.default_country.is_none() {
+            self.default_country = defaults.country.clone();
+        }
+        if self.default_license.is_none() {
+            self.default_license = defaults.license.clone();
+        }
+        if self.default_tags.is_empty() {
+            self.default_tags = defaults.tags.clone();
+        }
+    }
+}
+
+impl From<CsvArgs> for csv::CsvOptions {
+    fn from(args: CsvArgs) -> Self {
+        Self {
+            swap_lat_lng: args.swap_latlng,
+            expected_bbox: args.expect_bbox.or(args.expect_country),
+            fix_opening_hours: args.fix_opening_hours,
+            strict_opening_hours: args.strict_opening_hours,
+            limits: csv::FieldLimits {
+                title: args.max_title_length,
+                description: args.max_description_length,
+            },
+            truncate: args.truncate,
+            filter: filter::RecordFilter::default(),
+            geocoder_usage: None,
+            geocode_matches: None,
+            min_geocode_confidence: args.min_geocode_confidence,
+            normalize_addresses: args.normalize_addresses,
+            defaults: csv::RecordDefaults {
+                country: args.default_country,
+                license: args.default_license,
+                tags: args.default_tags,
+            },
+            hashtags: args.hashtags.into(),
+            tag_separator: args.tag_separator,
+            strict_columns: args.strict_columns,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileType {
+    Json,
+    Csv,
+    Kml,
+}
+
+impl FromStr for FileType {
+    type Err = anyhow::Error;
+    fn from_str(t: &str) -> Result<Self, Self::Err> {
+        match &*t.to_lowercase() {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "kml" => Ok(Self::Kml),
+            _ => Err(anyhow::anyhow!("Unsupported file type")),
+        }
+    }
+}
+
+/// Format of an export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExportFormat {
+    Csv,
+    Md,
+    Kml,
+    Gpx,
+    Parquet,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "csv" => Ok(Self::Csv),
+            "md" | "markdown" => Ok(Self::Md),
+            "kml" => Ok(Self::Kml),
+            "gpx" => Ok(Self::Gpx),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(anyhow::anyhow!(
+                "Unsupported export format '{s}' (expected 'csv', 'md', 'kml', 'gpx' or 'parquet')"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Csv => "csv",
+            Self::Md => "md",
+            Self::Kml => "kml",
+            Self::Gpx => "gpx",
+            Self::Parquet => "parquet",
+        })
+    }
+}
+
+/// The options and the limits of a run that all subcommands share.
+struct Context {
+    opt: Opt,
+    deadline: Deadline,
+    report_mail: Option<Rc<email::ReportMail>>,
+}
+
+impl Context {
+    /// The report file of a command, sent by e-mail with `--email-report`.
+    fn report_file(&self, path: PathBuf, format: ReportFormat) -> ReportFile {
+        ReportFile::new(path, format)
+            .with_mail(self.report_mail.clone())
+            .with_summary_json(self.opt.summary_json)
+    }
+
+    /// Run a command with the client of a login session and keep its cookies.
+    fn with_session<T>(
+        &self,
+        email: Option<String>,
+        password: Option<String>,
+        command: impl FnOnce(&OfdbClient) -> Result<T>,
+    ) -> Result<T> {
+        let (client, session) = login_session(self.opt.api()?, email, password, &self.opt)?;
+        let result = command(&client);
+        session.save_cookies()?;
+        result
+    }
+
+    /// Run a command with a geocoder and save the usage of the geocoding budget.
+    fn with_geo_coding<T>(
+        &self,
+        opencage_api_key: Option<String>,
+        command: impl FnOnce(
+            &dyn GeoCodingGateway,
+            &Arc<GeocoderUsage>,
+            Arc<GeocodeMatches>,
+        ) -> Result<T>,
+    ) -> Result<T> {
+        let usage = self.opt.geocoder_usage()?;
+        let matches = Arc::default();
+        let geo_coding = self.opt.geo_coding(opencage_api_key, &usage, &matches)?;
+        let result = command(&*geo_coding, &usage, matches);
+        save_geocoder_usage(&usage);
+        result
+    }
+}
+
+fn main() -> Result<()> {
+    if env::var("RUST_LOG").is_err() {
+        env::set_var("RUST_LOG", "info");
+    }
+    let logger = pretty_env_logger::formatted_builder()
+        .parse_env("RUST_LOG")
+        .build();
+    let level = logger.filter();
+    progress::init_logger(logger, level)?;
+    let config = config::Config::load()?;
+    let cli = Cli::command();
+    let takes_value = |flag: &str| {
+        cli.get_arguments().any(|a| {
+            let long = a.get_long().map(|l| format!("--{l}"));
+            let short = a.get_short().map(|s| format!("-{s}"));
+            a.get_action().takes_values()
+                && (long.as_deref() == Some(flag) || short.as_deref() == Some(flag))
+        })
+    };
+    let mut args = Cli::parse_from(config.expand_aliases(env::args_os().collect(), takes_value)?);
+    i18n::set_lang(args.opt.lang);
+    args.opt.load_fixtures()?;
+    let recording = replay::SaveRecording::new(args.opt.recording.clone());
+    if let Some(csv) = args.cmd.csv_args_mut() {
+        csv.apply_config_defaults(&config.defaults);
+    }
+    if !args.opt.email_report.is_empty() && !args.cmd.writes_report() {
+        bail!("This command does not write a report that could be sent with --email-report");
+    }
+    let ctx = Context {
+        deadline: Deadline::after(args.opt.max_duration.map(Into::into)),
+        report_mail: report_mail(&config, &args.opt.email_report)?,
+        opt: args.opt,
+    };
+
+    use SubCommand as C;
+    let result = match args.cmd {
+        C::Import(args) => cli::import::run(&ctx, args),
+        C::Validate {
+            file,
+            report_file,
+            report_format,
+            csv,
+            filter,
+        } => validate(
+            &ctx.opt.http_client()?,
+            file,
+            ctx.report_file(report_file, report_format),
+            &csv::CsvOptions {
+                filter: filter.into(),
+                ..csv.into()
+            },
+        ),
+        C::Preview {
+            file,
+            out,
+            opencage_api_key,
+            no_duplicate_check,
+            csv,
+            filter,
+        } => ctx.with_geo_coding(opencage_api_key, |geo_coding, usage, matches| {
+            preview(
+                &ctx.opt.client()?,
+                file,
+                geo_coding,
+                &csv::CsvOptions {
+                    geocoder_usage: Some(Arc::clone(usage)),
+                    geocode_matches: Some(matches),
+                    filter: filter.into(),
+                    ..csv.into()
+                },
+                !no_duplicate_check,
+                out,
+            )
+        }),
+        C::Inspect { file, limit, csv } => inspect(
+            &ctx.opt.http_client()?,
+            file,
+            limit as usize,
+            csv::CsvOptions {
+                filter: filter::RecordFilter {
+                    rows: Some(filter::Rows {
+                        first: 0,
+                        last: Some(limit as usize - 1),
+                    }),
+                    ..Default::default()
+                },
+                ..csv.into()
+            },
+        ),
+        C::Roundtrip { file, csv } => roundtrip(&ctx.opt.http_client()?, file, csv.into()),
+        C::Geocode {
+            file,
+            out,
+            opencage_api_key,
+            normalize_addresses,
+        } => ctx.with_geo_coding(opencage_api_key, |geo_coding, _, _| {
+            geocode_file(&file, &out, geo_coding, normalize_addresses)
+        }),
+        C::Read { uuids } => read(&ctx.opt.client()?, uuids),
+        C::Export {
+            bbox,
+            text,
+            out,
+            format,
+            markdown_style,
+            group_by,
+            with_computed_columns,
+            server_export,
+            email,
+            password,
+        } => {
+            if format != ExportFormat::Csv && with_computed_columns {
+                bail!("The computed columns are only available for CSV files");
+            }
+            if format != ExportFormat::Md && group_by.is_some() {
+                bail!("Only the entries of Markdown files can be grouped");
+            }
+            if format == ExportFormat::Parquet && cfg!(not(feature = "parquet")) {
+                bail!("The Parquet export requires the feature 'parquet' (cargo install --features parquet ...)");
+            }
+            let credentials = credentials(email, password)?;
+            export(
+                &ctx.opt.client()?,
+                bbox.unwrap_or_else(world_bbox),
+                &text,
+                out.unwrap_or_else(|| format!("entries.{format}").into()),
+                ExportOptions {
+                    format,
+                    markdown: markdown::MarkdownOptions {
+                        style: markdown_style,
+                        group_by,
+                    },
+                    with_computed_columns,
+                    server_export,
+                },
+                credentials,
+            )
+        }
+        C::Update {
+            files,
+            report_file,
+            report_format,
+            patch,
+            validate_webhook,
+            links_file,
+            ignore_unknown_ids,
+            protect,
+            match_by,
+            no_remerge,
+            state_file,
+            filter,
+            patch_args,
+        } => {
+            let client = with_server_check(ctx.opt.client()?);
+            let options = UpdateOptions {
+                patch,
+                validate_webhook,
+                links_file,
+                ignore_unknown_ids,
+                match_by,
+                remerge: !no_remerge,
+                filter: filter.into(),
+                protect: protect.unwrap_or_default(),
+                patch_options: patch_args.into(),
+            };
+            let command = if patch { "patch" } else { "update" };
+            let mut state = open_state(state_file, command)?;
+            process_files(
+                input_files(files)?,
+                ctx.report_file(report_file, report_format),
+                |path| update(&client, path, &options, state.as_mut(), ctx.deadline),
+            )
+        }
+        C::Patch {
+            files,
+            template,
+            ids_file,
+            report_file,
+            report_format,
+            validate_webhook,
+            ignore_unknown_ids,
+            protect,
+            no_remerge,
+            state_file,
+            filter,
+            patch_args,
+        } => {
+            let client = with_server_check(ctx.opt.client()?);
+            let options = UpdateOptions {
+                patch: true,
+                validate_webhook,
+                links_file: None,
+                ignore_unknown_ids,
+                match_by: None,
+                remerge: !no_remerge,
+                filter: filter.into(),
+                protect: protect.unwrap_or_default(),
+                patch_options: patch_args.into(),
+            };
+            let report_file = ctx.report_file(report_file, report_format);
+            let mut state = open_state(state_file, "patch")?;
+            match (template, ids_file) {
+                (Some(template), Some(ids_file)) => {
+                    process_files(vec![template], report_file, |path| {
+                        patch_template(
+                            &client,
+                            path,
+                            &ids_file,
+                            &options,
+                            state.as_mut(),
+                            ctx.deadline,
+                        )
+                    })
+                }
+                _ => process_files(input_files(files)?, report_file, |path| {
+                    update(&client, path, &options, state.as_mut(), ctx.deadline)
+                }),
+            }
+        }
+        C::CheckLinks {
+            bbox,
+            tags,
+            text,
+            concurrency,
+            timeout,
+            out,
+            patch_file,
+        } => check_links(
+            &ctx.opt.client()?,
+            &http_client(&http::HttpOptions {
+                timeout: Some(timeout.into()),
+                ..ctx.opt.http()
+            })?,
+            bbox.unwrap_or_else(world_bbox),
+            &search_text(&text, &tags),
+            concurrency,
+            out,
+            patch_file,
+        ),
+        C::Audit {
+            bbox,
+            tags,
+            text,
+            min_score,
+            out,
+        } => audit(
+            &ctx.opt.client()?,
+            bbox.unwrap_or_else(world_bbox),
+            &search_text(&text, &tags),
+            min_score,
+            out,
+        ),
+        C::Stats {
+            bbox,
+            tags,
+            text,
+            top,
+            json,
+        } => print_stats(
+            &ctx.opt.client()?,
+            &bbox.unwrap_or_else(world_bbox),
+            &search_text(&text, &tags),
+            top,
+            json,
+        ),
+        C::FindContact {
+            contact,
+            bbox,
+            tags,
+            text,
+            file,
+            json,
+        } => {
+            let client = ctx.opt.client()?;
+            let entries = match file {
+                Some(path) => read_entries_file(&client, &path)?,
+                None => {
+                    let bbox = bbox.unwrap_or_else(world_bbox);
+                    search_entries(&client, &search_text(&text, &tags), &bbox)?
+                }
+            };
+            find_contact(&entries, &contact, json)
+        }
+        C::Dedupe {
+            bbox,
+            tags,
+            text,
+            min_similarity,
+            max_distance,
+            out,
+        } => dedupe(
+            &ctx.opt.client()?,
+            bbox.unwrap_or_else(world_bbox),
+            &search_text(&text, &tags),
+            dedupe::DedupeOptions {
+                min_similarity,
+                max_distance,
+            },
+            out,
+        ),
+        C::Enrich {
+            ids_file,
+            source,
+            language,
+            min_similarity,
+            max_distance,
+            out,
+        } => enrich(
+            &ctx.opt.client()?,
+            &ctx.opt.http_client()?,
+            &ids_file,
+            source,
+            &enrich::EnrichOptions {
+                language,
+                min_similarity,
+                max_distance,
+            },
+            out,
+        ),
+        C::Merge {
+            email,
+            password,
+            concat_descriptions,
+            dry_run,
+            keep,
+            duplicate,
+        } => {
+            let options = merge::MergeOptions {
+                concat_descriptions,
+            };
+            if dry_run {
+                merge(&ctx.opt.client()?, keep, duplicate, options, None)
+            } else {
+                ctx.with_session(email, password, |client| {
+                    merge(client, keep, duplicate, options, Some(ctx.opt.yes))
+                })
+            }
+        }
+        C::Diff { file, json } => diff(&ctx.opt.client()?, file, json),
+        C::Login { email, password } => login(ctx.opt.api()?, email, password, &ctx.opt),
+        C::Logout { email } => logout(ctx.opt.api()?, email, &ctx.opt),
+        C::Review {
+            email,
+            password,
+            file,
+            filter,
+        } => ctx.with_session(email, password, |client| {
+            review(&ctx, client, file, &filter.into())
+        }),
+        C::History {
+            email,
+            password,
+            json,
+            uuid,
+        } => ctx.with_session(email, password, |client| history(client, uuid, json)),
+        C::Mirror {
+            source_api,
+            target_api,
+            tag,
+            id_mapping,
+            report_file,
+        } => mirror(
+            &ctx.opt.client_for(&source_api)?,
+            &ctx.opt.client_for(&target_api)?,
+            tag,
+            id_mapping,
+            report_file,
+            ctx.deadline,
+        ),
+        C::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "ofdb", &mut io::stdout());
+            Ok(())
+        }
+        C::Doctor {
+            opencage_api_key,
+            email,
+            password,
+        } => {
+            let credentials = credentials(email, password)?;
+            doctor(
+                ctx.opt.api.as_deref(),
+                &ctx.opt.http(),
+                opencage_api_key,
+                credentials,
+            )
+        }
+        C::Ratings { cmd } => match cmd {
+            RatingsCommand::Import {
+                file,
+                report_file,
+                report_format,
+            } => import_ratings(
+                &ctx.opt.client()?,
+                file,
+                ctx.report_file(report_file, report_format),
+                ctx.deadline,
+            ),
+        },
+        C::Links { cmd } => match cmd {
+            LinksCommand::Export { uuids, out } => export_links(&ctx.opt.client()?, uuids, out),
+        },
+        C::Fixtures { cmd } => match cmd {
+            FixturesCommand::Generate {
+                entries,
+                seed,
+                bbox,
+                tags,
+                out,
+            } => {
+                let options = fixtures::FixtureOptions { bbox, tags };
+                generate_fixtures(entries, seed, &options, &out)
+            }
+        },
+        C::Generate {
+            count,
+            bbox,
+            tags,
+            seed,
+            out,
+        } => generate_places(count, seed, &fixtures::FixtureOptions { bbox, tags }, &out),
+        C::Bench {
+            email,
+            password,
+            requests,
+            concurrency,
+            seed,
+            json,
+        } => {
+            let run_bench = |client: &OfdbClient| {
+                benchmark(client, requests, concurrency, seed, json, ctx.opt.yes)
+            };
+            match email {
+                Some(email) => ctx.with_session(Some(email), password, run_bench),
+                None => run_bench(&ctx.opt.client()?),
+            }
+        }
+        C::MockServer { host, port, seed } => {
+            if cfg!(not(feature = "mock-server")) {
+                bail!("The mock server requires the feature 'mock-server' (cargo install --features mock-server ...)");
+            }
+            let entries = match seed {
+                Some(path) => read_mock_entries(&path)?,
+                None => vec![],
+            };
+            log::info!("Start the mock server with {} entries", entries.len());
+            serve_mock_server(entries, &format!("{host}:{port}"))
+        }
+        C::Count { json } => count(&ctx.opt.client()?, json),
+        C::Tags { cmd } => match cmd {
+            TagsCommand::List {
+                min_count,
+                max_count,
+                contains,
+                offset,
+                limit,
+                json,
+                csv,
+            } => list_tags(
+                &ctx.opt.client()?,
+                &tags::TagQuery {
+                    min_count,
+                    max_count,
+                    offset,
+                    limit,
+                },
+                contains.as_deref(),
+                json,
+                csv,
+            ),
+        },
+        C::User { cmd } => match cmd {
+            UserCommand::Register {
+                email,
+                password,
+                file,
+            } => {
+                let users = match (email, file) {
+                    (Some(email), _) => {
+                        let password = match password {
+                            Some(password) => password,
+                            None => prompt_password(&email)?,
+                        };
+                        vec![users::NewUser { email, password }]
+                    }
+                    (None, Some(path)) => read_new_users(&path)?,
+                    (None, None) => unreachable!("required by clap"),
+                };
+                register_users(&ctx.opt.client()?, &users)
+            }
+            UserCommand::Delete {
+                email,
+                password,
+                users,
+            } => ctx.with_session(email, password, |client| {
+                confirm(
+                    &format!("Delete {} user accounts?", users.len()),
+                    ctx.opt.yes,
+                )?;
+                for_each_user(&users, "delete", |user| client.delete_user(user))
+            }),
+            UserCommand::ResetPassword { users } => {
+                let client = ctx.opt.client()?;
+                for_each_user(&users, "reset the password of", |user| {
+                    client.request_password_reset(user)
+                })
+            }
+        },
+        C::Clearance { cmd } => {
+            if ctx.opt.org_token.is_none() {
+                bail!("The clearance requires the token of an organization (--org-token)");
+            }
+            let client = ctx.opt.client()?;
+            match cmd {
+                ClearanceCommand::List {
+                    offset,
+                    limit,
+                    json,
+                } => list_pending_clearances(&client, offset, limit, json),
+                ClearanceCommand::Resolve { until, uuids } => {
+                    resolve_clearances(&client, until, uuids)
+                }
+            }
+        }
+        C::Report { cmd } => match cmd {
+            ReportCommand::Schema { several_files } => {
+                let schema = report::report_schema(several_files);
+                println!("{}", serde_json::to_string_pretty(&schema)?);
+                Ok(())
+            }
+            ReportCommand::Summarize { files, json } => {
+                let mut summary = report::Summary::default();
+                for r in read_json_reports(files)? {
+                    summary.add(&r.report);
+                }
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&summary)?);
+                } else {
+                    println!("{summary}");
+                }
+                Ok(())
+            }
+            ReportCommand::Merge {
+                files,
+                report_file,
+                report_format,
+            } => {
+                let reports = read_json_reports(files)?;
+                log::info!("Merge {} reports", reports.len());
+                let merged = report::merge_reports(reports.into_iter().map(|r| r.report).collect());
+                ctx.report_file(report_file, report_format).write(&merged)
+            }
+            ReportCommand::FailuresToCsv {
+                report,
+                source,
+                file,
+                out,
+                with_duplicates,
+            } => failures_to_csv(&report, source.as_deref(), file, &out, with_duplicates),
+        },
+    };
+    recording.save()?;
+    if let Err(err) = &result {
+        if err.is::<DeadlineExceeded>() {
+            log::error!("{err}");
+            process::exit(EXIT_CODE_DEADLINE_EXCEEDED);
+        }
+    }
+    result
+}
+
+fn read(client: &OfdbClient, uuids: Vec<Uuid>) -> Result<()> {
+    let entries = client.read_entries(uuids)?;
+    println!("{}", serde_json::to_string(&entries)?);
+    Ok(())
+}
+
+fn print_stats(
+    client: &OfdbClient,
+    bbox: &MapBbox,
+    text: &str,
+    top: usize,
+    json: bool,
+) -> Result<()> {
+    let entries = search_entries(client, text, bbox)?;
+    let stats = stats::stats(&entries, top);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else {
+        println!("{stats}");
+    }
+    Ok(())
+}
+
+fn find_contact(entries: &[Entry], contact: &contact::Contact, json: bool) -> Result<()> {
+    log::info!("Check {} entries", entries.len());
+    let matches = contact::find_contact(entries, contact);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&matches)?);
+    } else {
+        for m in &matches {
+            println!(
+                "{}  {}  ({})  {}",
+                m.id,
+                m.title,
+                m.fields.join(", "),
+                m.url
+            );
+        }
+        log::info!("Found {} entries", matches.len());
+    }
+    Ok(())
+}
+
+fn count(client: &OfdbClient, json: bool) -> Result<()> {
+    #[derive(Serialize)]
+    struct Counts {
+        entries: u64,
+        tags: u64,
+    }
+    let counts = Counts {
+        entries: client.count_entries()?,
+        tags: client.count_tags()?,
+    };
+    if json {
+        println!("{}", serde_json::to_string(&counts)?);
+    } else {
+        println!("Entries: {}", counts.entries);
+        println!("Tags:    {}", counts.tags);
+    }
+    Ok(())
+}
+
+fn list_tags(
+    client: &OfdbClient,
+    query: &tags::TagQuery,
+    contains: Option<&str>,
+    json: bool,
+    csv: bool,
+) -> Result<()> {
+    let tags = tags::find_tags(
+        |query| Ok(client.most_popular_tags(query)?),
+        query,
+        contains,
+    )?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&tags)?);
+    } else if csv {
+        tags::write_tags(io::stdout().lock(), &tags)?;
+    } else {
+        let width = tags.iter().map(|t| t.tag.chars().count()).max();
+        for tags::TagCount { tag, count } in &tags {
+            println!("{tag:<width$}  {count:>6}", width = width.unwrap_or(0));
+        }
+    }
+    Ok(())
+}
+
+fn benchmark(
+    client: &OfdbClient,
+    requests: usize,
+    concurrency: usize,
+    seed: Option<u64>,
+    json: bool,
+    yes: bool,
+) -> Result<()> {
+    confirm(
+        &format!(
+            "Create {requests} entries tagged '{}' on {}?",
+            bench::BENCH_TAG,
+            client.api_url()
+        ),
+        yes,
+    )?;
+    let seed = seed.unwrap_or_else(bench::random_seed);
+    let latencies = bench::bench(client, requests, concurrency, seed);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&latencies)?);
+    } else {
+        println!("{}", bench::TABLE_HEADER);
+        for latencies in latencies {
+            println!("{latencies}");
+        }
+    }
+    Ok(())
+}
+
+fn read_mock_entries(path: &Path) -> Result<Vec<Entry>> {
+    log::info!(
+        "Read the entries of the mock server from {}",
+        path.display()
+    );
+    Ok(serde_json::from_reader(io::BufReader::new(File::open(
+        path,
+    )?))?)
+}
+
+fn diff(client: &OfdbClient, path: PathBuf, json: bool) -> Result<()> {
+    let local_entries = read_entries_file(client, &path)?;
+    let uuids = local_entries
+        .iter()
+        .map(|e| e.id.parse::<Uuid>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let server_entries = client.read_entries(uuids)?;
+    let diffs: Vec<_> = local_entries
+        .iter()
+        .map(|local| {
+            server_entries
+                .iter()
+                .find(|e| e.id == local.id)
+                .map(|server| ofdb_cli::diff::diff_entries(server, local))
+                .unwrap_or_else(|| ofdb_cli::diff::EntryDiff::not_found(local))
+        })
+        .collect();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diffs)?);
+        return Ok(());
+    }
+    print_diffs(&diffs);
+    Ok(())
+}
+
+/// Read exported entries from a JSON or CSV file, invalid CSV records are skipped.
+fn read_entries_file(client: &OfdbClient, path: &Path) -> Result<Vec<Entry>> {
+    let (file_type, reader) = open_input(path, client.http_client())?;
+    log::info!(
+        "Read entries from file ({}): {}",
+        format!("{:?}", file_type).to_uppercase(),
+        path.display()
+    );
+    let entries = match file_type {
+        FileType::Kml => bail!("KML files can only be imported"),
+        FileType::Json => serde_json::from_reader(reader)?,
+        FileType::Csv => csv::places_from_reader(reader)?
+            .into_iter()
+            .filter_map(|r| match r.result {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    log::warn!("Skip record {}: {err}", r.record_nr);
+                    None
+                }
+            })
+            .collect(),
+    };
+    Ok(entries)
+}
+
+fn print_diffs(diffs: &[ofdb_cli::diff::EntryDiff]) {
+    let colored = io::stdout().is_terminal();
+    let paint = |code: &str, txt: String| {
+        if colored {
+            format!("\x1b[{code}m{txt}\x1b[0m")
+        } else {
+            txt
+        }
+    };
+    for diff in diffs {
+        println!("{} '{}'", diff.id, diff.title);
+        let Some(server_version) = diff.server_version else {
+            println!("  {}", paint("31", "Not found on the server".to_string()));
+            continue;
+        };
+        if diff.outdated {
+            println!(
+                "  {}",
+                paint(
+                    "33",
+                    format!(
+                        "Outdated: the server version ({server_version}) is newer than the local version ({})",
+                        diff.local_version
+                    )
+                )
+            );
+        }
+        if diff.changes.is_empty() {
+            println!("  No changes");
+        }
+        for change in &diff.changes {
+            println!("  {}:", change.field);
+            println!("    {}", paint("31", format!("- {}", change.old)));
+            println!("    {}", paint("32", format!("+ {}", change.new)));
+        }
+    }
+}
+
+fn merge(
+    client: &OfdbClient,
+    keep_id: Uuid,
+    dup_id: Uuid,
+    options: merge::MergeOptions,
+    // `None` for a dry run, otherwise whether the merge is already confirmed.
+    confirmed: Option<bool>,
+) -> Result<()> {
+    if keep_id == dup_id {
+        bail!("Can't merge an entry into itself");
+    }
+    let entries = client.read_entries(vec![keep_id, dup_id])?;
+    let find = |uuid: Uuid| {
+        entries
+            .iter()
+            .find(|e| e.id.parse::<Uuid>().ok() == Some(uuid))
+            .ok_or_else(|| anyhow!("Entry {uuid} does not exist"))
+    };
+    let (keep, dup) = (find(keep_id)?, find(dup_id)?);
+    let merged = merge::merge_entries(keep, dup, options);
+    print_diffs(&[ofdb_cli::diff::diff_entries(keep, &merged)]);
+    println!("{} '{}'", dup.id, dup.title);
+    println!("  Archive");
+    let Some(yes) = confirmed else {
+        return Ok(());
+    };
+    confirm(
+        &format!("Update '{}' and archive '{}'?", keep.title, dup.title),
+        yes,
+    )?;
+    log::info!("Update '{}' ({})", merged.title, merged.id);
+    let id = merged.id.clone();
+    client.update_place(&id, &UpdatePlace::from(merged))?;
+    log::info!("Archive '{}' ({})", dup.title, dup.id);
+    client.review(vec![dup_id], merge::archive_review(keep))?;
+    Ok(())
+}
+
+fn validate(
+    http: &reqwest::blocking::Client,
+    path: PathBuf,
+    report_file: ReportFile,
+    options: &csv::CsvOptions,
+) -> Result<()> {
+    log::info!("Validate file: {}", path.display());
+    let (_, reader) = open_input(&path, http)?;
+    let results = csv::validate_new_places(reader, options)?;
+    let report = Report::from(results);
+    report_file.write(&report)?;
+    let failures = report.csv_import_failures.len();
+    if failures > 0 {
+        bail!(
+            "{}",
+            tr!(
+                "{count} records contain errors (see {path})",
+                count = failures,
+                path = report_file.path.display()
+            )
+        );
+    }
+    log::info!(
+        "{}",
+        tr!(
+            "All {count} records are valid",
+            count = report.csv_import_successes.len()
+        )
+    );
+    Ok(())
+}
+
+fn inspect(
+    http: &reqwest::blocking::Client,
+    path: PathBuf,
+    rows: usize,
+    options: csv::CsvOptions,
+) -> Result<()> {
+    log::info!("Inspect the first {rows} records of {}", path.display());
+    let (file_type, reader) = open_input(&path, http)?;
+    if file_type != FileType::Csv {
+        bail!("Only CSV files can be inspected");
+    }
+    let cache = geocoding_cache::CachedGeoCoding::open(&inspect::NoGeoCoding)?;
+    let geo_coding = inspect::RecordingGeoCoding::new(cache);
+    let results = csv::new_places_with_options(reader, &geo_coding, &options)?;
+    inspect::write_records(io::stdout().lock(), &results, &geo_coding)
+}
+
+fn roundtrip(
+    http: &reqwest::blocking::Client,
+    path: PathBuf,
+    options: csv::CsvOptions,
+) -> Result<()> {
+    log::info!("Check the round trip of {}", path.display());
+    let (file_type, mut reader) = open_input(&path, http)?;
+    if file_type != FileType::Csv {
+        bail!("Only CSV files can be checked");
+    }
+    let mut source = vec![];
+    reader.read_to_end(&mut source)?;
+    let report = roundtrip::check(&source, &options)?;
+    print!("{report}");
+    if !report.is_lossless() {
+        bail!("Values of {} are lost in the round trip", path.display());
+    }
+    Ok(())
+}
+
+fn geocode_file(
+    path: &Path,
+    out: &Path,
+    geo_coding: &dyn GeoCodingGateway,
+    locale: Option<address::AddressLocale>,
+) -> Result<()> {
+    // Creating the output file would truncate the input file
+    if out.exists() && path.canonicalize()? == out.canonicalize()? {
+        bail!(
+            "The output file must not be the input file {}",
+            path.display()
+        );
+    }
+    log::info!("Geocode the addresses of {}", path.display());
+    let geo_coding = geocoding_cache::CachedGeoCoding::open(geo_coding)?;
+    let summary = geocode::geocode_csv(File::open(path)?, File::create(out)?, &geo_coding, locale)?;
+    log::debug!("Save geocoding cache: {}", geo_coding.path().display());
+    geo_coding.save()?;
+    log::info!("{summary}");
+    log::info!("Wrote {}", out.display());
+    Ok(())
+}
+
+fn preview(
+    client: &OfdbClient,
+    path: PathBuf,
+    geo_coding: &dyn GeoCodingGateway,
+    options: &csv::CsvOptions,
+    check_duplicates: bool,
+    out: PathBuf,
+) -> Result<()> {
+    let (file_type, reader) = open_input(&path, client.http_client())?;
+    if file_type != FileType::Csv {
+        bail!("Only CSV files can be previewed");
+    }
+    log::info!("Read entries from file: {}", path.display());
+    let geo_coding = geocoding_cache::CachedGeoCoding::open(geo_coding)?;
+    let results = csv::new_places_with_options(reader, &geo_coding, options)?;
+    log::debug!("Save geocoding cache: {}", geo_coding.path().display());
+    geo_coding.save()?;
+    let mut progress =
+        check_duplicates.then(|| progress::Progress::new("Check duplicates", results.len()));
+    let markers: Vec<_> = results
+        .into_iter()
+        .map(|r| {
+            let record_nr = r.record_nr;
+            let place = match r.result {
+                Ok(place) => place,
+                Err(err) => {
+                    if let Some(progress) = &mut progress {
+                        progress.inc(progress::Outcome::Failure);
+                    }
+                    return preview::Marker {
+                        record_nr,
+                        title: String::new(),
+                        lat_lng: None,
+                        status: preview::MarkerStatus::Error,
+                        message: Some(err.to_string()),
+                    };
+                }
+            };
+            let duplicates = match &mut progress {
+                Some(progress) => {
+                    let duplicates = client.search_duplicates(&place).unwrap_or_else(|err| {
+                        log::warn!("Unable to search duplicates of '{}': {err}", place.title);
+                        None
+                    });
+                    progress.inc(if duplicates.is_some() {
+                        progress::Outcome::Duplicate
+                    } else {
+                        progress::Outcome::Success
+                    });
+                    duplicates.unwrap_or_default()
+                }
+                None => vec![],
+            };
+            let (status, message) = if duplicates.is_empty() {
+                (preview::MarkerStatus::Ok, None)
+            } else {
+                let titles: Vec<_> = duplicates
+                    .iter()
+                    .map(|d| format!("'{}'", d.title))
+                    .collect();
+                (
+                    preview::MarkerStatus::Duplicate,
+                    Some(format!("Possible duplicate of {}", titles.join(", "))),
+                )
+            };
+            preview::Marker {
+                record_nr,
+                title: place.title,
+                lat_lng: Some((place.lat, place.lng)),
+                status,
+                message,
+            }
+        })
+        .collect();
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    log::info!(
+        "Write map of {} records to {}",
+        markers.len(),
+        out.display()
+    );
+    preview::write_map(
+        io::BufWriter::new(File::create(out)?),
+        &format!("Preview of {}", path.display()),
+        &markers,
+    )
+}
+
+fn history(client: &OfdbClient, uuid: Uuid, json: bool) -> Result<()> {
+    let history = client.place_history(uuid)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&history)?);
+        return Ok(());
+    }
+    println!(
+        "Entry {} (license: {})",
+        history.place.id, history.place.license
+    );
+    let mut previous: Option<&PlaceRevision> = None;
+    for (revision, status_logs) in &history.revisions {
+        println!();
+        println!(
+            "Revision {} created at {} by {}",
+            revision.revision,
+            format_timestamp(revision.created.at),
+            revision.created.by.as_deref().unwrap_or("anonymous")
+        );
+        match previous {
+            Some(previous) => {
+                let changes = ofdb_cli::history::changed_fields(previous, revision);
+                if changes.is_empty() {
+                    println!("  No fields changed");
+                } else {
+                    println!("  Changed fields: {}", changes.join(", "));
+                }
+            }
+            None => {
+                println!("  Initial revision: '{}'", revision.title);
+            }
+        }
+        for log in status_logs {
+            println!(
+                "  Status changed to {:?} at {} by {}{}",
+                log.status,
+                format_timestamp(log.activity.at),
+                log.activity.by.as_deref().unwrap_or("anonymous"),
+                log.activity
+                    .comment
+                    .as_ref()
+                    .map(|c| format!(": {c}"))
+                    .unwrap_or_default()
+            );
+        }
+        previous = Some(revision);
+    }
+    Ok(())
+}
+
+fn read_new_users(path: &Path) -> Result<Vec<users::NewUser>> {
+    log::info!("Read users from file: {}", path.display());
+    let results = users::users_from_reader(File::open(path)?)?;
+    let mut new_users = vec![];
+    for r in results {
+        match r.result {
+            Ok(user) => new_users.push(user),
+            Err(err) => bail!("Invalid record {}: {err}", r.record_nr),
+        }
+    }
+    Ok(new_users)
+}
+
+fn register_users(client: &OfdbClient, new_users: &[users::NewUser]) -> Result<()> {
+    let emails: Vec<_> = new_users.iter().map(|u| u.email.clone()).collect();
+    for_each_user(&emails, "register", |email| {
+        let user = new_users.iter().find(|u| u.email == email).unwrap();
+        client.register_user(user)
+    })
+}
+
+/// Run a request for each user and report the failures at the end.
+fn for_each_user(
+    users: &[String],
+    action: &str,
+    request: impl Fn(&str) -> ofdb_cli::Result<()>,
+) -> Result<()> {
+    let mut failures = 0;
+    for user in users {
+        match request(user) {
+            Ok(()) => log::info!("{action} {user}: OK"),
+            Err(err) => {
+                failures += 1;
+                log::warn!("Unable to {action} {user}: {err}");
+            }
+        }
+    }
+    if failures > 0 {
+        bail!("Unable to {action} {failures} of {} users", users.len());
+    }
+    Ok(())
+}
+
+fn list_pending_clearances(
+    client: &OfdbClient,
+    offset: Option<u64>,
+    limit: Option<u64>,
+    json: bool,
+) -> Result<()> {
+    let pending = client.pending_clearances(offset, limit)?;
+    let uuids = pending
+        .iter()
+        .map(|p| p.place_id.parse::<Uuid>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let entries = client.read_entries(uuids)?;
+    let pending = clearance::pending_entries(pending, &entries);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&pending)?);
+        return Ok(());
+    }
+    let revision = |r: Option<u64>| r.map_or_else(|| "-".to_string(), |r| r.to_string());
+    for p in &pending {
+        println!(
+            "{}  {}  (revision {}, last cleared {}, since {})  {}",
+            p.id,
+            p.title,
+            revision(p.current_revision),
+            revision(p.last_cleared_revision),
+            format_timestamp(p.created_at),
+            p.url
+        );
+    }
+    log::info!("{} entries are waiting for a clearance", pending.len());
+    Ok(())
+}
+
+fn resolve_clearances(client: &OfdbClient, until: Option<u64>, uuids: Vec<Uuid>) -> Result<()> {
+    let entries = match until {
+        Some(_) => vec![],
+        None => client.read_entries(uuids.clone())?,
+    };
+    let clearances = clearance::clearances(&uuids, until, &entries)?;
+    let count = client.clear_places(&clearances)?;
+    log::info!("Cleared {count} of {} entries", clearances.len());
+    Ok(())
+}
+
+fn format_timestamp(millis: i64) -> String {
+    OffsetDateTime::from_unix_timestamp_nanos(i128::from(millis) * 1_000_000)
+        .map(|t| t.to_string())
+        .unwrap_or_else(|_| millis.to_string())
+}
+
+fn mirror(
+    source_client: &OfdbClient,
+    target_client: &OfdbClient,
+    tag: String,
+    id_mapping_path: PathBuf,
+    report_file_path: PathBuf,
+    deadline: Deadline,
+) -> Result<()> {
+    use ofdb_cli::mirror::*;
+
+    let mut mapping: IdMapping = if id_mapping_path.exists() {
+        let file = File::open(&id_mapping_path)?;
+        serde_json::from_reader(io::BufReader::new(file))?
+    } else {
+        log::info!(
+            "ID mapping file {} does not exist yet",
+            id_mapping_path.display()
+        );
+        IdMapping::default()
+    };
+
+    let bbox = world_bbox();
+    log::info!(
+        "Search entries tagged with '{tag}' on {}",
+        source_client.api_url()
+    );
+    let source_entries: Vec<_> = search_entries(source_client, &format!("#{tag}"), &bbox)?
+        .into_iter()
+        .filter(|e| e.tags.contains(&tag))
+        .collect();
+    log::info!("Found {} entries to mirror", source_entries.len());
+
+    let target_uuids = source_entries
+        .iter()
+        .filter_map(|e| mapping.entries.get(&e.id))
+        .map(|m| m.target_id.parse::<Uuid>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let target_entries = target_client.read_entries(target_uuids)?;
+
+    let mut report = MirrorReport::default();
+    let mut deadline_exceeded = false;
+    for source in source_entries {
+        if deadline.is_expired() {
+            deadline_exceeded = true;
+            break;
+        }
+        let mapped = mapping.entries.get(&source.id).cloned();
+        let target = mapped
+            .as_ref()
+            .and_then(|m| target_entries.iter().find(|e| e.id == m.target_id));
+        let source_id = source.id.clone();
+        let source_version = source.version;
+        let title = source.title.clone();
+        let action = plan(&source, mapped.as_ref(), target.map(|t| t.version));
+        let result = match action {
+            Action::Create => target_client
+                .create_place(&new_place_from_entry(source))
+                .map(|target_id| (target_id, 0)),
+            Action::Update => {
+                let target = target.expect("mapped target entry");
+                let mut update = UpdatePlace::from(source);
+                update.version = target.version;
+                target_client
+                    .update_place(&target.id, &update)
+                    .map(|target_id| (target_id, target.version + 1))
+            }
+            Action::Unchanged | Action::Conflict => {
+                let target_id = mapped.expect("mapped entry").target_id;
+                let entry = MirroredEntry {
+                    source_id,
+                    target_id,
+                    title,
+                };
+                if action == Action::Conflict {
+                    log::warn!(
+                        "'{}' was modified on the target instance (ID={})",
+                        entry.title,
+                        entry.target_id
+                    );
+                    report.conflicts.push(entry);
+                } else {
+                    report.unchanged.push(entry);
+                }
+                continue;
+            }
+        };
+        match result {
+            Ok((target_id, target_version)) => {
+                log::debug!("Mirrored '{title}' to ID={target_id}");
+                mapping.entries.insert(
+                    source_id.clone(),
+                    MappedEntry {
+                        target_id: target_id.clone(),
+                        source_version,
+                        target_version,
+                    },
+                );
+                // An interrupted run must not create the entry again
+                save_id_mapping(&id_mapping_path, &mapping)?;
+                let entry = MirroredEntry {
+                    source_id,
+                    target_id,
+                    title,
+                };
+                if action == Action::Create {
+                    report.created.push(entry);
+                } else {
+                    report.updated.push(entry);
+                }
+            }
+            Err(err) => {
+                log::warn!("Could not mirror '{title}': {err}");
+                report.failures.push(MirrorFailure {
+                    source_id,
+                    title,
+                    error: err.to_string(),
+                });
+            }
+        }
+    }
+    save_id_mapping(&id_mapping_path, &mapping)?;
+
+    log::info!(
+        "Created {}, updated {} and skipped {} unchanged entries",
+        report.created.len(),
+        report.updated.len(),
+        report.unchanged.len()
+    );
+    if !report.conflicts.is_empty() {
+        log::warn!("{} entries have conflicts", report.conflicts.len());
+    }
+    if !report.failures.is_empty() {
+        log::warn!("{} entries could not be mirrored", report.failures.len());
+    }
+    let file = File::create(report_file_path)?;
+    serde_json::to_writer_pretty(io::BufWriter::new(file), &report)?;
+    if deadline_exceeded {
+        return Err(DeadlineExceeded.into());
+    }
+    Ok(())
+}
+
+/// Write the ID mapping to a temporary file first,
+/// so that an interrupted mirror never leaves a broken mapping file.
+fn save_id_mapping(path: &Path, mapping: &ofdb_cli::mirror::IdMapping) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut w = io::BufWriter::new(File::create(&tmp)?);
+    serde_json::to_writer_pretty(&mut w, mapping)?;
+    w.flush()?;
+    drop(w);
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+struct ExportOptions {
+    format: ExportFormat,
+    markdown: markdown::MarkdownOptions,
+    with_computed_columns: bool,
+    /// Use the export endpoint instead of searching and reading the entries.
+    server_export: bool,
+}
+
+fn export(
+    client: &OfdbClient,
+    bbox: MapBbox,
+    text: &str,
+    path: PathBuf,
+    options: ExportOptions,
+    credentials: Option<Credentials>,
+) -> Result<()> {
+    let login = || match &credentials {
+        Some(credentials) => client
+            .login(credentials)
+            .map_err(|err| anyhow!("Unable to login: {err}")),
+        None => Ok(()),
+    };
+    let (entries, statuses) = if options.server_export {
+        login()?;
+        log::info!("Read entries from the export endpoint");
+        let entries = client
+            .export_places(&bbox, text)?
+            .collect::<Result<Vec<_>, _>>()?;
+        (entries, HashMap::new())
+    } else {
+        let query = search::SearchQuery {
+            text: text.to_string(),
+            bbox,
+            ..Default::default()
+        };
+        let results = client.search_all(query).collect::<Result<Vec<_>, _>>()?;
+        let uuids = results
+            .iter()
+            .map(|e| e.id.parse::<Uuid>())
+            .collect::<Result<Vec<_>, _>>()?;
+        let statuses: HashMap<_, _> = results
+            .into_iter()
+            .filter_map(|r| Some((r.id, r.status?)))
+            .collect();
+        (client.read_entries(uuids)?, statuses)
+    };
+
+    let computed = if options.with_computed_columns {
+        if credentials.is_some() {
+            if !options.server_export {
+                login()?;
+            }
+        } else {
+            log::warn!(
+                "Without a login days_since_created is only known for entries without any update"
+            );
+        }
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut computed = HashMap::new();
+        for entry in &entries {
+            let status = statuses.get(&entry.id).cloned();
+            let created = if credentials.is_some() && entry.version > 0 {
+                let history = client.place_history(entry.id.parse()?)?;
+                history
+                    .revisions
+                    .iter()
+                    .map(|(revision, _)| revision.created.at / 1000)
+                    .min()
+            } else {
+                None
+            };
+            let columns = csv::ComputedColumns::new(entry, created, status, now);
+            computed.insert(entry.id.clone(), columns);
         }
+        Some(computed)
+    } else {
+        None
+    };
+
+    log::info!("Write {} entries to {}", entries.len(), path.display());
+    let w = io::BufWriter::new(File::create(path)?);
+    match options.format {
+        ExportFormat::Csv => csv::write_places(w, &entries, computed.as_ref())?,
+        ExportFormat::Md => markdown::write_entries(w, &entries, &options.markdown)?,
+        ExportFormat::Kml => kml::write_entries(w, &entries)?,
+        ExportFormat::Gpx => gpx::write_entries(w, &entries)?,
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => parquet_export::write_entries(w, &entries)?,
+        #[cfg(not(feature = "parquet"))]
+        ExportFormat::Parquet => unreachable!("checked by the export command"),
     }
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    if env::var("RUST_LOG").is_err() {
-        env::set_var("RUST_LOG", "info");
+/// Read all entries that are found by a search.
+///
+/// The search is split into several requests if the server limits the number of results.
+fn search_entries(client: &OfdbClient, text: &str, bbox: &MapBbox) -> Result<Vec<Entry>> {
+    let query = search::SearchQuery {
+        text: text.to_string(),
+        bbox: *bbox,
+        ..Default::default()
+    };
+    let mut uuids = vec![];
+    for result in client.search_all(query) {
+        uuids.push(result?.id.parse::<Uuid>()?);
     }
-    pretty_env_logger::init();
-    let args = Cli::parse();
+    Ok(client.read_entries(uuids)?)
+}
 
-    use SubCommand as C;
-    match args.cmd {
-        C::Import {
-            file,
-            report_file,
-            opencage_api_key,
-            ignore_duplicates,
-        } => import(
-            &args.opt.api,
-            file,
-            report_file,
-            opencage_api_key,
-            ignore_duplicates,
-        ),
-        C::Read { uuids } => read(&args.opt.api, uuids),
-        C::Update {
-            file,
-            report_file,
-            patch,
-        } => update(&args.opt.api, file, report_file, patch),
-        C::Review {
-            email,
-            password,
-            file,
-        } => review(&args.opt.api, email, password, file),
+/// Combine a search text and tags (`#tag`).
+fn search_text(text: &str, tags: &[String]) -> String {
+    let mut words: Vec<_> = tags
+        .iter()
+        .map(|tag| format!("#{}", tag.trim_start_matches('#')))
+        .collect();
+    if !text.trim().is_empty() {
+        words.insert(0, text.trim().to_string());
     }
+    words.join(" ")
 }
 
-fn read(api: &str, uuids: Vec<Uuid>) -> Result<()> {
-    let client = new_client()?;
-    let entries = read_entries(api, &client, uuids)?;
-    println!("{}", serde_json::to_string(&entries)?);
+fn check_links(
+    client: &OfdbClient,
+    http: &reqwest::blocking::Client,
+    bbox: MapBbox,
+    text: &str,
+    concurrency: usize,
+    out: PathBuf,
+    patch_file: Option<PathBuf>,
+) -> Result<()> {
+    let entries = search_entries(client, text, &bbox)?;
+    let links = link_check::links(&entries);
+    log::info!("Check {} links of {} entries", links.len(), entries.len());
+    let mut progress = progress::Progress::new("Check links", links.len());
+    let broken = link_check::check_links(http, links, concurrency, |ok| {
+        progress.inc(if ok {
+            progress::Outcome::Success
+        } else {
+            progress::Outcome::Failure
+        })
+    });
+    progress.finish();
+    log::info!("Write {} broken links to {}", broken.len(), out.display());
+    link_check::write_report(io::BufWriter::new(File::create(out)?), &broken)?;
+    if let Some(path) = patch_file {
+        log::info!("Write patches to {}", path.display());
+        link_check::write_patches(io::BufWriter::new(File::create(path)?), &broken)?;
+    }
     Ok(())
 }
 
-fn update(api: &str, path: PathBuf, report_file_path: PathBuf, patch: bool) -> Result<()> {
-    let ext = path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .ok_or_else(|| anyhow!("Unsupported file extension"))?;
-    let file_type = ext.parse()?;
+fn audit(
+    client: &OfdbClient,
+    bbox: MapBbox,
+    text: &str,
+    min_score: u32,
+    out: PathBuf,
+) -> Result<()> {
+    let entries = search_entries(client, text, &bbox)?;
+    log::info!("Audit {} entries", entries.len());
+    let mut records = audit::audit(&entries);
+    records.retain(|r| r.score >= min_score);
     log::info!(
-        "Update entries from file ({}): {}",
-        format!("{:?}", file_type).to_uppercase(),
-        path.display()
+        "Write {} suspicious entries to {}",
+        records.len(),
+        out.display()
     );
-    let file = File::open(path)?;
-    let reader = io::BufReader::new(file);
+    audit::write_audit(io::BufWriter::new(File::create(out)?), &records)
+}
 
-    let client = new_client()?;
+fn enrich(
+    client: &OfdbClient,
+    http: &reqwest::blocking::Client,
+    ids_file: &Path,
+    source: enrich::Source,
+    options: &enrich::EnrichOptions,
+    out: PathBuf,
+) -> Result<()> {
+    log::info!("Read IDs from file: {}", ids_file.display());
+    let uuids = csv::uuids_from_reader(io::BufReader::new(File::open(ids_file)?))?;
+    let entries = client.read_entries(uuids)?;
+    log::info!("Look up {} entries on {source}", entries.len());
+    let mut progress = progress::Progress::new("Look up entries", entries.len());
+    let proposals = enrich::enrich(http, source, &entries, options, |ok| {
+        progress.inc(if ok {
+            progress::Outcome::Success
+        } else {
+            progress::Outcome::Failure
+        })
+    });
+    progress.finish();
+    log::info!("Write {} proposals to {}", proposals.len(), out.display());
+    enrich::write_proposals(io::BufWriter::new(File::create(out)?), &proposals)
+}
 
-    let places = match file_type {
-        FileType::Json => {
-            if patch {
-                bail!("Patch updates are currently not supported for JSON files");
-            }
-            let places: Vec<Entry> = serde_json::from_reader(reader)?;
-            log::debug!("Read {} places from JSON file", places.len());
-            places
-        }
-        FileType::Csv => {
-            let csv_results = if patch {
-                csv::patch_places_with_reader(reader, api, &client)?
-            } else {
-                csv::places_from_reader(reader)?
-            };
-            if csv_results.iter().any(|r| r.result.is_err()) {
-                let report = Report::from(csv_results.clone());
-                log::warn!(
-                    "{} csv records contain errors ",
-                    report.csv_import_failures.len()
-                );
-                write_import_report(report, report_file_path)?;
-            }
-            let places: Vec<_> = csv_results
-                .into_iter()
-                .filter_map(|r| r.result.ok())
-                .collect();
-            log::debug!("Import {} places from CSV file", places.len());
-            places
-        }
+fn dedupe(
+    client: &OfdbClient,
+    bbox: MapBbox,
+    text: &str,
+    options: dedupe::DedupeOptions,
+    out: PathBuf,
+) -> Result<()> {
+    let entries = search_entries(client, text, &bbox)?;
+    log::info!("Search duplicates in {} entries", entries.len());
+    let records = dedupe::duplicates(&entries, options);
+    log::info!(
+        "Write {} entries in {} clusters to {}",
+        records.len(),
+        records.last().map_or(0, |r| r.cluster),
+        out.display()
+    );
+    dedupe::write_duplicates(io::BufWriter::new(File::create(out)?), &records)
+}
+
+fn parse_bbox(s: &str) -> Result<MapBbox> {
+    let coords = s
+        .split(',')
+        .map(|c| c.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| anyhow!("Invalid bounding box '{s}': {err}"))?;
+    let [sw_lat, sw_lng, ne_lat, ne_lng] = coords[..] else {
+        bail!("A bounding box needs four coordinates: sw_lat,sw_lng,ne_lat,ne_lng");
     };
+    Ok(MapBbox {
+        sw: MapPoint {
+            lat: sw_lat,
+            lng: sw_lng,
+        },
+        ne: MapPoint {
+            lat: ne_lat,
+            lng: ne_lng,
+        },
+    })
+}
 
-    for entry in places {
-        let id = entry.id.clone();
-        let update = UpdatePlace::from(entry);
-        match update_place(api, &client, &id, &update) {
-            Ok(updated_id) => {
-                debug_assert!(updated_id == id);
-                log::debug!("Successfully updated '{}' with ID={}", update.title, id);
-            }
-            Err(err) => {
-                log::warn!("Could not update '{}': {err}", update.title);
+fn parse_country(s: &str) -> Result<MapBbox> {
+    csv::country_bbox(s).ok_or_else(|| {
+        anyhow!(
+            "Unknown country '{s}', supported are: {}",
+            csv::COUNTRY_CODES.join(", ")
+        )
+    })
+}
+
+/// Store the geocoding requests of the run and print the quota usage.
+fn save_geocoder_usage(usage: &GeocoderUsage) {
+    log::info!("{usage}");
+    if let Err(err) = usage.save() {
+        log::warn!(
+            "Unable to save the geocoder usage to {}: {err}",
+            usage.path().display()
+        );
+    }
+}
+
+#[cfg(feature = "mock-server")]
+fn serve_mock_server(entries: Vec<Entry>, addr: &str) -> Result<()> {
+    ofdb_cli::mock_server::MockServer::new(entries).serve(addr)
+}
+
+#[cfg(not(feature = "mock-server"))]
+fn serve_mock_server(_: Vec<Entry>, _: &str) -> Result<()> {
+    unreachable!("checked by the mock-server command")
+}
+
+/// Query the version of the server, warn if it doesn't fit to the API types
+/// and use the optional features of the server.
+fn with_server_check(client: OfdbClient) -> OfdbClient {
+    match compat::server_info(&client) {
+        Ok(info) => {
+            log::debug!("Server version: {}", info.version_text);
+            if let Some(warning) = info.warning() {
+                log::warn!("{warning}");
             }
+            client.with_capabilities(info.capabilities)
+        }
+        Err(err) => {
+            log::warn!("Unable to read the server version: {err}");
+            client
         }
     }
-    Ok(())
 }
 
-fn import(
-    api: &str,
-    path: PathBuf,
-    report_file_path: PathBuf,
+fn doctor(
+    api: Option<&str>,
+    http_options: &http::HttpOptions,
     opencage_api_key: Option<String>,
-    ignore_duplicates: bool,
+    credentials: Option<Credentials>,
 ) -> Result<()> {
-    let ext = path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .ok_or_else(|| anyhow!("Unsupported file extension"))?;
-    let file_type = ext.parse()?;
+    use crate::cli::doctor::*;
+
+    let (api_check, api) = check_api_url(api);
+    let mut checks = vec![api_check];
+    if let Some(api) = api {
+        let client = OfdbClient::with_http_options(api, http_options)?;
+        let connection = check_connection(&client);
+        let connected = connection.status == CheckStatus::Ok;
+        checks.push(connection);
+        if connected {
+            checks.push(check_server_version(&client));
+            checks.push(check_login(&client, credentials));
+        }
+    }
+    checks.push(check_opencage_api_key(opencage_api_key));
+    for check in &checks {
+        println!("{check}");
+    }
+    let failed = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Failed)
+        .count();
+    if failed > 0 {
+        bail!("{failed} checks failed");
+    }
+    Ok(())
+}
+
+fn export_links(client: &OfdbClient, uuids: Vec<Uuid>, path: PathBuf) -> Result<()> {
+    let entries = client.read_entries(uuids)?;
+    let records = csv::custom_link_records(&entries);
     log::info!(
-        "Import entries from file ({}): {}",
-        format!("{:?}", file_type).to_uppercase(),
+        "Write {} custom links of {} entries to {}",
+        records.len(),
+        entries.len(),
         path.display()
     );
-    if ignore_duplicates {
-        log::warn!("Ignore duplicates: create a new entry, even if it becomes a duplicate");
+    let file = File::create(path)?;
+    csv::write_custom_link_records(io::BufWriter::new(file), &records)?;
+    Ok(())
+}
+
+fn generate_fixtures(
+    count: usize,
+    seed: u64,
+    options: &fixtures::FixtureOptions,
+    dir: &Path,
+) -> Result<()> {
+    let fixtures::Fixtures {
+        new_places,
+        entries,
+    } = fixtures::generate_with(count, seed, options);
+    std::fs::create_dir_all(dir)?;
+    log::info!("Write {count} fake entries to {}", dir.display());
+
+    let file = File::create(dir.join("new-places.json"))?;
+    serde_json::to_writer_pretty(io::BufWriter::new(file), &new_places)?;
+    let file = File::create(dir.join("new-places.csv"))?;
+    csv::write_new_places(io::BufWriter::new(file), &new_places)?;
+
+    let file = File::create(dir.join("entries.json"))?;
+    serde_json::to_writer_pretty(io::BufWriter::new(file), &entries)?;
+    let file = File::create(dir.join("entries.csv"))?;
+    csv::write_places(io::BufWriter::new(file), &entries, None)?;
+    Ok(())
+}
+
+/// Write fake places to a CSV file or a JSON file (if the name ends with `.json`).
+fn generate_places(
+    count: usize,
+    seed: u64,
+    options: &fixtures::FixtureOptions,
+    out: &Path,
+) -> Result<()> {
+    let new_places = fixtures::generate_with(count, seed, options).new_places;
+    log::info!("Write {count} fake places to {}", out.display());
+    let file = io::BufWriter::new(File::create(out)?);
+    if out.extension().is_some_and(|ext| ext == "json") {
+        serde_json::to_writer_pretty(file, &new_places)?;
+    } else {
+        csv::write_new_places(file, &new_places)?;
     }
+    Ok(())
+}
+
+fn import_ratings(
+    client: &OfdbClient,
+    path: PathBuf,
+    report_file: ReportFile,
+    deadline: Deadline,
+) -> Result<()> {
+    log::info!("Read ratings from file: {}", path.display());
     let file = File::open(path)?;
     let reader = io::BufReader::new(file);
-    let places = match file_type {
-        FileType::Json => {
-            let places: Vec<NewPlace> = serde_json::from_reader(reader)?;
-            log::debug!("Import {} places from JSON file", places.len());
-            places
-        }
-        FileType::Csv => {
-            let csv_results = csv::new_places_from_reader(reader, opencage_api_key)?;
-            if csv_results.iter().any(|r| r.result.is_err()) {
-                let report = Report::from(csv_results);
-                log::warn!(
-                    "{} csv records contain errors ",
-                    report.csv_import_failures.len()
-                );
-                write_import_report(report, report_file_path)?;
-                return Ok(());
-            } else {
-                let places: Vec<NewPlace> =
-                    csv_results.into_iter().map(|r| r.result.unwrap()).collect();
-                log::debug!("Import {} places from CSV file", places.len());
-                places
-            }
-        }
-    };
-    let client = new_client()?;
+    let csv_results = csv::ratings_from_reader(reader)?;
+    let csv_report = Report::from(csv_results.clone());
+    if !csv_report.csv_import_failures.is_empty() {
+        log::warn!(
+            "{} csv records contain errors ",
+            csv_report.csv_import_failures.len()
+        );
+    }
+    let ratings: Vec<_> = csv_results
+        .into_iter()
+        .filter_map(|r| r.result.ok().map(|rating| (r.record_nr, rating)))
+        .collect();
+    log::info!("{} ratings where found in CSV file", ratings.len());
     let mut results = vec![];
-    for (i, new_place) in places.iter().enumerate() {
-        let import_id = Some(i.to_string());
-
-        let possible_duplicates = if ignore_duplicates {
-            None
-        } else {
-            search_duplicates(api, &client, new_place)?
-        };
-
-        if let Some(possible_duplicates) = possible_duplicates {
-            log::warn!(
-                "Found {} possible duplicates for '{}':",
-                possible_duplicates.len(),
-                new_place.title
-            );
-            for p in &possible_duplicates {
-                log::warn!(" - {} (id: {})", p.title, p.id);
-            }
-            results.push(ImportResult {
-                new_place,
-                import_id,
-                result: Err(Error::Duplicates(possible_duplicates)),
-            });
-            continue;
+    let mut deadline_exceeded = false;
+    for (record_nr, rating) in &ratings {
+        if deadline.is_expired() {
+            deadline_exceeded = true;
+            break;
         }
-        match create_new_place(api, &client, new_place) {
-            Ok(id) => {
-                log::debug!("Successfully imported '{}' with ID={}", new_place.title, id);
-                results.push(ImportResult {
-                    new_place,
+        let import_id = Some(record_nr.to_string());
+        match client.create_rating(rating) {
+            Ok(()) => {
+                log::debug!(
+                    "Successfully rated entry {} with '{}'",
+                    rating.entry,
+                    rating.title
+                );
+                results.push(RatingResult {
+                    rating,
                     import_id,
-                    result: Ok(id),
+                    result: Ok(()),
                 });
             }
             Err(err) => {
-                log::warn!("Could not import '{}': {}", new_place.title, err);
-                results.push(ImportResult {
-                    new_place,
+                log::warn!("Could not rate entry {}: {err}", rating.entry);
+                results.push(RatingResult {
+                    rating,
                     import_id,
-                    result: Err(Error::Other(err.to_string())),
+                    result: Err(import::Error::from(err)),
                 });
             }
         }
     }
-    let report = Report::from(results);
+    let mut report = Report::from(results);
+    report.csv_import_failures = csv_report.csv_import_failures;
     if !report.successes.is_empty() {
-        log::info!("Successfully imported {} places", report.successes.len());
+        log::info!("Successfully imported {} ratings", report.successes.len());
     }
-    if !report.duplicates.is_empty() {
-        log::warn!(
-            "Found {} places with possible duplicates",
-            report.duplicates.len()
+    if !report.failures.is_empty() {
+        log::warn!("{} ratings could not be imported", report.failures.len());
+    }
+    report_file.write(&report)?;
+    if deadline_exceeded {
+        return Err(DeadlineExceeded.into());
+    }
+    Ok(())
+}
+
+/// Wrap places that were read from a JSON file,
+/// so that they can be processed like CSV records.
+/// The selected places of a JSON file (see [filter::RecordFilter::select_items]).
+fn json_results<T>(places: Vec<T>, filter: &filter::RecordFilter) -> Vec<CsvImportResult<T>> {
+    filter
+        .select_items(places)
+        .into_iter()
+        .map(|(record_nr, place)| CsvImportResult::new(record_nr, Ok(place)))
+        .collect()
+}
+
+/// Open the state file of a run that can be resumed.
+fn open_state(path: Option<PathBuf>, command: &str) -> Result<Option<state::RunState>> {
+    Ok(path
+        .map(|path| state::RunState::open(&path, command))
+        .transpose()?)
+}
+
+/// Skip the records that were processed by a previous run with the same state file.
+fn skip_processed(filter: &mut filter::RecordFilter, state: Option<&state::RunState>, file: &str) {
+    let Some(state) = state else {
+        return;
+    };
+    let processed = state.processed(file);
+    if !processed.is_empty() {
+        log::info!(
+            "Skip the {} records that were already processed (see the state file)",
+            processed.len()
         );
     }
-    if !report.failures.is_empty() {
-        log::warn!("{} places contain errors ", report.failures.len());
+    filter.skip.extend(processed);
+}
+
+/// The report of an input file
+/// and an error that should stop the run after the report is written.
+struct FileOutcome<T> {
+    report: Report<T, SuccessReport<T>>,
+    error: Option<anyhow::Error>,
+}
+
+/// Process the files one after another and write a single report.
+fn process_files<T>(
+    files: Vec<PathBuf>,
+    report_file: ReportFile,
+    mut process: impl FnMut(PathBuf) -> Result<FileOutcome<T>>,
+) -> Result<()>
+where
+    T: report::ReportRecord + Serialize,
+{
+    let several_files = files.len() > 1;
+    let mut reports = vec![];
+    let mut error = None;
+    for path in files {
+        let file = path.display().to_string();
+        match process(path) {
+            Ok(FileOutcome { report, error: e }) => {
+                reports.push(report::FileReport { file, report });
+                if e.is_some() {
+                    error = e;
+                    break;
+                }
+            }
+            Err(err) => {
+                // The entries of the previous files are reported anyway
+                error = Some(err);
+                break;
+            }
+        }
+    }
+    let written = match reports.as_slice() {
+        [] => Ok(()),
+        [r] if !several_files => report_file.write(&r.report),
+        _ => report_file.write_files(&reports),
+    };
+    match error {
+        Some(err) => {
+            if let Err(write_err) = written {
+                log::error!("Unable to write the report: {write_err}");
+            }
+            Err(err)
+        }
+        None => written,
+    }
+}
+
+/// A client for other services than the API.
+fn http_client(options: &http::HttpOptions) -> Result<reqwest::blocking::Client> {
+    let builder = options.apply(reqwest::blocking::Client::builder())?;
+    Ok(builder
+        .user_agent(concat!("ofdb-cli/", env!("CARGO_PKG_VERSION")))
+        .build()?)
+}
+
+/// Expand glob patterns like `data/*.csv`.
+fn input_files(args: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for arg in args {
+        let pattern = arg.to_string_lossy();
+        if arg.exists() || input_url(&pattern).is_some() || !pattern.contains(['*', '?', '[']) {
+            files.push(arg);
+            continue;
+        }
+        let mut matches = glob::glob(&pattern)?.collect::<Result<Vec<_>, _>>()?;
+        if matches.is_empty() {
+            bail!("No file matches '{pattern}'");
+        }
+        matches.sort();
+        files.append(&mut matches);
+    }
+    Ok(files)
+}
+
+fn input_url(arg: &str) -> Option<reqwest::Url> {
+    reqwest::Url::parse(arg)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// Open a file, an HTTP(S) URL or `-` (stdin).
+///
+/// The file type is detected by the extension
+/// or, if there is none, by the content.
+fn open_input(
+    path: &Path,
+    http: &reqwest::blocking::Client,
+) -> Result<(FileType, Box<dyn BufRead>)> {
+    let arg = path.to_string_lossy();
+    let (ext, reader): (_, Box<dyn Read>) = if arg == "-" {
+        (None, Box::new(io::stdin()))
+    } else if let Some(url) = input_url(&arg) {
+        log::info!("Download {url}");
+        let ext = Path::new(url.path())
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned());
+        let res = http.get(url).send()?.error_for_status()?;
+        (ext, Box::new(res))
+    } else {
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned());
+        (ext, Box::new(File::open(path)?))
+    };
+    let mut reader = io::BufReader::new(reader);
+    let file_type = match ext {
+        Some(ext) => ext.parse()?,
+        None => {
+            let content = reader.fill_buf()?;
+            match content.iter().find(|b| !b.is_ascii_whitespace()) {
+                Some(b'[') => FileType::Json,
+                Some(b'<') => FileType::Kml,
+                _ => FileType::Csv,
+            }
+        }
+    };
+    Ok((file_type, Box::new(reader)))
+}
+
+/// File and format of a report.
+fn read_json_reports(files: Vec<PathBuf>) -> Result<Vec<report::FileReport<serde_json::Value>>> {
+    let mut reports = vec![];
+    for path in input_files(files)? {
+        let file = io::BufReader::new(File::open(&path)?);
+        let name = path.display().to_string();
+        reports.extend(
+            report::read_json_report(file, &name)
+                .map_err(|err| anyhow!("Unable to read the report {name}: {err}"))?,
+        );
+    }
+    Ok(reports)
+}
+
+/// The reports of the input files for `--only-failures`.
+///
+/// The report of a single input file has no sections,
+/// so it can only be used for a single input file.
+fn failure_reports(
+    path: &Path,
+    files: &[PathBuf],
+) -> Result<Vec<report::FileReport<serde_json::Value>>> {
+    let mut reports = read_json_reports(vec![path.to_path_buf()])?;
+    let name = path.display().to_string();
+    if let [single] = reports.as_mut_slice() {
+        if single.file == name {
+            let [file] = files else {
+                bail!(
+                    "The report {name} belongs to a single file, but there are {} input files",
+                    files.len()
+                );
+            };
+            single.file = file.display().to_string();
+        }
+    }
+    Ok(reports)
+}
+
+/// The report of a file (or the only report).
+fn report_section<'a>(
+    reports: &'a [report::FileReport<serde_json::Value>],
+    file: &str,
+) -> Result<&'a report::JsonReport> {
+    if let [single] = reports {
+        return Ok(&single.report);
+    }
+    let section = reports.iter().find(|r| r.file == file).ok_or_else(|| {
+        let files: Vec<_> = reports.iter().map(|r| r.file.as_str()).collect();
+        anyhow!(
+            "The report has no section '{file}' (available: {})",
+            files.join(", ")
+        )
+    })?;
+    Ok(&section.report)
+}
+
+fn failures_to_csv(
+    report_path: &Path,
+    source: Option<&Path>,
+    file: Option<String>,
+    out: &Path,
+    with_duplicates: bool,
+) -> Result<()> {
+    let reports = read_json_reports(vec![report_path.to_path_buf()])?;
+    let file = file
+        .or_else(|| source.map(|source| source.display().to_string()))
+        .unwrap_or_default();
+    let report = report_section(&reports, &file)?;
+    let count = match source {
+        Some(source) => report::write_failed_records(
+            report,
+            File::open(source)?,
+            io::BufWriter::new(File::create(out)?),
+            with_duplicates,
+        )?,
+        None => {
+            let (places, unreadable) = report::failed_new_places(report, with_duplicates)?;
+            if unreadable > 0 {
+                log::warn!(
+                    "{unreadable} records could not be read from the source file, \
+                     use --source to copy them as well"
+                );
+            }
+            csv::write_failed_new_places(io::BufWriter::new(File::create(out)?), &places)?;
+            places.len()
+        }
+    };
+    log::info!("Wrote {count} failed records to {}", out.display());
+    Ok(())
+}
+
+fn report_mail(config: &config::Config, to: &[String]) -> Result<Option<Rc<email::ReportMail>>> {
+    if to.is_empty() {
+        return Ok(None);
+    }
+    let smtp = config.smtp.as_ref().ok_or_else(|| {
+        anyhow!("Sending reports by e-mail requires the [smtp] section in the config file")
+    })?;
+    Ok(Some(Rc::new(email::ReportMail::new(smtp, to)?)))
+}
+
+struct ReportFile {
+    path: PathBuf,
+    format: ReportFormat,
+    mail: Option<Rc<email::ReportMail>>,
+    summary_json: bool,
+}
+
+impl ReportFile {
+    fn new(path: PathBuf, format: ReportFormat) -> Self {
+        Self {
+            path,
+            format,
+            mail: None,
+            summary_json: false,
+        }
+    }
+
+    fn with_mail(mut self, mail: Option<Rc<email::ReportMail>>) -> Self {
+        self.mail = mail;
+        self
     }
-    write_import_report(report, report_file_path)?;
+
+    fn with_summary_json(mut self, summary_json: bool) -> Self {
+        self.summary_json = summary_json;
+        self
+    }
+
+    fn write<T>(&self, report: &Report<T, SuccessReport<T>>) -> Result<()>
+    where
+        T: report::ReportRecord + Serialize,
+    {
+        let file = File::create(&self.path)?;
+        report::write_report(io::BufWriter::new(file), report, self.format)?;
+        let mut summary = report::Summary::default();
+        summary.add(report);
+        self.send(summary)
+    }
+
+    fn write_files<T>(&self, reports: &[report::FileReport<T>]) -> Result<()>
+    where
+        T: report::ReportRecord + Serialize,
+    {
+        let file = File::create(&self.path)?;
+        report::write_file_reports(io::BufWriter::new(file), reports, self.format)?;
+        let mut summary = report::Summary::default();
+        for r in reports {
+            summary.add(&r.report);
+        }
+        self.send(summary)
+    }
+
+    fn send(&self, summary: report::Summary) -> Result<()> {
+        if self.summary_json {
+            let report = self.path.display().to_string();
+            println!("{}", summary.json_line(Some(&report))?);
+        }
+        let Some(mail) = &self.mail else {
+            return Ok(());
+        };
+        let subject = format!(
+            "OpenFairDB report: {} successes, {} duplicates, {} failures",
+            summary.successes,
+            summary.duplicates,
+            summary.failures + summary.invalid_records
+        );
+        let text = format!(
+            "{summary}\n\nThe report was written to {}.\n",
+            self.path.display()
+        );
+        mail.send(&subject, &text, &self.path, self.format)
+    }
+}
+
+/// Login and remember the user as the current user of the instance.
+fn login(api: &str, email: String, password: Option<String>, opt: &Opt) -> Result<()> {
+    let session = session::Session::open(api, &email)?;
+    let password = match (password, session.load_credentials()?) {
+        (Some(password), _) => password,
+        (None, Some(stored)) => stored.password,
+        (None, None) => prompt_password(&email)?,
+    };
+    let (_, session) = login_session(api, Some(email), Some(password), opt)?;
+    session.set_current()?;
+    log::info!(
+        "Logged in, the session is stored in {}",
+        session.dir().display()
+    );
     Ok(())
 }
 
-fn review(api: &str, email: String, password: String, path: PathBuf) -> Result<()> {
-    let _ = EmailAddress::parse(&email, None)
-        .ok_or(anyhow::anyhow!("Invalid email address '{email}'"))?;
-    log::info!("Read reviews from file: {}", path.display());
-    let file = File::open(path)?;
-    let reader = io::BufReader::new(file);
-    let reviews = csv::reviews_from_reader(reader)?;
-    log::info!("{} reviews where found in CSV file", reviews.len());
-    let client = new_client()?;
-    login(api, &client, &Credentials { email, password })
-        .map_err(|err| anyhow::anyhow!("Unable to login: {err}"))?;
-    let review_groups = review::group_reviews(reviews);
-    for (rev, uuids) in review_groups {
-        log::info!("Review the following place IDs: {uuids:#?}");
-        if let Err(err) = review_places(api, &client, uuids.into_iter().collect(), rev) {
-            log::warn!("Unable to review: {err}");
+fn logout(api: &str, email: Option<String>, opt: &Opt) -> Result<()> {
+    let email = match email {
+        Some(email) => email,
+        None => logged_in_user(api)?,
+    };
+    let session = session::Session::open(api, &email)?;
+    if session.has_cookies() {
+        if let Err(err) = opt.session_client(&session)?.logout() {
+            log::warn!("Unable to invalidate the session on the server: {err}");
         }
     }
+    session.remove()?;
+    log::info!("Logged out {email}");
     Ok(())
 }
 
-fn write_import_report<P: AsRef<Path>, T, S>(report: Report<T, S>, path: P) -> Result<()>
-where
-    T: Serialize,
-    S: Serialize,
-{
-    let file = File::create(path)?;
-    let writer = io::BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, &report)?;
+fn logged_in_user(api: &str) -> Result<String> {
+    session::current_user(api)?
+        .ok_or_else(|| anyhow!("Nobody is logged in on {api}, use 'ofdb login' or --email"))
+}
+
+/// Complete the optional credentials by asking for a missing password.
+fn credentials(email: Option<String>, password: Option<String>) -> Result<Option<Credentials>> {
+    let Some(email) = email else {
+        return Ok(None);
+    };
+    let password = match password {
+        Some(password) => password,
+        None => prompt_password(&email)?,
+    };
+    Ok(Some(Credentials { email, password }))
+}
+
+/// Ask before a destructive operation.
+///
+/// Without a terminal the operation is refused unless it is confirmed with `--yes`.
+fn confirm(question: &str, yes: bool) -> Result<()> {
+    if yes {
+        return Ok(());
+    }
+    if !io::stdin().is_terminal() {
+        bail!("{question} Confirm with --yes if stdin is not a terminal");
+    }
+    eprint!("{question} [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        bail!("Aborted");
+    }
     Ok(())
 }
 
-fn new_client() -> Result<Client> {
-    let client = Client::builder()
-        // Disable idle pool:
-        // see https://github.com/hyperium/hyper/issues/2136#issuecomment-861826148
-        .pool_max_idle_per_host(0)
-        .cookie_store(true)
-        .build()?;
-    Ok(client)
+/// Ask for the password without echoing it.
+fn prompt_password(email: &str) -> Result<String> {
+    if !io::stdin().is_terminal() {
+        bail!("A password is required for {email} (--password or OFDB_PASSWORD)");
+    }
+    let password = rpassword::prompt_password(format!("Password for {email}: "))?;
+    Ok(password)
+}
+
+/// Login with the session of the user on this instance.
+///
+/// Without a password the stored credentials are used (or the password is prompted),
+/// and if there is a stored session, the login is deferred
+/// until the server rejects the session (the password is only prompted then).
+fn login_session(
+    api: &str,
+    email: Option<String>,
+    password: Option<String>,
+    opt: &Opt,
+) -> Result<(OfdbClient, session::Session)> {
+    let email = match email {
+        Some(email) => email,
+        None => logged_in_user(api)?,
+    };
+    let _ = EmailAddress::parse(&email, None)
+        .ok_or(anyhow::anyhow!("Invalid email address '{email}'"))?;
+    let session = session::Session::open(api, &email)?;
+    let stored = session.load_credentials()?;
+    if password.is_none() && stored.is_none() && !opt.store_credentials && session.has_cookies() {
+        log::debug!("Use the stored session of {email}");
+        let prompt_email = email.clone();
+        let client = opt
+            .session_client(&session)?
+            .with_lazy_reauthentication(email, move || {
+                prompt_password(&prompt_email)
+                    .map_err(|err| ofdb_cli::Error::Unauthorized(err.to_string()))
+            });
+        return Ok((client, session));
+    }
+    let login_now = password.is_some() || !session.has_cookies();
+    let password = match (password, stored.as_ref()) {
+        (Some(password), _) => password,
+        (None, Some(stored)) => stored.password.clone(),
+        (None, None) => prompt_password(&email)?,
+    };
+    if opt.store_credentials {
+        session.store_credentials(&session::StoredCredentials {
+            email: email.clone(),
+            password: password.clone(),
+        })?;
+    }
+    let mut client = opt.session_client(&session)?;
+    if opt.store_credentials || stored.is_some() {
+        client = client.with_reauthentication(email.clone(), password.clone());
+    }
+    if login_now {
+        client
+            .login(&Credentials { email, password })
+            .map_err(|err| anyhow::anyhow!("Unable to login: {err}"))?;
+        session.save_cookies()?;
+    } else {
+        log::debug!("Use the stored session of {email}");
+    }
+    Ok((client, session))
 }
@@ -0,0 +1,230 @@
+//! Lists of entries in Markdown, e.g. for the websites and wikis of regional groups.
+
+use std::{collections::BTreeMap, io::Write, str::FromStr};
+
+use anyhow::{bail, Result};
+use ofdb_boundary::Entry;
+
+use crate::{address::address_line, i18n::lang, report::entry_url};
+
+/// Max. number of characters of the description.
+const EXCERPT_LEN: usize = 150;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    #[default]
+    Table,
+    List,
+}
+
+impl FromStr for Style {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_str() {
+            "table" => Self::Table,
+            "list" => Self::List,
+            _ => bail!("Unknown Markdown style '{s}' (expected 'table' or 'list')"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    /// An entry is listed under each of its tags.
+    Tag,
+    City,
+}
+
+impl FromStr for GroupBy {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_str() {
+            "tag" | "tags" => Self::Tag,
+            "city" => Self::City,
+            _ => bail!("Unknown grouping '{s}' (expected 'tag' or 'city')"),
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MarkdownOptions {
+    pub style: Style,
+    pub group_by: Option<GroupBy>,
+}
+
+/// Write the entries sorted by title, in sections (`## …`) if they are grouped.
+///
+/// Entries without a tag or city are listed in the last section.
+pub fn write_entries<W: Write>(
+    mut w: W,
+    entries: &[Entry],
+    options: &MarkdownOptions,
+) -> Result<()> {
+    let mut entries: Vec<_> = entries.iter().collect();
+    entries.sort_by_key(|e| e.title.to_lowercase());
+    let Some(group_by) = options.group_by else {
+        return write_group(&mut w, &entries, options.style);
+    };
+    let mut groups: BTreeMap<String, Vec<&Entry>> = BTreeMap::new();
+    let mut other = vec![];
+    for entry in entries {
+        let keys = match group_by {
+            GroupBy::Tag => entry.tags.clone(),
+            GroupBy::City => entry.city.iter().map(|c| c.trim().to_string()).collect(),
+        };
+        let keys: Vec<_> = keys.into_iter().filter(|k| !k.is_empty()).collect();
+        if keys.is_empty() {
+            other.push(entry);
+        }
+        for key in keys {
+            groups.entry(key).or_default().push(entry);
+        }
+    }
+    let other_title = lang().tr("Other").to_string();
+    let sections = groups
+        .iter()
+        .chain((!other.is_empty()).then_some((&other_title, &other)));
+    for (i, (title, entries)) in sections.enumerate() {
+        if i > 0 {
+            writeln!(w)?;
+        }
+        writeln!(w, "## {}", escape(title))?;
+        writeln!(w)?;
+        write_group(&mut w, entries, options.style)?;
+    }
+    Ok(())
+}
+
+fn write_group<W: Write>(w: &mut W, entries: &[&Entry], style: Style) -> Result<()> {
+    let tr = |label| lang().tr(label);
+    if style == Style::Table {
+        writeln!(
+            w,
+            "| {} | {} | {} | {} |",
+            tr("Title"),
+            tr("Description"),
+            tr("Address"),
+            tr("Map")
+        )?;
+        writeln!(w, "| --- | --- | --- | --- |")?;
+    }
+    for e in entries {
+        let title = escape(&e.title);
+        let description = escape(&excerpt(&e.description, EXCERPT_LEN));
+        let address = escape(&address_line(e, false));
+        let link = format!("[{}]({})", tr("Map"), entry_url(&e.id));
+        match style {
+            Style::Table => writeln!(w, "| {title} | {description} | {address} | {link} |")?,
+            Style::List => {
+                write!(w, "- **{title}**")?;
+                if !description.is_empty() {
+                    write!(w, ": {description}")?;
+                }
+                if !address.is_empty() {
+                    write!(w, " – {address}")?;
+                }
+                writeln!(w, " ({link})")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The beginning of a text in one line, shortened at a word boundary.
+fn excerpt(text: &str, max_chars: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+    let cut: String = text.chars().take(max_chars).collect();
+    let cut = cut
+        .rsplit_once(' ')
+        .map_or(cut.as_str(), |(start, _)| start);
+    format!(
+        "{}…",
+        cut.trim_end_matches(|c: char| c.is_ascii_punctuation())
+    )
+}
+
+/// Escape the characters with a meaning in Markdown (and in tables).
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '#'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<Entry> {
+        let mut entries = crate::fixtures::generate(3, 0).entries;
+        for (e, (title, city, tags)) in entries.iter_mut().zip([
+            (
+                "Unverpackt | Laden",
+                Some("Köln"),
+                vec!["zero-waste", "bio"],
+            ),
+            ("Bio-Café", Some("Berlin"), vec!["bio"]),
+            ("Repair *Café*", None, vec![]),
+        ]) {
+            e.title = title.to_string();
+            e.description = "Ein  Laden\nmit vielen Produkten".to_string();
+            e.street = Some("Hauptstr. 1".to_string());
+            e.zip = city.map(|_| "12345".to_string());
+            e.city = city.map(ToString::to_string);
+            e.tags = tags.into_iter().map(ToString::to_string).collect();
+        }
+        entries
+    }
+
+    #[test]
+    fn write_a_table() {
+        let mut md = vec![];
+        write_entries(&mut md, &entries(), &MarkdownOptions::default()).unwrap();
+        let md = String::from_utf8(md).unwrap();
+        let lines: Vec<_> = md.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "| Title | Description | Address | Map |");
+        assert!(lines[2].starts_with(
+            "| Bio-Café | Ein Laden mit vielen Produkten | Hauptstr. 1, 12345 Berlin | [Map](https://kartevonmorgen.org/m/main?entry="
+        ));
+        assert!(lines[3]
+            .starts_with(r"| Repair \*Café\* | Ein Laden mit vielen Produkten | Hauptstr. 1 |"));
+        assert!(lines[4].starts_with(r"| Unverpackt \| Laden |"));
+    }
+
+    #[test]
+    fn write_a_list_grouped_by_tag() {
+        let options = MarkdownOptions {
+            style: Style::List,
+            group_by: Some(GroupBy::Tag),
+        };
+        let mut md = vec![];
+        write_entries(&mut md, &entries(), &options).unwrap();
+        let md = String::from_utf8(md).unwrap();
+        let headings: Vec<_> = md.lines().filter(|l| l.starts_with("## ")).collect();
+        assert_eq!(headings, ["## bio", "## zero-waste", "## Other"]);
+        assert_eq!(md.matches("Unverpackt").count(), 2);
+        assert!(md.contains(
+            "- **Bio-Café**: Ein Laden mit vielen Produkten – Hauptstr. 1, 12345 Berlin ([Map]("
+        ));
+    }
+
+    #[test]
+    fn shorten_descriptions() {
+        assert_eq!(excerpt("Ein kurzer Text", 20), "Ein kurzer Text");
+        assert_eq!(
+            excerpt("Ein etwas längerer Text, der gekürzt wird", 26),
+            "Ein etwas längerer Text…"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Helpers to compare entries that were not created from the same source,
+//! e.g. to detect duplicates or to find already imported places.
+
+use std::cmp::Ordering;
+
+/// Legal forms that are often (but not always) part of a title.
+/// They are only removed at the end of a title.
+const LEGAL_FORMS: &[&str] = &[
+    "ev", "ggmbh", "gmbh", "mbh", "gug", "ug", "eg", "ag", "kg", "gbr", "ohg", "co",
+];
+
+/// Normalize a title so that slightly different spellings
+/// of the same name can be compared.
+///
+/// - case is ignored
+/// - umlauts, `ß` and common accents are transliterated (`ä` → `ae`)
+/// - punctuation is removed
+/// - trailing legal forms (`e.V.`, `gGmbH`, ...) are removed
+pub fn normalize_title(title: &str) -> String {
+    let mut transliterated = String::with_capacity(title.len());
+    for c in title.chars().flat_map(char::to_lowercase) {
+        match c {
+            'ä' => transliterated.push_str("ae"),
+            'ö' => transliterated.push_str("oe"),
+            'ü' => transliterated.push_str("ue"),
+            'ß' => transliterated.push_str("ss"),
+            'à' | 'á' | 'â' | 'ã' | 'å' => transliterated.push('a'),
+            'ç' => transliterated.push('c'),
+            'è' | 'é' | 'ê' | 'ë' => transliterated.push('e'),
+            'ì' | 'í' | 'î' | 'ï' => transliterated.push('i'),
+            'ñ' => transliterated.push('n'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ø' => transliterated.push('o'),
+            'ù' | 'ú' | 'û' => transliterated.push('u'),
+            // Abbreviations like "e.V." should become one word
+            '.' => {}
+            c if c.is_alphanumeric() => transliterated.push(c),
+            _ => transliterated.push(' '),
+        }
+    }
+    let mut words: Vec<_> = transliterated.split_whitespace().collect();
+    loop {
+        let n = words.len();
+        if n > 1 && LEGAL_FORMS.contains(&words[n - 1]) {
+            words.pop();
+        } else if n > 2 && LEGAL_FORMS.contains(&&*format!("{}{}", words[n - 2], words[n - 1])) {
+            // e.g. "e. V."
+            words.truncate(n - 2);
+        } else {
+            break;
+        }
+    }
+    words.join(" ")
+}
+
+/// Check if two titles are equal after normalization.
+pub fn is_same_title(a: &str, b: &str) -> bool {
+    normalize_title(a) == normalize_title(b)
+}
+
+/// Similarity of two titles between `0.0` and `1.0`
+/// (Dice coefficient of the character pairs of the normalized titles).
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize_title(a), normalize_title(b));
+    if a == b {
+        return 1.0;
+    }
+    let pairs = |s: &str| {
+        let chars: Vec<_> = s.chars().collect();
+        let mut pairs: Vec<_> = chars.windows(2).map(|w| (w[0], w[1])).collect();
+        pairs.sort_unstable();
+        pairs
+    };
+    let (a, b) = (pairs(&a), pairs(&b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let (mut i, mut j, mut common) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            Ordering::Less => i += 1,
+            Ordering::Greater => j += 1,
+            Ordering::Equal => {
+                common += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    (2 * common) as f64 / (a.len() + b.len()) as f64
+}
+
+/// Distance between two coordinates (`lat`, `lng`) in meters.
+pub fn distance_in_meters(a: (f64, f64), b: (f64, f64)) -> f64 {
+    const EARTH_RADIUS: f64 = 6_371_000.0;
+    let (lat_a, lat_b) = (a.0.to_radians(), b.0.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lng = (b.1 - a.1).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lng / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().min(1.0).asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignore_case_and_whitespace() {
+        assert_eq!(normalize_title("  GLS   Bank "), "gls bank");
+        assert!(is_same_title("GLS Bank", "gls bank"));
+    }
+
+    #[test]
+    fn transliterate_umlauts() {
+        assert_eq!(normalize_title("Bäckerei Müller"), "baeckerei mueller");
+        assert_eq!(normalize_title("Grüße aus Köln"), "gruesse aus koeln");
+        assert!(is_same_title("Straßenfest", "Strassenfest"));
+        assert!(is_same_title("Bäckerei Müller", "Baeckerei Mueller"));
+        assert!(is_same_title("Café Größenwahn", "Cafe Groessenwahn"));
+    }
+
+    #[test]
+    fn remove_punctuation() {
+        assert_eq!(
+            normalize_title("Repair-Café \"Kaputt?\""),
+            "repair cafe kaputt"
+        );
+        assert!(is_same_title("Foo-Bar!", "foo bar"));
+    }
+
+    #[test]
+    fn remove_trailing_legal_forms() {
+        assert_eq!(
+            normalize_title("Solidarische Landwirtschaft e.V."),
+            "solidarische landwirtschaft"
+        );
+        assert_eq!(normalize_title("Tafel Bochum e. V."), "tafel bochum");
+        assert_eq!(normalize_title("Werkstatt gGmbH"), "werkstatt");
+        assert_eq!(normalize_title("Energie GmbH & Co. KG"), "energie");
+        assert!(is_same_title("Foodsharing e.V.", "foodsharing"));
+        assert!(is_same_title("Bürgerenergie eG", "Buergerenergie"));
+    }
+
+    #[test]
+    fn compare_similar_titles() {
+        assert_eq!(title_similarity("Foodsharing e.V.", "foodsharing"), 1.0);
+        assert!(title_similarity("Repair Café Bochum", "Repaircafe Bochum") > 0.8);
+        assert!(title_similarity("Repair Café Bochum", "Unverpackt Bochum") < 0.5);
+        assert_eq!(title_similarity("", "Foo"), 0.0);
+    }
+
+    #[test]
+    fn calculate_distances() {
+        let bochum = (51.4818, 7.2162);
+        let dortmund = (51.5136, 7.4653);
+        let d = distance_in_meters(bochum, dortmund);
+        assert!((17_000.0..18_000.0).contains(&d), "{d}");
+        assert_eq!(distance_in_meters(bochum, bochum), 0.0);
+    }
+
+    #[test]
+    fn keep_legal_forms_that_are_not_at_the_end() {
+        assert_eq!(normalize_title("AG Umwelt"), "ag umwelt");
+        assert_eq!(normalize_title("GmbH"), "gmbh");
+    }
+}
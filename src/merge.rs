@@ -0,0 +1,150 @@
+//! Combine two entries that represent the same place.
+
+use ofdb_boundary::{Entry, Review, ReviewStatus};
+
+use crate::matching::normalize_title;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MergeOptions {
+    /// Append the description of the duplicate instead of
+    /// keeping only the longer one.
+    pub concat_descriptions: bool,
+}
+
+/// Merge `dup` into `keep`.
+///
+/// - tags, categories and custom links are united
+/// - the longer description wins (or both are concatenated)
+/// - empty fields of `keep` are filled with the values of `dup`
+///
+/// The result has the next version of `keep`, so it can be used to update it.
+pub fn merge_entries(keep: &Entry, dup: &Entry, options: MergeOptions) -> Entry {
+    let mut merged = keep.clone();
+    merged.version = keep.version + 1;
+    merged.description = merge_descriptions(&keep.description, &dup.description, options);
+    for (field, other) in [
+        (&mut merged.street, &dup.street),
+        (&mut merged.zip, &dup.zip),
+        (&mut merged.city, &dup.city),
+        (&mut merged.country, &dup.country),
+        (&mut merged.state, &dup.state),
+        (&mut merged.contact_name, &dup.contact_name),
+        (&mut merged.email, &dup.email),
+        (&mut merged.telephone, &dup.telephone),
+        (&mut merged.homepage, &dup.homepage),
+        (&mut merged.opening_hours, &dup.opening_hours),
+        (&mut merged.image_url, &dup.image_url),
+        (&mut merged.image_link_url, &dup.image_link_url),
+    ] {
+        if field.as_deref().map_or(true, |v| v.trim().is_empty()) {
+            field.clone_from(other);
+        }
+    }
+    if merged.founded_on.is_none() {
+        merged.founded_on.clone_from(&dup.founded_on);
+    }
+    for tag in &dup.tags {
+        if !merged.tags.contains(tag) {
+            merged.tags.push(tag.clone());
+        }
+    }
+    for category in &dup.categories {
+        if !merged.categories.contains(category) {
+            merged.categories.push(category.clone());
+        }
+    }
+    for link in &dup.custom_links {
+        if !merged.custom_links.iter().any(|l| l.url == link.url) {
+            merged.custom_links.push(link.clone());
+        }
+    }
+    merged
+}
+
+fn merge_descriptions(keep: &str, dup: &str, options: MergeOptions) -> String {
+    let (keep, dup) = (keep.trim(), dup.trim());
+    if normalize_title(keep).contains(&normalize_title(dup)) {
+        return keep.to_string();
+    }
+    if normalize_title(dup).contains(&normalize_title(keep)) {
+        return dup.to_string();
+    }
+    if options.concat_descriptions {
+        return format!("{keep}\n\n{dup}");
+    }
+    if dup.chars().count() > keep.chars().count() {
+        dup.to_string()
+    } else {
+        keep.to_string()
+    }
+}
+
+/// The review to archive the duplicate.
+pub fn archive_review(keep: &Entry) -> Review {
+    Review {
+        status: ReviewStatus::Archived,
+        comment: Some(format!("Merged into {} ({})", keep.title, keep.id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ofdb_boundary::CustomLink;
+
+    use super::*;
+
+    fn entries() -> (Entry, Entry) {
+        let mut entries = crate::fixtures::generate(2, 11).entries.into_iter();
+        let mut keep = entries.next().unwrap();
+        let mut dup = entries.next().unwrap();
+        keep.version = 3;
+        keep.tags = vec!["repaircafe".to_string(), "bochum".to_string()];
+        dup.tags = vec!["bochum".to_string(), "reparatur".to_string()];
+        keep.description = "Reparieren statt wegwerfen".to_string();
+        dup.description = "Reparieren statt wegwerfen, jeden ersten Samstag im Monat".to_string();
+        keep.telephone = None;
+        dup.telephone = Some("0234 123456".to_string());
+        keep.homepage = Some("https://example.org".to_string());
+        dup.homepage = Some("https://example.com".to_string());
+        let link = |url: &str| CustomLink {
+            url: url.to_string(),
+            title: None,
+            description: None,
+        };
+        keep.custom_links = vec![link("https://a.example.org")];
+        dup.custom_links = vec![link("https://b.example.org"), link("https://a.example.org")];
+        (keep, dup)
+    }
+
+    #[test]
+    fn merge_two_entries() {
+        let (keep, dup) = entries();
+        let merged = merge_entries(&keep, &dup, MergeOptions::default());
+        assert_eq!(merged.id, keep.id);
+        assert_eq!(merged.version, 4);
+        assert_eq!(merged.tags, vec!["repaircafe", "bochum", "reparatur"]);
+        assert_eq!(merged.description, dup.description);
+        assert_eq!(merged.telephone.as_deref(), Some("0234 123456"));
+        assert_eq!(merged.homepage.as_deref(), Some("https://example.org"));
+        let urls: Vec<_> = merged.custom_links.iter().map(|l| l.url.as_str()).collect();
+        assert_eq!(urls, vec!["https://a.example.org", "https://b.example.org"]);
+    }
+
+    #[test]
+    fn concat_different_descriptions() {
+        let (mut keep, mut dup) = entries();
+        keep.description = "Ein Repair Café".to_string();
+        dup.description = "Jeden ersten Samstag im Monat".to_string();
+        let options = MergeOptions {
+            concat_descriptions: true,
+        };
+        assert_eq!(
+            merge_entries(&keep, &dup, options).description,
+            "Ein Repair Café\n\nJeden ersten Samstag im Monat"
+        );
+        assert_eq!(
+            merge_entries(&keep, &dup, MergeOptions::default()).description,
+            "Jeden ersten Samstag im Monat"
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Timing of the phases of a run and of the API requests,
+//! e.g. to tune the rate limits or to notice that the server slows down.
+
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use ofdb_core::gateways::geocode::GeoCodingGateway;
+use ofdb_entities::address::Address;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Metrics of a run that are added to the report.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Metrics {
+    /// Duration of the whole run in milliseconds.
+    pub duration_ms: u64,
+    pub phases: PhaseDurations,
+    /// Number of requests to the OpenFairDB API.
+    pub requests: u64,
+    /// Average duration until the response of the API arrived in milliseconds.
+    pub average_request_latency_ms: Option<f64>,
+    /// Processed records (of all kinds of results) per second.
+    pub records_per_second: f64,
+}
+
+/// Durations of the phases in milliseconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PhaseDurations {
+    /// Reading and checking the records.
+    pub parse_ms: u64,
+    pub geocode_ms: u64,
+    pub duplicate_check_ms: u64,
+    /// Creating or updating the entries.
+    pub create_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Reading the records including geocoding.
+    Read,
+    Geocode,
+    DuplicateCheck,
+    /// Importing the records including the duplicate check.
+    Import,
+}
+
+/// Measures the phases of a run.
+///
+/// Phases may be nested (e.g. geocoding while reading)
+/// and may be entered several times (e.g. when streaming).
+#[derive(Debug)]
+pub struct PhaseTimer {
+    started: Instant,
+    durations: [Cell<Duration>; 4],
+}
+
+impl Default for PhaseTimer {
+    fn default() -> Self {
+        Self::start()
+    }
+}
+
+impl PhaseTimer {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            durations: Default::default(),
+        }
+    }
+
+    fn duration(&self, phase: Phase) -> &Cell<Duration> {
+        &self.durations[phase as usize]
+    }
+
+    /// Total duration of the phase up to now.
+    pub fn get(&self, phase: Phase) -> Duration {
+        self.duration(phase).get()
+    }
+
+    pub fn add(&self, phase: Phase, duration: Duration) {
+        let total = self.duration(phase);
+        total.set(total.get() + duration);
+    }
+
+    /// Run `f` and add its duration to the phase.
+    pub fn time<T>(&self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.add(phase, started.elapsed());
+        result
+    }
+
+    /// Add the time that is spent in [Iterator::next] to the phase.
+    pub fn time_iter<'a, I: Iterator + 'a>(
+        &'a self,
+        phase: Phase,
+        mut iter: I,
+    ) -> impl Iterator<Item = I::Item> + 'a {
+        std::iter::from_fn(move || self.time(phase, || iter.next()))
+    }
+
+    /// The metrics of the run up to now.
+    pub fn metrics(&self, records: usize, requests: &RequestStats) -> Metrics {
+        let ms = |d: Duration| d.as_millis() as u64;
+        let get = |phase| self.get(phase);
+        let duration = self.started.elapsed();
+        let secs = duration.as_secs_f64();
+        Metrics {
+            duration_ms: ms(duration),
+            phases: PhaseDurations {
+                parse_ms: ms(get(Phase::Read).saturating_sub(get(Phase::Geocode))),
+                geocode_ms: ms(get(Phase::Geocode)),
+                duplicate_check_ms: ms(get(Phase::DuplicateCheck)),
+                create_ms: ms(get(Phase::Import).saturating_sub(get(Phase::DuplicateCheck))),
+            },
+            requests: requests.count(),
+            average_request_latency_ms: requests
+                .average_latency()
+                .map(|d| d.as_secs_f64() * 1000.0),
+            records_per_second: if secs > 0.0 {
+                records as f64 / secs
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+/// Geocoding that adds its duration to [Phase::Geocode].
+pub struct TimedGeoCoding<'a> {
+    inner: &'a dyn GeoCodingGateway,
+    timer: &'a PhaseTimer,
+}
+
+impl<'a> TimedGeoCoding<'a> {
+    pub fn new(inner: &'a dyn GeoCodingGateway, timer: &'a PhaseTimer) -> Self {
+        Self { inner, timer }
+    }
+}
+
+impl GeoCodingGateway for TimedGeoCoding<'_> {
+    fn resolve_address_lat_lng(&self, addr: &Address) -> Option<(f64, f64)> {
+        self.timer
+            .time(Phase::Geocode, || self.inner.resolve_address_lat_lng(addr))
+    }
+}
+
+/// Number and latency of the requests of a client.
+#[derive(Debug, Default)]
+pub struct RequestStats {
+    count: AtomicU64,
+    nanos: AtomicU64,
+}
+
+impl RequestStats {
+    pub fn record(&self, latency: Duration) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.nanos
+            .fetch_add(latency.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn average_latency(&self) -> Option<Duration> {
+        let count = self.count();
+        (count > 0).then(|| Duration::from_nanos(self.nanos.load(Ordering::Relaxed) / count))
+    }
+
+    /// The requests since the `earlier` snapshot.
+    pub fn since(&self, earlier: &RequestStats) -> RequestStats {
+        RequestStats {
+            count: AtomicU64::new(self.count().saturating_sub(earlier.count())),
+            nanos: AtomicU64::new(
+                self.nanos
+                    .load(Ordering::Relaxed)
+                    .saturating_sub(earlier.nanos.load(Ordering::Relaxed)),
+            ),
+        }
+    }
+
+    pub fn snapshot(&self) -> RequestStats {
+        self.since(&RequestStats::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compute_metrics() {
+        let timer = PhaseTimer::start();
+        timer.add(Phase::Read, Duration::from_millis(300));
+        timer.add(Phase::Geocode, Duration::from_millis(200));
+        timer.add(Phase::Import, Duration::from_millis(500));
+        timer.add(Phase::DuplicateCheck, Duration::from_millis(150));
+        let requests = RequestStats::default();
+        let before = requests.snapshot();
+        requests.record(Duration::from_millis(10));
+        requests.record(Duration::from_millis(30));
+        let metrics = timer.metrics(4, &requests.since(&before));
+        assert_eq!(
+            metrics.phases,
+            PhaseDurations {
+                parse_ms: 100,
+                geocode_ms: 200,
+                duplicate_check_ms: 150,
+                create_ms: 350,
+            }
+        );
+        assert_eq!(metrics.requests, 2);
+        assert_eq!(metrics.average_request_latency_ms, Some(20.0));
+        assert!(metrics.records_per_second > 0.0);
+
+        let items: Vec<_> = timer.time_iter(Phase::Read, 0..3).collect();
+        assert_eq!(items, vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use ofdb_boundary::{Entry, NewPlace};
+use serde::{Deserialize, Serialize};
+
+/// License that is used if the source entry has none.
+const DEFAULT_LICENSE: &str = "CC0-1.0";
+
+/// Maps the IDs of entries of the source instance
+/// to the IDs of the corresponding entries on the target instance.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct IdMapping {
+    pub entries: HashMap<String, MappedEntry>,
+}
+
+/// State of a mirrored entry after the last successful run.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MappedEntry {
+    pub target_id: String,
+    pub source_version: u64,
+    pub target_version: u64,
+}
+
+/// What has to be done to mirror a source entry.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    /// The entry does not exist on the target instance.
+    Create,
+    /// The entry was modified on the source instance.
+    Update,
+    /// Nothing changed since the last run.
+    Unchanged,
+    /// The entry was modified on the target instance,
+    /// so it can't be updated without losing these changes.
+    Conflict,
+}
+
+/// Decide what to do with a source entry.
+///
+/// `target_version` is the current version on the target instance
+/// or `None` if the mapped entry could not be found.
+pub fn plan(source: &Entry, mapped: Option<&MappedEntry>, target_version: Option<u64>) -> Action {
+    let (Some(mapped), Some(target_version)) = (mapped, target_version) else {
+        return Action::Create;
+    };
+    if target_version != mapped.target_version {
+        return Action::Conflict;
+    }
+    if source.version != mapped.source_version {
+        return Action::Update;
+    }
+    Action::Unchanged
+}
+
+pub fn new_place_from_entry(entry: Entry) -> NewPlace {
+    let Entry {
+        title,
+        description,
+        lat,
+        lng,
+        street,
+        zip,
+        city,
+        country,
+        state,
+        contact_name,
+        email,
+        telephone,
+        homepage,
+        opening_hours,
+        founded_on,
+        categories,
+        tags,
+        license,
+        image_url,
+        image_link_url,
+        custom_links,
+        ..
+    } = entry;
+    NewPlace {
+        title,
+        description,
+        lat,
+        lng,
+        street,
+        zip,
+        city,
+        country,
+        state,
+        contact_name,
+        email,
+        telephone,
+        homepage,
+        opening_hours,
+        founded_on,
+        categories,
+        tags,
+        license: license.unwrap_or_else(|| DEFAULT_LICENSE.to_string()),
+        image_url,
+        image_link_url,
+        links: custom_links,
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MirroredEntry {
+    pub source_id: String,
+    pub target_id: String,
+    pub title: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MirrorFailure {
+    pub source_id: String,
+    pub title: String,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct MirrorReport {
+    pub created: Vec<MirroredEntry>,
+    pub updated: Vec<MirroredEntry>,
+    pub unchanged: Vec<MirroredEntry>,
+    pub conflicts: Vec<MirroredEntry>,
+    pub failures: Vec<MirrorFailure>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(version: u64) -> Entry {
+        Entry {
+            id: "source".to_string(),
+            created: Default::default(),
+            version,
+            title: Default::default(),
+            description: Default::default(),
+            lat: Default::default(),
+            lng: Default::default(),
+            street: Default::default(),
+            zip: Default::default(),
+            city: Default::default(),
+            country: Default::default(),
+            state: Default::default(),
+            contact_name: Default::default(),
+            email: Default::default(),
+            telephone: Default::default(),
+            homepage: Default::default(),
+            opening_hours: Default::default(),
+            founded_on: Default::default(),
+            categories: Default::default(),
+            tags: Default::default(),
+            ratings: Default::default(),
+            license: Default::default(),
+            image_url: Default::default(),
+            image_link_url: Default::default(),
+            custom_links: Default::default(),
+        }
+    }
+
+    fn mapped(source_version: u64, target_version: u64) -> MappedEntry {
+        MappedEntry {
+            target_id: "target".to_string(),
+            source_version,
+            target_version,
+        }
+    }
+
+    #[test]
+    fn create_unmapped_entries() {
+        assert_eq!(plan(&entry(1), None, None), Action::Create);
+        assert_eq!(plan(&entry(1), Some(&mapped(1, 1)), None), Action::Create);
+    }
+
+    #[test]
+    fn update_modified_entries() {
+        assert_eq!(
+            plan(&entry(2), Some(&mapped(1, 0)), Some(0)),
+            Action::Update
+        );
+        assert_eq!(
+            plan(&entry(1), Some(&mapped(1, 0)), Some(0)),
+            Action::Unchanged
+        );
+    }
+
+    #[test]
+    fn detect_conflicts() {
+        assert_eq!(
+            plan(&entry(2), Some(&mapped(1, 0)), Some(1)),
+            Action::Conflict
+        );
+    }
+
+    #[test]
+    fn use_default_license() {
+        assert_eq!(new_place_from_entry(entry(0)).license, DEFAULT_LICENSE);
+    }
+}
@@ -0,0 +1,467 @@
+//! A small in-memory OpenFairDB server with the endpoints that the CLI uses,
+//! e.g. for workshops or to test an import in CI without a real instance.
+//!
+//! The server accepts any login and keeps the entries until it is stopped.
+//! It serves the API with and without the `/v0` prefix.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Mutex,
+};
+
+use anyhow::Result;
+use ofdb_boundary::{
+    Entry, EntrySearchRatings, NewPlace, PlaceSearchResult, Review, ReviewStatus, SearchResponse,
+    UpdatePlace,
+};
+use reqwest::{StatusCode, Url};
+use serde::Serialize;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    matching::{distance_in_meters, title_similarity},
+    search::{DuplicateSearch, DEFAULT_PAGE_SIZE},
+};
+
+/// Version that is reported by `server/version`
+/// (the version of the API types, see [crate::compat]).
+pub const MOCK_SERVER_VERSION: &str = "0.12.7-mock";
+
+#[derive(Debug)]
+struct MockPlace {
+    entry: Entry,
+    status: ReviewStatus,
+}
+
+#[derive(Debug, Default)]
+pub struct MockServer {
+    places: Mutex<Vec<MockPlace>>,
+}
+
+type Response = (StatusCode, String);
+
+impl MockServer {
+    /// A server with existing entries (e.g. generated fixtures).
+    pub fn new(entries: Vec<Entry>) -> Self {
+        let places = entries
+            .into_iter()
+            .map(|entry| MockPlace {
+                entry,
+                status: ReviewStatus::Created,
+            })
+            .collect();
+        Self {
+            places: Mutex::new(places),
+        }
+    }
+
+    /// Serve the API until the process is stopped.
+    pub fn serve(&self, addr: &str) -> Result<()> {
+        let server = tiny_http::Server::http(addr).map_err(|err| anyhow::anyhow!("{err}"))?;
+        self.run(server);
+        Ok(())
+    }
+
+    fn run(&self, server: tiny_http::Server) {
+        log::info!(
+            "The mock server listens on http://{}/v0",
+            server.server_addr()
+        );
+        for mut request in server.incoming_requests() {
+            let mut body = vec![];
+            // A broken connection of one client must not stop the server
+            let (status, json) = match request.as_reader().read_to_end(&mut body) {
+                Ok(_) => self.handle(request.method().as_str(), request.url(), &body),
+                Err(err) => {
+                    log::warn!("Unable to read the request body: {err}");
+                    error(StatusCode::BAD_REQUEST, "Unable to read the request body")
+                }
+            };
+            log::info!("{} {} -> {status}", request.method(), request.url());
+            let content_type = tiny_http::Header::from_bytes("Content-Type", "application/json")
+                .expect("valid header");
+            let response = tiny_http::Response::from_string(json)
+                .with_status_code(status.as_u16())
+                .with_header(content_type);
+            if let Err(err) = request.respond(response) {
+                log::warn!("Unable to respond: {err}");
+            }
+        }
+    }
+
+    /// Handle a request with the path and query (e.g. `/v0/search?text=foo`)
+    /// and return the status and JSON body of the response.
+    pub fn handle(&self, method: &str, path_and_query: &str, body: &[u8]) -> Response {
+        let Ok(url) = Url::parse(&format!("http://mock{path_and_query}")) else {
+            return error(StatusCode::BAD_REQUEST, "Invalid URL");
+        };
+        let query: HashMap<_, _> = url.query_pairs().into_owned().collect();
+        let path = url.path().trim_start_matches("/v0");
+        let segments: Vec<_> = path.trim_matches('/').split('/').collect();
+        match (method, segments.as_slice()) {
+            // A plain text response like on a real instance
+            ("GET", ["server", "version"]) => (StatusCode::OK, MOCK_SERVER_VERSION.to_string()),
+            ("POST", ["login"] | ["logout"]) => ok(&()),
+            ("GET", ["count", "entries"]) => ok(&self.places.lock().unwrap().len()),
+            ("GET", ["count", "tags"]) => ok(&self.tags().len()),
+            ("POST", ["entries"]) => parse(body).map_or_else(|err| err, |p| self.create(p)),
+            ("PUT", ["entries", id]) => parse(body).map_or_else(|err| err, |p| self.update(id, p)),
+            ("GET", ["entries", ids]) => ok(&self.read(ids)),
+            ("POST", ["places", ids, "review"]) => {
+                parse(body).map_or_else(|err| err, |r| self.review(ids, r))
+            }
+            ("GET", ["search"]) => ok(&self.search(&query)),
+            ("POST", ["search", "duplicates"]) => {
+                parse(body).map_or_else(|err| err, |p| ok(&self.duplicates(&p, &query)))
+            }
+            _ => error(StatusCode::NOT_FOUND, "Not found"),
+        }
+    }
+
+    fn tags(&self) -> BTreeSet<String> {
+        let places = self.places.lock().unwrap();
+        places
+            .iter()
+            .flat_map(|p| p.entry.tags.iter().cloned())
+            .collect()
+    }
+
+    fn create(&self, new_place: NewPlace) -> Response {
+        if new_place.title.trim().is_empty() {
+            return error(StatusCode::BAD_REQUEST, "The title is missing");
+        }
+        let mut places = self.places.lock().unwrap();
+        // Predictable IDs make the runs reproducible
+        let id = Uuid::from_u128(0x0fdb_0000_0000_0000_0000_0000_0000_0000 + places.len() as u128)
+            .simple()
+            .to_string();
+        let entry = new_entry(id.clone(), new_place);
+        places.push(MockPlace {
+            entry,
+            status: ReviewStatus::Created,
+        });
+        ok(&id)
+    }
+
+    fn update(&self, id: &str, place: UpdatePlace) -> Response {
+        let mut places = self.places.lock().unwrap();
+        let Some(existing) = places.iter_mut().find(|p| p.entry.id == id) else {
+            return error(StatusCode::NOT_FOUND, "The entry does not exist");
+        };
+        if place.version != existing.entry.version + 1 {
+            return error(
+                StatusCode::CONFLICT,
+                &format!(
+                    "Invalid version {} (the current version is {})",
+                    place.version, existing.entry.version
+                ),
+            );
+        }
+        apply_update(&mut existing.entry, place);
+        ok(&id)
+    }
+
+    fn read(&self, ids: &str) -> Vec<Entry> {
+        let places = self.places.lock().unwrap();
+        ids.split(',')
+            .filter_map(|id| places.iter().find(|p| p.entry.id == id))
+            .map(|p| p.entry.clone())
+            .collect()
+    }
+
+    fn review(&self, ids: &str, review: Review) -> Response {
+        let mut places = self.places.lock().unwrap();
+        for id in ids.split(',') {
+            match places.iter_mut().find(|p| p.entry.id == id) {
+                Some(place) => place.status = review.status,
+                None => return error(StatusCode::NOT_FOUND, &format!("Unknown entry {id}")),
+            }
+        }
+        ok(&())
+    }
+
+    fn search(&self, query: &HashMap<String, String>) -> SearchResponse {
+        let text = query
+            .get("text")
+            .map(|t| t.to_lowercase())
+            .unwrap_or_default();
+        let list = |name: &str| -> Vec<String> {
+            query
+                .get(name)
+                .map(|v| {
+                    v.split(',')
+                        .filter(|s| !s.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let (categories, tags, status) = (list("categories"), list("tags"), list("status"));
+        let bbox: Vec<f64> = query
+            .get("bbox")
+            .map(|b| b.split(',').filter_map(|v| v.parse().ok()).collect())
+            .unwrap_or_default();
+        let limit = query
+            .get("limit")
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(DEFAULT_PAGE_SIZE);
+        let places = self.places.lock().unwrap();
+        let visible = places
+            .iter()
+            .filter(|p| {
+                let e = &p.entry;
+                let status_name = format!("{:?}", p.status).to_lowercase();
+                let visible = if status.is_empty() {
+                    matches!(p.status, ReviewStatus::Created | ReviewStatus::Confirmed)
+                } else {
+                    status.contains(&status_name)
+                };
+                visible
+                    && (text.is_empty()
+                        || e.title.to_lowercase().contains(&text)
+                        || e.description.to_lowercase().contains(&text))
+                    && categories.iter().all(|c| e.categories.contains(c))
+                    && tags.iter().all(|t| e.tags.contains(t))
+                    && match bbox[..] {
+                        [sw_lat, sw_lng, ne_lat, ne_lng] => {
+                            (sw_lat..=ne_lat).contains(&e.lat) && (sw_lng..=ne_lng).contains(&e.lng)
+                        }
+                        _ => true,
+                    }
+            })
+            .take(limit)
+            .map(search_result)
+            .collect();
+        SearchResponse {
+            visible,
+            invisible: vec![],
+        }
+    }
+
+    fn duplicates(
+        &self,
+        new_place: &NewPlace,
+        query: &HashMap<String, String>,
+    ) -> Vec<PlaceSearchResult> {
+        let default = DuplicateSearch::default();
+        let param = |name, default| {
+            query
+                .get(name)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        let max_distance = param("max_distance", default.max_distance);
+        let min_similarity = param("min_similarity", default.min_similarity);
+        let places = self.places.lock().unwrap();
+        places
+            .iter()
+            .filter(|p| !matches!(p.status, ReviewStatus::Archived | ReviewStatus::Rejected))
+            .filter(|p| {
+                distance_in_meters((new_place.lat, new_place.lng), (p.entry.lat, p.entry.lng))
+                    <= max_distance
+                    && title_similarity(&new_place.title, &p.entry.title) >= min_similarity
+            })
+            .map(search_result)
+            .collect()
+    }
+}
+
+fn parse<T: for<'de> serde::Deserialize<'de>>(body: &[u8]) -> Result<T, Response> {
+    serde_json::from_slice(body)
+        .map_err(|err| error(StatusCode::BAD_REQUEST, &format!("Invalid body: {err}")))
+}
+
+fn ok<T: Serialize>(value: &T) -> Response {
+    (
+        StatusCode::OK,
+        serde_json::to_string(value).expect("serializable response"),
+    )
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    let error = ofdb_boundary::Error {
+        http_status: status.as_u16(),
+        message: message.to_string(),
+    };
+    (
+        status,
+        serde_json::to_string(&error).expect("serializable error"),
+    )
+}
+
+fn search_result(place: &MockPlace) -> PlaceSearchResult {
+    let e = &place.entry;
+    PlaceSearchResult {
+        id: e.id.clone(),
+        status: Some(place.status),
+        lat: e.lat,
+        lng: e.lng,
+        title: e.title.clone(),
+        description: e.description.clone(),
+        categories: e.categories.clone(),
+        tags: e.tags.clone(),
+        ratings: EntrySearchRatings::default(),
+    }
+}
+
+fn new_entry(id: String, place: NewPlace) -> Entry {
+    let NewPlace {
+        title,
+        description,
+        lat,
+        lng,
+        street,
+        zip,
+        city,
+        country,
+        state,
+        contact_name,
+        email,
+        telephone,
+        homepage,
+        opening_hours,
+        founded_on,
+        categories,
+        tags,
+        license,
+        image_url,
+        image_link_url,
+        links,
+    } = place;
+    Entry {
+        id,
+        created: OffsetDateTime::now_utc().unix_timestamp(),
+        version: 0,
+        title,
+        description,
+        lat,
+        lng,
+        street,
+        zip,
+        city,
+        country,
+        state,
+        contact_name,
+        email,
+        telephone,
+        homepage,
+        opening_hours,
+        founded_on,
+        categories,
+        tags,
+        ratings: vec![],
+        license: Some(license),
+        image_url,
+        image_link_url,
+        custom_links: links,
+    }
+}
+
+fn apply_update(entry: &mut Entry, place: UpdatePlace) {
+    let UpdatePlace {
+        version,
+        title,
+        description,
+        lat,
+        lng,
+        street,
+        zip,
+        city,
+        country,
+        state,
+        contact_name,
+        email,
+        telephone,
+        homepage,
+        opening_hours,
+        founded_on,
+        categories,
+        tags,
+        image_url,
+        image_link_url,
+        links,
+    } = place;
+    *entry = Entry {
+        id: entry.id.clone(),
+        created: entry.created,
+        version,
+        title,
+        description,
+        lat,
+        lng,
+        street,
+        zip,
+        city,
+        country,
+        state,
+        contact_name,
+        email,
+        telephone,
+        homepage,
+        opening_hours,
+        founded_on,
+        categories,
+        tags,
+        ratings: entry.ratings.clone(),
+        license: entry.license.clone(),
+        image_url,
+        image_link_url,
+        custom_links: links,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, OfdbClient};
+
+    #[test]
+    fn import_update_and_review() {
+        let fixtures = fixtures::generate(3, 7);
+        let server = MockServer::new(fixtures.entries.clone());
+
+        let body = serde_json::to_vec(&fixtures.new_places[0]).unwrap();
+        let (status, duplicates) = server.handle("POST", "/v0/search/duplicates", &body);
+        assert_eq!(status, StatusCode::OK);
+        let duplicates: Vec<PlaceSearchResult> = serde_json::from_str(&duplicates).unwrap();
+        assert_eq!(duplicates[0].id, fixtures.entries[0].id);
+
+        let (status, id) = server.handle("POST", "/v0/entries", &body);
+        assert_eq!(status, StatusCode::OK);
+        let id: String = serde_json::from_str(&id).unwrap();
+        let (_, count) = server.handle("GET", "/count/entries", &[]);
+        assert_eq!(count, "4");
+
+        let mut update = UpdatePlace::from(fixtures.entries[0].clone());
+        update.version = fixtures.entries[0].version;
+        let path = format!("/v0/entries/{}", fixtures.entries[0].id);
+        let (status, _) = server.handle("PUT", &path, &serde_json::to_vec(&update).unwrap());
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let review = br#"{"status":"archived","comment":null}"#;
+        let (status, _) = server.handle("POST", &format!("/v0/places/{id}/review"), review);
+        assert_eq!(status, StatusCode::OK, "{review:?}");
+        let (_, search) = server.handle("GET", "/v0/search?text=&bbox=-90,-180,90,180", &[]);
+        let search: SearchResponse = serde_json::from_str(&search).unwrap();
+        assert_eq!(search.visible.len(), 3);
+        assert!(search.visible.iter().all(|r| r.id != id));
+
+        let (status, _) = server.handle("GET", "/v0/unknown", &[]);
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn serve_the_client() {
+        let entries = fixtures::generate(2, 3).entries;
+        let server = MockServer::new(entries.clone());
+        let http = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let api = format!("http://{}/v0", http.server_addr());
+        std::thread::spawn(move || server.run(http));
+        let client = OfdbClient::new(&api).unwrap();
+        let ids = entries.iter().map(|e| e.id.parse().unwrap()).collect();
+        assert_eq!(client.read_entries(ids).unwrap(), entries);
+        assert_eq!(client.count_entries().unwrap(), 2);
+        assert_eq!(client.server_version().unwrap(), MOCK_SERVER_VERSION);
+    }
+}
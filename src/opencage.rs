@@ -0,0 +1,237 @@
+//! A client of the OpenCage geocoder that keeps the confidence and bounds of the matches,
+//! e.g. to reject addresses that were only resolved to the center of a city.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{bail, Result};
+use ofdb_core::gateways::geocode::GeoCodingGateway;
+use ofdb_entities::address::Address;
+use reqwest::blocking::Client;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::geocoding_cache::cache_key;
+
+const OPENCAGE_URL: &str = "https://api.opencagedata.com/geocode/v1/json";
+
+/// The best match of the geocoder for an address.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct GeocodeMatch {
+    pub lat: f64,
+    pub lng: f64,
+    /// Precision of the match from 1 (more than 25 km) to 10 (less than 250 m).
+    pub confidence: u8,
+    /// Area of the match (e.g. the whole city if only the city was found).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<GeocodeBounds>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct GeocodeBounds {
+    pub sw_lat: f64,
+    pub sw_lng: f64,
+    pub ne_lat: f64,
+    pub ne_lng: f64,
+}
+
+/// The matches of the geocoded addresses of a run.
+#[derive(Debug, Default)]
+pub struct GeocodeMatches(Mutex<HashMap<String, GeocodeMatch>>);
+
+impl GeocodeMatches {
+    /// The match of the address if it was geocoded in this run
+    /// (addresses of the geocoding cache have no match).
+    pub fn get(&self, addr: &Address) -> Option<GeocodeMatch> {
+        self.0.lock().unwrap().get(&cache_key(addr)).copied()
+    }
+
+    pub(crate) fn insert(&self, addr: &Address, m: GeocodeMatch) {
+        self.0.lock().unwrap().insert(cache_key(addr), m);
+    }
+}
+
+pub struct OpenCageGeocoder {
+    api_key: String,
+    url: String,
+    http: Client,
+    matches: Arc<GeocodeMatches>,
+}
+
+impl OpenCageGeocoder {
+    pub fn new(api_key: String, http: Client, matches: Arc<GeocodeMatches>) -> Self {
+        Self {
+            api_key,
+            url: OPENCAGE_URL.to_string(),
+            http,
+            matches,
+        }
+    }
+
+    /// The best match of the address.
+    ///
+    /// The URL is removed from the errors because it contains the API key.
+    pub fn forward(&self, addr: &Address) -> Result<Option<GeocodeMatch>> {
+        let res = self
+            .http
+            .get(&self.url)
+            .query(&[
+                ("q", query(addr).as_str()),
+                ("key", &self.api_key),
+                ("limit", "1"),
+                ("no_annotations", "1"),
+            ])
+            .send()
+            .map_err(reqwest::Error::without_url)?;
+        let status = res.status();
+        let response: Response = res.json().map_err(reqwest::Error::without_url)?;
+        if !status.is_success() {
+            bail!("OpenCage error {}: {}", status, response.status.message);
+        }
+        Ok(response.results.into_iter().next().map(GeocodeMatch::from))
+    }
+}
+
+impl GeoCodingGateway for OpenCageGeocoder {
+    fn resolve_address_lat_lng(&self, addr: &Address) -> Option<(f64, f64)> {
+        match self.forward(addr) {
+            Ok(Some(m)) => {
+                log::debug!("Geocoded {addr:?} with confidence {}", m.confidence);
+                self.matches.insert(addr, m);
+                Some((m.lat, m.lng))
+            }
+            Ok(None) => None,
+            Err(err) => {
+                log::warn!("Unable to geocode {addr:?}: {err}");
+                None
+            }
+        }
+    }
+}
+
+/// The address as a single line (e.g. `Hauptstraße 1, 44787 Bochum, Deutschland`).
+fn query(addr: &Address) -> String {
+    let zip_city = [&addr.zip, &addr.city]
+        .into_iter()
+        .flatten()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    [
+        addr.street.as_deref().unwrap_or_default().trim(),
+        &zip_city,
+        addr.state.as_deref().unwrap_or_default().trim(),
+        addr.country.as_deref().unwrap_or_default().trim(),
+    ]
+    .into_iter()
+    .filter(|s| !s.is_empty())
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    #[serde(default)]
+    results: Vec<ResponseResult>,
+    status: ResponseStatus,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseStatus {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseResult {
+    confidence: u8,
+    geometry: Point,
+    bounds: Option<Bounds>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Point {
+    lat: f64,
+    lng: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Bounds {
+    northeast: Point,
+    southwest: Point,
+}
+
+impl From<ResponseResult> for GeocodeMatch {
+    fn from(r: ResponseResult) -> Self {
+        Self {
+            lat: r.geometry.lat,
+            lng: r.geometry.lng,
+            confidence: r.confidence,
+            bounds: r.bounds.map(|b| GeocodeBounds {
+                sw_lat: b.southwest.lat,
+                sw_lng: b.southwest.lng,
+                ne_lat: b.northeast.lat,
+                ne_lng: b.northeast.lng,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_query() {
+        let addr = Address {
+            street: Some("Hauptstraße 1".to_string()),
+            zip: Some("44787".to_string()),
+            city: Some("Bochum".to_string()),
+            country: Some("Deutschland".to_string()),
+            state: None,
+        };
+        assert_eq!(query(&addr), "Hauptstraße 1, 44787 Bochum, Deutschland");
+        let addr = Address {
+            city: Some("Bochum".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(query(&addr), "Bochum");
+    }
+
+    #[test]
+    fn read_the_best_match() {
+        let json = r#"{
+            "results": [{
+                "bounds": {
+                    "northeast": {"lat": 51.53, "lng": 7.35},
+                    "southwest": {"lat": 51.41, "lng": 7.10}
+                },
+                "components": {"city": "Bochum"},
+                "confidence": 5,
+                "formatted": "Bochum, Deutschland",
+                "geometry": {"lat": 51.4818, "lng": 7.2162}
+            }],
+            "status": {"code": 200, "message": "OK"},
+            "total_results": 1
+        }"#;
+        let response: Response = serde_json::from_str(json).unwrap();
+        let m = GeocodeMatch::from(response.results.into_iter().next().unwrap());
+        assert_eq!(m.confidence, 5);
+        assert_eq!(m.lat, 51.4818);
+        assert_eq!(m.bounds.unwrap().sw_lng, 7.10);
+
+        let matches = GeocodeMatches::default();
+        let addr = Address {
+            city: Some("Bochum".to_string()),
+            ..Default::default()
+        };
+        matches.insert(&addr, m);
+        let same = Address {
+            city: Some(" bochum".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(matches.get(&same), Some(m));
+    }
+}
@@ -0,0 +1,376 @@
+//! Check and fix values of the `opening_hours` field.
+//!
+//! The frontend only understands the
+//! [OSM opening_hours syntax](https://wiki.openstreetmap.org/wiki/Key:opening_hours),
+//! so other formats can't be rendered.
+
+const WEEKDAYS: &[&str] = &["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+
+/// Public and school holidays.
+const HOLIDAYS: &[&str] = &["PH", "SH"];
+
+const MONTHS: &[&str] = &[
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+const MODIFIERS: &[&str] = &["open", "closed", "off", "unknown"];
+
+const EVENTS: &[&str] = &["sunrise", "sunset", "dawn", "dusk"];
+
+/// The selectors of a rule in the required order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Selector {
+    Date,
+    Weekday,
+    Time,
+    Modifier,
+}
+
+/// Check if a value follows the OSM opening_hours syntax.
+///
+/// Only the commonly used subset is supported:
+/// years, months, days, weeks, weekdays, holidays, times, `24/7`,
+/// the modifiers (`open`, `closed`, `off`, `unknown`) and comments.
+pub fn validate(value: &str) -> Result<(), String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err("The opening hours are empty".to_string());
+    }
+    for rule in value.split(';').flat_map(|r| r.split("||")) {
+        validate_rule(rule.trim())?;
+    }
+    Ok(())
+}
+
+fn validate_rule(rule: &str) -> Result<(), String> {
+    let (selectors, has_comment) = match rule.split_once('"') {
+        Some((selectors, comment)) => {
+            let Some(comment) = comment.strip_suffix('"') else {
+                return Err(format!("Unterminated comment in '{rule}'"));
+            };
+            if comment.contains('"') {
+                return Err(format!("Unexpected '\"' in '{rule}'"));
+            }
+            (selectors, true)
+        }
+        None => (rule, false),
+    };
+    let mut last = None;
+    for token in selectors.split_whitespace() {
+        let unexpected = || format!("Unexpected '{token}' in '{rule}'");
+        let selector = selector(token, last).ok_or_else(unexpected)?;
+        if let Some(last) = last {
+            if selector < last || (selector == last && selector != Selector::Date) {
+                return Err(unexpected());
+            }
+        }
+        last = Some(selector);
+    }
+    if last.is_none() && !has_comment {
+        return Err("Empty rule".to_string());
+    }
+    Ok(())
+}
+
+fn selector(token: &str, last: Option<Selector>) -> Option<Selector> {
+    if token == "24/7" || list(token).all(is_time_span) {
+        Some(Selector::Time)
+    } else if MODIFIERS.contains(&token) {
+        Some(Selector::Modifier)
+    } else if list(token).all(is_weekday_range) {
+        Some(Selector::Weekday)
+    } else if token == "week"
+        || is_year_range(token)
+        || list(token).all(is_month_range)
+        // e.g. "Dec 24-26" or "week 01-26"
+        || (last == Some(Selector::Date) && list(token).all(is_number_range))
+    {
+        Some(Selector::Date)
+    } else {
+        None
+    }
+}
+
+/// Split a comma separated list, ignoring commas within brackets (e.g. `Su[1,3]`).
+fn list(token: &str) -> impl Iterator<Item = &str> {
+    let mut items = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in token.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&token[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&token[start..]);
+    items.into_iter()
+}
+
+fn is_weekday_range(s: &str) -> bool {
+    if HOLIDAYS.contains(&s) {
+        return true;
+    }
+    if let Some((day, nth)) = s.split_once('[') {
+        return WEEKDAYS.contains(&day)
+            && nth.strip_suffix(']').is_some_and(|nth| {
+                list(nth).all(|n| {
+                    let n = n.strip_prefix('-').unwrap_or(n);
+                    range(n, |n| n.parse::<u8>().is_ok_and(|n| (1..=5).contains(&n)))
+                })
+            });
+    }
+    range(s, |day| WEEKDAYS.contains(&day))
+}
+
+fn is_month_range(s: &str) -> bool {
+    range(s, |month| MONTHS.contains(&month))
+}
+
+fn is_year_range(s: &str) -> bool {
+    let is_year = |y: &str| y.len() == 4 && y.parse::<u16>().is_ok();
+    range(s.strip_suffix('+').unwrap_or(s), is_year)
+}
+
+fn is_number_range(s: &str) -> bool {
+    range(s, |n| {
+        n.len() <= 2 && n.parse::<u8>().is_ok_and(|n| (1..=53).contains(&n))
+    })
+}
+
+fn is_time_span(s: &str) -> bool {
+    if let Some(open_end) = s.strip_suffix('+') {
+        return is_time(open_end);
+    }
+    match s.split_once('-') {
+        Some((from, to)) => is_time(from) && is_time(to),
+        None => is_time(s),
+    }
+}
+
+/// `HH:MM` (up to `48:00` for times after midnight) or an event like `sunset`.
+fn is_time(s: &str) -> bool {
+    if EVENTS.contains(&s) {
+        return true;
+    }
+    let Some((h, m)) = s.split_once(':') else {
+        return false;
+    };
+    h.len() == 2
+        && m.len() == 2
+        && h.parse::<u8>().is_ok_and(|h| h <= 48)
+        && m.parse::<u8>().is_ok_and(|m| m < 60)
+}
+
+/// A single value or a range `from-to`.
+fn range(s: &str, is_valid: impl Fn(&str) -> bool) -> bool {
+    match s.split_once('-') {
+        Some((from, to)) => is_valid(from) && is_valid(to),
+        None => is_valid(s),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Weekday(&'static str),
+    Time(String),
+    Modifier(&'static str),
+    Dash,
+    Comma,
+    Semicolon,
+}
+
+/// Try to convert a common human format into the OSM syntax,
+/// e.g. `Mo.-Fr. 9-17 Uhr, Sa 10-14 Uhr` into `Mo-Fr 09:00-17:00; Sa 10:00-14:00`.
+///
+/// German and English weekdays are supported.
+/// Returns `None` if the value can't be converted.
+pub fn normalize(value: &str) -> Option<String> {
+    let value = value.trim();
+    if validate(value).is_ok() {
+        return Some(value.to_string());
+    }
+    let tokens = tokenize(value)?;
+    let mut osm = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|i| &tokens[i]);
+        let next = tokens.get(i + 1);
+        match token {
+            Token::Weekday(s) | Token::Modifier(s) => {
+                if matches!(
+                    prev,
+                    Some(Token::Weekday(_) | Token::Time(_) | Token::Modifier(_))
+                ) {
+                    osm.push(' ');
+                }
+                osm.push_str(s);
+            }
+            Token::Time(s) => {
+                if matches!(prev, Some(Token::Weekday(_) | Token::Modifier(_))) {
+                    osm.push(' ');
+                }
+                osm.push_str(s);
+            }
+            Token::Dash => osm.push('-'),
+            // "Mo-Fr 9-17, Sa 10-14" are two rules
+            Token::Comma
+                if matches!(prev, Some(Token::Time(_) | Token::Modifier(_)))
+                    && matches!(next, Some(Token::Weekday(_))) =>
+            {
+                osm.push_str("; ")
+            }
+            Token::Comma => osm.push(','),
+            Token::Semicolon => osm.push_str("; "),
+        }
+    }
+    let osm = osm.trim_matches(|c: char| c == ';' || c == ',' || c.is_whitespace());
+    validate(osm).ok().map(|()| osm.to_string())
+}
+
+fn tokenize(value: &str) -> Option<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = value.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() || c == '.' => {}
+            '-' | '–' | '—' => tokens.push(Token::Dash),
+            ',' | '&' => tokens.push(Token::Comma),
+            ';' => tokens.push(Token::Semicolon),
+            c if c.is_ascii_digit() => {
+                let mut end = start + 1;
+                while let Some((i, c)) = chars.peek().copied() {
+                    let is_separator = (c == ':' || c == '.')
+                        && value[i + 1..].starts_with(|c: char| c.is_ascii_digit());
+                    if !c.is_ascii_digit() && !is_separator {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Time(time(&value[start..end])?));
+            }
+            c if c.is_alphabetic() => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = chars.peek().copied() {
+                    if !c.is_alphabetic() {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.extend(word(&value[start..end].to_lowercase())?);
+            }
+            _ => return None,
+        }
+    }
+    Some(tokens)
+}
+
+/// Convert `9`, `9:30` or `9.30` into `09:00` or `09:30`.
+fn time(s: &str) -> Option<String> {
+    let (h, m) = s.split_once([':', '.']).unwrap_or((s, "0"));
+    let h = h.parse::<u8>().ok().filter(|h| *h <= 48)?;
+    let m = m.parse::<u8>().ok().filter(|m| *m < 60)?;
+    Some(format!("{h:02}:{m:02}"))
+}
+
+fn word(word: &str) -> Option<Vec<Token>> {
+    let weekday = match word {
+        "mo" | "mon" | "monday" | "montag" => "Mo",
+        "di" | "tu" | "tue" | "tuesday" | "dienstag" => "Tu",
+        "mi" | "we" | "wed" | "wednesday" | "mittwoch" => "We",
+        "do" | "th" | "thu" | "thursday" | "donnerstag" => "Th",
+        "fr" | "fri" | "friday" | "freitag" => "Fr",
+        "sa" | "sat" | "saturday" | "samstag" | "sonnabend" => "Sa",
+        "so" | "su" | "sun" | "sunday" | "sonntag" => "Su",
+        "ph" | "feiertag" | "feiertags" | "feiertage" => "PH",
+        "uhr" | "h" => return Some(vec![]),
+        "bis" | "to" => return Some(vec![Token::Dash]),
+        "und" | "and" => return Some(vec![Token::Comma]),
+        "täglich" | "daily" => {
+            return Some(vec![
+                Token::Weekday("Mo"),
+                Token::Dash,
+                Token::Weekday("Su"),
+            ])
+        }
+        "geschlossen" | "ruhetag" | "closed" | "off" => return Some(vec![Token::Modifier("off")]),
+        _ => return None,
+    };
+    Some(vec![Token::Weekday(weekday)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_osm_syntax() {
+        for valid in [
+            "24/7",
+            "Mo-Fr 09:00-17:00",
+            "Mo-Fr 09:00-12:00,14:00-18:00; Sa 10:00-14:00; PH off",
+            "Tu,Th 15:00-19:00",
+            "Su[1] 10:00-12:00",
+            "Dec 24-26 off",
+            "Jan-Mar Mo-Fr 08:00-16:00",
+            "Fr 18:00-02:00",
+            "Sa 20:00+",
+            "Mo-Su sunrise-sunset",
+            "Mo-Fr 10:00-18:00 \"nach Vereinbarung\"",
+            "\"nur nach Vereinbarung\"",
+        ] {
+            assert_eq!(validate(valid), Ok(()), "{valid}");
+        }
+        for invalid in [
+            "",
+            "Mo-Fr 9-17 Uhr",
+            "Mo-Fr 9:00-17:00",
+            "mo-fr 09:00-17:00",
+            "09:00-17:00 Mo-Fr",
+            "Mo-Fr 09:00-12:00 14:00-18:00",
+            "Mo-Fr 09:00-25:70",
+            "Mo-Fr 09:00-17:00;",
+            "nach Vereinbarung",
+            "Mo 10:00 \"open",
+        ] {
+            assert!(validate(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn convert_human_formats() {
+        for (human, osm) in [
+            ("Mo-Fr 9-17 Uhr", "Mo-Fr 09:00-17:00"),
+            (
+                "Mo.-Fr. 9.30-18.00 Uhr, Sa. 10-14 Uhr",
+                "Mo-Fr 09:30-18:00; Sa 10:00-14:00",
+            ),
+            ("Di und Do 15 bis 19 Uhr", "Tu,Th 15:00-19:00"),
+            (
+                "Mo - Fr 9:00 - 12:00 & 14:00 - 18:00",
+                "Mo-Fr 09:00-12:00,14:00-18:00",
+            ),
+            (
+                "täglich 8-20 Uhr; So geschlossen",
+                "Mo-Su 08:00-20:00; Su off",
+            ),
+            ("Monday to Friday 9-17", "Mo-Fr 09:00-17:00"),
+            (" Mo-Fr 09:00-17:00 ", "Mo-Fr 09:00-17:00"),
+            ("Mo-Fr 09:00-17:00;", "Mo-Fr 09:00-17:00"),
+        ] {
+            assert_eq!(normalize(human).as_deref(), Some(osm), "{human}");
+        }
+        for unknown in [
+            "nach Vereinbarung",
+            "Mo-Fr 9-17 Uhr (außer Ferien)",
+            "Mo 9-17 Uhr!",
+        ] {
+            assert_eq!(normalize(unknown), None, "{unknown}");
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Entries as a Parquet file for data analysis (e.g. with pandas or DuckDB).
+//!
+//! The columns are the columns of the CSV export,
+//! but with typed values: the tags are a list and `founded_on` is a date.
+
+use std::{io::Write, sync::Arc};
+
+use anyhow::Result;
+use arrow_array::{
+    builder::{ListBuilder, StringBuilder},
+    ArrayRef, Date32Array, Float64Array, Int64Array, RecordBatch, StringArray, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema};
+use ofdb_boundary::Entry;
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+
+/// Julian day of 1970-01-01, the first day of a Parquet date.
+const UNIX_EPOCH_JULIAN_DAY: i32 = 2_440_588;
+
+const OPTIONAL_TEXT_COLUMNS: [&str; 13] = [
+    "street",
+    "zip",
+    "city",
+    "country",
+    "state",
+    "contact_name",
+    "contact_email",
+    "contact_phone",
+    "opening_hours",
+    "homepage",
+    "license",
+    "image_url",
+    "image_link_url",
+];
+
+fn schema() -> Schema {
+    let mut fields = vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("created", DataType::Int64, false),
+        Field::new("version", DataType::UInt64, false),
+        Field::new("title", DataType::Utf8, false),
+        Field::new("description", DataType::Utf8, false),
+        Field::new("lat", DataType::Float64, false),
+        Field::new("lng", DataType::Float64, false),
+    ];
+    for name in OPTIONAL_TEXT_COLUMNS {
+        fields.push(Field::new(name, DataType::Utf8, true));
+    }
+    fields.push(Field::new("founded_on", DataType::Date32, true));
+    fields.push(Field::new(
+        "tags",
+        DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+        false,
+    ));
+    Schema::new(fields)
+}
+
+/// Write the entries as one row group (Snappy compressed).
+pub fn write_entries<W: Write + Send>(w: W, entries: &[Entry]) -> Result<()> {
+    let strings = |f: fn(&Entry) -> &str| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(entries.iter().map(f)))
+    };
+    let optional = |f: fn(&Entry) -> &Option<String>| -> ArrayRef {
+        Arc::new(
+            entries
+                .iter()
+                .map(|e| f(e).as_deref())
+                .collect::<StringArray>(),
+        )
+    };
+    let mut tags = ListBuilder::new(StringBuilder::new());
+    for e in entries {
+        tags.append_value(e.tags.iter().map(Some));
+    }
+    let columns: Vec<ArrayRef> = vec![
+        strings(|e| &e.id),
+        Arc::new(Int64Array::from_iter_values(
+            entries.iter().map(|e| e.created),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            entries.iter().map(|e| e.version),
+        )),
+        strings(|e| &e.title),
+        strings(|e| &e.description),
+        Arc::new(Float64Array::from_iter_values(
+            entries.iter().map(|e| e.lat),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            entries.iter().map(|e| e.lng),
+        )),
+        optional(|e| &e.street),
+        optional(|e| &e.zip),
+        optional(|e| &e.city),
+        optional(|e| &e.country),
+        optional(|e| &e.state),
+        optional(|e| &e.contact_name),
+        optional(|e| &e.email),
+        optional(|e| &e.telephone),
+        optional(|e| &e.opening_hours),
+        optional(|e| &e.homepage),
+        optional(|e| &e.license),
+        optional(|e| &e.image_url),
+        optional(|e| &e.image_link_url),
+        Arc::new(
+            entries
+                .iter()
+                .map(|e| {
+                    e.founded_on
+                        .map(|d| d.to_julian_day() - UNIX_EPOCH_JULIAN_DAY)
+                })
+                .collect::<Date32Array>(),
+        ),
+        Arc::new(tags.finish()),
+    ];
+    let batch = RecordBatch::try_new(Arc::new(schema()), columns)?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(w, batch.schema(), Some(properties))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{cast::AsArray, types::Date32Type, Array};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn write_entries_with_typed_columns() {
+        let mut entries = crate::fixtures::generate(3, 0).entries;
+        entries[0].founded_on =
+            Some(time::Date::from_calendar_date(1970, time::Month::January, 11).unwrap());
+        entries[1].founded_on = None;
+        entries[1].tags = vec!["bio".to_string(), "regional".to_string()];
+        entries[2].city = None;
+
+        let path =
+            std::env::temp_dir().join(format!("ofdb-export-test-{}.parquet", std::process::id()));
+        write_entries(std::fs::File::create(&path).unwrap(), &entries).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        let batch = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.num_columns(), 22);
+        let title = batch.column_by_name("title").unwrap().as_string::<i32>();
+        assert_eq!(title.value(1), entries[1].title);
+        let city = batch.column_by_name("city").unwrap();
+        assert!(city.is_null(2));
+        let founded_on = batch
+            .column_by_name("founded_on")
+            .unwrap()
+            .as_primitive::<Date32Type>();
+        assert_eq!(founded_on.value(0), 10);
+        assert!(founded_on.is_null(1));
+        let tags = batch.column_by_name("tags").unwrap().as_list::<i32>();
+        let tags = tags.value(1);
+        let tags = tags.as_string::<i32>();
+        assert_eq!(
+            tags.iter().collect::<Vec<_>>(),
+            [Some("bio"), Some("regional")]
+        );
+    }
+}
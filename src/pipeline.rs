@@ -0,0 +1,474 @@
+//! A configurable import of new places
+//! that can be embedded into other applications.
+//!
+//! ```no_run
+//! use ofdb_cli::{pipeline::*, OfdbClient};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let api = OfdbClient::new("https://dev.ofdb.io/v0")?;
+//! let file = std::fs::File::open("import.csv")?;
+//! let outcome = ImportPipeline::new(&api, CsvSource::new(file))
+//!     .duplicate_policy(DuplicatePolicy::Ignore)
+//!     .on_result(|result| log::info!("{:?}", result.result))
+//!     .run()?;
+//! println!("{}", serde_json::to_string(&outcome.report)?);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::Read;
+
+use anyhow::Result;
+use ofdb_boundary::{NewPlace, PlaceSearchResult};
+use ofdb_core::gateways::geocode::GeoCodingGateway;
+use ofdb_gateways::opencage::OpenCage;
+
+use crate::{
+    csv,
+    deadline::Deadline,
+    import::{self, CsvImportResult, ImportResult, PlacesImport, Report, SuccessReport},
+    search::DuplicateSearch,
+    OfdbApi,
+};
+
+type Records<'g> = Box<dyn Iterator<Item = CsvImportResult<NewPlace>> + 'g>;
+
+/// Source of new places.
+pub trait PlaceSource {
+    /// Read all records and resolve missing geo coordinates.
+    fn read(self, geo_coding: &dyn GeoCodingGateway) -> Result<Vec<CsvImportResult<NewPlace>>>;
+
+    /// Read the records one after another.
+    ///
+    /// By default all records are read first.
+    fn stream<'g>(self, geo_coding: &'g dyn GeoCodingGateway) -> Result<Records<'g>>
+    where
+        Self: Sized + 'g,
+    {
+        Ok(Box::new(self.read(geo_coding)?.into_iter()))
+    }
+}
+
+/// New places in the CSV format of the `import` command.
+pub struct CsvSource<R>(R);
+
+impl<R: Read> CsvSource<R> {
+    pub fn new(reader: R) -> Self {
+        Self(reader)
+    }
+}
+
+impl<R: Read> PlaceSource for CsvSource<R> {
+    fn read(self, geo_coding: &dyn GeoCodingGateway) -> Result<Vec<CsvImportResult<NewPlace>>> {
+        csv::new_places_with_geo_coding(self.0, geo_coding)
+    }
+
+    fn stream<'g>(self, geo_coding: &'g dyn GeoCodingGateway) -> Result<Records<'g>>
+    where
+        Self: Sized + 'g,
+    {
+        let places = csv::stream_new_places(self.0, geo_coding, csv::CsvOptions::default())?;
+        Ok(Box::new(places))
+    }
+}
+
+/// Places that are already complete, e.g. from a JSON file.
+impl PlaceSource for Vec<NewPlace> {
+    fn read(self, _: &dyn GeoCodingGateway) -> Result<Vec<CsvImportResult<NewPlace>>> {
+        Ok(self
+            .into_iter()
+            .enumerate()
+            .map(|(record_nr, place)| CsvImportResult::new(record_nr, Ok(place)))
+            .collect())
+    }
+}
+
+type DuplicateCheck<'a> =
+    Box<dyn Fn(&NewPlace) -> crate::Result<Option<Vec<PlaceSearchResult>>> + 'a>;
+
+/// How to deal with possible duplicates.
+#[derive(Default)]
+pub enum DuplicatePolicy<'a> {
+    /// Don't create places if the server finds possible duplicates.
+    #[default]
+    Skip,
+    /// Create all places, even if they become duplicates.
+    Ignore,
+    /// Don't create places if there are places with a similar title nearby.
+    Search(DuplicateSearch),
+    /// Don't create places if the given function finds possible duplicates.
+    Custom(DuplicateCheck<'a>),
+}
+
+/// Result of an import pipeline run.
+pub struct PipelineOutcome {
+    pub report: Report<NewPlace, SuccessReport<NewPlace>>,
+    /// The run was stopped before all places were imported.
+    pub deadline_exceeded: bool,
+}
+
+type ResultSink<'a> = Box<dyn FnMut(&ImportResult<'_>) + 'a>;
+
+/// Builder of an import of new places.
+pub struct ImportPipeline<'a, A, S> {
+    api: &'a A,
+    source: S,
+    geo_coding: Box<dyn GeoCodingGateway + 'a>,
+    duplicate_policy: DuplicatePolicy<'a>,
+    on_result: Option<ResultSink<'a>>,
+    deadline: Deadline,
+    streaming: bool,
+}
+
+impl<'a, A: OfdbApi, S: PlaceSource> ImportPipeline<'a, A, S> {
+    /// Create a pipeline that does not resolve geo coordinates
+    /// and skips possible duplicates.
+    pub fn new(api: &'a A, source: S) -> Self {
+        Self {
+            api,
+            source,
+            geo_coding: Box::new(OpenCage::new(None)),
+            duplicate_policy: DuplicatePolicy::default(),
+            on_result: None,
+            deadline: Deadline::none(),
+            streaming: false,
+        }
+    }
+
+    /// Resolve missing geo coordinates with this gateway.
+    pub fn geo_coding(mut self, geo_coding: impl GeoCodingGateway + 'a) -> Self {
+        self.geo_coding = Box::new(geo_coding);
+        self
+    }
+
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy<'a>) -> Self {
+        self.duplicate_policy = policy;
+        self
+    }
+
+    /// Call `f` with the result of each place, e.g. to store the new IDs.
+    pub fn on_result(mut self, f: impl FnMut(&ImportResult<'_>) + 'a) -> Self {
+        self.on_result = Some(Box::new(f));
+        self
+    }
+
+    /// Stop gracefully after the deadline.
+    pub fn deadline(mut self, deadline: Deadline) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Create each place right after its record was read (and geocoded),
+    /// so that the memory usage does not grow with the size of the source.
+    ///
+    /// Invalid records are reported but don't stop the import of the valid ones.
+    pub fn streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Read the source and create the places.
+    ///
+    /// If a record of the source is invalid, nothing is imported
+    /// and the report contains the invalid records
+    /// (unless the pipeline is [streaming](Self::streaming)).
+    pub fn run(self) -> Result<PipelineOutcome>
+    where
+        S: 'a,
+    {
+        let Self {
+            api,
+            source,
+            geo_coding,
+            duplicate_policy,
+            mut on_result,
+            deadline,
+            streaming,
+        } = self;
+        let check_duplicates = |p: &NewPlace| match &duplicate_policy {
+            DuplicatePolicy::Skip => api.search_duplicates(p),
+            DuplicatePolicy::Ignore => Ok(None),
+            DuplicatePolicy::Search(options) => api.search_duplicates_with(p, options),
+            DuplicatePolicy::Custom(check) => check(p),
+        };
+        let on_result = |result: &ImportResult<'_>| {
+            if let Some(f) = on_result.as_mut() {
+                f(result);
+            }
+        };
+        if streaming {
+            let records = source.stream(&*geo_coding)?;
+            return stream_places(api, records, check_duplicates, deadline, on_result);
+        }
+        let records = source.read(&*geo_coding)?;
+        if records.iter().any(|r| r.result.is_err()) {
+            let report = Report::from(records);
+            log::warn!(
+                "{} records contain errors",
+                report.csv_import_failures.len()
+            );
+            return Ok(PipelineOutcome {
+                report,
+                deadline_exceeded: false,
+            });
+        }
+        let places: Vec<_> = records
+            .into_iter()
+            .filter_map(|r| r.result.ok().map(|p| (r.record_nr, p)))
+            .collect();
+        let places_with_ids = places
+            .iter()
+            .map(|(record_nr, p)| (Some(record_nr.to_string()), p))
+            .collect();
+        let PlacesImport {
+            results,
+            deadline_exceeded,
+        } = import::import_places_with(
+            api,
+            places_with_ids,
+            check_duplicates,
+            deadline,
+            on_result,
+        )?;
+        Ok(PipelineOutcome {
+            report: Report::from(results),
+            deadline_exceeded,
+        })
+    }
+}
+
+/// Create the places of the records one after another.
+pub fn stream_places<A, D, R>(
+    api: &A,
+    records: impl Iterator<Item = CsvImportResult<NewPlace>>,
+    check_duplicates: D,
+    deadline: Deadline,
+    mut on_result: R,
+) -> Result<PipelineOutcome>
+where
+    A: OfdbApi,
+    D: Fn(&NewPlace) -> crate::Result<Option<Vec<PlaceSearchResult>>>,
+    R: FnMut(&ImportResult<'_>),
+{
+    let mut report = Report::default();
+    for record in records {
+        let place = match record.result {
+            Ok(place) => place,
+            Err(err) => {
+                log::warn!("Record {} contains errors: {err}", record.record_nr);
+                report.append(Report::from(vec![CsvImportResult::<NewPlace>::new(
+                    record.record_nr,
+                    Err(err),
+                )]));
+                continue;
+            }
+        };
+        let PlacesImport {
+            results,
+            deadline_exceeded,
+        } = import::import_places_with(
+            api,
+            vec![(Some(record.record_nr.to_string()), &place)],
+            &check_duplicates,
+            deadline,
+            &mut on_result,
+        )?;
+        report.append(Report::from(results));
+        if deadline_exceeded {
+            log::warn!("Stop after record {}", record.record_nr);
+            return Ok(PipelineOutcome {
+                report,
+                deadline_exceeded,
+            });
+        }
+    }
+    Ok(PipelineOutcome {
+        report,
+        deadline_exceeded: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::CsvImportError;
+    use ofdb_boundary::{Entry, MapBbox, Review, SearchResponse, UpdatePlace};
+    use std::{cell::RefCell, time::Duration};
+    use uuid::Uuid;
+
+    /// In-memory API that reports places with the title "Duplicate" as duplicates.
+    #[derive(Default)]
+    struct FakeApi {
+        created: RefCell<Vec<String>>,
+    }
+
+    impl OfdbApi for FakeApi {
+        fn create_place(&self, new_place: &NewPlace) -> crate::Result<String> {
+            let mut created = self.created.borrow_mut();
+            created.push(new_place.title.clone());
+            Ok(format!("id-{}", created.len()))
+        }
+        fn search_duplicates(
+            &self,
+            new_place: &NewPlace,
+        ) -> crate::Result<Option<Vec<PlaceSearchResult>>> {
+            if new_place.title != "Duplicate" {
+                return Ok(None);
+            }
+            Ok(Some(vec![]))
+        }
+        fn update_place(&self, _: &str, _: &UpdatePlace) -> crate::Result<String> {
+            Err(crate::not_supported("update_place"))
+        }
+        fn read_entries(&self, _: Vec<Uuid>) -> crate::Result<Vec<Entry>> {
+            Err(crate::not_supported("read_entries"))
+        }
+        fn search(&self, _: &str, _: &MapBbox) -> crate::Result<SearchResponse> {
+            Err(crate::not_supported("search"))
+        }
+        fn review(&self, _: Vec<Uuid>, _: Review) -> crate::Result<()> {
+            Err(crate::not_supported("review"))
+        }
+    }
+
+    fn new_place(title: &str) -> NewPlace {
+        NewPlace {
+            title: title.to_string(),
+            description: "A place".to_string(),
+            lat: 51.5,
+            lng: 7.2,
+            street: None,
+            zip: None,
+            city: None,
+            country: None,
+            state: None,
+            contact_name: None,
+            email: None,
+            telephone: None,
+            homepage: None,
+            opening_hours: None,
+            founded_on: None,
+            categories: vec![],
+            tags: vec![],
+            license: "CC0-1.0".to_string(),
+            image_url: None,
+            image_link_url: None,
+            links: vec![],
+        }
+    }
+
+    fn places(titles: &[&str]) -> Vec<NewPlace> {
+        titles.iter().map(|t| new_place(t)).collect()
+    }
+
+    #[test]
+    fn create_places_in_the_order_of_the_source() {
+        let api = FakeApi::default();
+        let mut results = vec![];
+        let outcome = ImportPipeline::new(&api, places(&["A", "B", "C"]))
+            .on_result(|r| results.push((r.import_id.clone(), r.result.as_ref().ok().cloned())))
+            .run()
+            .unwrap();
+        assert!(!outcome.deadline_exceeded);
+        assert_eq!(*api.created.borrow(), vec!["A", "B", "C"]);
+        assert_eq!(
+            results,
+            vec![
+                (Some("0".to_string()), Some("id-1".to_string())),
+                (Some("1".to_string()), Some("id-2".to_string())),
+                (Some("2".to_string()), Some("id-3".to_string())),
+            ]
+        );
+        let import_ids: Vec<_> = outcome
+            .report
+            .successes
+            .iter()
+            .map(|s| s.import_id.as_deref().unwrap())
+            .collect();
+        assert_eq!(import_ids, vec!["0", "1", "2"]);
+    }
+
+    #[test]
+    fn skip_duplicates_by_default() {
+        let api = FakeApi::default();
+        let outcome = ImportPipeline::new(&api, places(&["A", "Duplicate"]))
+            .run()
+            .unwrap();
+        assert_eq!(*api.created.borrow(), vec!["A"]);
+        assert_eq!(outcome.report.successes.len(), 1);
+        assert_eq!(outcome.report.duplicates.len(), 1);
+        assert_eq!(outcome.report.duplicates[0].import_id.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn apply_the_duplicate_policy() {
+        let api = FakeApi::default();
+        let outcome = ImportPipeline::new(&api, places(&["A", "Duplicate"]))
+            .duplicate_policy(DuplicatePolicy::Ignore)
+            .run()
+            .unwrap();
+        assert_eq!(*api.created.borrow(), vec!["A", "Duplicate"]);
+        assert!(outcome.report.duplicates.is_empty());
+
+        let api = FakeApi::default();
+        let check = |p: &NewPlace| -> crate::Result<_> { Ok((p.title == "A").then(Vec::new)) };
+        let outcome = ImportPipeline::new(&api, places(&["A", "Duplicate"]))
+            .duplicate_policy(DuplicatePolicy::Custom(Box::new(check)))
+            .run()
+            .unwrap();
+        assert_eq!(*api.created.borrow(), vec!["Duplicate"]);
+        assert_eq!(outcome.report.duplicates[0].new_place.title, "A");
+    }
+
+    #[test]
+    fn stop_after_the_deadline() {
+        for streaming in [false, true] {
+            let api = FakeApi::default();
+            let outcome = ImportPipeline::new(&api, places(&["A", "B"]))
+                .deadline(Deadline::after(Some(Duration::ZERO)))
+                .streaming(streaming)
+                .run()
+                .unwrap();
+            assert!(outcome.deadline_exceeded);
+            assert!(api.created.borrow().is_empty());
+            assert!(outcome.report.successes.is_empty());
+        }
+    }
+
+    #[test]
+    fn stream_valid_records_after_invalid_ones() {
+        let api = FakeApi::default();
+        let records = vec![
+            CsvImportResult::new(0, Err(CsvImportError::Record("invalid".to_string()))),
+            CsvImportResult::new(1, Ok(new_place("A"))),
+        ];
+        let outcome = stream_places(
+            &api,
+            records.into_iter(),
+            |p| api.search_duplicates(p),
+            Deadline::none(),
+            |_| {},
+        )
+        .unwrap();
+        assert_eq!(*api.created.borrow(), vec!["A"]);
+        assert_eq!(outcome.report.csv_import_failures[0].record_nr, 0);
+        assert_eq!(outcome.report.successes.len(), 1);
+    }
+
+    #[test]
+    fn import_nothing_if_a_record_is_invalid() {
+        struct InvalidSource;
+        impl PlaceSource for InvalidSource {
+            fn read(self, _: &dyn GeoCodingGateway) -> Result<Vec<CsvImportResult<NewPlace>>> {
+                Ok(vec![
+                    CsvImportResult::new(0, Ok(new_place("A"))),
+                    CsvImportResult::new(1, Err(CsvImportError::Record("invalid".to_string()))),
+                ])
+            }
+        }
+        let api = FakeApi::default();
+        let outcome = ImportPipeline::new(&api, InvalidSource).run().unwrap();
+        assert!(api.created.borrow().is_empty());
+        assert_eq!(outcome.report.csv_import_failures.len(), 1);
+    }
+}
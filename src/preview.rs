@@ -0,0 +1,177 @@
+//! A map of the records of an import file to check the locations before the import.
+
+use std::io::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::xml::escape;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkerStatus {
+    Ok,
+    Duplicate,
+    Error,
+}
+
+impl MarkerStatus {
+    fn color(self) -> &'static str {
+        match self {
+            Self::Ok => "#2e7d32",
+            Self::Duplicate => "#ef6c00",
+            Self::Error => "#c62828",
+        }
+    }
+}
+
+/// A record of the import file.
+#[derive(Debug, Clone, Serialize)]
+pub struct Marker {
+    pub record_nr: usize,
+    pub title: String,
+    /// Missing if the record could not be read or geocoded.
+    pub lat_lng: Option<(f64, f64)>,
+    pub status: MarkerStatus,
+    /// The error or the titles of the possible duplicates.
+    pub message: Option<String>,
+}
+
+/// Write a HTML page with a [Leaflet](https://leafletjs.com) map.
+///
+/// Records without coordinates are listed below the map.
+pub fn write_map<W: Write>(mut w: W, title: &str, markers: &[Marker]) -> Result<()> {
+    let data = serde_json::to_string(
+        &markers
+            .iter()
+            .filter_map(|m| {
+                let (lat, lng) = m.lat_lng?;
+                Some(serde_json::json!({
+                    "lat": lat,
+                    "lng": lng,
+                    "color": m.status.color(),
+                    "popup": popup(m),
+                }))
+            })
+            .collect::<Vec<_>>(),
+    )?
+    // Prevent that a title closes the script element
+    .replace("</", "<\\/");
+    let count = |status| markers.iter().filter(|m| m.status == status).count();
+    let title = escape(title);
+    write!(
+        w,
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<link rel="stylesheet" href="https://unpkg.com/leaflet@1.9.4/dist/leaflet.css">
+<script src="https://unpkg.com/leaflet@1.9.4/dist/leaflet.js"></script>
+<style>
+body {{ font-family: sans-serif; margin: 1em; }}
+#map {{ height: 70vh; }}
+.legend span {{ display: inline-block; width: 0.8em; height: 0.8em; border-radius: 50%; margin: 0 0.3em 0 1em; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p class="legend"><span style="background: {ok_color}"></span>{ok} OK<span style="background: {duplicate_color}"></span>{duplicates} possible duplicates<span style="background: {error_color}"></span>{errors} errors</p>
+<div id="map"></div>
+<script>
+const markers = {data};
+const map = L.map("map");
+L.tileLayer("https://tile.openstreetmap.org/{{z}}/{{x}}/{{y}}.png", {{
+  maxZoom: 19,
+  attribution: '&copy; <a href="https://www.openstreetmap.org/copyright">OpenStreetMap</a>'
+}}).addTo(map);
+const bounds = [];
+for (const m of markers) {{
+  L.circleMarker([m.lat, m.lng], {{ color: m.color, fillOpacity: 0.7, radius: 7 }})
+    .bindPopup(m.popup)
+    .addTo(map);
+  bounds.push([m.lat, m.lng]);
+}}
+if (bounds.length > 0) {{
+  map.fitBounds(bounds, {{ padding: [20, 20], maxZoom: 15 }});
+}} else {{
+  map.setView([51.0, 10.0], 5);
+}}
+</script>
+"#,
+        ok_color = MarkerStatus::Ok.color(),
+        ok = count(MarkerStatus::Ok),
+        duplicate_color = MarkerStatus::Duplicate.color(),
+        duplicates = count(MarkerStatus::Duplicate),
+        error_color = MarkerStatus::Error.color(),
+        errors = count(MarkerStatus::Error),
+    )?;
+    let without_location: Vec<_> = markers.iter().filter(|m| m.lat_lng.is_none()).collect();
+    if !without_location.is_empty() {
+        writeln!(w, "<h2>Records without location</h2>")?;
+        writeln!(w, "<table>")?;
+        writeln!(w, "<tr><th>Record</th><th>Title</th><th>Message</th></tr>")?;
+        for m in without_location {
+            writeln!(
+                w,
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                m.record_nr,
+                escape(&m.title),
+                escape(m.message.as_deref().unwrap_or_default())
+            )?;
+        }
+        writeln!(w, "</table>")?;
+    }
+    writeln!(w, "</body>")?;
+    writeln!(w, "</html>")?;
+    Ok(())
+}
+
+fn popup(m: &Marker) -> String {
+    let mut popup = format!("<b>{}</b><br>Record {}", escape(&m.title), m.record_nr);
+    if let Some(message) = &m.message {
+        popup.push_str("<br>");
+        popup.push_str(&escape(message));
+    }
+    popup
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_markers_and_records_without_location() {
+        let markers = vec![
+            Marker {
+                record_nr: 0,
+                title: "Repair Café".to_string(),
+                lat_lng: Some((51.48, 7.22)),
+                status: MarkerStatus::Ok,
+                message: None,
+            },
+            Marker {
+                record_nr: 1,
+                title: "</script><b>".to_string(),
+                lat_lng: Some((51.49, 7.23)),
+                status: MarkerStatus::Duplicate,
+                message: Some("Possible duplicate of 'Foo'".to_string()),
+            },
+            Marker {
+                record_nr: 2,
+                title: "Nowhere".to_string(),
+                lat_lng: None,
+                status: MarkerStatus::Error,
+                message: Some("Invalid address".to_string()),
+            },
+        ];
+        let mut html = vec![];
+        write_map(&mut html, "Preview", &markers).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains(r#""lat":51.48"#));
+        assert!(html.contains("#ef6c00"));
+        assert!(!html.contains("</script><b>"));
+        assert!(html.contains("<tr><td>2</td><td>Nowhere</td><td>Invalid address</td></tr>"));
+        assert!(html.contains("1 OK"));
+    }
+}
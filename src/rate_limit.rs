@@ -0,0 +1,167 @@
+//! Client side rate limiting.
+
+use std::{
+    fmt,
+    str::FromStr,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use ofdb_core::gateways::geocode::GeoCodingGateway;
+use ofdb_entities::address::Address;
+
+/// Max. number of requests per period, e.g. `5/s` or `100/min`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+impl FromStr for RateLimit {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (requests, per) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow!("Invalid rate limit '{s}' (expected e.g. '5/s')"))?;
+        let requests = requests
+            .trim()
+            .parse::<u32>()
+            .map_err(|err| anyhow!("Invalid number of requests '{requests}': {err}"))?;
+        if requests == 0 {
+            bail!("The number of requests must be greater than zero");
+        }
+        let per = match per.trim() {
+            "s" | "sec" => Duration::from_secs(1),
+            "m" | "min" => Duration::from_secs(60),
+            "h" => Duration::from_secs(60 * 60),
+            per => humantime::parse_duration(per)
+                .map_err(|err| anyhow!("Invalid period '{per}': {err}"))?,
+        };
+        if per.is_zero() {
+            bail!("The period must be greater than zero");
+        }
+        Ok(Self { requests, per })
+    }
+}
+
+impl fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}",
+            self.requests,
+            humantime::format_duration(self.per)
+        )
+    }
+}
+
+/// Token bucket that allows bursts of up to `requests` requests
+/// and then blocks until new tokens are available.
+#[derive(Debug)]
+pub struct TokenBucket {
+    limit: RateLimit,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(limit: RateLimit) -> Self {
+        let state = State {
+            tokens: f64::from(limit.requests),
+            last_refill: Instant::now(),
+        };
+        Self {
+            limit,
+            state: Mutex::new(state),
+        }
+    }
+
+    fn tokens_per_sec(&self) -> f64 {
+        f64::from(self.limit.requests) / self.limit.per.as_secs_f64()
+    }
+
+    /// Take a token or return the time to wait for the next one.
+    fn try_acquire(&self, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().expect("unpoisoned lock");
+        let elapsed = now.saturating_duration_since(state.last_refill);
+        state.tokens = (state.tokens + elapsed.as_secs_f64() * self.tokens_per_sec())
+            .min(f64::from(self.limit.requests));
+        state.last_refill = now;
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - state.tokens;
+            Err(Duration::from_secs_f64(missing / self.tokens_per_sec()))
+        }
+    }
+
+    /// Block until a request is allowed.
+    pub fn acquire(&self) {
+        while let Err(wait) = self.try_acquire(Instant::now()) {
+            log::trace!("Rate limit ({}) reached, wait {wait:?}", self.limit);
+            thread::sleep(wait);
+        }
+    }
+}
+
+/// Geo coding gateway with a limited number of requests.
+pub struct RateLimitedGeoCoding<G> {
+    inner: G,
+    bucket: TokenBucket,
+}
+
+impl<G> RateLimitedGeoCoding<G> {
+    pub fn new(inner: G, limit: RateLimit) -> Self {
+        let bucket = TokenBucket::new(limit);
+        Self { inner, bucket }
+    }
+}
+
+impl<G: GeoCodingGateway> GeoCodingGateway for RateLimitedGeoCoding<G> {
+    fn resolve_address_lat_lng(&self, addr: &Address) -> Option<(f64, f64)> {
+        self.bucket.acquire();
+        self.inner.resolve_address_lat_lng(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rate_limits() {
+        let limit = "5/s".parse::<RateLimit>().unwrap();
+        assert_eq!(limit.requests, 5);
+        assert_eq!(limit.per, Duration::from_secs(1));
+        assert_eq!(
+            "100/min".parse::<RateLimit>().unwrap().per,
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            "1/500ms".parse::<RateLimit>().unwrap().per,
+            Duration::from_millis(500)
+        );
+        assert!("0/s".parse::<RateLimit>().is_err());
+        assert!("5".parse::<RateLimit>().is_err());
+        assert!("5/0s".parse::<RateLimit>().is_err());
+    }
+
+    #[test]
+    fn limit_requests() {
+        let bucket = TokenBucket::new("2/s".parse().unwrap());
+        let start = Instant::now();
+        assert!(bucket.try_acquire(start).is_ok());
+        assert!(bucket.try_acquire(start).is_ok());
+        let wait = bucket.try_acquire(start).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+        assert!(bucket.try_acquire(start + wait).is_ok());
+    }
+}
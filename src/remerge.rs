@@ -0,0 +1,271 @@
+//! Re-apply the changes of an update onto a newer version of an entry,
+//! if someone else edited the entry after the file was exported
+//! (the update is rejected with a version conflict).
+
+use anyhow::{anyhow, Result};
+use ofdb_boundary::{Entry, PlaceRevision};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::OfdbApi;
+
+/// Fields that are not merged, they are taken from the current version.
+const IGNORED_FIELDS: &[&str] = &["id", "created", "version", "ratings", "license"];
+
+/// A field that was changed by the update and by someone else.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct FieldConflict {
+    pub field: String,
+    /// The value of the version that the update is based on.
+    pub base: Value,
+    /// The current value on the server.
+    pub theirs: Value,
+    /// The value of the update.
+    pub mine: Value,
+}
+
+/// Apply the changes between `base` and `mine` to `theirs`.
+///
+/// A field that was changed on both sides to different values is a conflict,
+/// except for the tags: the added and removed tags are applied to the current tags.
+/// The merged entry has the version of `theirs`.
+pub fn three_way_merge(
+    base: &Entry,
+    theirs: &Entry,
+    mine: &Entry,
+) -> Result<Result<Entry, Vec<FieldConflict>>> {
+    let base = serde_json::to_value(base)?;
+    let mine = serde_json::to_value(mine)?;
+    let mut merged = serde_json::to_value(theirs)?;
+    let (Value::Object(base), Value::Object(mine), Value::Object(merged_fields)) =
+        (&base, &mine, &mut merged)
+    else {
+        return Err(anyhow!("Entries must be JSON objects"));
+    };
+    let mut conflicts = vec![];
+    for (field, mine) in mine {
+        if IGNORED_FIELDS.contains(&field.as_str()) {
+            continue;
+        }
+        let base = base.get(field).unwrap_or(&Value::Null);
+        let theirs = merged_fields.get(field).cloned().unwrap_or(Value::Null);
+        if mine == base || *mine == theirs {
+            continue;
+        }
+        if field == "tags" {
+            merged_fields.insert(field.clone(), merge_tags(base, &theirs, mine));
+        } else if theirs == *base {
+            merged_fields.insert(field.clone(), mine.clone());
+        } else {
+            conflicts.push(FieldConflict {
+                field: field.clone(),
+                base: base.clone(),
+                theirs,
+                mine: mine.clone(),
+            });
+        }
+    }
+    if !conflicts.is_empty() {
+        return Ok(Err(conflicts));
+    }
+    Ok(Ok(serde_json::from_value(merged)?))
+}
+
+fn merge_tags(base: &Value, theirs: &Value, mine: &Value) -> Value {
+    let tags = |v: &Value| -> Vec<String> {
+        v.as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(ToString::to_string)
+            .collect()
+    };
+    let (base, mut merged, mine) = (tags(base), tags(theirs), tags(mine));
+    merged.retain(|tag| mine.contains(tag) || !base.contains(tag));
+    for tag in mine {
+        if !base.contains(&tag) && !merged.contains(&tag) {
+            merged.push(tag);
+        }
+    }
+    Value::Array(merged.into_iter().map(Value::String).collect())
+}
+
+/// The entry in the state of a revision.
+///
+/// Fields that are not part of a revision are taken from the entry.
+pub fn entry_at_revision(entry: &Entry, revision: &PlaceRevision) -> Entry {
+    let address = revision.location.address.clone().unwrap_or_default();
+    Entry {
+        version: revision.revision,
+        title: revision.title.clone(),
+        description: revision.description.clone(),
+        lat: revision.location.lat,
+        lng: revision.location.lng,
+        street: address.street,
+        zip: address.zip,
+        city: address.city,
+        country: address.country,
+        state: address.state,
+        contact_name: revision.contact.name.clone(),
+        email: revision.contact.email.clone(),
+        telephone: revision.contact.phone.clone(),
+        homepage: revision.links.homepage.clone(),
+        opening_hours: revision.opening_hours.clone(),
+        founded_on: revision.founded_on,
+        tags: revision.tags.clone(),
+        image_url: revision.links.image.clone(),
+        image_link_url: revision.links.image_href.clone(),
+        custom_links: revision.links.custom.clone(),
+        ..entry.clone()
+    }
+}
+
+/// The outcome of a re-merge after a version conflict.
+#[derive(Debug, Clone)]
+pub enum Remerge {
+    /// The changes were applied to the current version.
+    Merged { entry: Entry, theirs: Entry },
+    /// Both sides changed the same fields.
+    Conflicts {
+        base: Entry,
+        theirs: Entry,
+        conflicts: Vec<FieldConflict>,
+    },
+}
+
+/// Read the current version of the entry and apply the changes of `mine`.
+///
+/// `base_version` is the version that `mine` is based on.
+/// If `base` is not the entry in this version, it is read from the history of the entry.
+/// `mine` and the merged entry differ by the same number of versions
+/// from the base and the current version.
+pub fn remerge(
+    api: &impl OfdbApi,
+    mine: &Entry,
+    base: Option<&Entry>,
+    base_version: u64,
+) -> Result<Remerge> {
+    let uuid: Uuid = mine.id.parse()?;
+    let theirs = api
+        .read_entries(vec![uuid])?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("The entry {} does not exist anymore", mine.id))?;
+    let base = match base.filter(|base| base.version == base_version) {
+        Some(base) => base.clone(),
+        None => {
+            let history = api.place_history(uuid).map_err(|err| {
+                anyhow!("Unable to read version {base_version} of the entry: {err}")
+            })?;
+            let (revision, _) = history
+                .revisions
+                .iter()
+                .find(|(revision, _)| revision.revision == base_version)
+                .ok_or_else(|| anyhow!("The history has no version {base_version}"))?;
+            entry_at_revision(&theirs, revision)
+        }
+    };
+    Ok(match three_way_merge(&base, &theirs, mine)? {
+        Ok(mut entry) => {
+            entry.version = theirs.version + (mine.version - base_version);
+            Remerge::Merged { entry, theirs }
+        }
+        Err(conflicts) => Remerge::Conflicts {
+            base,
+            theirs,
+            conflicts,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ofdb_boundary::{Activity, Contact, Links, Location};
+
+    fn entry() -> Entry {
+        let mut entry = crate::fixtures::generate(1, 5).entries.remove(0);
+        entry.version = 2;
+        entry.tags = vec!["bio".to_string(), "regional".to_string()];
+        entry
+    }
+
+    #[test]
+    fn apply_the_changes_to_the_current_version() {
+        let base = entry();
+        let mut theirs = base.clone();
+        theirs.version = 3;
+        theirs.description = "Changed by someone else".to_string();
+        theirs.tags.push("repair".to_string());
+        let mut mine = base.clone();
+        mine.homepage = Some("https://example.org".to_string());
+        mine.tags.retain(|t| t != "regional");
+        mine.tags.push("cafe".to_string());
+
+        let merged = three_way_merge(&base, &theirs, &mine).unwrap().unwrap();
+        assert_eq!(merged.version, 3);
+        assert_eq!(merged.description, "Changed by someone else");
+        assert_eq!(merged.homepage.as_deref(), Some("https://example.org"));
+        assert_eq!(merged.tags, ["bio", "repair", "cafe"]);
+    }
+
+    #[test]
+    fn detect_conflicting_changes() {
+        let base = entry();
+        let mut theirs = base.clone();
+        theirs.title = "Their title".to_string();
+        theirs.city = Some("Essen".to_string());
+        let mut mine = base.clone();
+        mine.title = "My title".to_string();
+        mine.city = Some("Essen".to_string());
+
+        let conflicts = three_way_merge(&base, &theirs, &mine).unwrap().unwrap_err();
+        assert_eq!(
+            conflicts,
+            [FieldConflict {
+                field: "title".to_string(),
+                base: Value::String(base.title.clone()),
+                theirs: Value::String("Their title".to_string()),
+                mine: Value::String("My title".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn entry_of_a_revision() {
+        let current = entry();
+        let revision = PlaceRevision {
+            revision: 1,
+            created: Activity { at: 0, by: None },
+            title: "Old title".to_string(),
+            description: current.description.clone(),
+            location: Location {
+                lat: current.lat,
+                lng: current.lng,
+                address: None,
+            },
+            contact: Contact {
+                name: None,
+                email: None,
+                phone: None,
+            },
+            opening_hours: None,
+            founded_on: None,
+            links: Links {
+                homepage: None,
+                image: None,
+                image_href: None,
+                custom: vec![],
+            },
+            tags: vec!["bio".to_string()],
+        };
+        let base = entry_at_revision(&current, &revision);
+        assert_eq!(base.version, 1);
+        assert_eq!(base.title, "Old title");
+        assert_eq!(base.city, None);
+        assert_eq!(base.id, current.id);
+        assert_eq!(base.categories, current.categories);
+    }
+}
@@ -0,0 +1,291 @@
+//! Record the API interactions of a run and replay them without a network,
+//! e.g. to turn a failing run of a user into a deterministic regression test
+//! or to try an import in a sandbox.
+//!
+//! The URLs are stored relative to the API URL, so a recording can be replayed
+//! with any `--api-url`. Request and response bodies are redacted like in the
+//! [trace](crate::trace), but the response bodies are not shortened.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use reqwest::{
+    blocking::{Request, Response},
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::trace::{rebuild_response, redact_body, redact_json, redact_url};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub method: String,
+    /// URL relative to the API URL.
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<String>,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    pub response_body: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Fixtures {
+    interactions: Vec<Interaction>,
+}
+
+/// Method, relative URL and redacted body of a request.
+pub(crate) struct RecordedRequest {
+    method: String,
+    url: String,
+    body: Option<String>,
+}
+
+impl RecordedRequest {
+    pub(crate) fn new(api: &str, request: &Request) -> Self {
+        Self {
+            method: request.method().to_string(),
+            url: relative_url(api, redact_url(request.url()).as_str()),
+            body: request.body().and_then(|b| b.as_bytes()).map(redact_body),
+        }
+    }
+}
+
+impl fmt::Display for RecordedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.url)
+    }
+}
+
+fn relative_url(api: &str, url: &str) -> String {
+    url.strip_prefix(api.trim_end_matches('/'))
+        .unwrap_or(url)
+        .trim_start_matches('/')
+        .to_string()
+}
+
+/// The interactions of the current run,
+/// they are written to the file by [Recording::save].
+#[derive(Debug)]
+pub struct Recording {
+    path: PathBuf,
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+impl Recording {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            interactions: Mutex::default(),
+        }
+    }
+
+    /// Record the response (it is read completely).
+    pub(crate) fn record(
+        &self,
+        request: RecordedRequest,
+        response: Response,
+    ) -> reqwest::Result<Response> {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes()?.to_vec();
+        self.interactions.lock().unwrap().push(Interaction {
+            method: request.method,
+            url: request.url,
+            request_body: request.body,
+            status: status.as_u16(),
+            content_type: content_type(&headers),
+            response_body: redact_json(&body),
+        });
+        Ok(rebuild_response(status, headers, body))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let interactions = self.interactions.lock().unwrap().clone();
+        log::info!(
+            "Write {} recorded API interactions to {}",
+            interactions.len(),
+            self.path.display()
+        );
+        let json = serde_json::to_string_pretty(&Fixtures { interactions })?;
+        fs::write(&self.path, json)?;
+        Ok(())
+    }
+}
+
+/// Saves a recording when it is dropped,
+/// so that the interactions are kept if a command fails early.
+#[derive(Debug)]
+pub struct SaveRecording(Option<Arc<Recording>>);
+
+impl SaveRecording {
+    pub fn new(recording: Option<Arc<Recording>>) -> Self {
+        Self(recording)
+    }
+
+    /// Save the recording now to report errors.
+    pub fn save(mut self) -> Result<()> {
+        match self.0.take() {
+            Some(recording) => recording.save(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SaveRecording {
+    fn drop(&mut self) {
+        if let Some(recording) = self.0.take() {
+            if let Err(err) = recording.save() {
+                log::error!("Could not save the recording: {err}");
+            }
+        }
+    }
+}
+
+/// Responses of a previous recording.
+///
+/// Each request gets the first unused response with the same method and URL.
+#[derive(Debug)]
+pub struct Replay {
+    interactions: Mutex<Vec<(Interaction, bool)>>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self> {
+        let fixtures: Fixtures = serde_json::from_slice(&fs::read(path)?)?;
+        log::info!(
+            "Replay {} API interactions from {}",
+            fixtures.interactions.len(),
+            path.display()
+        );
+        Ok(Self::new(fixtures.interactions))
+    }
+
+    pub fn new(interactions: Vec<Interaction>) -> Self {
+        Self {
+            interactions: Mutex::new(interactions.into_iter().map(|i| (i, false)).collect()),
+        }
+    }
+
+    pub(crate) fn respond(&self, request: RecordedRequest) -> Option<Response> {
+        let mut interactions = self.interactions.lock().unwrap();
+        let (interaction, used) = interactions
+            .iter_mut()
+            .find(|(i, used)| !used && i.method == request.method && i.url == request.url)?;
+        *used = true;
+        let mut headers = HeaderMap::new();
+        if let Some(value) = interaction
+            .content_type
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(CONTENT_TYPE, value);
+        }
+        let status = StatusCode::from_u16(interaction.status).unwrap_or(StatusCode::OK);
+        Some(rebuild_response(
+            status,
+            headers,
+            interaction.response_body.clone().into_bytes(),
+        ))
+    }
+}
+
+fn content_type(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(ToString::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const API: &str = "http://localhost:6767/v0";
+
+    fn request(method: &str, url: &str) -> RecordedRequest {
+        let client = reqwest::blocking::Client::new();
+        let request = client
+            .request(method.parse().unwrap(), format!("{API}/{url}"))
+            .build()
+            .unwrap();
+        RecordedRequest::new(API, &request)
+    }
+
+    fn interaction(url: &str, response_body: &str) -> Interaction {
+        Interaction {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            request_body: None,
+            status: 200,
+            content_type: Some("application/json".to_string()),
+            response_body: response_body.to_string(),
+        }
+    }
+
+    #[test]
+    fn replay_responses_in_order() {
+        let replay = Replay::new(vec![
+            interaction("count/entries", "1"),
+            interaction("count/tags", "7"),
+            interaction("count/entries", "2"),
+        ]);
+        let text = |response: Option<Response>| response.unwrap().text().unwrap();
+        assert_eq!(text(replay.respond(request("GET", "count/entries"))), "1");
+        assert_eq!(text(replay.respond(request("GET", "count/entries"))), "2");
+        assert!(replay.respond(request("GET", "count/entries")).is_none());
+        assert!(replay.respond(request("POST", "count/tags")).is_none());
+        let response = replay.respond(request("GET", "count/tags")).unwrap();
+        assert_eq!(
+            content_type(response.headers()).as_deref(),
+            Some("application/json")
+        );
+    }
+
+    #[test]
+    fn save_redacted_interactions_on_drop() {
+        let path =
+            std::env::temp_dir().join(format!("ofdb-recording-test-{}.json", std::process::id()));
+        let recording = Arc::new(Recording::new(path.clone()));
+        let body = r#"{"email":"me@example.org","title":"Repair Café"}"#;
+        let response = rebuild_response(StatusCode::OK, HeaderMap::new(), body.into());
+        recording
+            .record(
+                request("GET", "users/current?email=me@example.org&limit=1"),
+                response,
+            )
+            .unwrap();
+        drop(SaveRecording::new(Some(recording)));
+
+        let fixtures: Fixtures = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        let url = &fixtures.interactions[0].url;
+        assert!(url.starts_with("users/current?"), "{url}");
+        assert!(
+            !url.contains("me@example.org") && !url.contains("me%40example.org"),
+            "{url}"
+        );
+        assert!(url.contains("limit=1"), "{url}");
+        let body = &fixtures.interactions[0].response_body;
+        assert!(!body.contains("me@example.org"), "{body}");
+        assert!(body.contains("Repair Café"), "{body}");
+    }
+
+    #[test]
+    fn relative_urls() {
+        assert_eq!(
+            relative_url(
+                "https://dev.ofdb.io/v0/",
+                "https://dev.ofdb.io/v0/entries/1,2"
+            ),
+            "entries/1,2"
+        );
+        assert_eq!(relative_url(API, "http://other/x"), "http://other/x");
+    }
+}
@@ -0,0 +1,1220 @@
+//! Output formats of import and update reports.
+//!
+//! Besides the JSON report there is a single-file HTML report
+//! for coordinators that are not used to reading JSON
+//! and a CSV report with one row per record for spreadsheets.
+//!
+//! If several files are processed in one run,
+//! a single report with one section per file is written.
+//!
+//! The JSON reports of previous runs can be read again
+//! to summarize or merge them or to extract the failed records.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt,
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Result};
+use ofdb_boundary::{Entry, NewPlace, NewPlaceRating};
+use schemars::{schema::RootSchema, schema_for, JsonSchema};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    headers::struct_fields,
+    i18n::{lang, Lang},
+    import::{FailureKind, Report, SuccessReport, REPORT_SCHEMA_VERSION},
+};
+
+/// URL of an entry on the map.
+pub const ENTRY_URL: &str = "https://kartevonmorgen.org/m/main?entry=";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Html,
+    Csv,
+}
+
+impl FromStr for ReportFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "json" => Self::Json,
+            "html" => Self::Html,
+            "csv" => Self::Csv,
+            _ => bail!("Unknown report format '{s}' (expected 'json', 'html' or 'csv')"),
+        })
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Json => "json",
+            Self::Html => "html",
+            Self::Csv => "csv",
+        })
+    }
+}
+
+/// A record of a report.
+pub trait ReportRecord {
+    fn title(&self) -> &str;
+    /// ID of the corresponding entry if it already exists.
+    fn entry_id(&self) -> Option<&str>;
+}
+
+impl ReportRecord for NewPlace {
+    fn title(&self) -> &str {
+        &self.title
+    }
+    fn entry_id(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl ReportRecord for Entry {
+    fn title(&self) -> &str {
+        &self.title
+    }
+    fn entry_id(&self) -> Option<&str> {
+        Some(&self.id)
+    }
+}
+
+impl ReportRecord for NewPlaceRating {
+    fn title(&self) -> &str {
+        &self.title
+    }
+    fn entry_id(&self) -> Option<&str> {
+        Some(&self.entry)
+    }
+}
+
+/// A record of a report that was read from a JSON file.
+impl ReportRecord for Value {
+    fn title(&self) -> &str {
+        self["title"].as_str().unwrap_or_default()
+    }
+    fn entry_id(&self) -> Option<&str> {
+        self.get("id")
+            .or_else(|| self.get("entry"))
+            .and_then(Value::as_str)
+    }
+}
+
+/// The title of a validated record.
+impl ReportRecord for String {
+    fn title(&self) -> &str {
+        self
+    }
+    fn entry_id(&self) -> Option<&str> {
+        None
+    }
+}
+
+pub fn entry_url(id: &str) -> String {
+    format!("{ENTRY_URL}{id}")
+}
+
+/// The report of one of several input files.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[schemars(bound = "", rename = "FileReport")]
+pub struct FileReport<T> {
+    pub file: String,
+    #[serde(flatten)]
+    pub report: Report<T, SuccessReport<T>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+#[schemars(bound = "", rename = "FileReports")]
+struct FileReports<'a, T> {
+    files: &'a [FileReport<T>],
+}
+
+/// JSON schema of the JSON reports of imports, updates and rating imports,
+/// either of a single input file or of several files.
+pub fn report_schema(several_files: bool) -> RootSchema {
+    if several_files {
+        schema_for!(FileReports<'static, NewPlace>)
+    } else {
+        schema_for!(Report<NewPlace, SuccessReport<NewPlace>>)
+    }
+}
+
+pub fn write_report<W, T>(
+    w: W,
+    report: &Report<T, SuccessReport<T>>,
+    format: ReportFormat,
+) -> Result<()>
+where
+    W: Write,
+    T: ReportRecord + Serialize,
+{
+    match format {
+        ReportFormat::Json => serde_json::to_writer_pretty(w, report)?,
+        ReportFormat::Html => write_html(w, report, lang())?,
+        ReportFormat::Csv => write_csv(w, report, lang())?,
+    }
+    Ok(())
+}
+
+/// Write the reports of several input files into a single report.
+pub fn write_file_reports<W, T>(w: W, reports: &[FileReport<T>], format: ReportFormat) -> Result<()>
+where
+    W: Write,
+    T: ReportRecord + Serialize,
+{
+    match format {
+        ReportFormat::Json => serde_json::to_writer_pretty(w, &FileReports { files: reports })?,
+        ReportFormat::Html => write_html_files(w, reports, lang())?,
+        ReportFormat::Csv => write_csv_files(w, reports, lang())?,
+    }
+    Ok(())
+}
+
+fn write_html<W, T>(mut w: W, report: &Report<T, SuccessReport<T>>, lang: Lang) -> Result<()>
+where
+    W: Write,
+    T: ReportRecord,
+{
+    let mut html = Html::new(lang);
+    html.begin();
+    html.summary(counts(report));
+    html.report(report, 2);
+    html.end();
+    w.write_all(html.0.as_bytes())?;
+    Ok(())
+}
+
+fn write_html_files<W, T>(mut w: W, reports: &[FileReport<T>], lang: Lang) -> Result<()>
+where
+    W: Write,
+    T: ReportRecord,
+{
+    let mut html = Html::new(lang);
+    html.begin();
+    let total = reports
+        .iter()
+        .map(|r| counts(&r.report))
+        .reduce(|mut total, counts| {
+            for (sum, (_, count)) in total.iter_mut().zip(counts) {
+                sum.1 += count;
+            }
+            total
+        })
+        .unwrap_or_default();
+    html.summary(total);
+    for r in reports {
+        html.push(&format!("<h2>{}</h2>\n", Html::text(&r.file)));
+        html.summary(counts(&r.report));
+        html.report(&r.report, 3);
+    }
+    html.end();
+    w.write_all(html.0.as_bytes())?;
+    Ok(())
+}
+
+/// A report that was read from a JSON file,
+/// the records are kept in the format of the API.
+pub type JsonReport = Report<Value, SuccessReport<Value>>;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonReportFile {
+    Files { files: Vec<FileReport<Value>> },
+    Single(Box<JsonReport>),
+}
+
+/// Read a JSON report of a single or of several input files.
+///
+/// A report of a single input file is returned as one section named `name`.
+pub fn read_json_report<R: Read>(r: R, name: &str) -> Result<Vec<FileReport<Value>>> {
+    let reports = match serde_json::from_reader(r)? {
+        JsonReportFile::Files { files } => files,
+        JsonReportFile::Single(report) => vec![FileReport {
+            file: name.to_string(),
+            report: *report,
+        }],
+    };
+    for r in &reports {
+        if r.report.schema_version > REPORT_SCHEMA_VERSION {
+            bail!(
+                "The report of '{}' has the schema version {} (supported: {REPORT_SCHEMA_VERSION}), please update ofdb",
+                r.file,
+                r.report.schema_version
+            );
+        }
+    }
+    Ok(reports)
+}
+
+/// Combine the reports of several runs into a single report.
+pub fn merge_reports(reports: Vec<JsonReport>) -> JsonReport {
+    let mut merged = Report::default();
+    for report in reports {
+        merged.append(report);
+    }
+    merged.schema_version = REPORT_SCHEMA_VERSION;
+    // The timing of the single runs can't be combined
+    merged.metrics = None;
+    merged
+}
+
+/// Totals of one or more reports.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub reports: usize,
+    pub successes: usize,
+    pub duplicates: usize,
+    pub failures: usize,
+    /// Part of the failures.
+    pub permission_denied: usize,
+    /// Part of the failures.
+    pub conflicts: usize,
+    pub invalid_records: usize,
+    /// Part of the invalid records.
+    pub deferred: usize,
+    pub warnings: usize,
+    pub unknown_ids: usize,
+}
+
+impl Summary {
+    pub fn add<T>(&mut self, report: &Report<T, SuccessReport<T>>) {
+        self.reports += 1;
+        self.successes += report.successes.len();
+        self.duplicates += report.duplicates.len();
+        self.failures += report.failures.len();
+        self.permission_denied += report
+            .failures
+            .iter()
+            .filter(|f| f.kind == FailureKind::PermissionDenied)
+            .count();
+        self.conflicts += report.conflicts.len();
+        self.invalid_records += report.csv_import_failures.len();
+        self.deferred += report
+            .csv_import_failures
+            .iter()
+            .filter(|f| f.deferred)
+            .count();
+        self.warnings += report.csv_import_warnings.len();
+        self.unknown_ids += report.unknown_ids.len();
+    }
+
+    /// The totals as one line of JSON for scripts,
+    /// e.g. `{"successes":120,"duplicates":7,"failures":3,"invalid_records":0,"report":"import-report.json"}`.
+    pub fn json_line(&self, report: Option<&str>) -> Result<String> {
+        #[derive(Serialize)]
+        struct SummaryLine<'a> {
+            successes: usize,
+            duplicates: usize,
+            failures: usize,
+            invalid_records: usize,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            report: Option<&'a str>,
+        }
+        Ok(serde_json::to_string(&SummaryLine {
+            successes: self.successes,
+            duplicates: self.duplicates,
+            failures: self.failures,
+            invalid_records: self.invalid_records,
+            report,
+        })?)
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Reports:           {}", self.reports)?;
+        writeln!(f, "Successes:         {}", self.successes)?;
+        writeln!(f, "Duplicates:        {}", self.duplicates)?;
+        writeln!(f, "Failures:          {}", self.failures)?;
+        writeln!(f, "Permission denied: {}", self.permission_denied)?;
+        writeln!(f, "Conflicts:         {}", self.conflicts)?;
+        writeln!(f, "Invalid records:   {}", self.invalid_records)?;
+        writeln!(f, "Deferred:          {}", self.deferred)?;
+        writeln!(f, "Warnings:          {}", self.warnings)?;
+        write!(f, "Unknown IDs:       {}", self.unknown_ids)
+    }
+}
+
+/// The errors of the failed (or invalid) records by record number.
+///
+/// The CLI uses the record number as import ID.
+pub fn failed_records<T>(
+    report: &Report<T, SuccessReport<T>>,
+    with_duplicates: bool,
+) -> BTreeMap<usize, Vec<&str>> {
+    let mut errors: BTreeMap<usize, Vec<&str>> = BTreeMap::new();
+    let record_nr = |import_id: &Option<String>| import_id.as_deref()?.parse::<usize>().ok();
+    for f in &report.failures {
+        if let Some(nr) = record_nr(&f.import_id) {
+            errors.entry(nr).or_default().push(&f.error);
+        }
+    }
+    for f in &report.csv_import_failures {
+        errors.entry(f.record_nr).or_default().push(&f.error);
+    }
+    if with_duplicates {
+        for d in &report.duplicates {
+            if let Some(nr) = record_nr(&d.import_id) {
+                errors
+                    .entry(nr)
+                    .or_default()
+                    .push("Found possible duplicates");
+            }
+        }
+    }
+    errors
+}
+
+/// The records that were imported or found as possible duplicates.
+///
+/// All other records of the source file failed, are invalid or were never sent
+/// (e.g. because invalid records stopped the import).
+pub fn imported_records<T>(report: &Report<T, SuccessReport<T>>) -> BTreeSet<usize> {
+    let record_nr = |import_id: &Option<String>| import_id.as_deref()?.parse::<usize>().ok();
+    report
+        .successes
+        .iter()
+        .map(|s| &s.import_id)
+        .chain(report.duplicates.iter().map(|d| &d.import_id))
+        .filter_map(record_nr)
+        .collect()
+}
+
+/// Name of the column with the error in the CSV of failed records.
+pub const ERROR_COLUMN: &str = "import_error";
+
+/// Copy the records of the source file that failed (or are invalid)
+/// and add a column with the error, so that they can be fixed and imported again.
+///
+/// Returns the number of copied records.
+pub fn write_failed_records<R, W, T>(
+    report: &Report<T, SuccessReport<T>>,
+    source: R,
+    w: W,
+    with_duplicates: bool,
+) -> Result<usize>
+where
+    R: Read,
+    W: Write,
+{
+    let errors = failed_records(report, with_duplicates);
+    let mut rdr = csv::ReaderBuilder::new().flexible(true).from_reader(source);
+    let mut wtr = csv::WriterBuilder::new().flexible(true).from_writer(w);
+    let headers = rdr.headers()?.clone();
+    // The source might be a file of failed records of a previous run
+    let error_column = headers.iter().position(|h| h == ERROR_COLUMN);
+    let with_error = |record: &csv::StringRecord, error: &str| -> csv::StringRecord {
+        match error_column {
+            Some(i) => record
+                .iter()
+                .enumerate()
+                .map(|(j, field)| if i == j { error } else { field })
+                .collect(),
+            None => record.iter().chain([error]).collect(),
+        }
+    };
+    wtr.write_record(&with_error(&headers, ERROR_COLUMN))?;
+    let mut copied = HashSet::new();
+    for (nr, record) in rdr.records().enumerate() {
+        let Some(errors) = errors.get(&nr) else {
+            continue;
+        };
+        wtr.write_record(&with_error(&record?, &errors.join("; ")))?;
+        copied.insert(nr);
+    }
+    wtr.flush()?;
+    if let Some(missing) = errors.keys().find(|nr| !copied.contains(nr)) {
+        bail!("The source file has no record {missing}, is it the file of the report?");
+    }
+    Ok(copied.len())
+}
+
+/// The new places of a JSON import report that failed
+/// (or have possible duplicates) with their errors,
+/// e.g. to write them with [crate::csv::write_failed_new_places]
+/// if the source file is not available.
+///
+/// Records that could not be read from the source file are not part of the report,
+/// their number is returned as well.
+pub fn failed_new_places(
+    report: &JsonReport,
+    with_duplicates: bool,
+) -> Result<(Vec<(NewPlace, String)>, usize)> {
+    let mut places = vec![];
+    for f in &report.failures {
+        if f.place.entry_id().is_some() {
+            bail!("The report contains updated entries instead of new places");
+        }
+        let place = serde_json::from_value(f.place.clone())
+            .map_err(|err| anyhow!("Invalid place '{}': {err}", f.place.title()))?;
+        places.push((place, f.error.clone()));
+    }
+    if with_duplicates {
+        places.extend(
+            report
+                .duplicates
+                .iter()
+                .map(|d| (d.new_place.clone(), "Found possible duplicates".to_string())),
+        );
+    }
+    Ok((places, report.csv_import_failures.len()))
+}
+
+fn counts<T>(report: &Report<T, SuccessReport<T>>) -> [(&'static str, usize); 7] {
+    [
+        ("Successes", report.successes.len()),
+        ("Duplicates", report.duplicates.len()),
+        ("Failures", report.failures.len()),
+        ("Conflicts", report.conflicts.len()),
+        ("Invalid records", report.csv_import_failures.len()),
+        ("Warnings", report.csv_import_warnings.len()),
+        ("Unknown IDs", report.unknown_ids.len()),
+    ]
+}
+
+/// A row of the CSV report.
+///
+/// It is only deserialized to get the names of the columns.
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRecord<'a> {
+    #[serde(borrow, skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+    record_nr: Option<usize>,
+    #[serde(borrow)]
+    import_id: Option<&'a str>,
+    title: &'a str,
+    status: &'a str,
+    #[serde(borrow)]
+    uuid: Option<&'a str>,
+    #[serde(borrow)]
+    error: Option<&'a str>,
+    duplicate_ids: String,
+    warnings: String,
+}
+
+impl<'a> CsvRecord<'a> {
+    fn new(import_id: Option<&'a str>, title: &'a str, status: &'a str) -> Self {
+        Self {
+            file: None,
+            // The CLI uses the record number as import ID
+            record_nr: import_id.and_then(|id| id.parse().ok()),
+            import_id,
+            title,
+            status,
+            uuid: None,
+            error: None,
+            duplicate_ids: String::new(),
+            warnings: String::new(),
+        }
+    }
+}
+
+/// The columns of the CSV report (see [CsvRecord]).
+fn csv_columns() -> &'static [&'static str] {
+    struct_fields::<CsvRecord<'static>>()
+}
+
+/// Write one row per record, sorted by the record number.
+fn write_csv<W, T>(w: W, report: &Report<T, SuccessReport<T>>, lang: Lang) -> Result<()>
+where
+    W: Write,
+    T: ReportRecord,
+{
+    write_csv_records(w, csv_records(report), lang, &csv_columns()[1..])
+}
+
+/// Write one row per record with the file as first column.
+fn write_csv_files<W, T>(w: W, reports: &[FileReport<T>], lang: Lang) -> Result<()>
+where
+    W: Write,
+    T: ReportRecord,
+{
+    let mut records = vec![];
+    for r in reports {
+        records.extend(csv_records(&r.report).into_iter().map(|mut record| {
+            record.file = Some(&r.file);
+            record
+        }));
+    }
+    write_csv_records(w, records, lang, csv_columns())
+}
+
+fn write_csv_records<W: Write>(
+    w: W,
+    records: Vec<CsvRecord>,
+    lang: Lang,
+    columns: &[&'static str],
+) -> Result<()> {
+    let mut wtr = csv::WriterBuilder::new().has_headers(false).from_writer(w);
+    wtr.write_record(columns.iter().map(|c| lang.tr(c)))?;
+    for r in records {
+        wtr.serialize(r)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn csv_records<T: ReportRecord>(report: &Report<T, SuccessReport<T>>) -> Vec<CsvRecord<'_>> {
+    let mut records = vec![];
+    for s in &report.successes {
+        let mut r = CsvRecord::new(s.import_id.as_deref(), s.place.title(), "success");
+        r.uuid = Some(&s.uuid);
+        records.push(r);
+    }
+    for d in &report.duplicates {
+        let mut r = CsvRecord::new(d.import_id.as_deref(), &d.new_place.title, "duplicate");
+        r.duplicate_ids = d
+            .duplicates
+            .iter()
+            .map(|c| c.id.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        records.push(r);
+    }
+    for f in &report.failures {
+        let status = match f.kind {
+            FailureKind::Other => "failure",
+            FailureKind::PermissionDenied => "permission_denied",
+        };
+        let mut r = CsvRecord::new(f.import_id.as_deref(), f.place.title(), status);
+        r.uuid = f.place.entry_id();
+        r.error = Some(&f.error);
+        records.push(r);
+    }
+    for f in &report.csv_import_failures {
+        let status = if f.deferred { "deferred" } else { "invalid" };
+        let mut r = CsvRecord::new(None, "", status);
+        r.record_nr = Some(f.record_nr);
+        r.error = Some(&f.error);
+        records.push(r);
+    }
+    for id in &report.unknown_ids {
+        let mut r = CsvRecord::new(None, "", "unknown_id");
+        r.uuid = Some(id);
+        records.push(r);
+    }
+    for warning in &report.csv_import_warnings {
+        let record = records
+            .iter_mut()
+            .find(|r| r.record_nr == Some(warning.record_nr));
+        match record {
+            Some(r) if !r.warnings.is_empty() => {
+                r.warnings.push_str("; ");
+                r.warnings.push_str(&warning.warning);
+            }
+            Some(r) => r.warnings.push_str(&warning.warning),
+            None => {
+                let mut r = CsvRecord::new(None, "", "warning");
+                r.record_nr = Some(warning.record_nr);
+                r.warnings = warning.warning.clone();
+                records.push(r);
+            }
+        }
+    }
+    // Records without number last
+    records.sort_by_key(|r| (r.record_nr.is_none(), r.record_nr));
+    records
+}
+
+/// A value of a conflicting field as text (strings without quotes, lists separated by commas).
+fn conflict_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(conflict_value)
+            .collect::<Vec<_>>()
+            .join(", "),
+        value => value.to_string(),
+    }
+}
+
+struct Html(String, Lang);
+
+impl Html {
+    fn new(lang: Lang) -> Self {
+        Self(String::new(), lang)
+    }
+
+    fn push(&mut self, s: &str) {
+        self.0.push_str(s);
+    }
+
+    fn begin(&mut self) {
+        let lang = self.1;
+        let title = lang.tr("Report");
+        self.push(&format!(
+            "<!DOCTYPE html>\n<html lang=\"{lang}\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+             <style>\
+             body{{font-family:sans-serif;margin:2em}}\
+             table{{border-collapse:collapse;margin-bottom:2em}}\
+             th,td{{border:1px solid #ccc;padding:.3em .6em;text-align:left;vertical-align:top}}\
+             th{{background:#eee}}\
+             </style>\n</head>\n<body>\n<h1>{title}</h1>\n",
+        ));
+    }
+
+    fn end(&mut self) {
+        self.push("</body>\n</html>\n");
+    }
+
+    fn summary(&mut self, counts: [(&'static str, usize); 7]) {
+        self.push("<ul>\n");
+        for (label, count) in counts {
+            let label = self.1.tr(label);
+            self.push(&format!("<li>{label}: {count}</li>\n"));
+        }
+        self.push("</ul>\n");
+    }
+
+    /// Add the tables of a report with headings of the given level.
+    fn report<T: ReportRecord>(&mut self, report: &Report<T, SuccessReport<T>>, level: u8) {
+        let lang = self.1;
+        self.table(
+            level,
+            "Successes",
+            &["Title", "Import ID", "Entry"],
+            report.successes.iter().map(|s| {
+                vec![
+                    Html::text(s.place.title()),
+                    Html::text(s.import_id.as_deref().unwrap_or_default()),
+                    Html::entry_link(&s.uuid, &s.uuid),
+                ]
+            }),
+        );
+        self.table(
+            level,
+            "Duplicates",
+            &["Title", "Import ID", "Possible duplicates"],
+            report.duplicates.iter().map(|d| {
+                let candidates = d
+                    .duplicates
+                    .iter()
+                    .map(|c| Html::entry_link(&c.id, &c.title))
+                    .collect::<Vec<_>>()
+                    .join("<br>");
+                vec![
+                    Html::text(&d.new_place.title),
+                    Html::text(d.import_id.as_deref().unwrap_or_default()),
+                    candidates,
+                ]
+            }),
+        );
+        self.table(
+            level,
+            "Failures",
+            &["Title", "Import ID", "Kind", "Error"],
+            report.failures.iter().map(|f| {
+                let title = match f.place.entry_id() {
+                    Some(id) => Html::entry_link(id, f.place.title()),
+                    None => Html::text(f.place.title()),
+                };
+                let kind = lang.tr(match f.kind {
+                    FailureKind::Other => "other",
+                    FailureKind::PermissionDenied => "permission denied",
+                });
+                vec![
+                    title,
+                    Html::text(f.import_id.as_deref().unwrap_or_default()),
+                    Html::text(kind),
+                    Html::text(&f.error),
+                ]
+            }),
+        );
+        self.table(
+            level,
+            "Conflicts",
+            &["Title", "Import ID", "Field", "Exported", "Current", "File"],
+            report.conflicts.iter().flat_map(|c| {
+                let title = Html::entry_link(&c.entry_id, &c.title);
+                let import_id = Html::text(c.import_id.as_deref().unwrap_or_default());
+                c.fields.iter().map(move |f| {
+                    vec![
+                        title.clone(),
+                        import_id.clone(),
+                        Html::text(&f.field),
+                        Html::text(&conflict_value(&f.base)),
+                        Html::text(&conflict_value(&f.theirs)),
+                        Html::text(&conflict_value(&f.mine)),
+                    ]
+                })
+            }),
+        );
+        self.table(
+            level,
+            "Invalid records",
+            &["Record", "Error"],
+            report
+                .csv_import_failures
+                .iter()
+                .map(|f| vec![f.record_nr.to_string(), Html::text(&f.error)]),
+        );
+        self.table(
+            level,
+            "Warnings",
+            &["Record", "Warning"],
+            report
+                .csv_import_warnings
+                .iter()
+                .map(|w| vec![w.record_nr.to_string(), Html::text(&w.warning)]),
+        );
+        self.table(
+            level,
+            "Unknown IDs",
+            &["ID"],
+            report.unknown_ids.iter().map(|id| vec![Html::text(id)]),
+        );
+    }
+
+    /// Add a table unless there are no rows.
+    fn table(
+        &mut self,
+        level: u8,
+        title: &'static str,
+        columns: &[&'static str],
+        rows: impl Iterator<Item = Vec<String>>,
+    ) {
+        let rows: Vec<_> = rows.collect();
+        if rows.is_empty() {
+            return;
+        }
+        let title = self.1.tr(title);
+        self.push(&format!("<h{level}>{title}</h{level}>\n<table>\n<tr>"));
+        for c in columns {
+            let c = self.1.tr(c);
+            self.push(&format!("<th>{c}</th>"));
+        }
+        self.push("</tr>\n");
+        for row in rows {
+            self.push("<tr>");
+            for cell in row {
+                self.push(&format!("<td>{cell}</td>"));
+            }
+            self.push("</tr>\n");
+        }
+        self.push("</table>\n");
+    }
+
+    fn text(s: &str) -> String {
+        let mut escaped = String::with_capacity(s.len());
+        for c in s.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
+
+    fn entry_link(id: &str, label: &str) -> String {
+        format!(
+            "<a href=\"{}\">{}</a>",
+            Html::text(&entry_url(id)),
+            Html::text(label)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        import::{
+            ConflictReport, CsvImportFailureReport, CsvImportWarningReport, DuplicateReport,
+            FailureReport, REPORT_SCHEMA_VERSION,
+        },
+        remerge::FieldConflict,
+    };
+
+    #[test]
+    fn parse_report_format() {
+        assert_eq!("json".parse::<ReportFormat>().unwrap(), ReportFormat::Json);
+        assert_eq!("HTML".parse::<ReportFormat>().unwrap(), ReportFormat::Html);
+        assert_eq!("csv".parse::<ReportFormat>().unwrap(), ReportFormat::Csv);
+        assert!("xml".parse::<ReportFormat>().is_err());
+    }
+
+    #[test]
+    fn write_csv_report() {
+        let entries = crate::fixtures::generate(2, 0).entries;
+        let report = Report {
+            successes: vec![SuccessReport {
+                place: entries[0].clone(),
+                import_id: Some("2".to_string()),
+                uuid: entries[0].id.clone(),
+            }],
+            failures: vec![FailureReport {
+                place: entries[1].clone(),
+                import_id: Some("0".to_string()),
+                error: "Forbidden".to_string(),
+                kind: FailureKind::PermissionDenied,
+            }],
+            duplicates: vec![],
+            csv_import_successes: vec![],
+            csv_import_failures: vec![CsvImportFailureReport {
+                record_nr: 1,
+                error: "Invalid lat".to_string(),
+                deferred: false,
+            }],
+            csv_import_warnings: vec![CsvImportWarningReport {
+                record_nr: 2,
+                warning: "No homepage".to_string(),
+            }],
+            unknown_ids: vec![],
+            ..Default::default()
+        };
+        let mut csv = vec![];
+        write_report(&mut csv, &report, ReportFormat::Csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "record_nr,import_id,title,status,uuid,error,duplicate_ids,warnings"
+        );
+        assert!(lines[1].starts_with("0,0,"));
+        assert!(lines[1].contains(",permission_denied,"));
+        assert_eq!(lines[2], "1,,,invalid,,Invalid lat,,");
+        assert!(lines[3].starts_with("2,2,"));
+        assert!(lines[3].ends_with(&format!(",success,{},,,No homepage", entries[0].id)));
+    }
+
+    #[test]
+    fn write_a_report_of_several_files() {
+        let entries = crate::fixtures::generate(2, 0).entries;
+        let report = |i: usize| Report {
+            successes: vec![SuccessReport {
+                place: entries[i].clone(),
+                import_id: Some("0".to_string()),
+                uuid: entries[i].id.clone(),
+            }],
+            failures: vec![],
+            duplicates: vec![],
+            csv_import_successes: vec![],
+            csv_import_failures: vec![],
+            csv_import_warnings: vec![],
+            unknown_ids: vec![],
+            ..Default::default()
+        };
+        let reports = vec![
+            FileReport {
+                file: "bochum.csv".to_string(),
+                report: report(0),
+            },
+            FileReport {
+                file: "hamburg.csv".to_string(),
+                report: report(1),
+            },
+        ];
+        let write = |format| {
+            let mut out = vec![];
+            write_file_reports(&mut out, &reports, format).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&write(ReportFormat::Json)).unwrap();
+        assert_eq!(json["files"][1]["file"], "hamburg.csv");
+        assert_eq!(
+            json["files"][1]["successes"][0]["uuid"],
+            entries[1].id.as_str()
+        );
+
+        let csv = write(ReportFormat::Csv);
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "file,record_nr,import_id,title,status,uuid,error,duplicate_ids,warnings"
+        );
+        assert!(lines[1].starts_with("bochum.csv,0,0,"));
+        assert!(lines[2].starts_with("hamburg.csv,0,0,"));
+
+        let html = write(ReportFormat::Html);
+        assert!(html.contains("<li>Successes: 2</li>"));
+        assert!(html.contains("<h2>hamburg.csv</h2>"));
+        assert!(html.contains("<h3>Successes</h3>"));
+    }
+
+    #[test]
+    fn write_html_report() {
+        let entries = crate::fixtures::generate(3, 0).entries;
+        let mut failed = entries[1].clone();
+        failed.title = "Café <Müller>".to_string();
+        let report = Report {
+            successes: vec![SuccessReport {
+                place: entries[0].clone(),
+                import_id: Some("a-1".to_string()),
+                uuid: entries[0].id.clone(),
+            }],
+            failures: vec![FailureReport {
+                place: failed.clone(),
+                import_id: None,
+                error: "Forbidden".to_string(),
+                kind: FailureKind::PermissionDenied,
+            }],
+            duplicates: vec![DuplicateReport {
+                new_place: crate::mirror::new_place_from_entry(entries[2].clone()),
+                import_id: None,
+                duplicates: vec![],
+            }],
+            csv_import_successes: vec![],
+            csv_import_failures: vec![],
+            csv_import_warnings: vec![],
+            unknown_ids: vec![],
+            ..Default::default()
+        };
+        let mut html = vec![];
+        write_report(&mut html, &report, ReportFormat::Html).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains(&format!("<a href=\"{ENTRY_URL}{}\">", entries[0].id)));
+        assert!(html.contains("Café &lt;Müller&gt;"));
+        assert!(html.contains("permission denied"));
+        assert!(html.contains("<li>Duplicates: 1</li>"));
+        assert!(!html.contains("<h2>Warnings</h2>"));
+    }
+
+    #[test]
+    fn write_german_reports() {
+        let report: Report<Entry, SuccessReport<Entry>> = Report {
+            csv_import_failures: vec![CsvImportFailureReport {
+                record_nr: 1,
+                error: "Invalid lat".to_string(),
+                deferred: false,
+            }],
+            ..Default::default()
+        };
+        let mut html = vec![];
+        write_html(&mut html, &report, Lang::De).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("<html lang=\"de\">"));
+        assert!(html.contains("<h1>Bericht</h1>"));
+        assert!(html.contains("<li>Ungültige Datensätze: 1</li>"));
+        assert!(html.contains("<th>Datensatz</th><th>Fehler</th>"));
+
+        let mut csv = vec![];
+        write_csv(&mut csv, &report, Lang::De).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(
+            csv.lines().next().unwrap(),
+            "datensatz,import_id,titel,status,uuid,fehler,dubletten_ids,warnungen"
+        );
+    }
+
+    #[test]
+    fn report_schema_with_version() {
+        let schema = serde_json::to_value(report_schema(false)).unwrap();
+        assert!(schema["properties"]["schema_version"].is_object());
+        assert!(schema["definitions"]["SuccessReport"].is_object());
+        let schema = serde_json::to_value(report_schema(true)).unwrap();
+        assert!(schema["properties"]["files"].is_object());
+
+        let report: Report<NewPlace, SuccessReport<NewPlace>> = Report::default();
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["schema_version"], REPORT_SCHEMA_VERSION);
+        let json = r#"{"duplicates":[],"failures":[],"successes":[],"csv_import_successes":[],"csv_import_failures":[]}"#;
+        let report: Report<NewPlace, SuccessReport<NewPlace>> = serde_json::from_str(json).unwrap();
+        assert_eq!(report.schema_version, 0);
+    }
+
+    #[test]
+    fn summarize_and_merge_reports() {
+        let single = r#"{
+            "schema_version": 1,
+            "duplicates": [],
+            "failures": [{"place": {"title": "A"}, "import_id": "1", "error": "Forbidden", "kind": "permission_denied"}],
+            "successes": [{"place": {"title": "B"}, "import_id": "0", "uuid": "x"}],
+            "csv_import_successes": [],
+            "csv_import_failures": [{"record_nr": 2, "error": "Invalid lat"}]
+        }"#;
+        let reports = read_json_report(single.as_bytes(), "report.json").unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].file, "report.json");
+        assert_eq!(reports[0].report.failures[0].place.title(), "A");
+
+        let several = format!(
+            r#"{{"files": [{{"file": "a.csv", {0}, {{"file": "b.csv", {0}]}}"#,
+            &single.trim()[1..]
+        );
+        let reports = read_json_report(several.as_bytes(), "report.json").unwrap();
+        assert_eq!(reports[1].file, "b.csv");
+
+        let mut summary = Summary::default();
+        for r in &reports {
+            summary.add(&r.report);
+        }
+        assert_eq!(summary.reports, 2);
+        assert_eq!(summary.successes, 2);
+        assert_eq!(summary.permission_denied, 2);
+        assert_eq!(summary.invalid_records, 2);
+        assert_eq!(
+            summary.json_line(Some("report.json")).unwrap(),
+            r#"{"successes":2,"duplicates":0,"failures":2,"invalid_records":2,"report":"report.json"}"#
+        );
+
+        let merged = merge_reports(reports.into_iter().map(|r| r.report).collect());
+        assert_eq!(merged.failures.len(), 2);
+
+        let newer = single.replace(r#""schema_version": 1"#, r#""schema_version": 99"#);
+        assert!(read_json_report(newer.as_bytes(), "report.json").is_err());
+    }
+
+    #[test]
+    fn copy_failed_records() {
+        let report: JsonReport = Report {
+            failures: vec![FailureReport {
+                place: Value::Null,
+                import_id: Some("2".to_string()),
+                error: "Forbidden".to_string(),
+                kind: FailureKind::PermissionDenied,
+            }],
+            csv_import_failures: vec![CsvImportFailureReport {
+                record_nr: 0,
+                error: "Invalid lat".to_string(),
+                deferred: false,
+            }],
+            ..Default::default()
+        };
+        let source = "title,lat\nA,x\nB,1\nC,2\n";
+        let mut csv = vec![];
+        let count = write_failed_records(&report, source.as_bytes(), &mut csv, false).unwrap();
+        assert_eq!(count, 2);
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(
+            csv,
+            "title,lat,import_error\nA,x,Invalid lat\nC,2,Forbidden\n"
+        );
+
+        assert!(
+            write_failed_records(&report, "title,lat\nA,x\n".as_bytes(), vec![], false).is_err()
+        );
+
+        // The failed records of the previous run are still invalid
+        let report: JsonReport = Report {
+            csv_import_failures: vec![CsvImportFailureReport {
+                record_nr: 0,
+                error: "Invalid lat".to_string(),
+                deferred: false,
+            }],
+            ..Default::default()
+        };
+        let mut again = vec![];
+        write_failed_records(&report, csv.as_bytes(), &mut again, false).unwrap();
+        assert_eq!(
+            String::from_utf8(again).unwrap(),
+            "title,lat,import_error\nA,x,Invalid lat\n"
+        );
+    }
+
+    #[test]
+    fn imported_records_of_an_aborted_run() {
+        let place = crate::fixtures::generate(1, 1).new_places.remove(0);
+        let report: JsonReport = Report {
+            successes: vec![SuccessReport {
+                place: Value::Null,
+                import_id: Some("0".to_string()),
+                uuid: "a".to_string(),
+            }],
+            duplicates: vec![DuplicateReport {
+                new_place: place,
+                import_id: Some("3".to_string()),
+                duplicates: vec![],
+            }],
+            failures: vec![FailureReport {
+                place: Value::Null,
+                import_id: Some("2".to_string()),
+                error: "Forbidden".to_string(),
+                kind: FailureKind::PermissionDenied,
+            }],
+            ..Default::default()
+        };
+        assert_eq!(imported_records(&report), [0, 3].into());
+
+        // Invalid records stop the import before any record is sent
+        let report: JsonReport = Report {
+            csv_import_failures: vec![CsvImportFailureReport {
+                record_nr: 1,
+                error: "Invalid lat".to_string(),
+                deferred: false,
+            }],
+            ..Default::default()
+        };
+        assert!(imported_records(&report).is_empty());
+    }
+
+    #[test]
+    fn failed_new_places_of_a_report() {
+        let place = crate::fixtures::generate(1, 1).new_places.remove(0);
+        let report: JsonReport = Report {
+            failures: vec![FailureReport {
+                place: serde_json::to_value(&place).unwrap(),
+                import_id: Some("1".to_string()),
+                error: "Forbidden".to_string(),
+                kind: FailureKind::PermissionDenied,
+            }],
+            csv_import_failures: vec![CsvImportFailureReport {
+                record_nr: 0,
+                error: "Invalid lat".to_string(),
+                deferred: false,
+            }],
+            ..Default::default()
+        };
+        let (places, unreadable) = failed_new_places(&report, false).unwrap();
+        assert_eq!(unreadable, 1);
+        assert_eq!(places[0].0.title, place.title);
+        assert_eq!(places[0].1, "Forbidden");
+
+        let entry = crate::fixtures::generate(1, 1).entries.remove(0);
+        let report: JsonReport = Report {
+            failures: vec![FailureReport {
+                place: serde_json::to_value(&entry).unwrap(),
+                import_id: None,
+                error: "Forbidden".to_string(),
+                kind: FailureKind::PermissionDenied,
+            }],
+            ..Default::default()
+        };
+        assert!(failed_new_places(&report, false).is_err());
+    }
+
+    #[test]
+    fn write_the_conflicts() {
+        let entry = crate::fixtures::generate(1, 2).entries.remove(0);
+        let report: Report<Entry, SuccessReport<Entry>> = Report {
+            conflicts: vec![ConflictReport {
+                import_id: Some("4".to_string()),
+                entry_id: entry.id.clone(),
+                title: entry.title.clone(),
+                base_version: 2,
+                current_version: 3,
+                fields: vec![FieldConflict {
+                    field: "tags".to_string(),
+                    base: serde_json::json!(["bio"]),
+                    theirs: serde_json::json!(["bio", "regional"]),
+                    mine: serde_json::json!(["fair"]),
+                }],
+            }],
+            ..Default::default()
+        };
+        let mut summary = Summary::default();
+        summary.add(&report);
+        assert_eq!(summary.conflicts, 1);
+        let mut html = vec![];
+        write_html(&mut html, &report, Lang::En).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("<li>Conflicts: 1</li>"));
+        assert!(html.contains(
+            "<td>4</td><td>tags</td><td>bio</td><td>bio, regional</td><td>fair</td></tr>"
+        ));
+    }
+}
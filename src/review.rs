@@ -1,4 +1,4 @@
-use ofdb_boundary::Review;
+use ofdb_boundary::{Review, ReviewStatus};
 use std::{
     collections::{HashMap, HashSet},
     hash::{Hash, Hasher},
@@ -17,6 +17,16 @@ pub fn group_reviews(reviews: Vec<(Uuid, Review)>) -> Vec<(Review, HashSet<Uuid>
         .collect()
 }
 
+/// Number of entries that are hidden on the map after the review
+/// (archived or rejected).
+pub fn count_hidden(groups: &[(Review, HashSet<Uuid>)]) -> usize {
+    groups
+        .iter()
+        .filter(|(rev, _)| matches!(rev.status, ReviewStatus::Archived | ReviewStatus::Rejected))
+        .map(|(_, uuids)| uuids.len())
+        .sum()
+}
+
 // Workaround:
 // because `Review` does not implement `PartialEq`, `Eq` and `Hash`.
 struct Rev(Review);
@@ -54,7 +64,6 @@ impl From<Rev> for Review {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ofdb_boundary::ReviewStatus;
 
     #[test]
     fn group_uuids_by_review() {
@@ -92,6 +101,7 @@ mod tests {
         assert_eq!(groups.len(), 3);
         assert_eq!(groups.iter().map(|(_, ids)| ids.len()).max().unwrap(), 2);
         assert_eq!(groups.iter().map(|(_, ids)| ids.len()).min().unwrap(), 1);
+        assert_eq!(count_hidden(&groups), 3);
 
         for (rev, ids) in groups {
             if ids.len() == 2 {
@@ -0,0 +1,377 @@
+//! Check that a CSV file survives reading, writing and reading again,
+//! to find the values that are changed or lost by the conversions of the readers
+//! (dates, tags, numbers, encodings) before they end up on the map.
+
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::Result;
+use csv::StringRecord;
+use ofdb_core::gateways::geocode::GeoCodingGateway;
+use ofdb_entities::address::Address;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    csv::{
+        new_places_with_options, places_from_reader, places_with_filter, write_new_places,
+        write_places, CsvOptions,
+    },
+    headers::{self, PLACE_SYNONYMS},
+    import::CsvImportResult,
+};
+
+/// A value that is different before and after a conversion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub record_nr: usize,
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Default)]
+pub struct RoundtripReport {
+    pub records: usize,
+    /// Records that could not be read, they are not checked.
+    pub invalid: Vec<(usize, String)>,
+    /// Values that are normalized by the reader, e.g. `01.05.2019` → `2019-05-01`.
+    pub conversions: Vec<Change>,
+    /// Values that are different after writing and reading again.
+    pub losses: Vec<Change>,
+    /// Records that could not be read again after writing them.
+    pub unreadable: Vec<(usize, String)>,
+    /// Lines with invalid or double encoded UTF-8.
+    pub encoding: Vec<String>,
+}
+
+impl RoundtripReport {
+    /// Nothing is lost by writing and reading the records again.
+    pub fn is_lossless(&self) -> bool {
+        self.losses.is_empty() && self.unreadable.is_empty()
+    }
+}
+
+impl fmt::Display for RoundtripReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} records read, {} invalid",
+            self.records,
+            self.invalid.len()
+        )?;
+        for (record_nr, err) in &self.invalid {
+            writeln!(f, "  record {record_nr}: {err}")?;
+        }
+        if !self.encoding.is_empty() {
+            writeln!(f, "Encoding:")?;
+            for problem in &self.encoding {
+                writeln!(f, "  {problem}")?;
+            }
+        }
+        if !self.conversions.is_empty() {
+            writeln!(f, "Converted values:")?;
+            let mut by_field: BTreeMap<&str, Vec<&Change>> = BTreeMap::new();
+            for c in &self.conversions {
+                by_field.entry(&c.field).or_default().push(c);
+            }
+            for (field, changes) in by_field {
+                let c = changes[0];
+                writeln!(
+                    f,
+                    "  {field}: {} values, e.g. record {}: '{}' → '{}'",
+                    changes.len(),
+                    c.record_nr,
+                    c.before,
+                    c.after
+                )?;
+            }
+        }
+        if self.is_lossless() {
+            return writeln!(
+                f,
+                "No values are lost by writing and reading the records again"
+            );
+        }
+        writeln!(f, "Lost values:")?;
+        for c in &self.losses {
+            writeln!(
+                f,
+                "  record {}, {}: '{}' → '{}'",
+                c.record_nr, c.field, c.before, c.after
+            )?;
+        }
+        for (record_nr, err) in &self.unreadable {
+            writeln!(f, "  record {record_nr} can't be read again: {err}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A geocoder that places every address at `0,0`,
+/// since the coordinates of the addresses are not part of the check.
+struct NullIsland;
+
+impl GeoCodingGateway for NullIsland {
+    fn resolve_address_lat_lng(&self, _: &Address) -> Option<(f64, f64)> {
+        Some((0.0, 0.0))
+    }
+}
+
+/// Read the file, write the records in the format of the readers
+/// ([write_new_places] or [write_places] if the file has an `id` column)
+/// and read them again.
+pub fn check(source: &[u8], options: &CsvOptions) -> Result<RoundtripReport> {
+    let (text, encoding) = decode(source);
+    let raw = raw_records(text.as_bytes())?;
+    let has_ids = raw.headers.iter().any(|h| h == "id");
+    let mut report = if has_ids {
+        roundtrip(
+            places_with_filter(
+                text.as_bytes(),
+                &options.filter,
+                options.tag_separator,
+                options.strict_columns,
+            )?,
+            |places| {
+                let mut csv = vec![];
+                write_places(&mut csv, places, None)?;
+                Ok(csv)
+            },
+            |csv| places_from_reader(csv),
+            &raw,
+        )?
+    } else {
+        roundtrip(
+            new_places_with_options(text.as_bytes(), &NullIsland, options)?,
+            |places| {
+                let mut csv = vec![];
+                write_new_places(&mut csv, places)?;
+                Ok(csv)
+            },
+            |csv| new_places_with_options(csv, &NullIsland, &CsvOptions::default()),
+            &raw,
+        )?
+    };
+    report.encoding = encoding;
+    report.encoding.extend(double_encoded(&raw));
+    Ok(report)
+}
+
+fn roundtrip<T: Serialize>(
+    results: Vec<CsvImportResult<T>>,
+    write: impl Fn(&[T]) -> Result<Vec<u8>>,
+    read_again: impl Fn(&[u8]) -> Result<Vec<CsvImportResult<T>>>,
+    raw: &RawRecords,
+) -> Result<RoundtripReport> {
+    let mut report = RoundtripReport {
+        records: results.len(),
+        ..Default::default()
+    };
+    let mut record_nrs = vec![];
+    let mut places = vec![];
+    for r in results {
+        match r.result {
+            Ok(place) => {
+                record_nrs.push(r.record_nr);
+                places.push(place);
+            }
+            Err(err) => report.invalid.push((r.record_nr, err.to_string())),
+        }
+    }
+    let csv = write(&places)?;
+
+    let written = raw_records(&csv[..])?;
+    for (record, record_nr) in written.records.iter().zip(&record_nrs) {
+        if let Some(source) = raw.records.get(*record_nr) {
+            report.conversions.extend(converted_fields(
+                *record_nr,
+                &raw.headers,
+                source,
+                &written.headers,
+                record,
+            ));
+        }
+    }
+
+    for (i, r) in read_again(&csv[..])?.into_iter().enumerate() {
+        let record_nr = record_nrs[i];
+        match r.result {
+            Ok(again) => report.losses.extend(changed_fields(
+                record_nr,
+                &serde_json::to_value(&places[i])?,
+                &serde_json::to_value(&again)?,
+            )),
+            Err(err) => report.unreadable.push((record_nr, err.to_string())),
+        }
+    }
+    Ok(report)
+}
+
+/// The non-empty values of the source that are written differently.
+fn converted_fields(
+    record_nr: usize,
+    source_headers: &StringRecord,
+    source: &StringRecord,
+    headers: &StringRecord,
+    record: &StringRecord,
+) -> Vec<Change> {
+    headers
+        .iter()
+        .zip(record)
+        .filter_map(|(field, after)| {
+            let idx = source_headers.iter().position(|h| h == field)?;
+            let before = source.get(idx)?.trim();
+            let after = after.trim();
+            if before.is_empty() || before == after || same_number(before, after) {
+                return None;
+            }
+            Some(Change {
+                record_nr,
+                field: field.to_string(),
+                before: before.to_string(),
+                after: after.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn same_number(a: &str, b: &str) -> bool {
+    matches!((a.parse::<f64>(), b.parse::<f64>()), (Ok(a), Ok(b)) if a == b)
+}
+
+/// The fields that are different after reading the records again.
+fn changed_fields(record_nr: usize, before: &Value, after: &Value) -> Vec<Change> {
+    let (Value::Object(before), Value::Object(after)) = (before, after) else {
+        return vec![];
+    };
+    let mut fields: Vec<_> = before.keys().chain(after.keys()).collect();
+    fields.sort();
+    fields.dedup();
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let before = before.get(field).unwrap_or(&Value::Null);
+            let after = after.get(field).unwrap_or(&Value::Null);
+            (before != after).then(|| Change {
+                record_nr,
+                field: field.to_string(),
+                before: before.to_string(),
+                after: after.to_string(),
+            })
+        })
+        .collect()
+}
+
+struct RawRecords {
+    headers: StringRecord,
+    records: Vec<StringRecord>,
+}
+
+/// The records as they are, with the normalized headers of the readers.
+fn raw_records(csv: &[u8]) -> Result<RawRecords> {
+    let mut rdr = headers::reader(csv, PLACE_SYNONYMS)?;
+    let headers = rdr.headers()?.clone();
+    let records = rdr.records().collect::<csv::Result<_>>()?;
+    Ok(RawRecords { headers, records })
+}
+
+/// The text of the file and the lines that are not valid UTF-8
+/// (their invalid characters are replaced).
+fn decode(source: &[u8]) -> (String, Vec<String>) {
+    let problems = source
+        .split(|b| *b == b'\n')
+        .enumerate()
+        .filter(|(_, line)| std::str::from_utf8(line).is_err())
+        .map(|(nr, _)| {
+            format!(
+                "line {} is not valid UTF-8 (e.g. Latin-1), invalid characters are replaced",
+                nr + 1
+            )
+        })
+        .collect();
+    (String::from_utf8_lossy(source).into_owned(), problems)
+}
+
+/// Values like `MÃ¼nchen` that were encoded twice.
+fn double_encoded(raw: &RawRecords) -> Vec<String> {
+    let mut problems = vec![];
+    for (record_nr, record) in raw.records.iter().enumerate() {
+        for (field, value) in raw.headers.iter().zip(record) {
+            let chars: Vec<_> = value.chars().collect();
+            if chars
+                .windows(2)
+                .any(|w| matches!(w[0], 'Ã' | 'Â') && ('\u{80}'..='\u{bf}').contains(&w[1]))
+            {
+                problems.push(format!(
+                    "record {record_nr}, {field}: '{value}' looks like double encoded UTF-8"
+                ));
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_conversions_of_new_places() {
+        let csv = "\
+title,description,lat,lng,license,tags,founded_on,homepage
+A,Foo,\"51,5\",7.2,CC0-1.0,Bio; #Regional,01.05.2019,www.example.org
+B,Foo,51.5,7.2,CC0-1.0,bio,2019-05-01,
+C,Foo,,,CC0-1.0,bio,,
+";
+        let report = check(csv.as_bytes(), &CsvOptions::default()).unwrap();
+        assert_eq!(report.records, 3);
+        assert_eq!(report.invalid.len(), 1);
+        assert!(report.is_lossless());
+        assert!(report.encoding.is_empty());
+        let fields: Vec<_> = report
+            .conversions
+            .iter()
+            .map(|c| c.field.as_str())
+            .collect();
+        assert_eq!(fields, ["lat", "founded_on", "tags", "homepage"]);
+        assert_eq!(report.conversions[1].after, "2019-05-01");
+        assert_eq!(report.conversions[2].after, "bio,regional");
+        let text = report.to_string();
+        assert!(text.contains("founded_on: 1 values, e.g. record 0: '01.05.2019' → '2019-05-01'"));
+        assert!(text.contains("No values are lost"));
+    }
+
+    #[test]
+    fn check_entries() {
+        let csv = std::fs::read("tests/update-example.csv").unwrap();
+        let report = check(&csv, &CsvOptions::default()).unwrap();
+        assert!(report.records > 0);
+        assert!(report.is_lossless(), "{report}");
+    }
+
+    #[test]
+    fn report_encoding_problems() {
+        let mut csv = b"title,description,lat,lng,license,tags,city\n".to_vec();
+        csv.extend(b"A,Foo,51.5,7.2,CC0-1.0,bio,M\xfcnchen\n");
+        csv.extend("B,Foo,51.5,7.2,CC0-1.0,bio,MÃ¼nchen\n".as_bytes());
+        let report = check(&csv, &CsvOptions::default()).unwrap();
+        assert_eq!(report.encoding.len(), 2);
+        assert!(report.encoding[0].starts_with("line 2 is not valid UTF-8"));
+        assert!(report.encoding[1].contains("double encoded"));
+    }
+
+    #[test]
+    fn diff_fields() {
+        let before = serde_json::json!({"title": "A", "tags": ["a", "b"]});
+        let after = serde_json::json!({"title": "A", "tags": ["a"]});
+        assert_eq!(
+            changed_fields(3, &before, &after),
+            [Change {
+                record_nr: 3,
+                field: "tags".to_string(),
+                before: r#"["a","b"]"#.to_string(),
+                after: r#"["a"]"#.to_string(),
+            }]
+        );
+    }
+}
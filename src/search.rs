@@ -0,0 +1,378 @@
+//! Search with all parameters of the API
+//! and paging through results that exceed the limit of the server.
+
+use std::collections::{HashSet, VecDeque};
+
+use ofdb_boundary::{MapBbox, MapPoint, NewPlace, PlaceSearchResult, ReviewStatus, SearchResponse};
+
+use crate::{
+    matching::{distance_in_meters, title_similarity},
+    OfdbApi, Result,
+};
+
+/// Number of results per request if the query has no limit.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Bounding boxes smaller than this (in degrees) are not split any further.
+const MIN_TILE_SIZE: f64 = 0.0001;
+
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pub text: String,
+    pub bbox: MapBbox,
+    pub categories: Vec<String>,
+    pub tags: Vec<String>,
+    /// Only entries with one of these review states.
+    pub status: Vec<ReviewStatus>,
+    /// Max. number of results (the server has its own limit as well).
+    pub limit: Option<usize>,
+}
+
+impl Default for SearchQuery {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            bbox: world_bbox(),
+            categories: vec![],
+            tags: vec![],
+            status: vec![],
+            limit: None,
+        }
+    }
+}
+
+impl SearchQuery {
+    pub(crate) fn params(&self) -> Vec<(&'static str, String)> {
+        let MapBbox { sw, ne } = &self.bbox;
+        let mut params = vec![
+            ("text", self.text.clone()),
+            (
+                "bbox",
+                format!("{},{},{},{}", sw.lat, sw.lng, ne.lat, ne.lng),
+            ),
+        ];
+        if !self.categories.is_empty() {
+            params.push(("categories", self.categories.join(",")));
+        }
+        if !self.tags.is_empty() {
+            let tags: Vec<_> = self
+                .tags
+                .iter()
+                .map(|t| t.trim_start_matches('#'))
+                .collect();
+            params.push(("tags", tags.join(",")));
+        }
+        if !self.status.is_empty() {
+            let status: Vec<_> = self.status.iter().map(status_name).collect();
+            params.push(("status", status.join(",")));
+        }
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        params
+    }
+}
+
+fn status_name(status: &ReviewStatus) -> &'static str {
+    match status {
+        ReviewStatus::Archived => "archived",
+        ReviewStatus::Confirmed => "confirmed",
+        ReviewStatus::Created => "created",
+        ReviewStatus::Rejected => "rejected",
+    }
+}
+
+pub fn world_bbox() -> MapBbox {
+    MapBbox {
+        sw: MapPoint {
+            lat: -90.0,
+            lng: -180.0,
+        },
+        ne: MapPoint {
+            lat: 90.0,
+            lng: 180.0,
+        },
+    }
+}
+
+/// Parameters of the search for possible duplicates of a new place.
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicateSearch {
+    /// Max. distance in meters.
+    pub max_distance: f64,
+    /// Min. similarity of the titles (0.0 - 1.0).
+    pub min_similarity: f64,
+}
+
+impl Default for DuplicateSearch {
+    fn default() -> Self {
+        Self {
+            max_distance: 100.0,
+            min_similarity: 0.8,
+        }
+    }
+}
+
+impl DuplicateSearch {
+    pub(crate) fn params(&self) -> [(&'static str, f64); 2] {
+        [
+            ("max_distance", self.max_distance),
+            ("min_similarity", self.min_similarity),
+        ]
+    }
+
+    /// A search for places with the same title around the new place.
+    pub fn query(&self, new_place: &NewPlace) -> SearchQuery {
+        SearchQuery {
+            text: new_place.title.clone(),
+            bbox: bbox_around((new_place.lat, new_place.lng), self.max_distance),
+            ..Default::default()
+        }
+    }
+
+    /// Keep the results that are close enough and have a similar title
+    /// (each result only once).
+    pub fn filter(
+        &self,
+        new_place: &NewPlace,
+        results: Vec<PlaceSearchResult>,
+    ) -> Vec<PlaceSearchResult> {
+        let mut ids = HashSet::new();
+        results
+            .into_iter()
+            .filter(|r| {
+                let distance = distance_in_meters((new_place.lat, new_place.lng), (r.lat, r.lng));
+                distance <= self.max_distance
+                    && title_similarity(&new_place.title, &r.title) >= self.min_similarity
+            })
+            .filter(|r| ids.insert(r.id.clone()))
+            .collect()
+    }
+}
+
+/// Emulate the duplicate search with a usual search.
+pub fn emulate_duplicate_search(
+    api: &impl OfdbApi,
+    new_place: &NewPlace,
+    options: &DuplicateSearch,
+) -> Result<Option<Vec<PlaceSearchResult>>> {
+    let query = options.query(new_place);
+    let results = api.search(&query.text, &query.bbox)?.visible;
+    let duplicates = options.filter(new_place, results);
+    Ok((!duplicates.is_empty()).then_some(duplicates))
+}
+
+/// A bounding box with the given distance (in meters) to the center in each direction.
+pub fn bbox_around((lat, lng): (f64, f64), distance: f64) -> MapBbox {
+    const METERS_PER_DEGREE: f64 = 111_320.0;
+    let d_lat = distance / METERS_PER_DEGREE;
+    let d_lng = distance / (METERS_PER_DEGREE * lat.to_radians().cos().max(0.01));
+    MapBbox {
+        sw: MapPoint {
+            lat: (lat - d_lat).max(-90.0),
+            lng: (lng - d_lng).max(-180.0),
+        },
+        ne: MapPoint {
+            lat: (lat + d_lat).min(90.0),
+            lng: (lng + d_lng).min(180.0),
+        },
+    }
+}
+
+/// All results of a search.
+///
+/// The API has no offset, so if a request returns as many results as the page size,
+/// its bounding box is split into four tiles that are searched one after another.
+/// Results are only returned once, even if they lie on the border of two tiles.
+pub struct SearchPages<F> {
+    search: F,
+    query: SearchQuery,
+    page_size: usize,
+    tiles: VecDeque<MapBbox>,
+    results: VecDeque<PlaceSearchResult>,
+    seen: HashSet<String>,
+}
+
+impl<F> SearchPages<F>
+where
+    F: FnMut(&SearchQuery) -> Result<SearchResponse>,
+{
+    pub fn new(query: SearchQuery, search: F) -> Self {
+        let page_size = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        Self {
+            search,
+            tiles: VecDeque::from([query.bbox]),
+            query,
+            page_size,
+            results: VecDeque::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    fn search_next_tile(&mut self) -> Option<Result<()>> {
+        let bbox = self.tiles.pop_front()?;
+        let query = SearchQuery {
+            bbox,
+            limit: Some(self.page_size),
+            ..self.query.clone()
+        };
+        let response = match (self.search)(&query) {
+            Ok(response) => response,
+            Err(err) => return Some(Err(err)),
+        };
+        if response.visible.len() >= self.page_size {
+            if let Some(tiles) = split(&bbox) {
+                log::debug!("Too many results, split the bounding box into four tiles");
+                self.tiles.extend(tiles);
+                return Some(Ok(()));
+            }
+            log::warn!("There might be more results within {bbox:?}");
+        }
+        for result in response.visible {
+            if self.seen.insert(result.id.clone()) {
+                self.results.push_back(result);
+            }
+        }
+        Some(Ok(()))
+    }
+}
+
+impl<F> Iterator for SearchPages<F>
+where
+    F: FnMut(&SearchQuery) -> Result<SearchResponse>,
+{
+    type Item = Result<PlaceSearchResult>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(result) = self.results.pop_front() {
+                return Some(Ok(result));
+            }
+            if let Err(err) = self.search_next_tile()? {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+fn split(bbox: &MapBbox) -> Option<[MapBbox; 4]> {
+    let MapBbox { sw, ne } = *bbox;
+    if ne.lat - sw.lat < MIN_TILE_SIZE && ne.lng - sw.lng < MIN_TILE_SIZE {
+        return None;
+    }
+    let mid = MapPoint {
+        lat: (sw.lat + ne.lat) / 2.0,
+        lng: (sw.lng + ne.lng) / 2.0,
+    };
+    let bbox = |sw_lat, sw_lng, ne_lat, ne_lng| MapBbox {
+        sw: MapPoint {
+            lat: sw_lat,
+            lng: sw_lng,
+        },
+        ne: MapPoint {
+            lat: ne_lat,
+            lng: ne_lng,
+        },
+    };
+    Some([
+        bbox(sw.lat, sw.lng, mid.lat, mid.lng),
+        bbox(sw.lat, mid.lng, mid.lat, ne.lng),
+        bbox(mid.lat, sw.lng, ne.lat, mid.lng),
+        bbox(mid.lat, mid.lng, ne.lat, ne.lng),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use ofdb_boundary::EntrySearchRatings;
+
+    use super::*;
+
+    fn result(id: usize, lat: f64, lng: f64) -> PlaceSearchResult {
+        PlaceSearchResult {
+            id: id.to_string(),
+            status: None,
+            lat,
+            lng,
+            title: format!("Entry {id}"),
+            description: String::new(),
+            categories: vec![],
+            tags: vec![],
+            ratings: EntrySearchRatings::default(),
+        }
+    }
+
+    #[test]
+    fn page_through_all_results() {
+        let places: Vec<_> = (0..10)
+            .map(|i| result(i, i as f64 * 8.0 - 40.0, i as f64 * 30.0 - 150.0))
+            .collect();
+        let mut requests = 0;
+        let search = |query: &SearchQuery| {
+            requests += 1;
+            let MapBbox { sw, ne } = query.bbox;
+            let visible = places
+                .iter()
+                .filter(|p| {
+                    p.lat >= sw.lat && p.lat <= ne.lat && p.lng >= sw.lng && p.lng <= ne.lng
+                })
+                .take(query.limit.unwrap())
+                .cloned()
+                .collect();
+            Ok(SearchResponse {
+                visible,
+                invisible: vec![],
+            })
+        };
+        let query = SearchQuery {
+            limit: Some(3),
+            ..Default::default()
+        };
+        let mut ids: Vec<_> = SearchPages::new(query, search)
+            .map(|r| r.unwrap().id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, (0..10).map(|i| i.to_string()).collect::<Vec<_>>());
+        assert!(requests > 1);
+    }
+
+    #[test]
+    fn filter_possible_duplicates() {
+        let new_place = NewPlace {
+            title: "Repair Café Bochum".to_string(),
+            lat: 51.4818,
+            lng: 7.2162,
+            ..crate::fixtures::generate(1, 2).new_places.remove(0)
+        };
+        let mut close = result(1, 51.4820, 7.2160);
+        close.title = "Repair-Café Bochum".to_string();
+        let mut far = result(2, 51.5136, 7.4653);
+        far.title = "Repair Café Bochum".to_string();
+        let mut other = result(3, 51.4818, 7.2162);
+        other.title = "Kleidertausch".to_string();
+        let options = DuplicateSearch::default();
+        let duplicates = options.filter(&new_place, vec![close.clone(), far, other, close]);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].id, "1");
+
+        let bbox = options.query(&new_place).bbox;
+        let corner = distance_in_meters((bbox.sw.lat, 7.2162), (51.4818, 7.2162));
+        assert!((99.0..101.0).contains(&corner), "{corner}");
+    }
+
+    #[test]
+    fn query_parameters() {
+        let query = SearchQuery {
+            text: "repair".to_string(),
+            tags: vec!["#solar".to_string(), "wind".to_string()],
+            status: vec![ReviewStatus::Created, ReviewStatus::Confirmed],
+            limit: Some(50),
+            ..Default::default()
+        };
+        let params = query.params();
+        assert_eq!(params[1], ("bbox", "-90,-180,90,180".to_string()));
+        assert!(params.contains(&("tags", "solar,wind".to_string())));
+        assert!(params.contains(&("status", "created,confirmed".to_string())));
+        assert!(params.contains(&("limit", "50".to_string())));
+        assert!(!params.iter().any(|(name, _)| *name == "categories"));
+    }
+}
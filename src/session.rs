@@ -0,0 +1,285 @@
+//! Login sessions that are scoped per instance and user.
+//!
+//! The cookies (and optionally the credentials) of a session are stored in
+//! `<state dir>/sessions/<instance>/<user>/`, so that several jobs that use
+//! different instances or accounts don't overwrite each other's sessions.
+//!
+//! After `ofdb login` the user is remembered as the current user of the instance
+//! (`<state dir>/sessions/<instance>/current_user`) until `ofdb logout`.
+
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Result};
+use reqwest::{
+    cookie::{CookieStore, Jar},
+    Url,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{client::http_client_builder, http::HttpOptions, OfdbClient};
+
+/// Environment variable to override the location of the state directory.
+pub const STATE_DIR_ENV: &str = "OFDB_STATE_DIR";
+
+const COOKIES_FILE: &str = "cookies.txt";
+const CREDENTIALS_FILE: &str = "credentials.json";
+const CURRENT_USER_FILE: &str = "current_user";
+
+/// Directory with the state of the CLI,
+/// e.g. `~/.local/share/ofdb` on Linux.
+pub fn state_dir() -> Option<PathBuf> {
+    env::var_os(STATE_DIR_ENV)
+        .map(PathBuf::from)
+        .or_else(|| dirs::data_local_dir().map(|dir| dir.join("ofdb")))
+}
+
+/// Credentials that the user decided to store
+/// to re-authenticate if a session expired.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StoredCredentials {
+    pub email: String,
+    pub password: String,
+}
+
+/// The user that is logged in on the instance (via `ofdb login`).
+pub fn current_user(api: &str) -> Result<Option<String>> {
+    let dir = state_dir().ok_or_else(|| anyhow!("Unable to find the session directory"))?;
+    current_user_in(&dir, api)
+}
+
+fn current_user_in(state_dir: &Path, api: &str) -> Result<Option<String>> {
+    let path = instance_dir(state_dir, api)?.join(CURRENT_USER_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let email = fs::read_to_string(path)?.trim().to_string();
+    Ok((!email.is_empty()).then_some(email))
+}
+
+fn instance_dir(state_dir: &Path, api: &str) -> Result<PathBuf> {
+    let url = api
+        .parse::<Url>()
+        .map_err(|err| anyhow!("Invalid API URL '{api}': {err}"))?;
+    Ok(state_dir.join("sessions").join(scope_key(url.as_str())))
+}
+
+#[derive(Debug)]
+pub struct Session {
+    email: String,
+    dir: PathBuf,
+    api: String,
+    url: Url,
+    jar: Arc<Jar>,
+}
+
+impl Session {
+    /// Open the session of a user on an instance
+    /// and load the stored cookies.
+    pub fn open(api: &str, email: &str) -> Result<Self> {
+        let dir = state_dir()
+            .ok_or_else(|| anyhow!("Unable to find a directory to store the session"))?;
+        Self::open_in(&dir, api, email)
+    }
+
+    fn open_in(state_dir: &Path, api: &str, email: &str) -> Result<Self> {
+        let url = api
+            .parse::<Url>()
+            .map_err(|err| anyhow!("Invalid API URL '{api}': {err}"))?;
+        let email = normalize_email(email);
+        let dir = instance_dir(state_dir, api)?.join(scope_key(&email));
+        let jar = Arc::new(Jar::default());
+        let cookies_file = dir.join(COOKIES_FILE);
+        if cookies_file.exists() {
+            log::debug!("Read session cookies from {}", cookies_file.display());
+            for cookie in fs::read_to_string(cookies_file)?.lines() {
+                jar.add_cookie_str(cookie, &url);
+            }
+        }
+        let api = api.to_string();
+        Ok(Self {
+            email,
+            dir,
+            api,
+            url,
+            jar,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Is there a (possibly expired) session cookie?
+    pub fn has_cookies(&self) -> bool {
+        self.jar.cookies(&self.url).is_some()
+    }
+
+    /// Create a client that uses the cookies of this session.
+    pub fn client(&self, options: &HttpOptions) -> Result<OfdbClient> {
+        let client = options
+            .apply_connection(http_client_builder())?
+            .cookie_provider(Arc::clone(&self.jar))
+            .build()?;
+        Ok(OfdbClient::with_client(&self.api, client).with_timeouts(options))
+    }
+
+    pub fn save_cookies(&self) -> Result<()> {
+        let cookies = self
+            .jar
+            .cookies(&self.url)
+            .and_then(|value| value.to_str().map(ToString::to_string).ok())
+            .unwrap_or_default()
+            .split("; ")
+            .filter(|c| !c.is_empty())
+            .map(|c| format!("{c}\n"))
+            .collect::<String>();
+        write_private_file(&self.dir.join(COOKIES_FILE), &cookies)
+    }
+
+    pub fn load_credentials(&self) -> Result<Option<StoredCredentials>> {
+        let path = self.dir.join(CREDENTIALS_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let credentials = serde_json::from_str(&fs::read_to_string(&path)?)
+            .map_err(|err| anyhow!("Invalid credentials file {}: {err}", path.display()))?;
+        Ok(Some(credentials))
+    }
+
+    pub fn store_credentials(&self, credentials: &StoredCredentials) -> Result<()> {
+        let path = self.dir.join(CREDENTIALS_FILE);
+        log::info!("Store credentials in {}", path.display());
+        write_private_file(&path, &serde_json::to_string(credentials)?)
+    }
+
+    /// Remember the user of this session as the current user of the instance.
+    pub fn set_current(&self) -> Result<()> {
+        write_private_file(&self.current_user_file(), &self.email)
+    }
+
+    /// Delete the cookies and the stored credentials of this session.
+    pub fn remove(self) -> Result<()> {
+        if self.dir.exists() {
+            log::debug!("Remove session {}", self.dir.display());
+            fs::remove_dir_all(&self.dir)?;
+        }
+        let current_user_file = self.current_user_file();
+        if current_user_file.exists()
+            && normalize_email(&fs::read_to_string(&current_user_file)?) == self.email
+        {
+            fs::remove_file(current_user_file)?;
+        }
+        Ok(())
+    }
+
+    fn current_user_file(&self) -> PathBuf {
+        self.dir
+            .parent()
+            .expect("instance directory")
+            .join(CURRENT_USER_FILE)
+    }
+}
+
+/// E-mail addresses are case-insensitive, so a user has only one session
+/// however the address is typed.
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Turn an URL or e-mail address into a directory name.
+fn scope_key(s: &str) -> String {
+    let s = s.split_once("://").map(|(_, rest)| rest).unwrap_or(s);
+    s.trim_end_matches('/')
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Write a file that is only readable by the current user.
+///
+/// The content is written to a temporary file that replaces the
+/// original file, so concurrent readers never see a partial file.
+fn write_private_file(path: &Path, content: &str) -> Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow!("Invalid path {}", path.display()))?;
+    fs::create_dir_all(dir)?;
+    let tmp_path = path.with_extension(format!("tmp.{}", std::process::id()));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&tmp_path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_sessions_by_instance_and_user() {
+        assert_eq!(scope_key("https://api.ofdb.io/v0/"), "api.ofdb.io_v0");
+        assert_eq!(scope_key("http://localhost:6767/v0"), "localhost_6767_v0");
+        assert_eq!(scope_key("me@example.org"), "me_example.org");
+    }
+
+    #[test]
+    fn save_and_load_session() {
+        let state_dir = env::temp_dir().join(format!("ofdb-session-test-{}", std::process::id()));
+        let api = "https://api.ofdb.io/v0";
+        let session = Session::open_in(&state_dir, api, "Me@example.org").unwrap();
+        assert!(!session.has_cookies());
+        session.jar.add_cookie_str("ofdb-session=abc", &session.url);
+        session.save_cookies().unwrap();
+        session
+            .store_credentials(&StoredCredentials {
+                email: "me@example.org".to_string(),
+                password: "secret".to_string(),
+            })
+            .unwrap();
+
+        let session = Session::open_in(&state_dir, api, "me@example.org").unwrap();
+        assert!(session.has_cookies());
+        let credentials = session.load_credentials().unwrap().unwrap();
+        assert_eq!(credentials.password, "secret");
+
+        let other_user = Session::open_in(&state_dir, api, "other@example.org").unwrap();
+        assert!(!other_user.has_cookies());
+        assert!(other_user.load_credentials().unwrap().is_none());
+
+        assert_eq!(current_user_in(&state_dir, api).unwrap(), None);
+        let session = Session::open_in(&state_dir, api, " Me@Example.org").unwrap();
+        session.set_current().unwrap();
+        assert_eq!(
+            current_user_in(&state_dir, api).unwrap().as_deref(),
+            Some("me@example.org")
+        );
+        other_user.remove().unwrap();
+        assert!(current_user_in(&state_dir, api).unwrap().is_some());
+        Session::open_in(&state_dir, api, "me@example.org")
+            .unwrap()
+            .remove()
+            .unwrap();
+        assert_eq!(current_user_in(&state_dir, api).unwrap(), None);
+        let session = Session::open_in(&state_dir, api, "me@example.org").unwrap();
+        assert!(!session.has_cookies());
+        assert!(session.load_credentials().unwrap().is_none());
+
+        fs::remove_dir_all(state_dir).unwrap();
+    }
+}
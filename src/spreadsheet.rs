@@ -0,0 +1,275 @@
+//! Read the records of online tables (Airtable, Google Sheets) via their APIs
+//! instead of a manual CSV export.
+//!
+//! The records are converted into a CSV file, so the columns are mapped
+//! and the records are imported like the records of any other CSV file.
+
+use std::{fmt, str::FromStr, thread, time::Duration};
+
+use anyhow::{anyhow, bail, Result};
+use reqwest::{blocking::Client, Url};
+use serde_json::{Map, Value};
+
+const AIRTABLE_API: &str = "https://api.airtable.com/v0/";
+const GOOGLE_SHEETS_API: &str = "https://sheets.googleapis.com/v4/spreadsheets/";
+
+/// Airtable allows 5 requests per second and base.
+const AIRTABLE_PAUSE: Duration = Duration::from_millis(200);
+
+/// A table of a spreadsheet, e.g. `appXXXXXXXX/Orte` (Airtable base and table)
+/// or `1BxiMVs0XRA5nFMdKvBdBZjgmUUqptlbs74OgvE2upms/Sheet1` (Google spreadsheet and sheet).
+///
+/// The sheet of a Google spreadsheet is optional (the first sheet).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    pub spreadsheet: String,
+    pub table: Option<String>,
+}
+
+impl FromStr for Table {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (spreadsheet, table) = match s.trim().split_once('/') {
+            Some((spreadsheet, table)) => (spreadsheet, Some(table.trim().to_string())),
+            None => (s.trim(), None),
+        };
+        if spreadsheet.is_empty() || table.as_deref() == Some("") {
+            bail!("Invalid table '{s}' (expected 'SPREADSHEET/TABLE')");
+        }
+        Ok(Self {
+            spreadsheet: spreadsheet.to_string(),
+            table,
+        })
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.spreadsheet)?;
+        if let Some(table) = &self.table {
+            write!(f, "/{table}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Read all records of an Airtable table (the table is required).
+pub fn airtable_csv(http: &Client, table: &Table, token: &str) -> Result<Vec<u8>> {
+    let Some(name) = &table.table else {
+        bail!(
+            "The Airtable table of the base {} is missing",
+            table.spreadsheet
+        );
+    };
+    let mut url = Url::parse(AIRTABLE_API)?;
+    url.path_segments_mut()
+        .map_err(|()| anyhow!("Invalid Airtable URL"))?
+        .pop_if_empty()
+        .push(&table.spreadsheet)
+        .push(name);
+    let mut records = vec![];
+    let mut offset: Option<String> = None;
+    loop {
+        let mut request = http.get(url.clone()).bearer_auth(token);
+        if let Some(offset) = &offset {
+            request = request.query(&[("offset", offset)]);
+        }
+        let page = json_response(request.send()?, "Airtable")?;
+        records.extend(airtable_records(&page)?);
+        log::debug!("Read {} records from Airtable", records.len());
+        offset = page["offset"].as_str().map(ToString::to_string);
+        if offset.is_none() {
+            break;
+        }
+        thread::sleep(AIRTABLE_PAUSE);
+    }
+    log::info!(
+        "Read {} records from the Airtable table {table}",
+        records.len()
+    );
+    records_to_csv(&records)
+}
+
+/// Read all rows of a Google sheet, the first row contains the column names.
+///
+/// The spreadsheet must be shared with anyone who has the link,
+/// since it is read with an API key.
+pub fn google_sheet_csv(http: &Client, table: &Table, api_key: &str) -> Result<Vec<u8>> {
+    // A range without a sheet is the range of the first sheet
+    let range = table.table.clone().unwrap_or_else(|| "A:ZZ".to_string());
+    let mut url = Url::parse(GOOGLE_SHEETS_API)?;
+    url.path_segments_mut()
+        .map_err(|()| anyhow!("Invalid Google Sheets URL"))?
+        .pop_if_empty()
+        .push(&table.spreadsheet)
+        .push("values")
+        .push(&range);
+    // Unformatted values, since formatted numbers depend on the locale of the sheet
+    // (e.g. `51,5` in German sheets), but formatted dates
+    let response = http
+        .get(url)
+        .query(&[
+            ("key", api_key),
+            ("valueRenderOption", "UNFORMATTED_VALUE"),
+            ("dateTimeRenderOption", "FORMATTED_STRING"),
+        ])
+        .send()
+        // The URL contains the API key
+        .map_err(reqwest::Error::without_url)?;
+    let values = json_response(response, "Google Sheets")?;
+    let rows = sheet_rows(&values)?;
+    log::info!(
+        "Read {} rows from the Google sheet {table}",
+        rows.len().saturating_sub(1)
+    );
+    rows_to_csv(&rows)
+}
+
+fn json_response(response: reqwest::blocking::Response, service: &str) -> Result<Value> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().unwrap_or_default();
+        bail!("{service} responded with {status}: {body}");
+    }
+    Ok(response.json().map_err(reqwest::Error::without_url)?)
+}
+
+/// The fields of the records of a page (empty fields are missing).
+fn airtable_records(page: &Value) -> Result<Vec<Map<String, Value>>> {
+    let records = page["records"]
+        .as_array()
+        .ok_or_else(|| anyhow!("Unexpected Airtable response"))?;
+    Ok(records
+        .iter()
+        .map(|r| r["fields"].as_object().cloned().unwrap_or_default())
+        .collect())
+}
+
+/// The columns are the fields in the order of their first appearance.
+fn records_to_csv(records: &[Map<String, Value>]) -> Result<Vec<u8>> {
+    let mut columns: Vec<&str> = vec![];
+    for field in records.iter().flat_map(Map::keys) {
+        if !columns.contains(&field.as_str()) {
+            columns.push(field);
+        }
+    }
+    let mut wtr = ::csv::Writer::from_writer(vec![]);
+    wtr.write_record(&columns)?;
+    for record in records {
+        wtr.write_record(
+            columns
+                .iter()
+                .map(|c| record.get(*c).map(cell).unwrap_or_default()),
+        )?;
+    }
+    Ok(wtr.into_inner()?)
+}
+
+/// The text of a value, e.g. the tags of a multiple select field (`bio,regional`)
+/// or the URL of an attachment.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(cell)
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>()
+            .join(","),
+        Value::Object(object) => ["url", "email", "name"]
+            .iter()
+            .find_map(|key| object.get(*key).and_then(Value::as_str))
+            .map_or_else(|| value.to_string(), ToString::to_string),
+        Value::Bool(_) | Value::Number(_) => value.to_string(),
+    }
+}
+
+fn sheet_rows(values: &Value) -> Result<Vec<Vec<String>>> {
+    let Some(rows) = values.get("values") else {
+        // An empty sheet has no values
+        return Ok(vec![]);
+    };
+    let rows = rows
+        .as_array()
+        .ok_or_else(|| anyhow!("Unexpected Google Sheets response"))?;
+    Ok(rows
+        .iter()
+        .map(|row| row.as_array().into_iter().flatten().map(cell).collect())
+        .collect())
+}
+
+/// Trailing empty cells are missing in the rows of a sheet.
+fn rows_to_csv(rows: &[Vec<String>]) -> Result<Vec<u8>> {
+    let Some(headers) = rows.first() else {
+        bail!("The sheet is empty");
+    };
+    let mut wtr = ::csv::Writer::from_writer(vec![]);
+    wtr.write_record(headers)?;
+    for row in &rows[1..] {
+        let mut row = row.clone();
+        row.resize(headers.len(), String::new());
+        wtr.write_record(&row)?;
+    }
+    Ok(wtr.into_inner()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn convert_airtable_records() {
+        let page = json!({
+            "records": [
+                {"id": "rec1", "fields": {"Name": "Bioladen", "Tags": ["bio", "regional"], "Lat": 51.5}},
+                {"id": "rec2", "fields": {"Name": "Repair Café", "Ort": "Bochum",
+                    "Bild": [{"id": "att1", "url": "https://example.org/a.jpg"}], "Aktiv": true}}
+            ],
+            "offset": "itr1"
+        });
+        let records = airtable_records(&page).unwrap();
+        let csv = records_to_csv(&records).unwrap();
+        let mut rdr = ::csv::Reader::from_reader(&csv[..]);
+        let headers = rdr.headers().unwrap().clone();
+        let rows: Vec<_> = rdr.records().map(Result::unwrap).collect();
+        let value = |row: usize, column: &str| {
+            let idx = headers.iter().position(|h| h == column).unwrap();
+            rows[row][idx].to_string()
+        };
+        assert_eq!(headers.len(), 6);
+        assert_eq!(value(0, "Name"), "Bioladen");
+        assert_eq!(value(0, "Tags"), "bio,regional");
+        assert_eq!(value(0, "Lat"), "51.5");
+        assert_eq!(value(0, "Ort"), "");
+        assert_eq!(value(1, "Bild"), "https://example.org/a.jpg");
+        assert_eq!(value(1, "Aktiv"), "true");
+        assert!(airtable_records(&json!({"error": "NOT_FOUND"})).is_err());
+    }
+
+    #[test]
+    fn convert_sheet_rows() {
+        let values = json!({
+            "range": "Sheet1!A1:C3",
+            "values": [["title", "city", "tags"], ["Bioladen", "Bochum"], ["Repair Café", "Essen", "repair"]]
+        });
+        let rows = sheet_rows(&values).unwrap();
+        let csv = String::from_utf8(rows_to_csv(&rows).unwrap()).unwrap();
+        assert_eq!(
+            csv,
+            "title,city,tags\nBioladen,Bochum,\nRepair Café,Essen,repair\n"
+        );
+        assert!(rows_to_csv(&sheet_rows(&json!({"range": "A:ZZ"})).unwrap()).is_err());
+    }
+
+    #[test]
+    fn parse_tables() {
+        let table: Table = "appABC/Orte in Bochum".parse().unwrap();
+        assert_eq!(table.spreadsheet, "appABC");
+        assert_eq!(table.table.as_deref(), Some("Orte in Bochum"));
+        assert_eq!(table.to_string(), "appABC/Orte in Bochum");
+        assert_eq!("1BxiM".parse::<Table>().unwrap().table, None);
+        assert!("appABC/".parse::<Table>().is_err());
+    }
+}
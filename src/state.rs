@@ -0,0 +1,205 @@
+//! Checkpoints of a run in a state file, to resume an interrupted run
+//! exactly where it stopped (independent of the report).
+//!
+//! A record is saved as processed right after its result is known
+//! (imported or duplicate), so a crash or Ctrl-C loses at most
+//! the record that was sent in that moment.
+//! Failed records (e.g. because of rate limiting, network errors or version conflicts)
+//! are not saved, so a resumed run tries them again.
+//!
+//! The state file contains the numbers of the processed records of each input file
+//! as ranges, e.g. `["0-999", "1005"]`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{filter::Rows, import};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StateFile {
+    /// The command of the run, e.g. `import`.
+    command: String,
+    /// The processed records by input file.
+    files: BTreeMap<String, Vec<String>>,
+}
+
+/// The processed records of a run.
+#[derive(Debug)]
+pub struct RunState {
+    path: PathBuf,
+    command: String,
+    processed: BTreeMap<String, BTreeSet<usize>>,
+}
+
+impl RunState {
+    /// Read the state file of a previous run or start a new one.
+    pub fn open(path: &Path, command: &str) -> Result<Self> {
+        let mut processed = BTreeMap::new();
+        if path.exists() {
+            let state: StateFile = serde_json::from_reader(io::BufReader::new(File::open(path)?))?;
+            if state.command != command {
+                bail!(
+                    "The state file {} belongs to a run of '{}', not of '{command}'",
+                    path.display(),
+                    state.command
+                );
+            }
+            for (file, ranges) in state.files {
+                let mut records = BTreeSet::new();
+                for range in ranges {
+                    let Rows { first, last } = range.parse()?;
+                    records.extend(first..=last.unwrap_or(first));
+                }
+                processed.insert(file, records);
+            }
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            command: command.to_string(),
+            processed,
+        })
+    }
+
+    /// The records of a file that were processed by a previous run.
+    pub fn processed(&self, file: &str) -> BTreeSet<usize> {
+        self.processed.get(file).cloned().unwrap_or_default()
+    }
+
+    /// Remember the processed records and save the state file.
+    pub fn mark_processed(
+        &mut self,
+        file: &str,
+        record_nrs: impl IntoIterator<Item = usize>,
+    ) -> Result<()> {
+        let processed = self.processed.entry(file.to_string()).or_default();
+        let count = processed.len();
+        processed.extend(record_nrs);
+        if processed.len() == count {
+            return Ok(());
+        }
+        self.save()
+    }
+
+    /// Save a processed record.
+    ///
+    /// Errors are only logged, to not stop a run because of its state file.
+    pub fn checkpoint(&mut self, file: &str, record_nr: usize) {
+        if let Err(err) = self.mark_processed(file, [record_nr]) {
+            log::warn!(
+                "Unable to save the state file {}: {err}",
+                self.path.display()
+            );
+        }
+    }
+
+    /// Save a processed record, unless it failed and should be tried again.
+    pub fn checkpoint_result<T>(
+        &mut self,
+        file: &str,
+        record_nr: usize,
+        result: &Result<T, import::Error>,
+    ) {
+        if is_final(result) {
+            self.checkpoint(file, record_nr);
+        }
+    }
+
+    /// Write the state to a temporary file first,
+    /// so that an interrupted run never leaves a broken state file.
+    fn save(&self) -> Result<()> {
+        let state = StateFile {
+            command: self.command.clone(),
+            files: self
+                .processed
+                .iter()
+                .map(|(file, records)| (file.clone(), ranges(records)))
+                .collect(),
+        };
+        let tmp = self.path.with_extension("tmp");
+        let mut w = io::BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer_pretty(&mut w, &state)?;
+        w.flush()?;
+        drop(w);
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Whether a resumed run can skip a record with this result.
+pub fn is_final<T>(result: &Result<T, import::Error>) -> bool {
+    matches!(result, Ok(_) | Err(import::Error::Duplicates(_)))
+}
+
+/// Consecutive record numbers as ranges, e.g. `0-999`.
+fn ranges(records: &BTreeSet<usize>) -> Vec<String> {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for &nr in records {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == nr => *last = nr,
+            _ => ranges.push((nr, nr)),
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{first}-{last}")
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_a_run() {
+        let path =
+            std::env::temp_dir().join(format!("ofdb-run-state-test-{}.json", std::process::id()));
+        let mut state = RunState::open(&path, "import").unwrap();
+        assert!(state.processed("a.csv").is_empty());
+        state.mark_processed("a.csv", [0, 1, 2, 5]).unwrap();
+        state.checkpoint("a.csv", 3);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["files"]["a.csv"], serde_json::json!(["0-3", "5"]));
+
+        let state = RunState::open(&path, "import").unwrap();
+        assert_eq!(state.processed("a.csv"), [0, 1, 2, 3, 5].into());
+        assert!(RunState::open(&path, "update").is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn retry_failed_records_after_resuming() {
+        let path = std::env::temp_dir().join(format!(
+            "ofdb-run-state-failed-test-{}.json",
+            std::process::id()
+        ));
+        let mut state = RunState::open(&path, "update").unwrap();
+        let results: [Result<(), import::Error>; 4] = [
+            Ok(()),
+            Err(import::Error::Other("Too many requests".to_string())),
+            Err(import::Error::Duplicates(vec![])),
+            Err(import::Error::PermissionDenied("Forbidden".to_string())),
+        ];
+        for (record_nr, result) in results.iter().enumerate() {
+            state.checkpoint_result("a.csv", record_nr, result);
+        }
+
+        let state = RunState::open(&path, "update").unwrap();
+        assert_eq!(state.processed("a.csv"), [0, 2].into());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,196 @@
+//! Statistics of the entries of a region, e.g. for regional reports.
+
+use std::{collections::HashMap, fmt};
+
+use ofdb_boundary::Entry;
+use serde::Serialize;
+use time::{Date, OffsetDateTime};
+
+use crate::category::category_name;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub entries: usize,
+    /// Date (`YYYY-MM-DD`) when the oldest entry was created.
+    pub oldest: Option<String>,
+    /// Date (`YYYY-MM-DD`) when the newest entry was created.
+    pub newest: Option<String>,
+    pub completeness: Completeness,
+    pub tags: Vec<Count>,
+    pub cities: Vec<Count>,
+    pub categories: Vec<Count>,
+}
+
+/// Percentage of the entries that have a certain field.
+#[derive(Debug, Clone, Serialize)]
+pub struct Completeness {
+    pub homepage: f64,
+    pub image: f64,
+    pub opening_hours: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub count: usize,
+}
+
+/// Calculate the statistics of the entries.
+///
+/// The counts are sorted by frequency, only the `top` most frequent ones are kept.
+pub fn stats(entries: &[Entry], top: usize) -> Stats {
+    let created = entries.iter().filter_map(|e| created_date(e.created));
+    let percent = |has: fn(&Entry) -> bool| {
+        if entries.is_empty() {
+            return 0.0;
+        }
+        let count = entries.iter().filter(|e| has(e)).count();
+        (count as f64 * 1000.0 / entries.len() as f64).round() / 10.0
+    };
+    Stats {
+        entries: entries.len(),
+        oldest: created.clone().min().map(|d| d.to_string()),
+        newest: created.max().map(|d| d.to_string()),
+        completeness: Completeness {
+            homepage: percent(|e| is_set(&e.homepage)),
+            image: percent(|e| is_set(&e.image_url)),
+            opening_hours: percent(|e| is_set(&e.opening_hours)),
+        },
+        tags: counts(entries.iter().flat_map(|e| e.tags.iter().cloned()), top),
+        cities: counts(
+            entries
+                .iter()
+                .filter_map(|e| e.city.as_deref())
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .map(ToString::to_string),
+            top,
+        ),
+        categories: counts(
+            entries
+                .iter()
+                .flat_map(|e| e.categories.iter())
+                .map(|c| category_name(c).to_string()),
+            top,
+        ),
+    }
+}
+
+fn is_set(field: &Option<String>) -> bool {
+    field.as_deref().is_some_and(|v| !v.trim().is_empty())
+}
+
+fn created_date(created: i64) -> Option<Date> {
+    OffsetDateTime::from_unix_timestamp(created)
+        .ok()
+        .map(|t| t.date())
+}
+
+fn counts(names: impl Iterator<Item = String>, top: usize) -> Vec<Count> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for name in names {
+        *counts.entry(name).or_default() += 1;
+    }
+    let mut counts: Vec<_> = counts
+        .into_iter()
+        .map(|(name, count)| Count { name, count })
+        .collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    counts.truncate(top);
+    counts
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let date = |d: &Option<String>| d.clone().unwrap_or_else(|| "-".to_string());
+        writeln!(f, "Entries:        {}", self.entries)?;
+        writeln!(f, "Oldest:         {}", date(&self.oldest))?;
+        writeln!(f, "Newest:         {}", date(&self.newest))?;
+        writeln!(f, "Homepage:       {:.1} %", self.completeness.homepage)?;
+        writeln!(f, "Image:          {:.1} %", self.completeness.image)?;
+        write!(
+            f,
+            "Opening hours:  {:.1} %",
+            self.completeness.opening_hours
+        )?;
+        for (title, counts) in [
+            ("Tags", &self.tags),
+            ("Cities", &self.cities),
+            ("Categories", &self.categories),
+        ] {
+            writeln!(f)?;
+            writeln!(f)?;
+            write!(f, "{title}:")?;
+            let width = counts.iter().map(|c| c.name.chars().count()).max();
+            for Count { name, count } in counts {
+                write!(
+                    f,
+                    "\n  {name:<width$}  {count:>6}",
+                    width = width.unwrap_or(0)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_entries() {
+        let mut entries = crate::fixtures::generate(4, 1).entries;
+        for (i, e) in entries.iter_mut().enumerate() {
+            e.tags = vec!["solar".to_string()];
+            e.city = Some(if i < 3 { "Bochum" } else { "Kassel" }.to_string());
+            e.categories = vec![crate::category::CATEGORY_INITIATIVE.to_string()];
+            e.homepage = (i == 0).then(|| "https://example.org".to_string());
+            e.created = 1_600_000_000 + i as i64 * 86_400;
+        }
+        entries[1].tags.push("wind".to_string());
+        let stats = stats(&entries, 10);
+        assert_eq!(stats.entries, 4);
+        assert_eq!(stats.oldest.as_deref().unwrap(), "2020-09-13");
+        assert_eq!(stats.newest.as_deref().unwrap(), "2020-09-16");
+        assert_eq!(stats.completeness.homepage, 25.0);
+        let names = |counts: &[Count]| {
+            counts
+                .iter()
+                .map(|c| (c.name.clone(), c.count))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&stats.tags),
+            vec![("solar".to_string(), 4), ("wind".to_string(), 1)]
+        );
+        assert_eq!(
+            names(&stats.cities),
+            vec![("Bochum".to_string(), 3), ("Kassel".to_string(), 1)]
+        );
+        assert_eq!(
+            names(&stats.categories),
+            vec![("initiative".to_string(), 4)]
+        );
+        assert!(stats.to_string().contains("\n  solar  "));
+    }
+
+    #[test]
+    fn keep_only_the_top_counts() {
+        let names = ["a", "b", "b", "c", "c", "c"].map(String::from);
+        let counts = counts(names.into_iter(), 2);
+        assert_eq!(
+            counts,
+            vec![
+                Count {
+                    name: "c".to_string(),
+                    count: 3
+                },
+                Count {
+                    name: "b".to_string(),
+                    count: 2
+                }
+            ]
+        );
+    }
+}
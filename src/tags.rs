@@ -0,0 +1,337 @@
+//! The tags that are in use, e.g. to check the spelling of a hashtag before an import,
+//! and the hashtags of descriptions.
+
+use std::{io::Write, str::FromStr};
+
+use anyhow::{bail, Result};
+use ofdb_boundary::TagFrequency;
+use serde::Serialize;
+
+/// Parameters of the request of the most popular tags.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TagQuery {
+    pub min_count: Option<u64>,
+    pub max_count: Option<u64>,
+    pub offset: Option<u64>,
+    pub limit: Option<u64>,
+}
+
+impl TagQuery {
+    pub(crate) fn params(&self) -> Vec<(&'static str, u64)> {
+        [
+            ("min_count", self.min_count),
+            ("max_count", self.max_count),
+            ("offset", self.offset),
+            ("limit", self.limit),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|v| (name, v)))
+        .collect()
+    }
+}
+
+/// A tag and the number of entries with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: u64,
+}
+
+/// Keep the tags that contain `text` (ignoring the case).
+pub fn filter_tags(tags: Vec<TagFrequency>, contains: Option<&str>) -> Vec<TagCount> {
+    let contains = contains.map(|c| c.trim_start_matches('#').to_lowercase());
+    tags.into_iter()
+        .filter(|TagFrequency(tag, _)| {
+            contains
+                .as_deref()
+                .map_or(true, |c| tag.to_lowercase().contains(c))
+        })
+        .map(|TagFrequency(tag, count)| TagCount { tag, count })
+        .collect()
+}
+
+/// Number of tags per request if all tags have to be read.
+const TAG_PAGE_SIZE: u64 = 100;
+
+/// The tags of the query that contain `contains` (ignoring the case).
+///
+/// The API can't search the tags by text, so all tags within the counts of the query
+/// are read page by page and the offset and limit are applied to the matching tags.
+pub fn find_tags(
+    mut fetch: impl FnMut(&TagQuery) -> Result<Vec<TagFrequency>>,
+    query: &TagQuery,
+    contains: Option<&str>,
+) -> Result<Vec<TagCount>> {
+    if contains.is_none() {
+        return Ok(filter_tags(fetch(query)?, None));
+    }
+    let mut page = TagQuery {
+        offset: Some(0),
+        limit: Some(TAG_PAGE_SIZE),
+        ..*query
+    };
+    let mut tags = vec![];
+    loop {
+        let frequencies = fetch(&page)?;
+        let last_page = (frequencies.len() as u64) < TAG_PAGE_SIZE;
+        tags.extend(filter_tags(frequencies, contains));
+        if last_page {
+            break;
+        }
+        page.offset = page.offset.map(|offset| offset + TAG_PAGE_SIZE);
+    }
+    let limit = query.limit.map_or(usize::MAX, |limit| limit as usize);
+    Ok(tags
+        .into_iter()
+        .skip(query.offset.unwrap_or_default() as usize)
+        .take(limit)
+        .collect())
+}
+
+pub fn write_tags<W: Write>(w: W, tags: &[TagCount]) -> Result<()> {
+    let mut wtr = csv::WriterBuilder::new().from_writer(w);
+    for tag in tags {
+        wtr.serialize(tag)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// What to do with hashtags in the description (e.g. `#repair #zerowaste`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DescriptionHashtags {
+    /// Leave them in the description.
+    #[default]
+    Keep,
+    /// Add them to the tags.
+    Extract,
+    /// Add them to the tags and remove them from the description.
+    Move,
+}
+
+impl DescriptionHashtags {
+    /// The hashtags of the description that should be added to the tags.
+    pub fn apply(self, description: &mut String) -> Vec<String> {
+        if self == Self::Keep {
+            return vec![];
+        }
+        let tags = hashtags(description);
+        if self == Self::Move && !tags.is_empty() {
+            *description = remove_hashtags(description);
+        }
+        tags
+    }
+}
+
+/// The separator between the tags of a `tags` column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TagSeparator {
+    /// `,` if the value contains one, otherwise `;`,
+    /// otherwise spaces if all words are hashtags (`#repair #zerowaste`).
+    #[default]
+    Auto,
+    Comma,
+    Semicolon,
+    Whitespace,
+}
+
+impl TagSeparator {
+    /// The tags of the value (untrimmed, maybe empty).
+    pub fn split(self, tags: &str) -> Vec<&str> {
+        match self.detect(tags) {
+            Self::Comma => tags.split(',').collect(),
+            Self::Semicolon => tags.split(';').collect(),
+            Self::Whitespace => tags.split_whitespace().collect(),
+            Self::Auto => vec![tags],
+        }
+    }
+
+    /// The tags as value of a column with this separator (`,` if it is `Auto`).
+    pub fn join(self, tags: &[String]) -> String {
+        match self {
+            Self::Auto | Self::Comma => tags.join(","),
+            Self::Semicolon => tags.join(";"),
+            Self::Whitespace => tags.join(" "),
+        }
+    }
+
+    /// The separator of the value, `Auto` if it is a single tag.
+    fn detect(self, tags: &str) -> Self {
+        if self != Self::Auto {
+            return self;
+        }
+        if tags.contains(',') {
+            Self::Comma
+        } else if tags.contains(';') {
+            Self::Semicolon
+        } else if tags.split_whitespace().all(|word| word.starts_with('#')) {
+            Self::Whitespace
+        } else {
+            Self::Auto
+        }
+    }
+}
+
+impl FromStr for TagSeparator {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_lowercase().as_str() {
+            "auto" => Self::Auto,
+            "," | "comma" => Self::Comma,
+            ";" | "semicolon" => Self::Semicolon,
+            "" | "space" | "whitespace" => Self::Whitespace,
+            _ => bail!("Unknown tag separator '{s}' (expected 'auto', ',', ';' or 'space')"),
+        })
+    }
+}
+
+/// The lowercase hashtags of a text without the `#`.
+pub fn hashtags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    for tag in text.split_whitespace().filter_map(hashtag) {
+        let tag = tag.to_lowercase();
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Remove all hashtags and the spaces around them.
+pub fn remove_hashtags(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            line.split(' ')
+                .filter(|word| !word.is_empty() && hashtag(word).is_none())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// The tag of a word like `#repair` or `#zero-waste,`
+/// (but not `#1` or `example.org/#anchor`).
+fn hashtag(word: &str) -> Option<&str> {
+    let tag = word
+        .strip_prefix('#')?
+        .trim_end_matches(|c: char| ".,;:!?)".contains(c));
+    let valid = tag.chars().any(char::is_alphabetic)
+        && tag
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    valid.then_some(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_tags_on_all_pages() {
+        let all: Vec<_> = (0..250)
+            .map(|i| TagFrequency(format!("tag-{i}"), 250 - i))
+            .collect();
+        let mut requests = 0;
+        let fetch = |query: &TagQuery| {
+            requests += 1;
+            let offset = query.offset.unwrap_or_default() as usize;
+            let limit = query.limit.unwrap_or(10) as usize;
+            Ok(all
+                .iter()
+                .skip(offset)
+                .take(limit)
+                .map(|TagFrequency(tag, count)| TagFrequency(tag.clone(), *count))
+                .collect())
+        };
+        let query = TagQuery {
+            offset: Some(1),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let tags = find_tags(fetch, &query, Some("#TAG-2")).unwrap();
+        assert_eq!(requests, 3);
+        let names: Vec<_> = tags.iter().map(|t| t.tag.as_str()).collect();
+        assert_eq!(names, vec!["tag-20", "tag-21"]);
+    }
+
+    #[test]
+    fn extract_hashtags() {
+        let text =
+            "Wir reparieren alles! #Repair #zerowaste,\nKontakt: example.org/#team #1 #repair";
+        assert_eq!(hashtags(text), vec!["repair", "zerowaste"]);
+        assert_eq!(
+            remove_hashtags(text),
+            "Wir reparieren alles!\nKontakt: example.org/#team #1"
+        );
+
+        let mut description = text.to_string();
+        assert!(DescriptionHashtags::Keep.apply(&mut description).is_empty());
+        assert_eq!(
+            DescriptionHashtags::Extract.apply(&mut description).len(),
+            2
+        );
+        assert_eq!(description, text);
+        DescriptionHashtags::Move.apply(&mut description);
+        assert!(!description.contains("#zerowaste"));
+    }
+
+    #[test]
+    fn split_tags() {
+        let auto = TagSeparator::Auto;
+        assert_eq!(auto.split("bio, zero waste"), vec!["bio", " zero waste"]);
+        assert_eq!(auto.split("bio;regional"), vec!["bio", "regional"]);
+        assert_eq!(auto.split("#bio  #regional"), vec!["#bio", "#regional"]);
+        assert_eq!(auto.split("zero waste"), vec!["zero waste"]);
+        assert_eq!(
+            TagSeparator::Whitespace.split("bio regional"),
+            vec!["bio", "regional"]
+        );
+        assert_eq!(TagSeparator::Semicolon.split("a,b;c"), vec!["a,b", "c"]);
+        assert_eq!(
+            " ".parse::<TagSeparator>().unwrap(),
+            TagSeparator::Whitespace
+        );
+        assert_eq!(
+            ";".parse::<TagSeparator>().unwrap(),
+            TagSeparator::Semicolon
+        );
+        assert!("|".parse::<TagSeparator>().is_err());
+        let tags = ["bio".to_string(), "regional".to_string()];
+        assert_eq!(auto.join(&tags), "bio,regional");
+        assert_eq!(TagSeparator::Whitespace.join(&tags), "bio regional");
+        assert_eq!(
+            auto.split(&TagSeparator::Semicolon.join(&tags)),
+            vec!["bio", "regional"]
+        );
+    }
+
+    #[test]
+    fn filter_and_write_tags() {
+        let tags = vec![
+            TagFrequency("solar".to_string(), 12),
+            TagFrequency("solarenergie".to_string(), 3),
+            TagFrequency("wind".to_string(), 7),
+        ];
+        let tags = filter_tags(tags, Some("#Solar"));
+        assert_eq!(tags.len(), 2);
+        let mut csv = vec![];
+        write_tags(&mut csv, &tags).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "tag,count\nsolar,12\nsolarenergie,3\n"
+        );
+    }
+
+    #[test]
+    fn query_parameters() {
+        let query = TagQuery {
+            min_count: Some(5),
+            limit: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(query.params(), vec![("min_count", 5), ("limit", 100)]);
+    }
+}
@@ -0,0 +1,154 @@
+//! Tracing of the API requests,
+//! e.g. to attach diagnostics to an issue without recompiling the CLI.
+//!
+//! Passwords, tokens and contact data in the bodies and query parameters are redacted.
+
+use std::time::Instant;
+
+use reqwest::{
+    blocking::{Client, Request, Response},
+    header::HeaderMap,
+    StatusCode, Url,
+};
+use serde_json::Value;
+
+/// Fields with secrets or personal data.
+const REDACTED_FIELDS: &[&str] = &["password", "token", "email", "telephone", "contact_name"];
+
+/// Query parameters with secrets or personal data.
+const REDACTED_PARAMS: &[&str] = &["password", "token", "key", "api_key", "email"];
+
+/// Max. number of characters of a body in the log.
+const MAX_BODY_LEN: usize = 4096;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpTrace {
+    /// Log the (redacted) bodies as well.
+    ///
+    /// Responses are read completely before they are processed.
+    pub bodies: bool,
+}
+
+impl HttpTrace {
+    pub(crate) fn execute(&self, client: &Client, request: Request) -> reqwest::Result<Response> {
+        let method = request.method().clone();
+        let url = redact_url(request.url());
+        log::info!("Request: {method} {url}");
+        if self.bodies {
+            if let Some(body) = request.body().and_then(|b| b.as_bytes()) {
+                log::info!("Request body: {}", redact_body(body));
+            }
+        }
+        let started = Instant::now();
+        let response = client.execute(request)?;
+        log::info!(
+            "Response: {} for {method} {url} in {}ms",
+            response.status(),
+            started.elapsed().as_millis()
+        );
+        if !self.bodies {
+            return Ok(response);
+        }
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes()?.to_vec();
+        log::info!("Response body: {}", redact_body(&body));
+        Ok(rebuild_response(status, headers, body))
+    }
+}
+
+/// A response with a body that was already read.
+pub(crate) fn rebuild_response(status: StatusCode, headers: HeaderMap, body: Vec<u8>) -> Response {
+    let mut response = ::http::Response::new(body);
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    Response::from(response)
+}
+
+/// The URL with redacted secrets and contact data in the query.
+pub(crate) fn redact_url(url: &Url) -> Url {
+    if url.query().is_none() {
+        return url.clone();
+    }
+    let mut redacted = url.clone();
+    redacted
+        .query_pairs_mut()
+        .clear()
+        .extend_pairs(url.query_pairs().map(|(name, value)| {
+            if REDACTED_PARAMS.contains(&name.to_lowercase().as_str()) {
+                (name, "<redacted>".into())
+            } else {
+                (name, value)
+            }
+        }));
+    redacted
+}
+
+/// The body with redacted secrets and contact data,
+/// shortened to [MAX_BODY_LEN] characters.
+pub fn redact_body(body: &[u8]) -> String {
+    let text = redact_json(body);
+    match text.char_indices().nth(MAX_BODY_LEN) {
+        Some((end, _)) => format!("{}... ({} bytes)", &text[..end], body.len()),
+        None => text,
+    }
+}
+
+/// The complete body with redacted secrets and contact data.
+pub(crate) fn redact_json(body: &[u8]) -> String {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(mut json) => {
+            redact(&mut json);
+            json.to_string()
+        }
+        Err(_) => String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+fn redact(json: &mut Value) {
+    match json {
+        Value::Object(fields) => {
+            for (name, value) in fields {
+                if REDACTED_FIELDS.contains(&name.as_str()) && !value.is_null() {
+                    *value = Value::String("<redacted>".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_secrets_and_contact_data() {
+        let body = r#"{"email":"me@example.org","password":"secret","entries":[{"title":"Repair Café","telephone":"0234 123456","homepage":null,"email":null}]}"#;
+        let redacted: Value = serde_json::from_str(&redact_body(body.as_bytes())).unwrap();
+        assert_eq!(redacted["email"], "<redacted>");
+        assert_eq!(redacted["password"], "<redacted>");
+        assert_eq!(redacted["entries"][0]["title"], "Repair Café");
+        assert_eq!(redacted["entries"][0]["telephone"], "<redacted>");
+        assert_eq!(redacted["entries"][0]["email"], Value::Null);
+
+        assert_eq!(redact_body(b"Not found"), "Not found");
+        let long = "x".repeat(MAX_BODY_LEN + 10);
+        assert!(redact_body(long.as_bytes()).ends_with(&format!("... ({} bytes)", long.len())));
+    }
+
+    #[test]
+    fn redact_secrets_in_the_query() {
+        let url =
+            Url::parse("https://api.example.org/v0/search?text=bio&key=secret&Token=abc").unwrap();
+        assert_eq!(
+            redact_url(&url).as_str(),
+            "https://api.example.org/v0/search?text=bio&key=%3Credacted%3E&Token=%3Credacted%3E"
+        );
+        let url = Url::parse("https://api.example.org/v0/entries/1").unwrap();
+        assert_eq!(redact_url(&url), url);
+    }
+}
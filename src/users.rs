@@ -0,0 +1,75 @@
+//! Administration of user accounts, e.g. to register a batch of regional moderators.
+
+use std::io::Read;
+
+use anyhow::Result;
+use email_address_parser::EmailAddress;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    headers,
+    import::{CsvImportError, CsvImportResult},
+};
+
+/// A user account to register.
+///
+/// The server sends an e-mail to confirm the address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewUser {
+    pub email: String,
+    pub password: String,
+}
+
+/// Read new users from a CSV file with the columns `email` and `password`.
+pub fn users_from_reader<R: Read>(r: R) -> Result<Vec<CsvImportResult<NewUser>>> {
+    let mut rdr = headers::reader(r, &[])?;
+    let mut results = vec![];
+    for (record_nr, result) in rdr.deserialize::<NewUser>().enumerate() {
+        let result = result
+            .map_err(|err| CsvImportError::Record(err.to_string()))
+            .and_then(|mut user| {
+                user.email = user.email.trim().to_string();
+                check_new_user(&user)?;
+                Ok(user)
+            });
+        results.push(CsvImportResult::new(record_nr, result));
+    }
+    Ok(results)
+}
+
+fn check_new_user(user: &NewUser) -> Result<(), CsvImportError> {
+    if EmailAddress::parse(&user.email, None).is_none() {
+        return Err(CsvImportError::Record(format!(
+            "Invalid email address '{}'",
+            user.email
+        )));
+    }
+    if user.password.is_empty() {
+        return Err(CsvImportError::Record(format!(
+            "The password of '{}' is missing",
+            user.email
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_users_from_csv() {
+        let csv = "email,password\n mod@example.org ,secret\ninvalid,secret\nnopw@example.org,\n";
+        let results = users_from_reader(csv.as_bytes()).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0].result.as_ref().unwrap(),
+            &NewUser {
+                email: "mod@example.org".to_string(),
+                password: "secret".to_string()
+            }
+        );
+        assert!(results[1].result.is_err());
+        assert!(results[2].result.is_err());
+    }
+}
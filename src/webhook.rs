@@ -0,0 +1,183 @@
+//! External validation services.
+//!
+//! Each parsed record is sent as JSON to the webhook:
+//!
+//! ```json
+//! { "record_nr": 0, "place": { "title": "..." } }
+//! ```
+//!
+//! The service responds with a list of errors and warnings:
+//!
+//! ```json
+//! { "errors": ["The title is too short"], "warnings": [] }
+//! ```
+//!
+//! Records with errors are not imported.
+
+use anyhow::Result;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::import::{CsvImportError, CsvImportResult, CsvImportWarningReport};
+
+#[derive(Debug, Serialize)]
+struct ValidationRequest<'a, T> {
+    record_nr: usize,
+    place: &'a T,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ValidationResponse {
+    #[serde(default)]
+    pub errors: Vec<String>,
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+pub fn validate<T: Serialize>(
+    client: &Client,
+    url: &str,
+    record_nr: usize,
+    place: &T,
+) -> Result<ValidationResponse> {
+    let res = client
+        .post(url)
+        .json(&ValidationRequest { record_nr, place })
+        .send()?
+        .error_for_status()?;
+    Ok(res.json()?)
+}
+
+/// Validate all successfully parsed records
+/// and turn records with validation errors into failures.
+pub fn validate_results<T: Serialize>(
+    client: &Client,
+    url: &str,
+    results: Vec<CsvImportResult<T>>,
+) -> Result<(Vec<CsvImportResult<T>>, Vec<CsvImportWarningReport>)> {
+    log::info!("Validate {} records with {url}", results.len());
+    let mut warnings = vec![];
+    let mut validated = Vec::with_capacity(results.len());
+    for mut r in results {
+        let record_nr = r.record_nr;
+        r.result = match r.result {
+            Ok(place) => {
+                let response = validate(client, url, record_nr, &place)?;
+                warnings.extend(response.warnings.into_iter().map(|warning| {
+                    log::warn!("Record {record_nr}: {warning}");
+                    CsvImportWarningReport { record_nr, warning }
+                }));
+                if response.errors.is_empty() {
+                    Ok(place)
+                } else {
+                    Err(CsvImportError::Validation(response.errors.join("; ")))
+                }
+            }
+            Err(err) => Err(err),
+        };
+        validated.push(r);
+    }
+    Ok((validated, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+    };
+
+    /// Answer each request with the status and the body returned by `respond`
+    /// and pass the JSON of the requests to the receiver.
+    fn webhook(respond: fn(&Value) -> (u16, String)) -> (String, mpsc::Receiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/validate", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end().to_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(len) = line.strip_prefix("content-length:") {
+                        content_length = len.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let request: Value = serde_json::from_slice(&body).unwrap();
+                let (status, body) = respond(&request);
+                let _ = tx.send(request);
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    #[test]
+    fn reject_records_with_validation_errors() {
+        let (url, requests) = webhook(|request| {
+            let response = if request["place"]["title"] == "Bad" {
+                json!({ "errors": ["The title is too short", "No tags"] })
+            } else {
+                json!({ "warnings": ["No homepage"] })
+            };
+            (200, response.to_string())
+        });
+        let results = vec![
+            CsvImportResult::new(0, Ok(json!({ "title": "Good" }))),
+            CsvImportResult::new(1, Err(CsvImportError::Record("invalid".to_string()))),
+            CsvImportResult::new(2, Ok(json!({ "title": "Bad" }))),
+        ];
+        let (validated, warnings) = validate_results(&Client::new(), &url, results).unwrap();
+
+        let requests: Vec<_> = requests.try_iter().collect();
+        assert_eq!(
+            requests,
+            vec![
+                json!({ "record_nr": 0, "place": { "title": "Good" } }),
+                json!({ "record_nr": 2, "place": { "title": "Bad" } }),
+            ]
+        );
+        assert!(validated[0].result.is_ok());
+        assert!(matches!(
+            validated[1].result,
+            Err(CsvImportError::Record(_))
+        ));
+        assert!(matches!(
+            &validated[2].result,
+            Err(CsvImportError::Validation(msg)) if msg == "The title is too short; No tags"
+        ));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].record_nr, 0);
+        assert_eq!(warnings[0].warning, "No homepage");
+    }
+
+    #[test]
+    fn fail_if_the_webhook_fails() {
+        let (url, _) = webhook(|_| (500, String::new()));
+        let results = vec![CsvImportResult::new(0, Ok(json!({ "title": "Good" })))];
+        assert!(validate_results(&Client::new(), &url, results).is_err());
+
+        let (url, _) = webhook(|_| (200, "no json".to_string()));
+        assert!(validate(&Client::new(), &url, 0, &json!({})).is_err());
+
+        let url = "http://127.0.0.1:9/validate";
+        assert!(validate(&Client::new(), url, 0, &json!({})).is_err());
+    }
+}
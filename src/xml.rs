@@ -0,0 +1,22 @@
+//! Escaping of texts in XML and HTML documents (e.g. KML, GPX and the preview map).
+
+/// Escape the characters with a meaning in XML, also within attribute values.
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_markup() {
+        assert_eq!(
+            escape(r#"<a href="?a=1&b=2">"#),
+            "&lt;a href=&quot;?a=1&amp;b=2&quot;&gt;"
+        );
+    }
+}